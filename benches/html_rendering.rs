@@ -1,4 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion};
+use pulldown_cmark::escape::escape_html;
 use pulldown_cmark::{html, Options, Parser};
 use std::str::from_utf8;
 
@@ -33,6 +34,30 @@ fn criterion_benchmark(c: &mut Criterion) {
         })
     });
 
+    c.bench_function("crdt_html_into_capacity", |b| {
+        let input = from_utf8(CRDT_BYTES).unwrap();
+        let events: Vec<_> = Parser::new_ext(input, Options::empty()).collect();
+        let options = html::Options::default();
+
+        b.iter(|| {
+            let mut buf = String::new();
+            html::push_html_into_capacity(&mut buf, &events, &options);
+            buf
+        })
+    });
+
+    c.bench_function("escape_clean_text", |b| {
+        let input = from_utf8(CRDT_BYTES)
+            .unwrap()
+            .replace(&['<', '>', '&', '"'][..], "");
+        let mut buf = String::with_capacity(input.len());
+
+        b.iter(|| {
+            buf.clear();
+            escape_html(&mut buf, &input).unwrap();
+        })
+    });
+
     c.bench_function("crdt_all_options_parse", |b| {
         let input = from_utf8(CRDT_BYTES).unwrap();
 
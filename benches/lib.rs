@@ -35,6 +35,23 @@ mod to_html {
         group.finish();
     }
 
+    pub fn pathological_emphasis(c: &mut Criterion) {
+        let mut group = c.benchmark_group("pathological_emphasis");
+        // two sizes of each, so that superlinear resolution shows up as a
+        // drop in throughput; larger inputs would also slow down from no
+        // longer fitting in the cache
+        for pattern in &["*a", "*a **a ", "***a*", "*a _a **a __a ~~a "] {
+            for &repeat in &[2_500, 10_000] {
+                let buf = pattern.repeat(repeat);
+                group.throughput(Throughput::Bytes(buf.len() as u64));
+                group.bench_with_input(BenchmarkId::new(*pattern, repeat), &buf, |b, buf| {
+                    b.iter(|| render_html(buf, Options::ENABLE_STRIKETHROUGH));
+                });
+            }
+        }
+        group.finish();
+    }
+
     fn render_html(text: &str, opts: Options) -> String {
         let mut s = String::with_capacity(text.len() * 3 / 2);
         let p = Parser::new_ext(text, opts);
@@ -46,6 +63,7 @@ mod to_html {
 criterion_group!(
     benches,
     to_html::pathological_codeblocks1,
-    to_html::advanced_pathological_codeblocks
+    to_html::advanced_pathological_codeblocks,
+    to_html::pathological_emphasis
 );
 criterion_main!(benches);
@@ -0,0 +1,71 @@
+// Inputs for the hot scanning loops that the `simd` feature accelerates.
+// Compare `cargo bench --bench simd_scanning` with and without
+// `--features simd`.
+
+use criterion::{criterion_group, criterion_main};
+
+mod scanning {
+    use criterion::{BenchmarkId, Criterion, Throughput};
+    use pulldown_cmark::escape::escape_html;
+    use pulldown_cmark::{Options, Parser};
+
+    /// Prose with a special inline byte every few hundred bytes, where the
+    /// scanners skip whole vectors at a time.
+    fn sparse_specials() -> String {
+        let line = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \
+                    eiusmod tempor incididunt ut labore et dolore magna aliqua.\n";
+        let paragraph = format!("{}Some *emphasis* here.\n\n", line.repeat(6));
+        paragraph.repeat(200)
+    }
+
+    /// Text where nearly every other byte is special.
+    fn dense_specials() -> String {
+        "a*b_c`d[e]f<g&h\\i!j ~k\n".repeat(5_000)
+    }
+
+    /// Link destinations and titles with escapes, entities and carriage
+    /// returns to unescape.
+    fn link_destinations() -> String {
+        "[link](/path/with\\_escapes&amp;entities/and/a/long/tail \"a \\\"title\\\" &copy;\r\nmore\")\n"
+            .repeat(2_000)
+    }
+
+    pub fn parse(c: &mut Criterion) {
+        let mut group = c.benchmark_group("simd_scanning_parse");
+        let inputs = [
+            ("sparse_specials", sparse_specials()),
+            ("dense_specials", dense_specials()),
+            ("link_destinations", link_destinations()),
+        ];
+        for (name, input) in &inputs {
+            group.throughput(Throughput::Bytes(input.len() as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(name), input, |b, input| {
+                b.iter(|| Parser::new_ext(input, Options::ENABLE_STRIKETHROUGH).count());
+            });
+        }
+        group.finish();
+    }
+
+    pub fn escape(c: &mut Criterion) {
+        let mut group = c.benchmark_group("simd_scanning_escape_html");
+        let sparse = sparse_specials().replace('*', "<");
+        let inputs = [
+            ("sparse_specials", sparse),
+            ("dense_specials", dense_specials()),
+        ];
+        for (name, input) in &inputs {
+            let mut buf = String::with_capacity(input.len() * 2);
+            group.throughput(Throughput::Bytes(input.len() as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(name), input, |b, input| {
+                b.iter(|| {
+                    buf.clear();
+                    escape_html(&mut buf, input).unwrap();
+                });
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, scanning::parse, scanning::escape);
+criterion_main!(benches);
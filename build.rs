@@ -1,7 +1,41 @@
 fn main() {
+    detect_compiler_features();
     generate_tests_from_spec()
 }
 
+// NEON intrinsics and `std::thread::available_parallelism` are only stable
+// from Rust 1.59. Older compilers, down to the minimum this crate supports,
+// build without them: scanning on aarch64 is then scalar, and the number of
+// CPUs is not known to the binary. Enums only find room for their tag in
+// one of several data-carrying variants from Rust 1.65; before that
+// `InlineStr` gives up a byte so that `CowStr` stays three words long.
+fn detect_compiler_features() {
+    println!("cargo:rustc-check-cfg=cfg(has_neon)");
+    println!("cargo:rustc-check-cfg=cfg(has_available_parallelism)");
+    println!("cargo:rustc-check-cfg=cfg(has_multi_variant_niche)");
+
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let minor = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .and_then(|version| {
+            // "rustc 1.59.0 (9d1b2106e 2022-02-23)"
+            let mut numbers = version.split_whitespace().nth(1)?.split('.');
+            numbers.nth(1)?.parse::<u32>().ok()
+        });
+    if minor.map_or(false, |minor| minor >= 59) {
+        if std::env::var("CARGO_CFG_TARGET_ARCH").map_or(false, |arch| arch == "aarch64") {
+            println!("cargo:rustc-cfg=has_neon");
+        }
+        println!("cargo:rustc-cfg=has_available_parallelism");
+    }
+    if minor.map_or(false, |minor| minor >= 65) {
+        println!("cargo:rustc-cfg=has_multi_variant_niche");
+    }
+}
+
 // If the "gen-tests" feature is absent,
 // this function will be compiled down to nothing
 #[cfg(not(feature = "gen-tests"))]
@@ -0,0 +1,118 @@
+//! Buffers of parser temporaries, recycled between parses.
+//!
+//! A parse builds the tree of its document, and uses the spine of the tree
+//! and the delimiter and link stacks of the inline passes as it goes. Rather
+//! than allocating those vectors for every document, a parser takes them
+//! from a pool kept per thread and, once its document is exhausted, gives
+//! them back cleared but with their capacity. A thread parsing many small
+//! documents so stops allocating for them after the first few. This is not
+//! an arena: each buffer is an ordinary `Vec`, and what the events borrow or
+//! own is allocated as before. Parsers dropped before the end of their
+//! document free their buffers instead.
+//!
+//! Callers can also hold the buffers themselves as a
+//! [`ParserScratch`](../struct.ParserScratch.html), which is then passed from
+//! parser to parser rather than kept per thread.
+
+use std::cell::RefCell;
+
+use crate::parse::{CodeDelims, InlineStack, Item, LinkStack};
+use crate::tree::{Node, TreeIndex};
+
+/// Buffers with room for more than this many nodes or entries, which only
+/// huge documents need, are freed rather than kept for the next parse.
+const MAX_RETAINED_CAPACITY: usize = 1 << 16;
+
+/// The retained vectors of a parser.
+#[derive(Default)]
+pub(crate) struct Buffers {
+    pub(crate) nodes: Vec<Node<Item>>,
+    pub(crate) spine: Vec<TreeIndex>,
+    pub(crate) inline_stack: InlineStack,
+    pub(crate) link_stack: LinkStack,
+    pub(crate) code_delims: CodeDelims,
+}
+
+thread_local! {
+    static BUFFERS: RefCell<Buffers> = RefCell::new(Buffers::default());
+}
+
+impl Buffers {
+    /// Takes the buffers of the current thread, leaving empty ones for
+    /// parsers created while these are in use.
+    pub(crate) fn take() -> Buffers {
+        BUFFERS
+            .try_with(|buffers| std::mem::take(&mut *buffers.borrow_mut()))
+            .unwrap_or_default()
+    }
+
+    /// Empties the storage, keeping the capacity of each buffer unless it is
+    /// too large to be worth retaining.
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.spine.clear();
+        self.inline_stack.clear();
+        self.link_stack.clear();
+        self.code_delims.clear();
+        if self.nodes.capacity() > MAX_RETAINED_CAPACITY {
+            self.nodes = Vec::new();
+        }
+        if self.spine.capacity() > MAX_RETAINED_CAPACITY {
+            self.spine = Vec::new();
+        }
+        if self.inline_stack.capacity() > MAX_RETAINED_CAPACITY {
+            self.inline_stack = InlineStack::default();
+        }
+        if self.link_stack.capacity() > MAX_RETAINED_CAPACITY {
+            self.link_stack = LinkStack::default();
+        }
+        if self.code_delims.capacity() > MAX_RETAINED_CAPACITY {
+            self.code_delims = CodeDelims::default();
+        }
+    }
+
+    /// Clears the buffers and makes them those of the current thread, unless
+    /// the thread's have more room.
+    pub(crate) fn give_back(mut self) {
+        self.clear();
+        let _ = BUFFERS.try_with(|buffers| {
+            let mut buffers = buffers.borrow_mut();
+            if buffers.nodes.capacity() <= self.nodes.capacity() {
+                *buffers = self;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn reused_after_exhaustion() {
+        let input = "*a* [b](c) `d`\n\n# e\n";
+        Parser::new(input).for_each(drop);
+        let buffers = Buffers::take();
+        let nodes = buffers.nodes.as_ptr();
+        assert!(buffers.nodes.capacity() > 0);
+        buffers.give_back();
+
+        Parser::new(input).into_offset_iter().for_each(drop);
+        assert_eq!(Buffers::take().nodes.as_ptr(), nodes);
+    }
+
+    #[test]
+    fn huge_buffers_freed() {
+        let inline = "*a [b ".repeat(MAX_RETAINED_CAPACITY + 1);
+        let nested = ">".repeat(MAX_RETAINED_CAPACITY + 1);
+        for input in &[inline, nested] {
+            Parser::new(input).for_each(drop);
+            let buffers = Buffers::take();
+            assert!(buffers.nodes.capacity() <= MAX_RETAINED_CAPACITY);
+            assert!(buffers.spine.capacity() <= MAX_RETAINED_CAPACITY);
+            assert!(buffers.inline_stack.capacity() <= MAX_RETAINED_CAPACITY);
+            assert!(buffers.link_stack.capacity() <= MAX_RETAINED_CAPACITY);
+        }
+    }
+}
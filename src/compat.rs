@@ -0,0 +1,13 @@
+//! Stand-ins for standard library functions that are newer than the oldest
+//! supported compiler. Shared by the library and the binary, which both
+//! declare this module.
+
+/// Returns `s` without `prefix`, if it starts with it, like
+/// `str::strip_prefix`.
+pub(crate) fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.starts_with(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
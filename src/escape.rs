@@ -20,9 +20,17 @@
 
 //! Utility functions for HTML escaping. Only useful when building your own
 //! HTML renderer.
-
-use std::fmt::{Arguments, Write as FmtWrite};
-use std::io::{self, ErrorKind, Write};
+//!
+//! [`escape_html`](fn.escape_html.html) and
+//! [`escape_href`](fn.escape_href.html) are what the HTML renderer uses for
+//! text and URLs. [`HtmlEscaper`](struct.HtmlEscaper.html) lets custom
+//! writers choose which characters to escape. All of them write to any
+//! [`StrWrite`](trait.StrWrite.html), like a `String` or a
+//! [`WriteWrapper`](struct.WriteWrapper.html) around an `io::Write`.
+
+use std::convert::TryInto;
+use std::fmt::{self, Arguments, Write as FmtWrite};
+use std::io::{self, Write};
 use std::str::from_utf8;
 
 #[rustfmt::skip]
@@ -47,18 +55,31 @@ static SLASH_ESCAPE: &str = "&#x27;";
 /// `Write` types.
 pub struct WriteWrapper<W>(pub W);
 
+/// Wrapper making a `fmt::Write` implementation, like a `fmt::Formatter`,
+/// usable as a [`StrWrite`](trait.StrWrite.html).
+pub struct FmtWriter<W>(pub W);
+
 /// Trait that allows writing string slices. This is basically an extension
 /// of `std::io::Write` in order to include `String`.
+///
+/// Implementations choose their own error type, which the HTML renderer and
+/// the escaping functions pass on unchanged. This lets sinks that fail for
+/// reasons of their own report them without going through `io::Error`.
 pub trait StrWrite {
-    fn write_str(&mut self, s: &str) -> io::Result<()>;
+    /// The error returned when writing fails.
+    type Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error>;
 
-    fn write_fmt(&mut self, args: Arguments) -> io::Result<()>;
+    fn write_fmt(&mut self, args: Arguments) -> Result<(), Self::Error>;
 }
 
 impl<W> StrWrite for WriteWrapper<W>
 where
     W: Write,
 {
+    type Error = io::Error;
+
     #[inline]
     fn write_str(&mut self, s: &str) -> io::Result<()> {
         self.0.write_all(s.as_bytes())
@@ -70,17 +91,35 @@ where
     }
 }
 
+impl<W> StrWrite for FmtWriter<W>
+where
+    W: FmtWrite,
+{
+    type Error = fmt::Error;
+
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, args: Arguments) -> fmt::Result {
+        self.0.write_fmt(args)
+    }
+}
+
 impl<'w> StrWrite for String {
+    type Error = fmt::Error;
+
     #[inline]
-    fn write_str(&mut self, s: &str) -> io::Result<()> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
         self.push_str(s);
         Ok(())
     }
 
     #[inline]
-    fn write_fmt(&mut self, args: Arguments) -> io::Result<()> {
-        // FIXME: translate fmt error to io error?
-        FmtWrite::write_fmt(self, args).map_err(|_| ErrorKind::Other.into())
+    fn write_fmt(&mut self, args: Arguments) -> fmt::Result {
+        FmtWrite::write_fmt(self, args)
     }
 }
 
@@ -88,19 +127,54 @@ impl<W> StrWrite for &'_ mut W
 where
     W: StrWrite,
 {
+    type Error = W::Error;
+
     #[inline]
-    fn write_str(&mut self, s: &str) -> io::Result<()> {
+    fn write_str(&mut self, s: &str) -> Result<(), W::Error> {
         (**self).write_str(s)
     }
 
     #[inline]
-    fn write_fmt(&mut self, args: Arguments) -> io::Result<()> {
+    fn write_fmt(&mut self, args: Arguments) -> Result<(), W::Error> {
         (**self).write_fmt(args)
     }
 }
 
+/// Implements `StrWrite::write_fmt` with `write_str`, keeping the first
+/// error of the writer, the way `io::Write::write_fmt` does.
+pub(crate) fn write_fmt_with_str<W>(w: &mut W, args: Arguments) -> Result<(), W::Error>
+where
+    W: StrWrite + ?Sized,
+{
+    struct Adapter<'a, W: StrWrite + ?Sized> {
+        inner: &'a mut W,
+        error: Option<W::Error>,
+    }
+
+    impl<W: StrWrite + ?Sized> FmtWrite for Adapter<'_, W> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.inner.write_str(s).map_err(|e| {
+                self.error = Some(e);
+                fmt::Error
+            })
+        }
+    }
+
+    let mut adapter = Adapter {
+        inner: w,
+        error: None,
+    };
+    match FmtWrite::write_fmt(&mut adapter, args) {
+        Ok(()) => Ok(()),
+        Err(..) => match adapter.error {
+            Some(e) => Err(e),
+            None => panic!("a formatting trait implementation returned an error"),
+        },
+    }
+}
+
 /// Writes an href to the buffer, escaping href unsafe bytes.
-pub fn escape_href<W>(mut w: W, s: &str) -> io::Result<()>
+pub fn escape_href<W>(mut w: W, s: &str) -> Result<(), W::Error>
 where
     W: StrWrite,
 {
@@ -152,7 +226,7 @@ static HTML_ESCAPES: [&str; 5] = ["", "&quot;", "&amp;", "&lt;", "&gt;"];
 
 /// Writes the given string to the Write sink, replacing special HTML bytes
 /// (<, >, &, ") by escape sequences.
-pub fn escape_html<W: StrWrite>(w: W, s: &str) -> io::Result<()> {
+pub fn escape_html<W: StrWrite>(w: W, s: &str) -> Result<(), W::Error> {
     #[cfg(all(target_arch = "x86_64", feature = "simd"))]
     {
         simd::escape_html(w, s)
@@ -163,41 +237,162 @@ pub fn escape_html<W: StrWrite>(w: W, s: &str) -> io::Result<()> {
     }
 }
 
-fn escape_html_scalar<W: StrWrite>(mut w: W, s: &str) -> io::Result<()> {
+fn escape_html_scalar<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
     let bytes = s.as_bytes();
     let mut mark = 0;
-    let mut i = 0;
-    while i < s.len() {
-        match bytes[i..]
-            .iter()
-            .position(|&c| HTML_ESCAPE_TABLE[c as usize] != 0)
+    // text without special characters is written in a single call
+    while let Some(pos) = find_html_special(&bytes[mark..]) {
+        let i = mark + pos;
+        let escape = HTML_ESCAPE_TABLE[bytes[i] as usize];
+        w.write_str(&s[mark..i])?;
+        w.write_str(HTML_ESCAPES[escape as usize])?;
+        mark = i + 1; // all escaped characters are ASCII
+    }
+    w.write_str(&s[mark..])
+}
+
+/// Returns the index of the first byte that `escape_html` escapes. Runs of
+/// text are skipped eight bytes at a time, by checking whole words for the
+/// special bytes.
+fn find_html_special(bytes: &[u8]) -> Option<usize> {
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGHS: u64 = 0x8080_8080_8080_8080;
+    // whether any byte of `word` equals `b`
+    let contains = |word: u64, b: u8| {
+        let x = word ^ (ONES * u64::from(b));
+        x.wrapping_sub(ONES) & !x & HIGHS != 0
+    };
+    let mut offset = 0;
+    for chunk in bytes.chunks_exact(8) {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        if contains(word, b'"')
+            || contains(word, b'&')
+            || contains(word, b'<')
+            || contains(word, b'>')
         {
-            Some(pos) => {
-                i += pos;
+            break;
+        }
+        offset += 8;
+    }
+    bytes[offset..]
+        .iter()
+        .position(|&c| HTML_ESCAPE_TABLE[c as usize] != 0)
+        .map(|pos| offset + pos)
+}
+
+/// A configurable set of characters to escape in HTML text.
+///
+/// [`escape_html`](fn.escape_html.html) escapes `<`, `>`, `&` and `"`, which
+/// is enough for text and double quoted attribute values. An `HtmlEscaper`
+/// starts from one of a few policies and can escape additional ASCII
+/// characters. Characters without a named reference are written as
+/// hexadecimal references, like `&#x27;`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::escape::HtmlEscaper;
+///
+/// let mut s = String::new();
+/// HtmlEscaper::minimal().escape(&mut s, "<a href=\"x\">").unwrap();
+/// assert_eq!(s, "&lt;a href=\"x\">");
+///
+/// let mut s = String::new();
+/// HtmlEscaper::attribute().escape(&mut s, "<a & \"b\">").unwrap();
+/// assert_eq!(s, "<a &amp; &quot;b&quot;>");
+///
+/// let mut s = String::new();
+/// HtmlEscaper::aggressive().with_char('%').escape(&mut s, "'50%'").unwrap();
+/// assert_eq!(s, "&#x27;50&#x25;&#x27;");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct HtmlEscaper {
+    // bit `c` is set when ASCII character `c` is escaped
+    table: u128,
+}
+
+impl HtmlEscaper {
+    /// Escapes `<` and `&`, the least needed in text outside of attributes.
+    pub fn minimal() -> Self {
+        HtmlEscaper { table: 0 }.with_char('<').with_char('&')
+    }
+
+    /// Escapes `&` and `"`, the least needed in double quoted attribute
+    /// values.
+    pub fn attribute() -> Self {
+        HtmlEscaper { table: 0 }.with_char('&').with_char('"')
+    }
+
+    /// Escapes `<`, `>`, `&` and `"`, like
+    /// [`escape_html`](fn.escape_html.html).
+    pub fn standard() -> Self {
+        HtmlEscaper::minimal().with_char('>').with_char('"')
+    }
+
+    /// Escapes `'`, `` ` ``, `=` and `/` in addition to the standard
+    /// characters, so that the output is also safe in unquoted and single
+    /// quoted attribute values.
+    pub fn aggressive() -> Self {
+        HtmlEscaper::standard()
+            .with_char('\'')
+            .with_char('`')
+            .with_char('=')
+            .with_char('/')
+    }
+
+    /// Escapes `c` as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `c` is not ASCII.
+    pub fn with_char(mut self, c: char) -> Self {
+        assert!(c.is_ascii(), "only ASCII characters can be escaped");
+        self.table |= 1 << c as u32;
+        self
+    }
+
+    /// Returns whether `c` is escaped.
+    pub fn escapes(&self, c: char) -> bool {
+        c.is_ascii() && self.table & 1 << c as u32 != 0
+    }
+
+    /// Writes `s` to `w`, escaping the characters of this policy.
+    pub fn escape<W: StrWrite>(&self, mut w: W, s: &str) -> Result<(), W::Error> {
+        let bytes = s.as_bytes();
+        let mut mark = 0;
+        for (i, &c) in bytes.iter().enumerate() {
+            if c >= 0x80 || self.table & 1 << c as u32 == 0 {
+                continue;
+            }
+            w.write_str(&s[mark..i])?;
+            match c {
+                b'"' => w.write_str("&quot;")?,
+                b'&' => w.write_str(AMP_ESCAPE)?,
+                b'<' => w.write_str("&lt;")?,
+                b'>' => w.write_str("&gt;")?,
+                _ => write!(w, "&#x{:02X};", c)?,
             }
-            None => break,
+            mark = i + 1; // all escaped characters are ASCII
         }
-        let c = bytes[i];
-        let escape = HTML_ESCAPE_TABLE[c as usize];
-        let escape_seq = HTML_ESCAPES[escape as usize];
-        w.write_str(&s[mark..i])?;
-        w.write_str(escape_seq)?;
-        i += 1;
-        mark = i; // all escaped characters are ASCII
+        w.write_str(&s[mark..])
+    }
+}
+
+impl Default for HtmlEscaper {
+    fn default() -> Self {
+        HtmlEscaper::standard()
     }
-    w.write_str(&s[mark..])
 }
 
 #[cfg(all(target_arch = "x86_64", feature = "simd"))]
 mod simd {
     use super::StrWrite;
     use std::arch::x86_64::*;
-    use std::io;
     use std::mem::size_of;
 
     const VECTOR_SIZE: usize = size_of::<__m128i>();
 
-    pub(super) fn escape_html<W: StrWrite>(mut w: W, s: &str) -> io::Result<()> {
+    pub(super) fn escape_html<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
         // The SIMD accelerated code uses the PSHUFB instruction, which is part
         // of the SSSE3 instruction set. Further, we can only use this code if
         // the buffer is at least one VECTOR_SIZE in length to prevent reading
@@ -212,11 +407,11 @@ mod simd {
                     let escape_ix = *bytes.get_unchecked(i) as usize;
                     let replacement =
                         super::HTML_ESCAPES[super::HTML_ESCAPE_TABLE[escape_ix] as usize];
-                    w.write_str(&s.get_unchecked(mark..i))?;
+                    w.write_str(s.get_unchecked(mark..i))?;
                     mark = i + 1; // all escaped characters are ASCII
                     w.write_str(replacement)
                 })?;
-                w.write_str(&s.get_unchecked(mark..))
+                w.write_str(s.get_unchecked(mark..))
             }
         } else {
             super::escape_html_scalar(w, s)
@@ -245,7 +440,7 @@ mod simd {
 
         let table = create_lookup();
         let lookup = _mm_loadu_si128(table.as_ptr() as *const __m128i);
-        let raw_ptr = bytes.as_ptr().offset(offset as isize) as *const __m128i;
+        let raw_ptr = bytes.as_ptr().add(offset) as *const __m128i;
 
         // Load the vector from memory.
         let vector = _mm_loadu_si128(raw_ptr);
@@ -273,13 +468,13 @@ mod simd {
     /// Make sure to only call this when `bytes.len() >= 16`, undefined behaviour may
     /// occur otherwise.
     #[target_feature(enable = "ssse3")]
-    unsafe fn foreach_special_simd<F>(
+    unsafe fn foreach_special_simd<E, F>(
         bytes: &[u8],
         mut offset: usize,
         mut callback: F,
-    ) -> io::Result<()>
+    ) -> Result<(), E>
     where
-        F: FnMut(usize) -> io::Result<()>,
+        F: FnMut(usize) -> Result<(), E>,
     {
         // The strategy here is to walk the byte buffer in chunks of VECTOR_SIZE (16)
         // bytes at a time starting at the given offset. For each chunk, we compute a
@@ -321,7 +516,8 @@ mod simd {
             let mut vec = Vec::new();
             unsafe {
                 super::foreach_special_simd("&aXaaaa.a'aa9a<>aab&".as_bytes(), 0, |ix| {
-                    Ok(vec.push(ix))
+                    vec.push(ix);
+                    Ok::<_, ()>(())
                 })
                 .unwrap();
             }
@@ -338,7 +534,7 @@ mod simd {
                 unsafe {
                     super::foreach_special_simd(&vek, 0, |_| {
                         match_count += 1;
-                        Ok(())
+                        Ok::<_, ()>(())
                     })
                     .unwrap();
                 }
@@ -354,3 +550,27 @@ mod simd {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn special_bytes_in_words() {
+        assert_eq!(None, find_html_special(b""));
+        assert_eq!(
+            None,
+            find_html_special("plain text, no specials ¢".as_bytes())
+        );
+        for i in 0..20 {
+            for &special in b"\"&<>" {
+                let mut bytes = vec![b'x'; 20];
+                bytes[i] = special;
+                assert_eq!(Some(i), find_html_special(&bytes));
+            }
+        }
+        let mut s = String::new();
+        escape_html_scalar(&mut s, "0123456789<abcdefgh>&\"").unwrap();
+        assert_eq!("0123456789&lt;abcdefgh&gt;&amp;&quot;", s);
+    }
+}
@@ -3,6 +3,7 @@
 
 use std::cmp::max;
 
+use crate::buffers::Buffers;
 use crate::parse::{scan_containers, Allocations, Item, ItemBody, LinkDef};
 use crate::scanners::*;
 use crate::strings::CowStr;
@@ -17,14 +18,20 @@ use unicase::UniCase;
 
 /// Runs the first pass, which resolves the block structure of the document,
 /// and returns the resulting tree.
-pub(crate) fn run_first_pass<'a>(text: &'a str, options: Options) -> (Tree<Item>, Allocations<'a>) {
+pub(crate) fn run_first_pass<'a>(
+    text: &'a str,
+    options: Options,
+    buffers: &mut Buffers,
+) -> (Tree<Item>, Allocations<'a>) {
     // This is a very naive heuristic for the number of nodes
     // we'll need.
     let start_capacity = max(128, text.len() / 32);
     let lookup_table = &create_lut(&options);
+    let nodes = std::mem::take(&mut buffers.nodes);
+    let spine = std::mem::take(&mut buffers.spine);
     let first_pass = FirstPass {
         text,
-        tree: Tree::with_capacity(start_capacity),
+        tree: Tree::with_storage(nodes, spine, start_capacity),
         begin_list_item: false,
         last_line_blank: false,
         allocs: Allocations::new(),
@@ -1381,14 +1388,14 @@ fn delim_run_can_close(s: &str, suffix: &str, run_len: usize, ix: usize) -> bool
 }
 
 fn create_lut(options: &Options) -> LookupTable {
-    #[cfg(all(target_arch = "x86_64", feature = "simd"))]
+    #[cfg(all(any(target_arch = "x86_64", has_neon), feature = "simd"))]
     {
         LookupTable {
             simd: simd::compute_lookup(options),
             scalar: special_bytes(options),
         }
     }
-    #[cfg(not(all(target_arch = "x86_64", feature = "simd")))]
+    #[cfg(not(all(any(target_arch = "x86_64", has_neon), feature = "simd")))]
     {
         special_bytes(options)
     }
@@ -1425,13 +1432,13 @@ enum LoopInstruction<T> {
     BreakAtWith(usize, T),
 }
 
-#[cfg(all(target_arch = "x86_64", feature = "simd"))]
+#[cfg(all(any(target_arch = "x86_64", has_neon), feature = "simd"))]
 struct LookupTable {
     simd: [u8; 16],
     scalar: [bool; 256],
 }
 
-#[cfg(not(all(target_arch = "x86_64", feature = "simd")))]
+#[cfg(not(all(any(target_arch = "x86_64", has_neon), feature = "simd")))]
 type LookupTable = [bool; 256];
 
 /// This function walks the byte slices from the given index and
@@ -1453,11 +1460,11 @@ fn iterate_special_bytes<F, T>(
 where
     F: FnMut(usize, u8) -> LoopInstruction<Option<T>>,
 {
-    #[cfg(all(target_arch = "x86_64", feature = "simd"))]
+    #[cfg(all(any(target_arch = "x86_64", has_neon), feature = "simd"))]
     {
         simd::iterate_special_bytes(lut, bytes, ix, callback)
     }
-    #[cfg(not(all(target_arch = "x86_64", feature = "simd")))]
+    #[cfg(not(all(any(target_arch = "x86_64", has_neon), feature = "simd")))]
     {
         scalar_iterate_special_bytes(lut, bytes, ix, callback)
     }
@@ -1490,7 +1497,7 @@ where
     (ix, None)
 }
 
-#[cfg(all(target_arch = "x86_64", feature = "simd"))]
+#[cfg(all(any(target_arch = "x86_64", has_neon), feature = "simd"))]
 mod simd {
     //! SIMD byte scanning logic.
     //!
@@ -1504,6 +1511,12 @@ mod simd {
     //! before moving on to the next chunk. This allows us to move quickly when there
     //! are no or few matches.
     //!
+    //! On x86_64, chunks of 32 bytes are used when AVX2 is available, and chunks of
+    //! 16 bytes with SSSE3 otherwise, both detected at runtime. On aarch64, NEON is
+    //! always available, but its intrinsics need Rust 1.59; the build script sets
+    //! `has_neon` when they can be used. Without any of them, the scalar loop is
+    //! used.
+    //!
     //! The table lookup is inspired by this [great overview]. However, since all of the
     //! bytes we're interested in are ASCII, we don't quite need the full generality of
     //! the universal algorithm and are hence able to skip a few instructions.
@@ -1512,9 +1525,6 @@ mod simd {
 
     use super::{LookupTable, LoopInstruction};
     use crate::Options;
-    use core::arch::x86_64::*;
-
-    const VECTOR_SIZE: usize = std::mem::size_of::<__m128i>();
 
     /// Generates a lookup table containing the bitmaps for our
     /// special marker bytes. This is effectively a 128 element 2d bitvector,
@@ -1536,7 +1546,7 @@ mod simd {
             add_lookup_byte(&mut lookup, b'~');
         }
         if options.contains(Options::ENABLE_SMART_PUNCTUATION) {
-            for &byte in &[b'.', b'-', b'"', b'\''] {
+            for &byte in b".-\"'" {
                 add_lookup_byte(&mut lookup, byte);
             }
         }
@@ -1548,47 +1558,111 @@ mod simd {
         lookup[(byte & 0x0f) as usize] |= 1 << (byte >> 4);
     }
 
-    /// Computes a bit mask for the given byteslice starting from the given index,
-    /// where the 16 least significant bits indicate (by value of 1) whether or not
-    /// there is a special character at that byte position. The least significant bit
-    /// corresponds to `bytes[ix]` and the most significant bit corresponds to
-    /// `bytes[ix + 15]`.
-    /// It is only safe to call this function when `bytes.len() >= ix + VECTOR_SIZE`.
-    #[target_feature(enable = "ssse3")]
-    #[inline]
-    unsafe fn compute_mask(lut: &[u8; 16], bytes: &[u8], ix: usize) -> i32 {
-        debug_assert!(bytes.len() >= ix + VECTOR_SIZE);
-
-        let bitmap = _mm_loadu_si128(lut.as_ptr() as *const __m128i);
-        // Small lookup table to compute single bit bitshifts
-        // for 16 bytes at once.
-        let bitmask_lookup =
-            _mm_setr_epi8(1, 2, 4, 8, 16, 32, 64, -128, -1, -1, -1, -1, -1, -1, -1, -1);
-
-        // Load input from memory.
-        let raw_ptr = bytes.as_ptr().add(ix) as *const __m128i;
-        let input = _mm_loadu_si128(raw_ptr);
-        // Compute the bitmap using the bottom nibble as an index
-        // into the lookup table. Note that non-ascii bytes will have
-        // their most significant bit set and will map to lookup[0].
-        let bitset = _mm_shuffle_epi8(bitmap, input);
-        // Compute the high nibbles of the input using a 16-bit rightshift of four
-        // and a mask to prevent most-significant bit issues.
-        let higher_nibbles = _mm_and_si128(_mm_srli_epi16(input, 4), _mm_set1_epi8(0x0f));
-        // Create a bitmask for the bitmap by perform a left shift of the value
-        // of the higher nibble. Bytes with their most significant set are mapped
-        // to -1 (all ones).
-        let bitmask = _mm_shuffle_epi8(bitmask_lookup, higher_nibbles);
-        // Test the bit of the bitmap by AND'ing the bitmap and the mask together.
-        let tmp = _mm_and_si128(bitset, bitmask);
-        // Check whether the result was not null. NEQ is not a SIMD intrinsic,
-        // but comparing to the bitmask is logically equivalent. This also prevents us
-        // from matching any non-ASCII bytes since none of the bitmaps were all ones
-        // (-1).
-        let result = _mm_cmpeq_epi8(tmp, bitmask);
-
-        // Return the resulting bitmask.
-        _mm_movemask_epi8(result)
+    #[cfg(target_arch = "x86_64")]
+    mod x86 {
+        use core::arch::x86_64::*;
+
+        /// Computes a bit mask for the given byteslice starting from the given index,
+        /// where the 16 least significant bits indicate (by value of 1) whether or not
+        /// there is a special character at that byte position. The least significant bit
+        /// corresponds to `bytes[ix]` and the most significant bit corresponds to
+        /// `bytes[ix + 15]`.
+        /// It is only safe to call this function when `bytes.len() >= ix + 16`.
+        #[target_feature(enable = "ssse3")]
+        #[inline]
+        pub(super) unsafe fn compute_mask(lut: &[u8; 16], bytes: &[u8], ix: usize) -> u32 {
+            debug_assert!(bytes.len() >= ix + 16);
+
+            let bitmap = _mm_loadu_si128(lut.as_ptr() as *const __m128i);
+            // Small lookup table to compute single bit bitshifts
+            // for 16 bytes at once.
+            let bitmask_lookup =
+                _mm_setr_epi8(1, 2, 4, 8, 16, 32, 64, -128, -1, -1, -1, -1, -1, -1, -1, -1);
+
+            // Load input from memory.
+            let raw_ptr = bytes.as_ptr().add(ix) as *const __m128i;
+            let input = _mm_loadu_si128(raw_ptr);
+            // Compute the bitmap using the bottom nibble as an index
+            // into the lookup table. Note that non-ascii bytes will have
+            // their most significant bit set and will map to lookup[0].
+            let bitset = _mm_shuffle_epi8(bitmap, input);
+            // Compute the high nibbles of the input using a 16-bit rightshift of four
+            // and a mask to prevent most-significant bit issues.
+            let higher_nibbles = _mm_and_si128(_mm_srli_epi16(input, 4), _mm_set1_epi8(0x0f));
+            // Create a bitmask for the bitmap by perform a left shift of the value
+            // of the higher nibble. Bytes with their most significant set are mapped
+            // to -1 (all ones).
+            let bitmask = _mm_shuffle_epi8(bitmask_lookup, higher_nibbles);
+            // Test the bit of the bitmap by AND'ing the bitmap and the mask together.
+            let tmp = _mm_and_si128(bitset, bitmask);
+            // Check whether the result was not null. NEQ is not a SIMD intrinsic,
+            // but comparing to the bitmask is logically equivalent. This also prevents us
+            // from matching any non-ASCII bytes since none of the bitmaps were all ones
+            // (-1).
+            let result = _mm_cmpeq_epi8(tmp, bitmask);
+
+            // Return the resulting bitmask.
+            _mm_movemask_epi8(result) as u32
+        }
+
+        /// The same as `compute_mask`, for the 32 bytes from the given index.
+        /// It is only safe to call this function when `bytes.len() >= ix + 32`.
+        #[target_feature(enable = "avx2")]
+        #[inline]
+        pub(super) unsafe fn compute_mask_avx2(lut: &[u8; 16], bytes: &[u8], ix: usize) -> u32 {
+            debug_assert!(bytes.len() >= ix + 32);
+
+            // The shuffles work on the two 16 byte lanes separately, so both
+            // get a copy of the tables.
+            let bitmap =
+                _mm256_broadcastsi128_si256(_mm_loadu_si128(lut.as_ptr() as *const __m128i));
+            let bitmask_lookup = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+                1, 2, 4, 8, 16, 32, 64, -128, -1, -1, -1, -1, -1, -1, -1, -1,
+            ));
+
+            let raw_ptr = bytes.as_ptr().add(ix) as *const __m256i;
+            let input = _mm256_loadu_si256(raw_ptr);
+            let bitset = _mm256_shuffle_epi8(bitmap, input);
+            let higher_nibbles =
+                _mm256_and_si256(_mm256_srli_epi16(input, 4), _mm256_set1_epi8(0x0f));
+            let bitmask = _mm256_shuffle_epi8(bitmask_lookup, higher_nibbles);
+            let tmp = _mm256_and_si256(bitset, bitmask);
+            let result = _mm256_cmpeq_epi8(tmp, bitmask);
+
+            _mm256_movemask_epi8(result) as u32
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    mod neon {
+        use core::arch::aarch64::*;
+
+        /// Computes the same mask as the x86 `compute_mask`, with NEON.
+        /// It is only safe to call this function when `bytes.len() >= ix + 16`.
+        #[inline]
+        pub(super) unsafe fn compute_mask(lut: &[u8; 16], bytes: &[u8], ix: usize) -> u32 {
+            debug_assert!(bytes.len() >= ix + 16);
+
+            // Unlike PSHUFB, TBL maps out of range indices to zero, so
+            // non-ASCII bytes get an empty bitmask instead of all ones.
+            const BITMASK_LOOKUP: [u8; 16] = [1, 2, 4, 8, 16, 32, 64, 128, 0, 0, 0, 0, 0, 0, 0, 0];
+            // The weight of every byte in its half of the resulting mask.
+            const BIT_WEIGHTS: [u8; 16] =
+                [1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128];
+
+            let bitmap = vld1q_u8(lut.as_ptr());
+            let input = vld1q_u8(bytes.as_ptr().add(ix));
+            let bitset = vqtbl1q_u8(bitmap, vandq_u8(input, vdupq_n_u8(0x0f)));
+            let bitmask = vqtbl1q_u8(vld1q_u8(BITMASK_LOOKUP.as_ptr()), vshrq_n_u8::<4>(input));
+            // All ones where the bit of the bitmap is set.
+            let result = vtstq_u8(bitset, bitmask);
+
+            // There is no movemask, so sum the weights of each half instead.
+            let weighted = vandq_u8(result, vld1q_u8(BIT_WEIGHTS.as_ptr()));
+            let low = vaddv_u8(vget_low_u8(weighted)) as u32;
+            let high = vaddv_u8(vget_high_u8(weighted)) as u32;
+            low | high << 8
+        }
     }
 
     /// Calls callback on byte indices and their value.
@@ -1604,11 +1678,22 @@ mod simd {
     where
         F: FnMut(usize, u8) -> LoopInstruction<Option<T>>,
     {
-        if is_x86_feature_detected!("ssse3") && bytes.len() >= VECTOR_SIZE {
-            unsafe { simd_iterate_special_bytes(&lut.simd, bytes, ix, callback) }
-        } else {
-            super::scalar_iterate_special_bytes(&lut.scalar, bytes, ix, callback)
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") && bytes.len() >= 32 {
+                return unsafe { avx2_iterate_special_bytes(&lut.simd, bytes, ix, callback) };
+            }
+            if is_x86_feature_detected!("ssse3") && bytes.len() >= 16 {
+                return unsafe { ssse3_iterate_special_bytes(&lut.simd, bytes, ix, callback) };
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if bytes.len() >= 16 {
+                return unsafe { neon_iterate_special_bytes(&lut.simd, bytes, ix, callback) };
+            }
         }
+        super::scalar_iterate_special_bytes(&lut.scalar, bytes, ix, callback)
     }
 
     /// Calls the callback function for every 1 in the given bitmask with
@@ -1616,7 +1701,7 @@ mod simd {
     /// Returns `Ok(ix)` to continue from index `ix`, `Err((end_ix, opt_val)` to break with
     /// final index `end_ix` and optional value `opt_val`.
     unsafe fn process_mask<F, T>(
-        mut mask: i32,
+        mut mask: u32,
         bytes: &[u8],
         mut offset: usize,
         callback: &mut F,
@@ -1630,7 +1715,8 @@ mod simd {
             match callback(offset, *bytes.get_unchecked(offset)) {
                 LoopInstruction::ContinueAndSkip(skip) => {
                     offset += skip + 1;
-                    mask >>= skip + 1 + mask_ix;
+                    // skips can go past the end of the mask
+                    mask = mask.checked_shr((skip + 1 + mask_ix) as u32).unwrap_or(0);
                 }
                 LoopInstruction::BreakAtWith(ix, val) => return Err((ix, val)),
             }
@@ -1638,33 +1724,39 @@ mod simd {
         Ok(offset)
     }
 
-    #[target_feature(enable = "ssse3")]
-    /// Important: only call this function when `bytes.len() >= 16`. Doing
-    /// so otherwise may exhibit undefined behaviour.
-    unsafe fn simd_iterate_special_bytes<F, T>(
-        lut: &[u8; 16],
+    /// Walks `bytes` in chunks of `width` bytes, with `mask_at` computing
+    /// the mask of the chunk at an index.
+    ///
+    /// Important: only call this function when `bytes.len() >= width`, with
+    /// a `mask_at` that is safe to call for such chunks. Doing so otherwise
+    /// may exhibit undefined behaviour.
+    #[inline(always)]
+    unsafe fn iterate_chunks<F, T, M>(
+        width: usize,
+        mask_at: M,
         bytes: &[u8],
         mut ix: usize,
         mut callback: F,
     ) -> (usize, Option<T>)
     where
         F: FnMut(usize, u8) -> LoopInstruction<Option<T>>,
+        M: Fn(usize) -> u32,
     {
-        debug_assert!(bytes.len() >= VECTOR_SIZE);
-        let upperbound = bytes.len() - VECTOR_SIZE;
+        debug_assert!(bytes.len() >= width);
+        let upperbound = bytes.len() - width;
 
         while ix < upperbound {
-            let mask = compute_mask(lut, bytes, ix);
+            let mask = mask_at(ix);
             let block_start = ix;
             ix = match process_mask(mask, bytes, ix, &mut callback) {
-                Ok(ix) => std::cmp::max(ix, VECTOR_SIZE + block_start),
+                Ok(ix) => std::cmp::max(ix, width + block_start),
                 Err((end_ix, val)) => return (end_ix, val),
             };
         }
 
         if bytes.len() > ix {
             // shift off the bytes at start we have already scanned
-            let mask = compute_mask(lut, bytes, upperbound) >> ix - upperbound;
+            let mask = mask_at(upperbound) >> (ix - upperbound);
             if let Err((end_ix, val)) = process_mask(mask, bytes, ix, &mut callback) {
                 return (end_ix, val);
             }
@@ -1673,6 +1765,56 @@ mod simd {
         (bytes.len(), None)
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "ssse3")]
+    /// Important: only call this function when `bytes.len() >= 16`. Doing
+    /// so otherwise may exhibit undefined behaviour.
+    unsafe fn ssse3_iterate_special_bytes<F, T>(
+        lut: &[u8; 16],
+        bytes: &[u8],
+        ix: usize,
+        callback: F,
+    ) -> (usize, Option<T>)
+    where
+        F: FnMut(usize, u8) -> LoopInstruction<Option<T>>,
+    {
+        let mask_at = |ix| x86::compute_mask(lut, bytes, ix);
+        iterate_chunks(16, mask_at, bytes, ix, callback)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    /// Important: only call this function when `bytes.len() >= 32`. Doing
+    /// so otherwise may exhibit undefined behaviour.
+    unsafe fn avx2_iterate_special_bytes<F, T>(
+        lut: &[u8; 16],
+        bytes: &[u8],
+        ix: usize,
+        callback: F,
+    ) -> (usize, Option<T>)
+    where
+        F: FnMut(usize, u8) -> LoopInstruction<Option<T>>,
+    {
+        let mask_at = |ix| x86::compute_mask_avx2(lut, bytes, ix);
+        iterate_chunks(32, mask_at, bytes, ix, callback)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    /// Important: only call this function when `bytes.len() >= 16`. Doing
+    /// so otherwise may exhibit undefined behaviour.
+    unsafe fn neon_iterate_special_bytes<F, T>(
+        lut: &[u8; 16],
+        bytes: &[u8],
+        ix: usize,
+        callback: F,
+    ) -> (usize, Option<T>)
+    where
+        F: FnMut(usize, u8) -> LoopInstruction<Option<T>>,
+    {
+        let mask_at = |ix| unsafe { neon::compute_mask(lut, bytes, ix) };
+        iterate_chunks(16, mask_at, bytes, ix, callback)
+    }
+
     #[cfg(test)]
     mod simd_test {
         use super::super::create_lut;
@@ -1741,5 +1883,46 @@ mod simd {
                 }
             }
         }
+
+        /// The indices found by the scalar loop and by the SIMD one, which
+        /// uses the widest instructions available.
+        fn scalar_and_simd_indices(bytes: &[u8], skip: usize) -> (Vec<usize>, Vec<usize>) {
+            let lut = create_lut(&Options::all());
+            let mut scalar = vec![];
+            super::super::scalar_iterate_special_bytes::<_, i32>(&lut.scalar, bytes, 0, |ix, _| {
+                scalar.push(ix);
+                LoopInstruction::ContinueAndSkip(skip)
+            });
+            let mut simd = vec![];
+            iterate_special_bytes::<_, i32>(&lut, bytes, 0, |ix, _| {
+                simd.push(ix);
+                LoopInstruction::ContinueAndSkip(skip)
+            });
+            (scalar, simd)
+        }
+
+        #[test]
+        fn same_as_scalar() {
+            let alphabet = b"abc \r\n*_&\\[]<!`|~.-\"'\xc3\xa9";
+            // xorshift, to get the same inputs on every run
+            let mut state = 0x2545_f491u32;
+            let mut next = move || {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as usize
+            };
+            for len in 0..100 {
+                for _ in 0..20 {
+                    let bytes: Vec<u8> = (0..len)
+                        .map(|_| alphabet[next() % alphabet.len()])
+                        .collect();
+                    for &skip in &[0, 1, 3, 40] {
+                        let (scalar, simd) = scalar_and_simd_indices(&bytes, skip);
+                        assert_eq!(scalar, simd, "{:?} skipping {}", bytes, skip);
+                    }
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,134 @@
+//! Highlighting of code blocks with syntect, with the `syntect` feature.
+//!
+//! A [`SyntectHighlighter`](struct.SyntectHighlighter.html) highlights code
+//! in the languages of syntect's default syntaxes, either with inline styles
+//! from one of its default themes, or with classes naming the scopes of the
+//! code, for a stylesheet to style. Its
+//! [`render`](struct.SyntectHighlighter.html#method.render) method gives a
+//! [`Highlight`](../html/struct.Highlight.html) renderer. Code blocks in
+//! other languages are rendered as usual.
+//!
+//! # Examples
+//!
+//! ```
+//! use pulldown_cmark::highlight::SyntectHighlighter;
+//! use pulldown_cmark::{html, Parser};
+//!
+//! let highlighter = SyntectHighlighter::with_classes();
+//! let parser = Parser::new("```rust\nfn f() {}\n```\n\n```\nplain\n```\n");
+//! let mut html_buf = String::new();
+//! let options = html::Options::default();
+//! html::push_html_with_renderer(&mut html_buf, parser, &options, highlighter.render());
+//! assert!(html_buf.starts_with("<pre><code class=\"language-rust\"><span class=\"source rust\">"));
+//! assert!(html_buf.ends_with("</code></pre>\n<pre><code>plain\n</code></pre>\n"));
+//!
+//! // the stylesheet going with the classes
+//! let css = SyntectHighlighter::css("InspiredGitHub").unwrap();
+//! assert!(css.contains(".source"));
+//! ```
+
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{
+    css_for_theme_with_class_style, highlighted_html_for_string, ClassStyle, ClassedHTMLGenerator,
+};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::escape::escape_html;
+use crate::html::Highlight;
+
+/// Highlights code with syntect's default syntaxes.
+pub struct SyntectHighlighter {
+    syntaxes: SyntaxSet,
+    /// The theme of the inline styles, `None` for classes.
+    theme: Option<Theme>,
+}
+
+impl SyntectHighlighter {
+    /// Creates a highlighter writing inline styles with the named theme of
+    /// syntect's default themes, like `base16-ocean.dark` or
+    /// `InspiredGitHub`. Returns `None` when there is no such theme.
+    pub fn with_theme(name: &str) -> Option<Self> {
+        let theme = ThemeSet::load_defaults().themes.remove(name)?;
+        Some(SyntectHighlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: Some(theme),
+        })
+    }
+
+    /// Creates a highlighter writing classes, to be styled by a stylesheet
+    /// like the one of [`css`](#method.css).
+    pub fn with_classes() -> Self {
+        SyntectHighlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: None,
+        }
+    }
+
+    /// The names of syntect's default themes.
+    pub fn themes() -> Vec<String> {
+        ThemeSet::load_defaults().themes.keys().cloned().collect()
+    }
+
+    /// Returns the stylesheet of the named theme for the classes written by
+    /// [`with_classes`](#method.with_classes) highlighters, or `None` when
+    /// there is no such theme.
+    pub fn css(theme: &str) -> Option<String> {
+        let themes = ThemeSet::load_defaults();
+        let theme = themes.themes.get(theme)?;
+        Some(css_for_theme_with_class_style(theme, ClassStyle::Spaced))
+    }
+
+    /// Returns the markup of a code block in `lang`, `<pre>` element
+    /// included, or `None` when the language is unknown.
+    pub fn highlight(&self, lang: &str, code: &str) -> Option<String> {
+        if lang.is_empty() {
+            return None;
+        }
+        let syntax = self.syntaxes.find_syntax_by_token(lang)?;
+        if let Some(ref theme) = self.theme {
+            return Some(highlighted_html_for_string(
+                code,
+                &self.syntaxes,
+                syntax,
+                theme,
+            ));
+        }
+        let mut generator =
+            ClassedHTMLGenerator::new_with_class_style(syntax, &self.syntaxes, ClassStyle::Spaced);
+        for line in LinesWithEndings::from(code) {
+            generator.parse_html_for_line_which_includes_newline(line);
+        }
+        let mut html = String::from("<pre><code class=\"language-");
+        escape_html(&mut html, lang).unwrap();
+        html.push_str("\">");
+        html.push_str(&generator.finalize());
+        html.push_str("</code></pre>");
+        Some(html)
+    }
+
+    /// Returns a renderer highlighting the code blocks it knows the
+    /// language of.
+    pub fn render(&self) -> Highlight<impl FnMut(&str, &str) -> Option<String> + '_> {
+        Highlight::new(move |lang: &str, code: &str| self.highlight(lang, code))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn themes_and_languages() {
+        assert!(SyntectHighlighter::with_theme("no such theme").is_none());
+        assert!(SyntectHighlighter::css("no such theme").is_none());
+        assert!(SyntectHighlighter::themes().contains(&"InspiredGitHub".to_owned()));
+
+        let highlighter = SyntectHighlighter::with_theme("InspiredGitHub").unwrap();
+        let html = highlighter.highlight("rs", "let x = 1;\n").unwrap();
+        assert!(html.starts_with("<pre style=\"background-color:#ffffff;\">"));
+        assert!(html.contains("<span style=\""));
+        assert_eq!(None, highlighter.highlight("no such language", "x"));
+        assert_eq!(None, highlighter.highlight("", "x"));
+    }
+}
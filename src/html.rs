@@ -20,25 +20,701 @@
 
 //! HTML renderer that takes an iterator of events as input.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt::{self, Arguments};
 use std::io::{self, Write};
+use std::ops::Range;
 
-use crate::escape::{escape_href, escape_html, StrWrite, WriteWrapper};
+use crate::escape::{
+    escape_href, escape_html, write_fmt_with_str, FmtWriter, HtmlEscaper, StrWrite, WriteWrapper,
+};
 use crate::strings::CowStr;
+use crate::utils::footnotes::footnote_order;
+use crate::utils::front_matter::{split_front_matter, FrontMatter, FrontMatterKind};
+use crate::utils::push_event_text;
+use crate::utils::rewrite::{BaseUrl, LinkKind};
+use crate::utils::slug::{SlugStyle, Slugger};
 use crate::Event::*;
-use crate::{Alignment, CodeBlockKind, Event, LinkType, Tag};
+use crate::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag};
 
 enum TableState {
     Head,
     Body,
 }
 
-struct HtmlWriter<'a, I, W> {
-    /// Iterator supplying events.
-    iter: I,
+/// Options of the HTML writer.
+///
+/// The defaults give the standard rendering.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::slug::Slugger;
+/// use pulldown_cmark::{html, Parser};
+///
+/// let options = html::Options {
+///     heading_ids: Some(Slugger::default()),
+///     heading_anchor_links: true,
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// html::push_html_ext(&mut html_buf, Parser::new("# Hello world\n"), &options);
+/// assert_eq!(
+///     html_buf,
+///     "<h1 id=\"hello-world\">Hello world<a class=\"anchor\" href=\"#hello-world\">¶</a></h1>\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Slugger used to give every heading an `id` attribute derived from its
+    /// text. Clones of a slugger share the slugs handed out, so passing a
+    /// clone of the slugger used by other adapters keeps ids unique across
+    /// them. An `id` attribute given to a heading by
+    /// [`Render::attributes`](trait.Render.html#method.attributes) is used
+    /// instead of the slug, and deduplicated the same way.
+    pub heading_ids: Option<Slugger>,
+    /// Whether headings with an id end with a `¶` link to themselves.
+    pub heading_anchor_links: bool,
+    /// Line positions of the source, to give block elements a
+    /// `data-sourcepos` attribute like cmark-gfm does. Only used when
+    /// rendering from an offset iterator, with
+    /// [`push_html_with_offsets`](fn.push_html_with_offsets.html) or
+    /// [`write_html_with_offsets`](fn.write_html_with_offsets.html).
+    pub source_positions: Option<SourcePositions>,
+    /// Number of spaces to indent nested block elements by. When set, the
+    /// tags of table rows and sections are also put on lines of their own.
+    /// Raw HTML and the contents of code blocks are not indented.
+    pub indent: Option<usize>,
+    /// Wraps the output in a complete HTML document.
+    pub document: Option<Document>,
+    /// How footnotes are rendered.
+    pub footnotes: FootnoteStyle,
+    /// Renders footnote references as `[1]`, `[2]`, and uses the numbers
+    /// instead of the labels in ids and links. Footnotes are numbered like
+    /// [`RenumberFootnotes`](../utils/footnotes/struct.RenumberFootnotes.html)
+    /// does, and [`footnote_order`](../utils/footnotes/fn.footnote_order.html)
+    /// gives the same mapping from labels to numbers. The whole stream is
+    /// buffered to number the footnotes before rendering.
+    pub numbered_footnotes: bool,
+    /// How the alignment of table cells is written.
+    pub table_alignment: TableAlignment,
+    /// Prefix of the class naming the language of fenced code blocks, such
+    /// as `lang-` or an empty string. `language-` is used when `None`.
+    pub code_class_prefix: Option<String>,
+    /// Gives images `loading="lazy"` and `decoding="async"` attributes. To
+    /// set the attributes per image, use
+    /// [`InjectImageAttributes`](../utils/images/struct.InjectImageAttributes.html)
+    /// or [`Render::attributes`](trait.Render.html#method.attributes).
+    pub lazy_images: bool,
+    /// Extra attributes for links to other sites.
+    pub external_links: Option<ExternalLinks>,
+    /// Base URL that relative link and image destinations are resolved
+    /// against, like
+    /// [`resolve_links`](../utils/rewrite/fn.resolve_links.html) does.
+    /// Renderers are handed the resolved destinations.
+    pub base_url: Option<BaseUrl>,
+    /// Safe mode for untrusted input: raw HTML is escaped, so it shows up as
+    /// text, and link and image destinations with a dangerous scheme, like
+    /// `javascript:`, are replaced by empty ones. Images may still use `data:`
+    /// URLs of PNG, GIF, JPEG and WebP images.
+    pub safe: bool,
+    /// Renders wiki links, like `[[Page]]` or `[[Page|label]]`, as links to
+    /// the URLs of their pages. The parser finds wiki links with
+    /// [`ENABLE_WIKILINKS`](../struct.Options.html#associatedconstant.ENABLE_WIKILINKS).
+    pub wikilinks: Option<WikiLinks>,
+    /// Number of levels to shift headings down by, so that with an offset of
+    /// one, `# Title` is rendered as `<h2>`. Headings shifted past the sixth
+    /// level are rendered as `<h6>`. Renderers are handed the shifted levels.
+    pub heading_offset: usize,
+    /// Writes the characters produced by smart punctuation, like `“` or `—`,
+    /// as named character references, like `&ldquo;` or `&mdash;`, for
+    /// consumers that do not handle UTF-8 reliably.
+    pub punctuation_entities: bool,
+    /// Markup written for thematic breaks instead of `<hr />`, like
+    /// `<hr class="fancy">`. The writer puts it on a line of its own.
+    pub rule: Option<String>,
+    /// Markup written for hard line breaks instead of `<br />`, like
+    /// `<br>`. The writer starts a new line after it.
+    pub hard_break: Option<String>,
+    /// Adds attributes for assistive technology: header cells of tables get
+    /// a `scope` attribute, and task list checkboxes an `aria-label` telling
+    /// whether the task is done.
+    pub accessibility: bool,
+    /// Renders the first cell of every table body row as a header cell of
+    /// the row, `<th scope="row">`.
+    pub table_row_headers: bool,
+    /// Wraps every heading and the content following it in a `<section>`
+    /// element, nested by heading level: a section ends before the next
+    /// heading of the same or a higher level. Only headings at the top level
+    /// of the document start sections.
+    pub sections: bool,
+    /// What happens to the front matter of documents rendered with
+    /// [`push_html_with_front_matter`](fn.push_html_with_front_matter.html).
+    pub front_matter: FrontMatterHtml,
+    /// Leaves out the line breaks the writer puts between elements, so that
+    /// the output is as small as possible. Line breaks that are part of the
+    /// content, in text, code blocks and raw HTML, are kept. Takes precedence
+    /// over [`indent`](#structfield.indent).
+    pub minify: bool,
+    /// Wraps every line of code blocks in a
+    /// `<span class="line" data-line="1">` element holding its number, so
+    /// that style sheets can show line numbers.
+    pub code_line_numbers: bool,
+    /// How the description of an image is turned into its alt text.
+    pub alt_text: AltText,
+    /// Line breaks at the end of the output.
+    pub trailing_newline: TrailingNewline,
+    /// Escapes only the characters that need it: `<` and `&` in text, and
+    /// `"` as well in attribute values and code spans. This leaves `>`
+    /// unescaped, which makes large documents smaller.
+    pub minimal_escaping: bool,
+}
+
+/// Line breaks at the end of the output, for
+/// [`Options::trailing_newline`](struct.Options.html#structfield.trailing_newline).
+/// The writer holds back line breaks until it knows whether more output
+/// follows them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailingNewline {
+    /// Output ends the way the last element is written, usually with a line
+    /// break.
+    AsIs,
+    /// Output that is not empty ends with exactly one line break.
+    One,
+    /// Output ends without line breaks.
+    Strip,
+}
+
+impl Default for TrailingNewline {
+    fn default() -> Self {
+        TrailingNewline::AsIs
+    }
+}
+
+/// Flattening of image descriptions into alt text, for
+/// [`Options::alt_text`](struct.Options.html#structfield.alt_text).
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::html::{self, AltText};
+/// use pulldown_cmark::Parser;
+///
+/// let options = html::Options {
+///     alt_text: AltText::Markers,
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// html::push_html_ext(&mut html_buf, Parser::new("![*a* `b`](c.png)"), &options);
+/// assert_eq!(html_buf, "<p><img src=\"c.png\" alt=\"*a* `b`\" /></p>\n");
+/// ```
+#[derive(Clone, Copy)]
+pub enum AltText {
+    /// The text of the description, without markup.
+    Plain,
+    /// The text of the description, with the markdown markers of emphasis,
+    /// strong emphasis, strikethrough and code spans kept around their text.
+    Markers,
+    /// The text returned by a function given the events of the description.
+    Custom(fn(&[Event]) -> String),
+}
+
+impl Default for AltText {
+    fn default() -> Self {
+        AltText::Plain
+    }
+}
+
+// Written out because function pointers taking references only implement
+// `Debug` on newer compilers.
+impl fmt::Debug for AltText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AltText::Plain => f.write_str("Plain"),
+            AltText::Markers => f.write_str("Markers"),
+            AltText::Custom(func) => f
+                .debug_tuple("Custom")
+                .field(&(*func as *const ()))
+                .finish(),
+        }
+    }
+}
+
+/// Rendering of front matter, for
+/// [`Options::front_matter`](struct.Options.html#structfield.front_matter).
+#[derive(Clone, Copy)]
+pub enum FrontMatterHtml {
+    /// Front matter is left out of the output.
+    Skip,
+    /// Front matter is rendered like a fenced code block in its format,
+    /// `<pre><code class="language-yaml">`.
+    Pre,
+    /// Front matter is rendered by a function returning raw HTML.
+    Callback(fn(&FrontMatter) -> String),
+}
+
+impl Default for FrontMatterHtml {
+    fn default() -> Self {
+        FrontMatterHtml::Skip
+    }
+}
+
+impl fmt::Debug for FrontMatterHtml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrontMatterHtml::Skip => f.write_str("Skip"),
+            FrontMatterHtml::Pre => f.write_str("Pre"),
+            FrontMatterHtml::Callback(func) => f
+                .debug_tuple("Callback")
+                .field(&(*func as *const ()))
+                .finish(),
+        }
+    }
+}
+
+/// Rendering of wiki links, for
+/// [`Options::wikilinks`](struct.Options.html#structfield.wikilinks).
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Options, Parser};
+///
+/// fn resolve(page: &str) -> Option<String> {
+///     if page == "Home" {
+///         Some("/".to_owned())
+///     } else {
+///         None
+///     }
+/// }
+///
+/// let options = html::Options {
+///     wikilinks: Some(html::WikiLinks {
+///         resolver: Some(resolve),
+///         unresolved_class: Some("new".to_owned()),
+///         ..html::WikiLinks::new("/notes/{}/")
+///     }),
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// let parser = Parser::new_ext("[[Home|Start]] and [[Reading List]]", Options::ENABLE_WIKILINKS);
+/// html::push_html_ext(&mut html_buf, parser, &options);
+/// assert_eq!(
+///     html_buf,
+///     "<p><a href=\"/\">Start</a> and \
+///      <a href=\"/notes/reading-list/\" class=\"new\">Reading List</a></p>\n"
+/// );
+/// ```
+#[derive(Clone)]
+pub struct WikiLinks {
+    /// URL of a page, where `{}` stands for the slug of the page name.
+    pub template: String,
+    /// Slugification of page names for the template.
+    pub slug_style: SlugStyle,
+    /// Function returning the URL of a page, or `None` for pages that do not
+    /// exist. Links to those point to the template URL. When unset, every
+    /// page has the template URL.
+    pub resolver: Option<fn(&str) -> Option<String>>,
+    /// Class of links to pages the resolver returns `None` for.
+    pub unresolved_class: Option<String>,
+}
+
+impl fmt::Debug for WikiLinks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WikiLinks")
+            .field("template", &self.template)
+            .field("slug_style", &self.slug_style)
+            .field(
+                "resolver",
+                &self.resolver.map(|resolver| resolver as *const ()),
+            )
+            .field("unresolved_class", &self.unresolved_class)
+            .finish()
+    }
+}
+
+impl WikiLinks {
+    /// Wiki links to `template`, like `/notes/{}/`, with kebab case slugs.
+    pub fn new(template: &str) -> Self {
+        WikiLinks {
+            template: template.to_owned(),
+            slug_style: SlugStyle::Kebab,
+            resolver: None,
+            unresolved_class: None,
+        }
+    }
+
+    /// Returns the template URL of a page.
+    pub fn page_url(&self, page: &str) -> String {
+        self.template.replace("{}", &self.slug_style.slugify(page))
+    }
+
+    /// Returns the URL of a page, and whether the resolver knows the page.
+    fn url(&self, page: &str) -> (String, bool) {
+        match self.resolver.map(|resolver| resolver(page)) {
+            Some(Some(url)) => (url, true),
+            Some(None) => (self.page_url(page), false),
+            None => (self.page_url(page), true),
+        }
+    }
+}
+
+/// Rendering of table cell alignment, for
+/// [`Options::table_alignment`](struct.Options.html#structfield.table_alignment).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableAlignment {
+    /// An `align="left"` attribute.
+    Attribute,
+    /// An inline `style="text-align: left"` attribute.
+    Style,
+    /// A `class="text-align-left"` attribute, for setups whose content
+    /// security policy forbids inline styles.
+    Class,
+}
+
+impl Default for TableAlignment {
+    fn default() -> Self {
+        TableAlignment::Attribute
+    }
+}
+
+/// Rendering of footnotes, for
+/// [`Options::footnotes`](struct.Options.html#structfield.footnotes).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FootnoteStyle {
+    /// Definitions are rendered as `<div class="footnote-definition">` where
+    /// they appear in the stream. Footnotes are numbered in order of first
+    /// appearance of either a reference or the definition.
+    InPlace,
+    /// Rendering like GitHub's: definitions are collected in a
+    /// `<section class="footnotes">` at the end of the document, as a list
+    /// ordered by first reference, and end with `↩` links back to each of
+    /// their references. Definitions that are never referenced are dropped.
+    Gfm,
+    /// Definitions are rendered like with `InPlace`, but collected in a
+    /// `<section class="footnotes">` at the end of the document, in the order
+    /// they appear in. Footnotes are numbered in order of first reference,
+    /// followed by the definitions that are never referenced.
+    End,
+}
+
+impl Default for FootnoteStyle {
+    fn default() -> Self {
+        FootnoteStyle::InPlace
+    }
+}
+
+/// The head of a complete HTML document, for
+/// [`Options::document`](struct.Options.html#structfield.document).
+///
+/// The document declares the UTF-8 charset, and the rendered events form its
+/// body.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+///
+/// let options = html::Options {
+///     document: Some(html::Document {
+///         title: Some("Notes".to_owned()),
+///         stylesheets: vec!["style.css".to_owned()],
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// html::push_html_ext(&mut html_buf, Parser::new("Hi\n"), &options);
+/// assert_eq!(
+///     html_buf,
+///     "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+///      <title>Notes</title>\n<link rel=\"stylesheet\" href=\"style.css\">\n\
+///      </head>\n<body>\n<p>Hi</p>\n</body>\n</html>\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// The language of the document, for the `lang` attribute of `<html>`.
+    pub lang: Option<String>,
+    /// The contents of the `<title>` element, omitted if `None`.
+    pub title: Option<String>,
+    /// URLs of style sheets to link to, in order.
+    pub stylesheets: Vec<String>,
+}
+
+/// Attributes of links leaving the site, for
+/// [`Options::external_links`](struct.Options.html#structfield.external_links).
+///
+/// A link is external when its destination is an absolute URL, like
+/// `https://example.org/` or `//example.org/`, with an origin other than the
+/// site's. Relative links, fragments and email links are never external.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+///
+/// let options = html::Options {
+///     external_links: Some(html::ExternalLinks::new("https://example.com")),
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// let parser = Parser::new("[home](https://example.com/) [spam](https://example.org/)");
+/// html::push_html_ext(&mut html_buf, parser, &options);
+/// assert_eq!(
+///     html_buf,
+///     "<p><a href=\"https://example.com/\">home</a> \
+///      <a href=\"https://example.org/\" rel=\"nofollow noopener\">spam</a></p>\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExternalLinks {
+    /// URL of the site, of which only the scheme and host matter.
+    pub site: String,
+    /// Value of the `rel` attribute of external links.
+    pub rel: Option<String>,
+    /// Whether external links get a `target="_blank"` attribute, so that
+    /// they open in a new tab.
+    pub target_blank: bool,
+}
+
+impl ExternalLinks {
+    /// Attributes for links leaving `site`: `rel="nofollow noopener"`.
+    pub fn new(site: &str) -> Self {
+        ExternalLinks {
+            site: site.to_owned(),
+            rel: Some("nofollow noopener".to_owned()),
+            target_blank: false,
+        }
+    }
+
+    /// Returns whether a link to `dest` leaves the site.
+    pub fn is_external(&self, dest: &str) -> bool {
+        let (scheme, host) = match split_origin(dest) {
+            Some(origin) => origin,
+            None => return false,
+        };
+        match split_origin(&self.site) {
+            Some((site_scheme, site_host)) => {
+                !host.eq_ignore_ascii_case(site_host)
+                    || !(scheme.is_empty() || scheme.eq_ignore_ascii_case(site_scheme))
+            }
+            None => true,
+        }
+    }
+}
+
+/// Splits the scheme and authority from an absolute URL. The scheme is empty
+/// for scheme relative URLs like `//example.com/`.
+fn split_origin(url: &str) -> Option<(&str, &str)> {
+    let (scheme, rest) = if url.starts_with("//") {
+        ("", &url[2..])
+    } else {
+        let colon = url.find(':')?;
+        let scheme = &url[..colon];
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b));
+        if !valid {
+            return None;
+        }
+        let rest = &url[colon + 1..];
+        if !rest.starts_with("//") {
+            return None;
+        }
+        (scheme, &rest[2..])
+    };
+    let end = rest.find(&['/', '?', '#'][..]).unwrap_or(rest.len());
+    Some((scheme, &rest[..end]))
+}
+
+/// Returns whether a destination has a scheme that can run code, or show
+/// content other than images, like `javascript:` or `data:`.
+pub(crate) fn is_dangerous_url(kind: LinkKind, dest: &str) -> bool {
+    // browsers ignore white space and control characters in the scheme
+    let scheme: String = dest
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .take_while(|&c| c != ':')
+        .take(11)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let rest = match dest.find(':') {
+        Some(ix) => &dest[ix + 1..],
+        None => return false,
+    };
+    match scheme.as_str() {
+        "javascript" | "vbscript" | "file" => true,
+        "data" => {
+            let image = ["image/png", "image/gif", "image/jpeg", "image/webp"]
+                .iter()
+                .any(|mime| {
+                    rest.len() >= mime.len() && rest[..mime.len()].eq_ignore_ascii_case(mime)
+                });
+            kind != LinkKind::Image || !image
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether `name` can be written as the name of an attribute.
+fn is_attribute_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"_.:-".contains(&b))
+}
+
+/// Returns whether an attribute can run code or change the look of the
+/// page, and is left out in safe mode.
+fn is_unsafe_attribute(name: &str, value: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    match name.as_str() {
+        "style" | "srcdoc" => true,
+        "src" | "poster" => is_dangerous_url(LinkKind::Image, value),
+        "href" | "action" | "formaction" | "cite" | "xlink:href" => {
+            is_dangerous_url(LinkKind::Link, value)
+        }
+        _ => name.starts_with("on"),
+    }
+}
+
+/// Line start offsets of a source text, used to turn byte offsets into line
+/// and column numbers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourcePositions {
+    line_starts: Vec<usize>,
+}
+
+impl SourcePositions {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            source
+                .bytes()
+                .enumerate()
+                .filter(|&(_, b)| b == b'\n')
+                .map(|(ix, _)| ix + 1),
+        );
+        SourcePositions { line_starts }
+    }
+
+    /// Returns the line and column of a byte offset, both starting at one.
+    /// Columns count bytes.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    /// Returns the position of the last character of a range, not counting
+    /// a final line ending.
+    fn end_position(&self, range: Range<usize>) -> (usize, usize) {
+        let mut last = range.end.saturating_sub(1);
+        if last > range.start && self.line_starts.binary_search(&range.end).is_ok() {
+            last -= 1;
+        }
+        self.position(last.max(range.start))
+    }
+}
+
+/// Maps a range of the HTML output to the range of the input it was rendered
+/// from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceMapping {
+    /// Byte range in the output.
+    pub output: Range<usize>,
+    /// Byte range in the markdown source.
+    pub input: Range<usize>,
+}
+
+/// The id given to a heading by
+/// [`Options::heading_ids`](struct.Options.html#structfield.heading_ids),
+/// returned by [`push_html_with_heading_ids`](fn.push_html_with_heading_ids.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadingId {
+    /// Level of the rendered heading, after
+    /// [`heading_offset`](struct.Options.html#structfield.heading_offset).
+    pub level: HeadingLevel,
+    /// Text of the heading, that the id was derived from.
+    pub text: String,
+    /// The deduplicated id.
+    pub id: String,
+}
 
-    /// Writer to write to.
+/// Writer that keeps count of the bytes written.
+struct CountingWriter<W> {
     writer: W,
+    count: usize,
+    trailing_newline: TrailingNewline,
+    /// Line breaks held back, unless `trailing_newline` is `AsIs`.
+    held_newlines: usize,
+    /// Whether anything other than line breaks has been written.
+    written: bool,
+}
+
+impl<W: StrWrite> CountingWriter<W> {
+    fn new(writer: W, trailing_newline: TrailingNewline) -> Self {
+        CountingWriter {
+            writer,
+            count: 0,
+            trailing_newline,
+            held_newlines: 0,
+            written: false,
+        }
+    }
+
+    fn put(&mut self, s: &str) -> Result<(), W::Error> {
+        self.count += s.len();
+        self.writer.write_str(s)
+    }
+
+    /// Ends the output with the line breaks asked for.
+    fn finish(&mut self) -> Result<(), W::Error> {
+        if self.trailing_newline == TrailingNewline::One && self.written {
+            self.put("\n")?;
+        }
+        self.held_newlines = 0;
+        Ok(())
+    }
+}
+
+impl<W: StrWrite> StrWrite for CountingWriter<W> {
+    type Error = W::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), W::Error> {
+        if self.trailing_newline == TrailingNewline::AsIs {
+            return self.put(s);
+        }
+        let content = s.trim_end_matches('\n');
+        if !content.is_empty() {
+            for _ in 0..self.held_newlines {
+                self.put("\n")?;
+            }
+            self.held_newlines = 0;
+            self.written = true;
+            self.put(content)?;
+        }
+        self.held_newlines += s.len() - content.len();
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: Arguments) -> Result<(), W::Error> {
+        write_fmt_with_str(self, args)
+    }
+}
+
+/// The output of the HTML writer, and the state it keeps while rendering.
+///
+/// All output written through the context, whether with its methods or as a
+/// [`StrWrite`](../escape/trait.StrWrite.html), is tracked so that block
+/// elements can start on a new line.
+pub struct HtmlContext<'a, W> {
+    writer: CountingWriter<W>,
 
     /// Whether or not the last write wrote a newline.
     end_newline: bool,
@@ -47,34 +723,79 @@ struct HtmlWriter<'a, I, W> {
     table_alignments: Vec<Alignment>,
     table_cell_index: usize,
     numbers: HashMap<CowStr<'a>, usize>,
+    references: HashMap<CowStr<'a>, usize>,
+
+    options: Options,
+    heading_id: Option<String>,
+    /// Source range of the event being rendered, if known.
+    range: Option<Range<usize>>,
+    /// Nesting depth of indented elements.
+    depth: usize,
+    /// Extra attributes of the element being opened.
+    attributes: Attributes,
+    /// Number of lines started in the current code block, with
+    /// [`Options::code_line_numbers`](struct.Options.html#structfield.code_line_numbers).
+    code_line: Option<usize>,
+    /// Whether the current code block line has been started and not ended.
+    code_line_open: bool,
 }
 
-impl<'a, I, W> HtmlWriter<'a, I, W>
-where
-    I: Iterator<Item = Event<'a>>,
-    W: StrWrite,
-{
-    fn new(iter: I, writer: W) -> Self {
-        Self {
-            iter,
-            writer,
+impl<'a, W: StrWrite> HtmlContext<'a, W> {
+    fn new(writer: W, options: &Options) -> Self {
+        HtmlContext {
+            writer: CountingWriter::new(writer, options.trailing_newline),
             end_newline: true,
             table_state: TableState::Head,
             table_alignments: vec![],
             table_cell_index: 0,
             numbers: HashMap::new(),
+            references: HashMap::new(),
+            options: options.clone(),
+            heading_id: None,
+            range: None,
+            depth: 0,
+            attributes: Attributes::default(),
+            code_line: None,
+            code_line_open: false,
         }
     }
 
-    /// Writes a new line.
-    fn write_newline(&mut self) -> io::Result<()> {
-        self.end_newline = true;
-        self.writer.write_str("\n")
+    /// Returns the options the writer was created with.
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Returns the id of the current heading, if
+    /// [`Options::heading_ids`](struct.Options.html#structfield.heading_ids)
+    /// is set.
+    pub fn heading_id(&self) -> Option<&str> {
+        self.heading_id.as_deref()
     }
 
-    /// Writes a buffer, and tracks whether or not a newline was written.
+    /// Writes a string as is. With
+    /// [`Options::indent`](struct.Options.html#structfield.indent) set, a
+    /// string starting a line is indented first.
     #[inline]
-    fn write(&mut self, s: &str) -> io::Result<()> {
+    pub fn write(&mut self, s: &str) -> Result<(), W::Error> {
+        if self.options.minify {
+            for part in s.split('\n') {
+                self.write_verbatim(part)?;
+            }
+            return Ok(());
+        }
+        if let Some(width) = self.options.indent {
+            if self.end_newline && self.depth > 0 && !s.is_empty() && !s.starts_with('\n') {
+                for _ in 0..width * self.depth {
+                    self.writer.write_str(" ")?;
+                }
+            }
+        }
+        self.write_verbatim(s)
+    }
+
+    /// Writes a string as is, without indenting it. This is for content
+    /// whose whitespace is significant, like the contents of `<pre>`.
+    pub fn write_verbatim(&mut self, s: &str) -> Result<(), W::Error> {
         self.writer.write_str(s)?;
 
         if !s.is_empty() {
@@ -83,305 +804,1631 @@ where
         Ok(())
     }
 
-    fn run(mut self) -> io::Result<()> {
-        while let Some(event) = self.iter.next() {
-            match event {
-                Start(tag) => {
-                    self.start_tag(tag)?;
-                }
-                End(tag) => {
-                    self.end_tag(tag)?;
-                }
-                Text(text) => {
-                    escape_html(&mut self.writer, &text)?;
-                    self.end_newline = text.ends_with('\n');
-                }
-                Code(text) => {
-                    self.write("<code>")?;
-                    escape_html(&mut self.writer, &text)?;
-                    self.write("</code>")?;
-                }
-                Html(html) => {
-                    self.write(&html)?;
-                }
-                SoftBreak => {
-                    self.write_newline()?;
-                }
-                HardBreak => {
-                    self.write("<br />\n")?;
-                }
-                Rule => {
-                    if self.end_newline {
-                        self.write("<hr />\n")?;
-                    } else {
-                        self.write("\n<hr />\n")?;
-                    }
-                }
-                FootnoteReference(name) => {
-                    let len = self.numbers.len() + 1;
-                    self.write("<sup class=\"footnote-reference\"><a href=\"#")?;
-                    escape_html(&mut self.writer, &name)?;
-                    self.write("\">")?;
-                    let number = *self.numbers.entry(name).or_insert(len);
-                    write!(&mut self.writer, "{}", number)?;
-                    self.write("</a></sup>")?;
-                }
-                TaskListMarker(true) => {
-                    self.write("<input disabled=\"\" type=\"checkbox\" checked=\"\"/>\n")?;
+    /// Writes text, escaping HTML special characters.
+    pub fn write_escaped(&mut self, s: &str) -> Result<(), W::Error> {
+        if self.options.minify {
+            // line breaks in text are content
+            if self.options.minimal_escaping {
+                HtmlEscaper::minimal()
+                    .with_char('"')
+                    .escape(&mut self.writer, s)?;
+            } else {
+                escape_html(&mut self.writer, s)?;
+            }
+            if !s.is_empty() {
+                self.end_newline = s.ends_with('\n');
+            }
+            return Ok(());
+        }
+        if self.options.minimal_escaping {
+            HtmlEscaper::minimal().with_char('"').escape(&mut *self, s)
+        } else {
+            escape_html(&mut *self, s)
+        }
+    }
+
+    /// Writes text the way text events are written: escaped, and with smart
+    /// punctuation as character references when
+    /// [`Options::punctuation_entities`](struct.Options.html#structfield.punctuation_entities)
+    /// is set.
+    pub fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        if self.code_line.is_some() {
+            return self.write_code_lines(text);
+        }
+        self.write_text_run(text)
+    }
+
+    /// Writes text of a code block with every line wrapped in a numbered
+    /// span.
+    fn write_code_lines(&mut self, text: &str) -> Result<(), W::Error> {
+        let mut rest = text;
+        while !rest.is_empty() {
+            if !self.code_line_open {
+                let number = self.code_line.unwrap() + 1;
+                self.code_line = Some(number);
+                self.code_line_open = true;
+                self.write_verbatim(&format!("<span class=\"line\" data-line=\"{}\">", number))?;
+            }
+            match rest.find('\n') {
+                Some(ix) => {
+                    self.write_text_run(&rest[..ix])?;
+                    self.end_code_line()?;
+                    self.write_verbatim("\n")?;
+                    rest = &rest[ix + 1..];
                 }
-                TaskListMarker(false) => {
-                    self.write("<input disabled=\"\" type=\"checkbox\"/>\n")?;
+                None => {
+                    self.write_text_run(rest)?;
+                    rest = "";
                 }
             }
         }
         Ok(())
     }
 
+    /// Ends the current code block line, if it has been started.
+    fn end_code_line(&mut self) -> Result<(), W::Error> {
+        if self.code_line_open {
+            self.code_line_open = false;
+            self.write_verbatim("</span>")?;
+        }
+        Ok(())
+    }
+
+    /// Writes text content, escaped.
+    fn write_text_escaped(&mut self, text: &str) -> Result<(), W::Error> {
+        if self.options.minimal_escaping {
+            HtmlEscaper::minimal().escape(&mut self.writer, text)
+        } else {
+            escape_html(&mut self.writer, text)
+        }
+    }
+
+    fn write_text_run(&mut self, text: &str) -> Result<(), W::Error> {
+        if !self.options.punctuation_entities {
+            self.write_text_escaped(text)?;
+            self.end_newline = text.ends_with('\n');
+            return Ok(());
+        }
+        let mut mark = 0;
+        for (ix, c) in text.char_indices() {
+            let entity = match c {
+                '‘' => "&lsquo;",
+                '’' => "&rsquo;",
+                '“' => "&ldquo;",
+                '”' => "&rdquo;",
+                '–' => "&ndash;",
+                '—' => "&mdash;",
+                '…' => "&hellip;",
+                _ => continue,
+            };
+            self.write_text_escaped(&text[mark..ix])?;
+            self.writer.write_str(entity)?;
+            mark = ix + c.len_utf8();
+        }
+        self.write_text_escaped(&text[mark..])?;
+        self.end_newline = text.ends_with('\n');
+        Ok(())
+    }
+
+    /// Writes a URL for use in an `href` or `src` attribute.
+    pub fn write_escaped_href(&mut self, s: &str) -> Result<(), W::Error> {
+        escape_href(&mut *self, s)
+    }
+
+    /// Writes a new line, unless the output is empty or already ends with one.
+    pub fn ensure_newline(&mut self) -> Result<(), W::Error> {
+        if self.end_newline {
+            Ok(())
+        } else {
+            self.write("\n")
+        }
+    }
+
+    /// Writes a new line when indenting, unless the output is empty or
+    /// already ends with one. This puts tags that are otherwise written on
+    /// the same line on lines of their own.
+    pub fn pretty_break(&mut self) -> Result<(), W::Error> {
+        if self.options.indent.is_some() {
+            self.ensure_newline()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Increases the indentation of the following lines by one level.
+    pub fn indent(&mut self) {
+        self.depth += 1;
+    }
+
+    /// Decreases the indentation of the following lines by one level.
+    pub fn dedent(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    /// Returns whether the output is empty or ends with a new line.
+    pub fn end_newline(&self) -> bool {
+        self.end_newline
+    }
+
+    /// Returns the number of a footnote. Footnotes are numbered in order of
+    /// first appearance, of either a reference or the definition, or as
+    /// described for
+    /// [`Options::numbered_footnotes`](struct.Options.html#structfield.numbered_footnotes).
+    pub fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    /// Returns the source range of the event being rendered, when the
+    /// writer was given an offset iterator.
+    pub fn source_range(&self) -> Option<Range<usize>> {
+        self.range.clone()
+    }
+
+    /// Writes a `data-sourcepos` attribute with the source position of the
+    /// event being rendered, preceded by a space. Writes nothing unless
+    /// [`Options::source_positions`](struct.Options.html#structfield.source_positions)
+    /// is set and the source range is known.
+    pub fn write_sourcepos(&mut self) -> Result<(), W::Error> {
+        let (positions, range) = match (&self.options.source_positions, &self.range) {
+            (Some(positions), Some(range)) => (positions, range),
+            _ => return Ok(()),
+        };
+        let (start_line, start_column) = positions.position(range.start);
+        let (end_line, end_column) = positions.end_position(range.clone());
+        write!(
+            self,
+            " data-sourcepos=\"{}:{}-{}:{}\"",
+            start_line, start_column, end_line, end_column
+        )
+    }
+
+    /// Writes a `class` attribute holding `class` and the extra classes
+    /// [`Render::attributes`](trait.Render.html#method.attributes) returned
+    /// for the element being opened, preceded by a space.
+    pub fn write_class(&mut self, class: &str) -> Result<(), W::Error> {
+        let classes = std::mem::take(&mut self.attributes.classes);
+        self.write(" class=\"")?;
+        self.write_escaped(class)?;
+        for extra in &classes {
+            self.write(" ")?;
+            self.write_escaped(extra)?;
+        }
+        self.write("\"")
+    }
+
+    /// Writes the extra attributes
+    /// [`Render::attributes`](trait.Render.html#method.attributes) returned
+    /// for the element being opened, each preceded by a space.
+    ///
+    /// Attributes whose names are not valid HTML attribute names are left
+    /// out. In [`safe`](struct.Options.html#structfield.safe) mode, so are
+    /// event handlers like `onclick`, `style`, and URL attributes holding a
+    /// URL that can run code.
+    pub fn write_attributes(&mut self) -> Result<(), W::Error> {
+        let attributes = std::mem::take(&mut self.attributes);
+        if !attributes.classes.is_empty() {
+            self.write(" class=\"")?;
+            self.write_escaped(&attributes.classes.join(" "))?;
+            self.write("\"")?;
+        }
+        for (name, value) in &attributes.attrs {
+            if !is_attribute_name(name) || self.options.safe && is_unsafe_attribute(name, value) {
+                continue;
+            }
+            self.write(" ")?;
+            self.write_escaped(name)?;
+            self.write("=\"")?;
+            self.write_escaped(value)?;
+            self.write("\"")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the identifier of a footnote used in element ids and links:
+    /// the escaped label, or the number with
+    /// [`Options::numbered_footnotes`](struct.Options.html#structfield.numbered_footnotes).
+    pub fn write_footnote_id(&mut self, name: &str) -> Result<(), W::Error> {
+        match self.numbers.get(name) {
+            Some(&number) if self.options.numbered_footnotes => write!(self, "{}", number),
+            _ => self.write_escaped(name),
+        }
+    }
+
+    /// Returns how many references to a footnote have been rendered so far.
+    pub fn footnote_reference_count(&self, name: &str) -> usize {
+        self.references.get(name).cloned().unwrap_or(0)
+    }
+
+    /// Returns whether the current table cell is a header cell.
+    pub fn in_table_head(&self) -> bool {
+        matches!(self.table_state, TableState::Head)
+    }
+
+    /// Returns whether the current table cell is the header cell of a body
+    /// row, with
+    /// [`Options::table_row_headers`](struct.Options.html#structfield.table_row_headers).
+    pub fn in_row_header(&self) -> bool {
+        self.options.table_row_headers && !self.in_table_head() && self.table_cell_index == 0
+    }
+
+    /// Returns the alignment of the current table cell's column.
+    pub fn table_cell_alignment(&self) -> Alignment {
+        self.table_alignments
+            .get(self.table_cell_index)
+            .cloned()
+            .unwrap_or(Alignment::None)
+    }
+
+    /// Applies the heading offset to a heading level.
+    fn shift_heading(&self, level: HeadingLevel) -> HeadingLevel {
+        let shifted = (level as usize).saturating_add(self.options.heading_offset);
+        HeadingLevel::try_from(shifted.min(6)).unwrap()
+    }
+
+    /// Resolves a link or image destination against the base URL, if any,
+    /// and removes it if it is dangerous in safe mode.
+    fn destination<'d>(&self, kind: LinkKind, link_type: LinkType, dest: CowStr<'d>) -> CowStr<'d> {
+        let dest = match self.options.base_url {
+            Some(ref base) if link_type != LinkType::Email => base.resolve(kind, dest),
+            _ => dest,
+        };
+        if self.options.safe && link_type != LinkType::Email && is_dangerous_url(kind, &dest) {
+            return "".into();
+        }
+        dest
+    }
+}
+
+impl<'a, W: StrWrite> StrWrite for HtmlContext<'a, W> {
+    type Error = W::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), W::Error> {
+        self.write(s)
+    }
+
+    fn write_fmt(&mut self, args: Arguments) -> Result<(), W::Error> {
+        write_fmt_with_str(self, args)
+    }
+}
+
+/// Extra attributes of an element, returned by
+/// [`Render::attributes`](trait.Render.html#method.attributes).
+///
+/// Classes are merged with the classes the writer gives the element.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Attributes {
+    pub classes: Vec<String>,
+    /// Names and values of other attributes, in order.
+    pub attrs: Vec<(String, String)>,
+}
+
+impl Attributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a class.
+    pub fn class(mut self, class: &str) -> Self {
+        self.classes.push(class.to_owned());
+        self
+    }
+
+    /// Adds an attribute.
+    pub fn attr(mut self, name: &str, value: &str) -> Self {
+        self.attrs.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+/// Renders like [`DefaultRender`](struct.DefaultRender.html), giving
+/// elements the extra attributes returned by a callback.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::html::{self, Attributes, WithAttributes};
+/// use pulldown_cmark::{Options, Parser, Tag};
+///
+/// let render = WithAttributes(|tag: &Tag| match tag {
+///     Tag::Table(_) => Attributes::new().class("table").class("table-striped"),
+///     _ => Attributes::new(),
+/// });
+/// let parser = Parser::new_ext("| a |\n|---|\n", Options::ENABLE_TABLES);
+/// let mut html_buf = String::new();
+/// html::push_html_with_renderer(&mut html_buf, parser, &html::Options::default(), render);
+/// assert_eq!(
+///     html_buf,
+///     "<table class=\"table table-striped\"><thead><tr><th>a</th></tr></thead><tbody>\n\
+///      </tbody></table>\n"
+/// );
+/// ```
+pub struct WithAttributes<F>(pub F);
+
+impl<F> Render for WithAttributes<F>
+where
+    F: FnMut(&Tag) -> Attributes,
+{
+    fn attributes(&mut self, tag: &Tag) -> Attributes {
+        (self.0)(tag)
+    }
+}
+
+/// Renders like [`DefaultRender`](struct.DefaultRender.html), but lets a
+/// callback write images, for example as a `<picture>` element or an `<img>`
+/// with a `srcset`.
+///
+/// The callback is called with the source, the title and the plain alt text
+/// of every image, none of them escaped. It returns the markup written
+/// instead of the `<img>` element, or `None` to write the usual element.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::escape::escape_href;
+/// use pulldown_cmark::html::{self, ImageHook};
+/// use pulldown_cmark::Parser;
+///
+/// let render = ImageHook(|src: &str, _title: &str, _alt: &str| {
+///     if !src.ends_with(".jpg") {
+///         return None;
+///     }
+///     let stem = &src[..src.len() - 4];
+///     let mut srcset = String::new();
+///     escape_href(&mut srcset, stem).unwrap();
+///     Some(format!("<img src=\"{0}.jpg\" srcset=\"{0}-2x.jpg 2x\">", srcset))
+/// });
+/// let parser = Parser::new("![Cat](cat.jpg) ![Dog](dog.png)");
+/// let mut html_buf = String::new();
+/// html::push_html_with_renderer(&mut html_buf, parser, &html::Options::default(), render);
+/// assert_eq!(
+///     html_buf,
+///     "<p><img src=\"cat.jpg\" srcset=\"cat-2x.jpg 2x\"> \
+///      <img src=\"dog.png\" alt=\"Dog\" /></p>\n"
+/// );
+/// ```
+pub struct ImageHook<F>(pub F);
+
+impl<F> Render for ImageHook<F>
+where
+    F: FnMut(&str, &str, &str) -> Option<String>,
+{
+    fn image<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        link_type: LinkType,
+        dest: &str,
+        title: &str,
+        alt: &str,
+    ) -> Result<(), W::Error> {
+        match (self.0)(dest, title, alt) {
+            Some(html) => ctx.write(&html),
+            None => DefaultRender.image(ctx, link_type, dest, title, alt),
+        }
+    }
+}
+
+/// Renders like [`DefaultRender`](struct.DefaultRender.html), but lets a
+/// callback write code blocks, to highlight them with a syntax highlighter.
+///
+/// The callback is called with the language of every code block, the first
+/// word of its info string or an empty string, and its code. It returns the
+/// markup of the complete block, `<pre>` element included, or `None` to write
+/// the usual block. With the `syntect` feature,
+/// [`SyntectHighlighter`](../highlight/struct.SyntectHighlighter.html) gives
+/// a ready-made one.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::html::{self, Highlight};
+/// use pulldown_cmark::Parser;
+///
+/// let render = Highlight::new(|lang: &str, code: &str| {
+///     if lang != "sh" {
+///         return None;
+///     }
+///     let lines: Vec<String> = code
+///         .lines()
+///         .map(|line| format!("<span class=\"prompt\">$</span> {}", line))
+///         .collect();
+///     Some(format!("<pre class=\"shell\">{}</pre>", lines.join("\n")))
+/// });
+/// let parser = Parser::new("```sh\nls\n```\n\n```\nplain\n```\n");
+/// let mut html_buf = String::new();
+/// html::push_html_with_renderer(&mut html_buf, parser, &html::Options::default(), render);
+/// assert_eq!(
+///     html_buf,
+///     "<pre class=\"shell\"><span class=\"prompt\">$</span> ls</pre>\n\
+///      <pre><code>plain\n</code></pre>\n"
+/// );
+/// ```
+pub struct Highlight<F> {
+    callback: F,
+    /// Code of the code block being rendered.
+    code: Option<String>,
+}
+
+impl<F> Highlight<F>
+where
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    pub fn new(callback: F) -> Self {
+        Highlight {
+            callback,
+            code: None,
+        }
+    }
+}
+
+impl<F> Render for Highlight<F>
+where
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    fn text<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        text: &str,
+    ) -> Result<(), W::Error> {
+        match self.code {
+            Some(ref mut code) => {
+                code.push_str(text);
+                Ok(())
+            }
+            None => DefaultRender.text(ctx, text),
+        }
+    }
+
+    fn start_code_block<'a, W: StrWrite>(
+        &mut self,
+        _ctx: &mut HtmlContext<'a, W>,
+        _kind: &CodeBlockKind<'a>,
+    ) -> Result<(), W::Error> {
+        self.code = Some(String::new());
+        Ok(())
+    }
+
+    fn end_code_block<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        kind: &CodeBlockKind<'a>,
+    ) -> Result<(), W::Error> {
+        let code = self.code.take().unwrap_or_default();
+        let lang = match kind {
+            CodeBlockKind::Fenced(info) => info.split(' ').next().unwrap(),
+            CodeBlockKind::Indented => "",
+        };
+        match (self.callback)(lang, &code) {
+            Some(html) => {
+                ctx.ensure_newline()?;
+                ctx.write_verbatim(&html)?;
+                ctx.ensure_newline()
+            }
+            None => {
+                DefaultRender.start_code_block(ctx, kind)?;
+                DefaultRender.text(ctx, &code)?;
+                DefaultRender.end_code_block(ctx, kind)
+            }
+        }
+    }
+}
+
+/// Rendering of events to HTML.
+///
+/// The HTML writer calls one method for every event, and for start and end
+/// tags one method per kind of tag. Every method defaults to the standard
+/// rendering, so an implementation only overrides the methods for the
+/// elements it renders differently. Images are rendered by the single method
+/// [`image`](#method.image), which gets the alt text of the image.
+///
+/// Table state and footnote numbers are tracked by the writer and are
+/// available from the [`HtmlContext`](struct.HtmlContext.html).
+///
+/// Before calling the method starting an element, the writer asks
+/// [`attributes`](#method.attributes) for extra attributes of the element.
+/// The default methods write them with
+/// [`HtmlContext::write_attributes`](struct.HtmlContext.html#method.write_attributes).
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::escape::StrWrite;
+/// use pulldown_cmark::html::{self, HtmlContext, Render};
+/// use pulldown_cmark::{CodeBlockKind, Parser};
+///
+/// struct Collapsible;
+///
+/// impl Render for Collapsible {
+///     fn start_code_block<'a, W: StrWrite>(
+///         &mut self,
+///         ctx: &mut HtmlContext<'a, W>,
+///         _kind: &CodeBlockKind<'a>,
+///     ) -> Result<(), W::Error> {
+///         ctx.ensure_newline()?;
+///         ctx.write("<details><pre><code>")
+///     }
+///
+///     fn end_code_block<'a, W: StrWrite>(
+///         &mut self,
+///         ctx: &mut HtmlContext<'a, W>,
+///         _kind: &CodeBlockKind<'a>,
+///     ) -> Result<(), W::Error> {
+///         ctx.write("</code></pre></details>\n")
+///     }
+/// }
+///
+/// let mut html_buf = String::new();
+/// let options = html::Options::default();
+/// html::push_html_with_renderer(&mut html_buf, Parser::new("    x < y\n"), &options, Collapsible);
+/// assert_eq!(html_buf, "<details><pre><code>x &lt; y\n</code></pre></details>\n");
+/// ```
+pub trait Render {
+    /// Returns extra attributes for the element opened for `tag`, like a
+    /// `class` for tables. Returns none by default.
+    fn attributes(&mut self, _tag: &Tag) -> Attributes {
+        Attributes::default()
+    }
+
+    fn text<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        text: &str,
+    ) -> Result<(), W::Error> {
+        ctx.write_text(text)
+    }
+
+    fn code<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        code: &str,
+    ) -> Result<(), W::Error> {
+        ctx.write("<code>")?;
+        ctx.write_escaped(code)?;
+        ctx.write("</code>")
+    }
+
+    fn html<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        html: &str,
+    ) -> Result<(), W::Error> {
+        ctx.write_verbatim(html)
+    }
+
+    fn soft_break<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write_verbatim("\n")
+    }
+
+    fn hard_break<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        match ctx.options.hard_break.clone() {
+            Some(markup) => ctx.write(&markup)?,
+            None => ctx.write("<br />")?,
+        }
+        ctx.write("\n")
+    }
+
+    fn rule<'a, W: StrWrite>(&mut self, ctx: &mut HtmlContext<'a, W>) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        if let Some(markup) = ctx.options.rule.clone() {
+            ctx.write(&markup)?;
+            return ctx.write("\n");
+        }
+        ctx.write("<hr")?;
+        ctx.write_sourcepos()?;
+        ctx.write(" />\n")
+    }
+
+    fn footnote_reference<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        name: CowStr<'a>,
+    ) -> Result<(), W::Error> {
+        if ctx.options.footnotes == FootnoteStyle::Gfm {
+            let count = ctx.footnote_reference_count(&name);
+            ctx.write("<sup class=\"footnote-ref\"><a href=\"#fn-")?;
+            ctx.write_footnote_id(&name)?;
+            ctx.write("\" id=\"fnref-")?;
+            ctx.write_footnote_id(&name)?;
+            if count > 1 {
+                write!(ctx, "-{}", count)?;
+            }
+            ctx.write("\" data-footnote-ref>")?;
+        } else {
+            ctx.write("<sup class=\"footnote-reference\"><a href=\"#")?;
+            ctx.write_footnote_id(&name)?;
+            ctx.write("\">")?;
+        }
+        let number = ctx.footnote_number(name);
+        if ctx.options.numbered_footnotes {
+            write!(ctx, "[{}]", number)?;
+        } else {
+            write!(ctx, "{}", number)?;
+        }
+        ctx.write("</a></sup>")
+    }
+
+    /// Starts the section holding the footnote definitions with
+    /// `FootnoteStyle::Gfm` and `FootnoteStyle::End`.
+    fn start_footnote_section<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        if ctx.options.footnotes == FootnoteStyle::End {
+            ctx.write("<section class=\"footnotes\">\n")?;
+        } else {
+            ctx.write("<section class=\"footnotes\" data-footnotes>\n<ol>\n")?;
+        }
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_footnote_section<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.dedent();
+        if ctx.options.footnotes == FootnoteStyle::End {
+            ctx.write("</section>\n")
+        } else {
+            ctx.write("</ol>\n</section>\n")
+        }
+    }
+
+    /// Starts the section of a heading, with
+    /// [`Options::sections`](struct.Options.html#structfield.sections).
+    fn start_section<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        _level: HeadingLevel,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        ctx.write("<section>\n")
+    }
+
+    fn end_section<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        ctx.write("</section>\n")
+    }
+
+    /// Writes the links back to the references of a footnote at the end of
+    /// its definition, with `FootnoteStyle::Gfm`.
+    fn footnote_backrefs<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        name: &str,
+    ) -> Result<(), W::Error> {
+        for index in 1..=ctx.footnote_reference_count(name) {
+            ctx.write(" <a href=\"#fnref-")?;
+            ctx.write_footnote_id(name)?;
+            if index > 1 {
+                write!(ctx, "-{}", index)?;
+            }
+            ctx.write("\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to reference ")?;
+            ctx.write_footnote_id(name)?;
+            if index > 1 {
+                write!(
+                    ctx,
+                    "-{}\">↩<sup class=\"footnote-ref\">{}</sup></a>",
+                    index, index
+                )?;
+            } else {
+                ctx.write("\">↩</a>")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn task_list_marker<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        checked: bool,
+    ) -> Result<(), W::Error> {
+        ctx.write("<input disabled=\"\" type=\"checkbox\"")?;
+        if checked {
+            ctx.write(" checked=\"\"")?;
+        }
+        if ctx.options.accessibility {
+            if checked {
+                ctx.write(" aria-label=\"Completed task\"")?;
+            } else {
+                ctx.write(" aria-label=\"Incomplete task\"")?;
+            }
+        }
+        ctx.write("/>\n")
+    }
+
+    fn start_paragraph<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        ctx.write("<p")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_paragraph<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("</p>\n")
+    }
+
+    fn start_heading<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        level: HeadingLevel,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        write!(ctx, "<{}", level)?;
+        if let Some(id) = ctx.heading_id.clone() {
+            ctx.write(" id=\"")?;
+            ctx.write_escaped(&id)?;
+            ctx.write("\"")?;
+        }
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_heading<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        level: HeadingLevel,
+    ) -> Result<(), W::Error> {
+        if let (Some(id), true) = (ctx.heading_id.clone(), ctx.options.heading_anchor_links) {
+            ctx.write("<a class=\"anchor\" href=\"#")?;
+            ctx.write_escaped(&id)?;
+            ctx.write("\">¶</a>")?;
+        }
+        writeln!(ctx, "</{}>", level)
+    }
+
+    fn start_block_quote<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        ctx.write("<blockquote")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">\n")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_block_quote<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.dedent();
+        ctx.write("</blockquote>\n")
+    }
+
+    fn start_code_block<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        kind: &CodeBlockKind<'a>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        ctx.write("<pre")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        match kind {
+            CodeBlockKind::Fenced(info) => {
+                let lang = info.split(' ').next().unwrap();
+                if lang.is_empty() {
+                    ctx.write("><code>")
+                } else {
+                    ctx.write("><code class=\"")?;
+                    let prefix = ctx.options().code_class_prefix.clone();
+                    ctx.write_escaped(prefix.as_deref().unwrap_or("language-"))?;
+                    ctx.write_escaped(lang)?;
+                    ctx.write("\">")
+                }
+            }
+            CodeBlockKind::Indented => ctx.write("><code>"),
+        }
+    }
+
+    fn end_code_block<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        _kind: &CodeBlockKind<'a>,
+    ) -> Result<(), W::Error> {
+        ctx.write_verbatim("</code></pre>")?;
+        ctx.write("\n")
+    }
+
+    /// Starts a list. `start` is the number of the first item of an ordered
+    /// list, and `None` for unordered lists.
+    fn start_list<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        start: Option<u64>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        match start {
+            Some(1) => ctx.write("<ol")?,
+            Some(start) => write!(ctx, "<ol start=\"{}\"", start)?,
+            None => ctx.write("<ul")?,
+        }
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">\n")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_list<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        start: Option<u64>,
+    ) -> Result<(), W::Error> {
+        ctx.dedent();
+        match start {
+            Some(_) => ctx.write("</ol>\n"),
+            None => ctx.write("</ul>\n"),
+        }
+    }
+
+    fn start_item<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        ctx.write("<li")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_item<'a, W: StrWrite>(&mut self, ctx: &mut HtmlContext<'a, W>) -> Result<(), W::Error> {
+        ctx.dedent();
+        ctx.write("</li>\n")
+    }
+
+    fn start_footnote_definition<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        name: CowStr<'a>,
+    ) -> Result<(), W::Error> {
+        ctx.ensure_newline()?;
+        if ctx.options.footnotes == FootnoteStyle::Gfm {
+            ctx.write("<li id=\"fn-")?;
+            ctx.write_footnote_id(&name)?;
+            ctx.write("\"")?;
+            ctx.write_sourcepos()?;
+            ctx.write_attributes()?;
+            ctx.write(">\n")?;
+            ctx.indent();
+            return Ok(());
+        }
+        ctx.write("<div")?;
+        ctx.write_class("footnote-definition")?;
+        ctx.write(" id=\"")?;
+        ctx.write_footnote_id(&name)?;
+        ctx.write("\"")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write("><sup class=\"footnote-definition-label\">")?;
+        let number = ctx.footnote_number(name);
+        write!(ctx, "{}", number)?;
+        ctx.write("</sup>")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_footnote_definition<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        _name: CowStr<'a>,
+    ) -> Result<(), W::Error> {
+        ctx.dedent();
+        if ctx.options.footnotes == FootnoteStyle::Gfm {
+            ctx.ensure_newline()?;
+            ctx.write("</li>\n")
+        } else {
+            ctx.write("</div>\n")
+        }
+    }
+
+    fn start_table<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        _alignments: &[Alignment],
+    ) -> Result<(), W::Error> {
+        ctx.write("<table")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_table<'a, W: StrWrite>(&mut self, ctx: &mut HtmlContext<'a, W>) -> Result<(), W::Error> {
+        ctx.dedent();
+        ctx.pretty_break()?;
+        ctx.write("</tbody>")?;
+        ctx.dedent();
+        ctx.pretty_break()?;
+        ctx.write("</table>\n")
+    }
+
+    fn start_table_head<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.pretty_break()?;
+        ctx.write("<thead")?;
+        ctx.write_attributes()?;
+        ctx.write(">")?;
+        ctx.indent();
+        ctx.pretty_break()?;
+        ctx.write("<tr")?;
+        ctx.write_sourcepos()?;
+        ctx.write(">")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_table_head<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.dedent();
+        ctx.pretty_break()?;
+        ctx.write("</tr>")?;
+        ctx.dedent();
+        ctx.pretty_break()?;
+        ctx.write("</thead>")?;
+        ctx.pretty_break()?;
+        ctx.write("<tbody>\n")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn start_table_row<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("<tr")?;
+        ctx.write_sourcepos()?;
+        ctx.write_attributes()?;
+        ctx.write(">")?;
+        ctx.indent();
+        Ok(())
+    }
+
+    fn end_table_row<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.dedent();
+        ctx.pretty_break()?;
+        ctx.write("</tr>\n")
+    }
+
+    fn start_table_cell<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.pretty_break()?;
+        if ctx.in_table_head() {
+            ctx.write("<th")?;
+            if ctx.options.accessibility {
+                ctx.write(" scope=\"col\"")?;
+            }
+        } else if ctx.in_row_header() {
+            ctx.write("<th scope=\"row\"")?;
+        } else {
+            ctx.write("<td")?;
+        }
+        ctx.write_sourcepos()?;
+        let align = match ctx.table_cell_alignment() {
+            Alignment::Left => Some("left"),
+            Alignment::Center => Some("center"),
+            Alignment::Right => Some("right"),
+            Alignment::None => None,
+        };
+        if let Some(align) = align {
+            match ctx.options().table_alignment {
+                TableAlignment::Attribute => write!(ctx, " align=\"{}\"", align)?,
+                TableAlignment::Style => write!(ctx, " style=\"text-align: {}\"", align)?,
+                TableAlignment::Class => ctx.write_class(&format!("text-align-{}", align))?,
+            }
+        }
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_table_cell<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        if ctx.in_table_head() || ctx.in_row_header() {
+            ctx.write("</th>")
+        } else {
+            ctx.write("</td>")
+        }
+    }
+
+    fn start_emphasis<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("<em")?;
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_emphasis<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("</em>")
+    }
+
+    fn start_strong<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("<strong")?;
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_strong<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("</strong>")
+    }
+
+    fn start_strikethrough<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("<del")?;
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_strikethrough<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+    ) -> Result<(), W::Error> {
+        ctx.write("</del>")
+    }
+
+    fn start_link<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        link_type: LinkType,
+        dest: &str,
+        title: &str,
+    ) -> Result<(), W::Error> {
+        if let LinkType::Email = link_type {
+            ctx.write("<a href=\"mailto:")?;
+        } else {
+            ctx.write("<a href=\"")?;
+        }
+        ctx.write_escaped_href(dest)?;
+        if !title.is_empty() {
+            ctx.write("\" title=\"")?;
+            ctx.write_escaped(title)?;
+        }
+        let external = match ctx.options().external_links {
+            Some(ref external) if link_type != LinkType::Email && external.is_external(dest) => {
+                Some(external.clone())
+            }
+            _ => None,
+        };
+        if let Some(external) = external {
+            if let Some(rel) = external.rel {
+                ctx.write("\" rel=\"")?;
+                ctx.write_escaped(&rel)?;
+            }
+            if external.target_blank {
+                ctx.write("\" target=\"_blank")?;
+            }
+        }
+        ctx.write("\"")?;
+        ctx.write_attributes()?;
+        ctx.write(">")
+    }
+
+    fn end_link<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        _link_type: LinkType,
+        _dest: &str,
+        _title: &str,
+    ) -> Result<(), W::Error> {
+        ctx.write("</a>")
+    }
+
+    /// Renders an image. `alt` is the plain text of the image description,
+    /// not yet escaped.
+    fn image<'a, W: StrWrite>(
+        &mut self,
+        ctx: &mut HtmlContext<'a, W>,
+        _link_type: LinkType,
+        dest: &str,
+        title: &str,
+        alt: &str,
+    ) -> Result<(), W::Error> {
+        ctx.write("<img src=\"")?;
+        ctx.write_escaped_href(dest)?;
+        ctx.write("\" alt=\"")?;
+        ctx.write_escaped(alt)?;
+        if !title.is_empty() {
+            ctx.write("\" title=\"")?;
+            ctx.write_escaped(title)?;
+        }
+        if ctx.options().lazy_images {
+            ctx.write("\" loading=\"lazy\" decoding=\"async")?;
+        }
+        ctx.write("\"")?;
+        ctx.write_attributes()?;
+        ctx.write(" />")
+    }
+}
+
+/// The standard HTML rendering.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultRender;
+
+impl Render for DefaultRender {}
+
+/// An event with its source range, if known.
+type SpannedEvent<'a> = (Event<'a>, Option<Range<usize>>);
+
+struct HtmlWriter<'a, I, W, R> {
+    /// Iterator supplying events, with their source ranges if known.
+    iter: I,
+
+    /// Events taken from `iter` ahead of time, to be rendered first.
+    pending: VecDeque<SpannedEvent<'a>>,
+
+    /// Output and rendering state.
+    ctx: HtmlContext<'a, W>,
+
+    render: R,
+
+    /// Source map being recorded, if requested.
+    source_map: Option<Vec<SourceMapping>>,
+
+    /// Ids given to headings, in document order.
+    headings: Vec<HeadingId>,
+
+    /// Footnote definitions held back for the footnote section.
+    definitions: HashMap<CowStr<'a>, Vec<SpannedEvent<'a>>>,
+
+    /// Labels of the held back definitions, in stream order.
+    definition_order: Vec<CowStr<'a>>,
+
+    /// Number of open elements.
+    depth: usize,
+
+    /// Levels of the headings of the open sections.
+    sections: Vec<HeadingLevel>,
+}
+
+impl<'a, I, W, R> HtmlWriter<'a, I, W, R>
+where
+    I: Iterator<Item = (Event<'a>, Option<Range<usize>>)>,
+    W: StrWrite,
+    R: Render,
+{
+    fn new(iter: I, writer: W, options: &Options, render: R) -> Self {
+        Self {
+            iter,
+            pending: VecDeque::new(),
+            ctx: HtmlContext::new(writer, options),
+            render,
+            source_map: None,
+            headings: Vec::new(),
+            definitions: HashMap::new(),
+            definition_order: Vec::new(),
+            depth: 0,
+            sections: Vec::new(),
+        }
+    }
+
+    fn next_event(&mut self) -> Option<SpannedEvent<'a>> {
+        self.pending.pop_front().or_else(|| self.iter.next())
+    }
+
+    fn run(&mut self) -> Result<(), W::Error> {
+        if let Some(document) = self.ctx.options.document.clone() {
+            self.start_document(&document)?;
+        }
+        if self.ctx.options.numbered_footnotes {
+            self.pending.extend(self.iter.by_ref());
+            let order = footnote_order(self.pending.iter().map(|(event, _)| event));
+            for (ix, label) in order.into_iter().enumerate() {
+                self.ctx.numbers.insert(label, ix + 1);
+            }
+        }
+        while let Some((event, range)) = self.next_event() {
+            match event {
+                Start(Tag::FootnoteDefinition(name))
+                    if self.ctx.options.footnotes != FootnoteStyle::InPlace =>
+                {
+                    self.hold_definition(name, range);
+                }
+                event => self.event(event, range)?,
+            }
+        }
+        self.close_sections(HeadingLevel::H1)?;
+        self.footnote_section()?;
+        if self.ctx.options.document.is_some() {
+            self.ctx.ensure_newline()?;
+            self.ctx.write("</body>\n</html>\n")?;
+        }
+        self.ctx.writer.finish()
+    }
+
+    /// Renders an event, recording it in the source map.
+    fn event(&mut self, event: Event<'a>, range: Option<Range<usize>>) -> Result<(), W::Error> {
+        let output_start = self.ctx.writer.count;
+        let ctx = &mut self.ctx;
+        ctx.range = range.clone();
+        match event {
+            Start(tag) => {
+                match tag {
+                    Tag::Heading(level) if ctx.options.sections && self.depth == 0 => {
+                        self.close_sections(level)?;
+                        self.render.start_section(&mut self.ctx, level)?;
+                        self.sections.push(level);
+                    }
+                    Tag::CodeBlock(_) if ctx.options.code_line_numbers => ctx.code_line = Some(0),
+                    _ => (),
+                }
+                // An image's end event is consumed along with its alt text.
+                if !matches!(tag, Tag::Image(..)) {
+                    self.depth += 1;
+                }
+                self.start_tag(tag)?;
+            }
+            End(tag) => {
+                if let Tag::CodeBlock(_) = tag {
+                    ctx.end_code_line()?;
+                    ctx.code_line = None;
+                }
+                self.depth -= 1;
+                self.end_tag(tag)?;
+            }
+            Text(text) => self.render.text(ctx, &text)?,
+            Code(text) => self.render.code(ctx, &text)?,
+            Html(html) if ctx.options.safe => ctx.write_escaped(&html)?,
+            Html(html) => self.render.html(ctx, &html)?,
+            SoftBreak => self.render.soft_break(ctx)?,
+            HardBreak => self.render.hard_break(ctx)?,
+            Rule => self.render.rule(ctx)?,
+            FootnoteReference(name) => {
+                *ctx.references.entry(name.clone()).or_insert(0) += 1;
+                self.render.footnote_reference(ctx, name)?
+            }
+            TaskListMarker(checked) => self.render.task_list_marker(ctx, checked)?,
+        }
+        let output_end = self.ctx.writer.count;
+        if let (Some(source_map), Some(input)) = (self.source_map.as_mut(), range) {
+            if output_end > output_start {
+                source_map.push(SourceMapping {
+                    output: output_start..output_end,
+                    input,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the open sections of headings at `level` or deeper.
+    fn close_sections(&mut self, level: HeadingLevel) -> Result<(), W::Error> {
+        while self.sections.last().map_or(false, |&open| open >= level) {
+            self.sections.pop();
+            self.render.end_section(&mut self.ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Takes the events of a footnote definition from the stream, to render
+    /// them in the footnote section.
+    fn hold_definition(&mut self, name: CowStr<'a>, range: Option<Range<usize>>) {
+        let mut events = vec![(Start(Tag::FootnoteDefinition(name.clone())), range)];
+        while let Some((event, range)) = self.next_event() {
+            let end = matches!(event, End(Tag::FootnoteDefinition(..)));
+            events.push((event, range));
+            if end {
+                break;
+            }
+        }
+        if !self.definitions.contains_key(&name) {
+            self.definition_order.push(name.clone());
+            self.definitions.insert(name, events);
+        }
+    }
+
+    /// Renders the held back definitions of referenced footnotes, in order of
+    /// their numbers.
+    fn footnote_section(&mut self) -> Result<(), W::Error> {
+        if self.ctx.options.footnotes == FootnoteStyle::End {
+            return self.footnote_section_in_order();
+        }
+        let mut started = false;
+        // definitions can reference more footnotes, so look for the next one
+        // after each definition
+        loop {
+            let definitions = &self.definitions;
+            let next = self
+                .ctx
+                .numbers
+                .iter()
+                .filter(|(name, _)| definitions.contains_key(*name))
+                .min_by_key(|&(_, &number)| number)
+                .map(|(name, _)| name.clone());
+            let name = match next {
+                Some(name) => name,
+                None => break,
+            };
+            if !started {
+                self.render.start_footnote_section(&mut self.ctx)?;
+                started = true;
+            }
+            let events = self.definitions.remove(&name).unwrap();
+            // the back links go at the end of a final paragraph, or after
+            // the contents
+            let len = events.len();
+            let backrefs_at = match events.get(len.wrapping_sub(2)) {
+                Some((End(Tag::Paragraph), _)) => len - 2,
+                _ => len - 1,
+            };
+            for (ix, (event, range)) in events.into_iter().enumerate() {
+                if ix == backrefs_at {
+                    self.render.footnote_backrefs(&mut self.ctx, &name)?;
+                }
+                self.event(event, range)?;
+            }
+        }
+        if started {
+            self.render.end_footnote_section(&mut self.ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the held back definitions in stream order, for
+    /// `FootnoteStyle::End`.
+    fn footnote_section_in_order(&mut self) -> Result<(), W::Error> {
+        if self.definition_order.is_empty() {
+            return Ok(());
+        }
+        self.render.start_footnote_section(&mut self.ctx)?;
+        for name in std::mem::take(&mut self.definition_order) {
+            for (event, range) in self.definitions.remove(&name).unwrap() {
+                self.event(event, range)?;
+            }
+        }
+        self.render.end_footnote_section(&mut self.ctx)
+    }
+
+    fn start_document(&mut self, document: &Document) -> Result<(), W::Error> {
+        let ctx = &mut self.ctx;
+        ctx.write("<!DOCTYPE html>\n")?;
+        match document.lang {
+            Some(ref lang) => {
+                ctx.write("<html lang=\"")?;
+                ctx.write_escaped(lang)?;
+                ctx.write("\">\n")?;
+            }
+            None => ctx.write("<html>\n")?,
+        }
+        ctx.write("<head>\n<meta charset=\"utf-8\">\n")?;
+        if let Some(ref title) = document.title {
+            ctx.write("<title>")?;
+            ctx.write_escaped(title)?;
+            ctx.write("</title>\n")?;
+        }
+        for stylesheet in &document.stylesheets {
+            ctx.write("<link rel=\"stylesheet\" href=\"")?;
+            ctx.write_escaped_href(stylesheet)?;
+            ctx.write("\">\n")?;
+        }
+        ctx.write("</head>\n<body>\n")
+    }
+
     /// Writes the start of an HTML tag.
-    fn start_tag(&mut self, tag: Tag<'a>) -> io::Result<()> {
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        self.ctx.attributes = self.render.attributes(&tag);
+        let ctx = &mut self.ctx;
         match tag {
-            Tag::Paragraph => {
-                if self.end_newline {
-                    self.write("<p>")
-                } else {
-                    self.write("\n<p>")
-                }
-            }
+            Tag::Paragraph => self.render.start_paragraph(ctx),
             Tag::Heading(level) => {
-                if self.end_newline {
-                    self.end_newline = false;
-                    write!(&mut self.writer, "<{}>", level)
-                } else {
-                    write!(&mut self.writer, "\n<{}>", level)
+                let level = self.ctx.shift_heading(level);
+                if self.ctx.options.heading_ids.is_some() {
+                    let text = self.heading_text();
+                    let attrs = &mut self.ctx.attributes.attrs;
+                    let given = attrs.iter().position(|(name, _)| name == "id");
+                    let given = given.map(|ix| attrs.remove(ix).1);
+                    let slugger = self.ctx.options.heading_ids.as_mut().unwrap();
+                    let id = match given {
+                        Some(id) => slugger.ids().allocate(&id),
+                        None => slugger.slug(&text),
+                    };
+                    self.ctx.heading_id = Some(id.clone());
+                    self.headings.push(HeadingId { level, text, id });
                 }
+                self.render.start_heading(&mut self.ctx, level)
             }
             Tag::Table(alignments) => {
-                self.table_alignments = alignments;
-                self.write("<table>")
+                self.render.start_table(ctx, &alignments)?;
+                ctx.table_alignments = alignments;
+                Ok(())
             }
             Tag::TableHead => {
-                self.table_state = TableState::Head;
-                self.table_cell_index = 0;
-                self.write("<thead><tr>")
+                ctx.table_state = TableState::Head;
+                ctx.table_cell_index = 0;
+                self.render.start_table_head(ctx)
             }
             Tag::TableRow => {
-                self.table_cell_index = 0;
-                self.write("<tr>")
-            }
-            Tag::TableCell => {
-                match self.table_state {
-                    TableState::Head => {
-                        self.write("<th")?;
-                    }
-                    TableState::Body => {
-                        self.write("<td")?;
-                    }
-                }
-                match self.table_alignments.get(self.table_cell_index) {
-                    Some(&Alignment::Left) => self.write(" align=\"left\">"),
-                    Some(&Alignment::Center) => self.write(" align=\"center\">"),
-                    Some(&Alignment::Right) => self.write(" align=\"right\">"),
-                    _ => self.write(">"),
-                }
-            }
-            Tag::BlockQuote => {
-                if self.end_newline {
-                    self.write("<blockquote>\n")
-                } else {
-                    self.write("\n<blockquote>\n")
-                }
+                ctx.table_cell_index = 0;
+                self.render.start_table_row(ctx)
             }
-            Tag::CodeBlock(info) => {
-                if !self.end_newline {
-                    self.write_newline()?;
-                }
-                match info {
-                    CodeBlockKind::Fenced(info) => {
-                        let lang = info.split(' ').next().unwrap();
-                        if lang.is_empty() {
-                            self.write("<pre><code>")
-                        } else {
-                            self.write("<pre><code class=\"language-")?;
-                            escape_html(&mut self.writer, lang)?;
-                            self.write("\">")
+            Tag::TableCell => self.render.start_table_cell(ctx),
+            Tag::BlockQuote => self.render.start_block_quote(ctx),
+            Tag::CodeBlock(kind) => self.render.start_code_block(ctx, &kind),
+            Tag::List(start) => self.render.start_list(ctx, start),
+            Tag::Item => self.render.start_item(ctx),
+            Tag::Emphasis => self.render.start_emphasis(ctx),
+            Tag::Strong => self.render.start_strong(ctx),
+            Tag::Strikethrough => self.render.start_strikethrough(ctx),
+            Tag::Link(link_type, dest, title) => {
+                let dest = match ctx.options.wikilinks {
+                    Some(ref wikilinks) if link_type == LinkType::WikiLink => {
+                        let (url, resolved) = wikilinks.url(&dest);
+                        match wikilinks.unresolved_class {
+                            Some(ref class) if !resolved => {
+                                ctx.attributes.classes.insert(0, class.clone())
+                            }
+                            _ => (),
                         }
+                        url.into()
                     }
-                    CodeBlockKind::Indented => self.write("<pre><code>"),
-                }
-            }
-            Tag::List(Some(1)) => {
-                if self.end_newline {
-                    self.write("<ol>\n")
-                } else {
-                    self.write("\n<ol>\n")
-                }
-            }
-            Tag::List(Some(start)) => {
-                if self.end_newline {
-                    self.write("<ol start=\"")?;
-                } else {
-                    self.write("\n<ol start=\"")?;
-                }
-                write!(&mut self.writer, "{}", start)?;
-                self.write("\">\n")
+                    _ => dest,
+                };
+                let dest = ctx.destination(LinkKind::Link, link_type, dest);
+                self.render.start_link(ctx, link_type, &dest, &title)
             }
-            Tag::List(None) => {
-                if self.end_newline {
-                    self.write("<ul>\n")
-                } else {
-                    self.write("\n<ul>\n")
-                }
-            }
-            Tag::Item => {
-                if self.end_newline {
-                    self.write("<li>")
-                } else {
-                    self.write("\n<li>")
-                }
-            }
-            Tag::Emphasis => self.write("<em>"),
-            Tag::Strong => self.write("<strong>"),
-            Tag::Strikethrough => self.write("<del>"),
-            Tag::Link(LinkType::Email, dest, title) => {
-                self.write("<a href=\"mailto:")?;
-                escape_href(&mut self.writer, &dest)?;
-                if !title.is_empty() {
-                    self.write("\" title=\"")?;
-                    escape_html(&mut self.writer, &title)?;
-                }
-                self.write("\">")
-            }
-            Tag::Link(_link_type, dest, title) => {
-                self.write("<a href=\"")?;
-                escape_href(&mut self.writer, &dest)?;
-                if !title.is_empty() {
-                    self.write("\" title=\"")?;
-                    escape_html(&mut self.writer, &title)?;
-                }
-                self.write("\">")
-            }
-            Tag::Image(_link_type, dest, title) => {
-                self.write("<img src=\"")?;
-                escape_href(&mut self.writer, &dest)?;
-                self.write("\" alt=\"")?;
-                self.raw_text()?;
-                if !title.is_empty() {
-                    self.write("\" title=\"")?;
-                    escape_html(&mut self.writer, &title)?;
-                }
-                self.write("\" />")
-            }
-            Tag::FootnoteDefinition(name) => {
-                if self.end_newline {
-                    self.write("<div class=\"footnote-definition\" id=\"")?;
-                } else {
-                    self.write("\n<div class=\"footnote-definition\" id=\"")?;
-                }
-                escape_html(&mut self.writer, &*name)?;
-                self.write("\"><sup class=\"footnote-definition-label\">")?;
-                let len = self.numbers.len() + 1;
-                let number = *self.numbers.entry(name).or_insert(len);
-                write!(&mut self.writer, "{}", number)?;
-                self.write("</sup>")
+            Tag::Image(link_type, dest, title) => {
+                let dest = ctx.destination(LinkKind::Image, link_type, dest);
+                let alt = self.raw_text();
+                self.render
+                    .image(&mut self.ctx, link_type, &dest, &title, &alt)
             }
+            Tag::FootnoteDefinition(name) => self.render.start_footnote_definition(ctx, name),
         }
     }
 
-    fn end_tag(&mut self, tag: Tag) -> io::Result<()> {
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        let ctx = &mut self.ctx;
         match tag {
-            Tag::Paragraph => {
-                self.write("</p>\n")?;
-            }
+            Tag::Paragraph => self.render.end_paragraph(ctx),
             Tag::Heading(level) => {
-                self.write("</")?;
-                write!(&mut self.writer, "{}", level)?;
-                self.write(">\n")?;
-            }
-            Tag::Table(_) => {
-                self.write("</tbody></table>\n")?;
+                let level = ctx.shift_heading(level);
+                self.render.end_heading(ctx, level)?;
+                ctx.heading_id = None;
+                Ok(())
             }
+            Tag::Table(_) => self.render.end_table(ctx),
             Tag::TableHead => {
-                self.write("</tr></thead><tbody>\n")?;
-                self.table_state = TableState::Body;
-            }
-            Tag::TableRow => {
-                self.write("</tr>\n")?;
+                self.render.end_table_head(ctx)?;
+                ctx.table_state = TableState::Body;
+                Ok(())
             }
+            Tag::TableRow => self.render.end_table_row(ctx),
             Tag::TableCell => {
-                match self.table_state {
-                    TableState::Head => {
-                        self.write("</th>")?;
-                    }
-                    TableState::Body => {
-                        self.write("</td>")?;
-                    }
-                }
-                self.table_cell_index += 1;
-            }
-            Tag::BlockQuote => {
-                self.write("</blockquote>\n")?;
-            }
-            Tag::CodeBlock(_) => {
-                self.write("</code></pre>\n")?;
-            }
-            Tag::List(Some(_)) => {
-                self.write("</ol>\n")?;
-            }
-            Tag::List(None) => {
-                self.write("</ul>\n")?;
-            }
-            Tag::Item => {
-                self.write("</li>\n")?;
+                self.render.end_table_cell(ctx)?;
+                ctx.table_cell_index += 1;
+                Ok(())
             }
-            Tag::Emphasis => {
-                self.write("</em>")?;
-            }
-            Tag::Strong => {
-                self.write("</strong>")?;
-            }
-            Tag::Strikethrough => {
-                self.write("</del>")?;
-            }
-            Tag::Link(_, _, _) => {
-                self.write("</a>")?;
+            Tag::BlockQuote => self.render.end_block_quote(ctx),
+            Tag::CodeBlock(kind) => self.render.end_code_block(ctx, &kind),
+            Tag::List(start) => self.render.end_list(ctx, start),
+            Tag::Item => self.render.end_item(ctx),
+            Tag::Emphasis => self.render.end_emphasis(ctx),
+            Tag::Strong => self.render.end_strong(ctx),
+            Tag::Strikethrough => self.render.end_strikethrough(ctx),
+            Tag::Link(link_type, dest, title) => {
+                let dest = match ctx.options.wikilinks {
+                    Some(ref wikilinks) if link_type == LinkType::WikiLink => {
+                        wikilinks.url(&dest).0.into()
+                    }
+                    _ => dest,
+                };
+                let dest = ctx.destination(LinkKind::Link, link_type, dest);
+                self.render.end_link(ctx, link_type, &dest, &title)
             }
-            Tag::Image(_, _, _) => (), // shouldn't happen, handled in start
-            Tag::FootnoteDefinition(_) => {
-                self.write("</div>\n")?;
+            Tag::Image(_, _, _) => Ok(()), // shouldn't happen, handled in start
+            Tag::FootnoteDefinition(name) => self.render.end_footnote_definition(ctx, name),
+        }
+    }
+
+    /// Returns the text of the heading being started, keeping its events
+    /// for rendering.
+    fn heading_text(&mut self) -> String {
+        let mut text = String::new();
+        let mut events = Vec::new();
+        while let Some((event, range)) = self.next_event() {
+            let end = matches!(event, End(Tag::Heading(..)));
+            push_event_text(&mut text, &event);
+            events.push((event, range));
+            if end {
+                break;
             }
         }
-        Ok(())
+        for event in events.into_iter().rev() {
+            self.pending.push_front(event);
+        }
+        text
     }
 
-    // run raw text, consuming end tag
-    fn raw_text(&mut self) -> io::Result<()> {
+    // collect raw text, consuming end tag
+    fn raw_text(&mut self) -> String {
+        let mut events = Vec::new();
         let mut nest = 0;
-        while let Some(event) = self.iter.next() {
+        while let Some((event, _)) = self.next_event() {
             match event {
                 Start(_) => nest += 1,
-                End(_) => {
-                    if nest == 0 {
-                        break;
-                    }
-                    nest -= 1;
-                }
-                Html(text) | Code(text) | Text(text) => {
-                    escape_html(&mut self.writer, &text)?;
-                    self.end_newline = text.ends_with('\n');
+                End(_) if nest == 0 => break,
+                End(_) => nest -= 1,
+                _ => (),
+            }
+            events.push(event);
+        }
+        let markers = match self.ctx.options.alt_text {
+            AltText::Plain => false,
+            AltText::Markers => true,
+            AltText::Custom(flatten) => return flatten(&events),
+        };
+        let mut text = String::new();
+        for event in events {
+            match event {
+                Start(Tag::Emphasis) | End(Tag::Emphasis) if markers => text.push('*'),
+                Start(Tag::Strong) | End(Tag::Strong) if markers => text.push_str("**"),
+                Start(Tag::Strikethrough) | End(Tag::Strikethrough) if markers => {
+                    text.push_str("~~")
                 }
-                SoftBreak | HardBreak | Rule => {
-                    self.write(" ")?;
+                Start(_) | End(_) => (),
+                Code(s) if markers => {
+                    text.push('`');
+                    text.push_str(&s);
+                    text.push('`');
                 }
+                Html(s) | Code(s) | Text(s) => text.push_str(&s),
+                SoftBreak | HardBreak | Rule => text.push(' '),
                 FootnoteReference(name) => {
-                    let len = self.numbers.len() + 1;
-                    let number = *self.numbers.entry(name).or_insert(len);
-                    write!(&mut self.writer, "[{}]", number)?;
+                    let number = self.ctx.footnote_number(name);
+                    text.push_str(&format!("[{}]", number));
                 }
-                TaskListMarker(true) => self.write("[x]")?,
-                TaskListMarker(false) => self.write("[ ]")?,
+                TaskListMarker(true) => text.push_str("[x]"),
+                TaskListMarker(false) => text.push_str("[ ]"),
             }
         }
-        Ok(())
+        text
     }
 }
 
@@ -416,7 +2463,7 @@ pub fn push_html<'a, I>(s: &mut String, iter: I)
 where
     I: Iterator<Item = Event<'a>>,
 {
-    HtmlWriter::new(iter, s).run().unwrap();
+    push_html_ext(s, iter, &Options::default());
 }
 
 /// Iterate over an `Iterator` of `Event`s, generate HTML for each `Event`, and
@@ -457,5 +2504,525 @@ where
     I: Iterator<Item = Event<'a>>,
     W: Write,
 {
-    HtmlWriter::new(iter, WriteWrapper(writer)).run()
+    write_html_ext(writer, iter, &Options::default())
+}
+
+/// Like [`push_html`](fn.push_html.html), but with the given options.
+pub fn push_html_ext<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    push_html_with_renderer(s, iter, options, DefaultRender);
+}
+
+/// Estimates the length of the HTML rendered from a markdown source of
+/// `source_len` bytes, to reserve the output buffer up front. HTML usually
+/// takes about one and a half times the size of its source.
+pub fn estimate_html_len(source_len: usize) -> usize {
+    source_len + source_len / 2
+}
+
+/// Estimates the length in bytes of the HTML rendered from `events` with the
+/// default options. The estimate counts the contents of the events and the
+/// tags written for them, plus some room for escaping.
+pub fn estimate_events_html_len(events: &[Event]) -> usize {
+    events
+        .iter()
+        .map(|event| match event {
+            Text(text) | Code(text) => text.len() + text.len() / 16 + 2,
+            Html(html) => html.len(),
+            Start(Tag::Link(_, dest, title)) | Start(Tag::Image(_, dest, title)) => {
+                dest.len() + title.len() + 24
+            }
+            Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => info.len() + 32,
+            Start(_) => 6,
+            End(_) => 8,
+            SoftBreak => 1,
+            HardBreak | Rule => 7,
+            FootnoteReference(name) => 2 * name.len() + 56,
+            TaskListMarker(_) => 48,
+        })
+        .sum()
+}
+
+/// Like [`push_html_ext`](fn.push_html_ext.html), but renders events that
+/// have already been collected, reserving room for the output first with
+/// [`estimate_events_html_len`](fn.estimate_events_html_len.html). This
+/// saves the reallocations of a growing string on large documents.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Event, Parser};
+///
+/// let events: Vec<Event> = Parser::new("*hello* world\n").collect();
+/// let mut html_buf = String::new();
+/// html::push_html_into_capacity(&mut html_buf, &events, &html::Options::default());
+/// assert_eq!(html_buf, "<p><em>hello</em> world</p>\n");
+/// ```
+pub fn push_html_into_capacity<'a>(s: &mut String, events: &[Event<'a>], options: &Options) {
+    s.reserve(estimate_events_html_len(events));
+    push_html_ext(s, events.iter().cloned(), options);
+}
+
+/// Like [`write_html`](fn.write_html.html), but with the given options.
+pub fn write_html_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: Write,
+{
+    write_html_with_renderer(writer, iter, options, DefaultRender)
+}
+
+/// Parses a document that may start with front matter and renders it to
+/// HTML, handling the front matter as set by
+/// [`Options::front_matter`](struct.Options.html#structfield.front_matter).
+///
+/// The parser has no notion of front matter, so it is split from the source
+/// with [`split_front_matter`](../utils/front_matter/fn.split_front_matter.html)
+/// before the body is parsed with the given parser options.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::html::{self, FrontMatterHtml};
+///
+/// let input = "---\ntitle: Hi\n---\n*Hi*\n";
+/// let options = html::Options {
+///     front_matter: FrontMatterHtml::Pre,
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// html::push_html_with_front_matter(&mut html_buf, input, pulldown_cmark::Options::empty(), &options);
+/// assert_eq!(
+///     html_buf,
+///     "<pre><code class=\"language-yaml\">title: Hi\n</code></pre>\n<p><em>Hi</em></p>\n"
+/// );
+/// ```
+pub fn push_html_with_front_matter(
+    s: &mut String,
+    input: &str,
+    parser_options: crate::Options,
+    options: &Options,
+) {
+    let (front_matter, body) = split_front_matter(input);
+    let front_matter = front_matter.map_or(Vec::new(), |front_matter| match options.front_matter {
+        FrontMatterHtml::Skip => Vec::new(),
+        FrontMatterHtml::Pre => {
+            let lang = match front_matter.kind {
+                FrontMatterKind::Yaml => "yaml",
+                FrontMatterKind::Toml => "toml",
+            };
+            let tag = || Tag::CodeBlock(CodeBlockKind::Fenced(lang.into()));
+            vec![Start(tag()), Text(front_matter.raw.into()), End(tag())]
+        }
+        FrontMatterHtml::Callback(callback) => vec![Html(callback(&front_matter).into())],
+    });
+    let parser = crate::Parser::new_ext(body, parser_options);
+    push_html_ext(s, front_matter.into_iter().chain(parser), options);
+}
+
+/// Renders untrusted markdown, like user comments, to HTML, with the
+/// `ammonia` feature.
+///
+/// The HTML is cleaned by [ammonia](https://docs.rs/ammonia) with the given
+/// policy, which removes the tags, attributes and URL schemes it doesn't
+/// allow, and the contents of elements like `script`. The default policy of
+/// `ammonia::Builder` is a reasonable one for comments.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+///
+/// let parser = Parser::new("[*hi*](javascript:evil()) <sup>2</sup> <script>alert(1)</script>");
+/// let mut html_buf = String::new();
+/// html::push_html_sanitized(&mut html_buf, parser, &ammonia::Builder::default());
+/// assert_eq!(
+///     html_buf,
+///     "<p><a rel=\"noopener noreferrer\"><em>hi</em></a> <sup>2</sup> </p>\n"
+/// );
+/// ```
+#[cfg(feature = "ammonia")]
+pub fn push_html_sanitized<'a, I>(s: &mut String, iter: I, policy: &ammonia::Builder)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut html = String::new();
+    push_html(&mut html, iter);
+    s.push_str(&policy.clean(&html).to_string());
+}
+
+/// Like [`push_html_ext`](fn.push_html_ext.html), but renders the events
+/// with the given [`Render`](trait.Render.html) implementation.
+pub fn push_html_with_renderer<'a, I, R>(s: &mut String, iter: I, options: &Options, render: R)
+where
+    I: Iterator<Item = Event<'a>>,
+    R: Render,
+{
+    HtmlWriter::new(iter.map(|event| (event, None)), s, options, render)
+        .run()
+        .unwrap();
+}
+
+/// Like [`write_html_ext`](fn.write_html_ext.html), but renders the events
+/// with the given [`Render`](trait.Render.html) implementation.
+pub fn write_html_with_renderer<'a, I, W, R>(
+    writer: W,
+    iter: I,
+    options: &Options,
+    render: R,
+) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: Write,
+    R: Render,
+{
+    HtmlWriter::new(
+        iter.map(|event| (event, None)),
+        WriteWrapper(writer),
+        options,
+        render,
+    )
+    .run()?;
+    Ok(())
+}
+
+/// Like [`write_html_ext`](fn.write_html_ext.html), but for large documents:
+/// output is collected in a buffer of at most `max_buffer` bytes, which is
+/// written out and flushed whenever it is full, so that memory use does not
+/// grow with the size of the output.
+///
+/// The writer streams most constructs, tables included, but some options
+/// hold back events until what follows them is known:
+///
+/// * [`numbered_footnotes`](struct.Options.html#structfield.numbered_footnotes)
+///   reads the whole stream before rendering,
+/// * [`FootnoteStyle::Gfm`](enum.FootnoteStyle.html#variant.Gfm) and
+///   [`FootnoteStyle::End`](enum.FootnoteStyle.html#variant.End) keep every
+///   footnote definition until the end of the document,
+/// * [`heading_ids`](struct.Options.html#structfield.heading_ids) keeps the
+///   events of a heading until its end.
+///
+/// The alt text of images is always collected before the image is written.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+///
+/// let markdown = "* item\n".repeat(1000);
+/// let mut bytes = Vec::new();
+/// html::write_html_streaming(&mut bytes, Parser::new(&markdown), &html::Options::default(), 4096)
+///     .unwrap();
+/// assert!(bytes.ends_with(b"<li>item</li>\n</ul>\n"));
+/// ```
+pub fn write_html_streaming<'a, I, W>(
+    writer: W,
+    iter: I,
+    options: &Options,
+    max_buffer: usize,
+) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: Write,
+{
+    let mut writer = FlushingWriter {
+        writer,
+        buf: String::with_capacity(max_buffer),
+        max_buffer,
+    };
+    HtmlWriter::new(
+        iter.map(|event| (event, None)),
+        &mut writer,
+        options,
+        DefaultRender,
+    )
+    .run()?;
+    writer.flush()
+}
+
+/// Writer buffering at most `max_buffer` bytes before writing them out.
+struct FlushingWriter<W> {
+    writer: W,
+    buf: String,
+    max_buffer: usize,
+}
+
+impl<W: Write> FlushingWriter<W> {
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(self.buf.as_bytes())?;
+        self.buf.clear();
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> StrWrite for FlushingWriter<W> {
+    type Error = io::Error;
+
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        if self.buf.len() + s.len() > self.max_buffer {
+            self.flush()?;
+            if s.len() > self.max_buffer {
+                return self.writer.write_all(s.as_bytes());
+            }
+        }
+        self.buf.push_str(s);
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: Arguments) -> io::Result<()> {
+        write_fmt_with_str(self, args)
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate HTML for each `Event`, and
+/// write it out to a `fmt::Write` implementation, like a `fmt::Formatter`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+/// use std::fmt;
+///
+/// struct Markdown<'a>(&'a str);
+///
+/// impl fmt::Display for Markdown<'_> {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         html::write_html_fmt(f, Parser::new(self.0))
+///     }
+/// }
+///
+/// assert_eq!(Markdown("*hi*").to_string(), "<p><em>hi</em></p>\n");
+/// ```
+pub fn write_html_fmt<'a, I, W>(writer: W, iter: I) -> fmt::Result
+where
+    I: Iterator<Item = Event<'a>>,
+    W: fmt::Write,
+{
+    write_html_fmt_ext(writer, iter, &Options::default())
+}
+
+/// Like [`write_html_fmt`](fn.write_html_fmt.html), but with the given
+/// options.
+pub fn write_html_fmt_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> fmt::Result
+where
+    I: Iterator<Item = Event<'a>>,
+    W: fmt::Write,
+{
+    HtmlWriter::new(
+        iter.map(|event| (event, None)),
+        FmtWriter(writer),
+        options,
+        DefaultRender,
+    )
+    .run()?;
+    Ok(())
+}
+
+/// Like [`write_html_with_renderer`](fn.write_html_with_renderer.html), but
+/// writes to any [`StrWrite`](../escape/trait.StrWrite.html) implementation
+/// and returns its errors as they are.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::escape::StrWrite;
+/// use pulldown_cmark::html::{self, DefaultRender};
+/// use pulldown_cmark::Parser;
+/// use std::fmt::Arguments;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Full;
+///
+/// /// Holds at most eight bytes.
+/// struct Small(String);
+///
+/// impl StrWrite for Small {
+///     type Error = Full;
+///
+///     fn write_str(&mut self, s: &str) -> Result<(), Full> {
+///         if self.0.len() + s.len() > 8 {
+///             return Err(Full);
+///         }
+///         self.0.push_str(s);
+///         Ok(())
+///     }
+///
+///     fn write_fmt(&mut self, args: Arguments) -> Result<(), Full> {
+///         self.write_str(&args.to_string())
+///     }
+/// }
+///
+/// let options = html::Options::default();
+/// let mut small = Small(String::new());
+/// let result = html::write_html_to(&mut small, Parser::new("*hi*"), &options, DefaultRender);
+/// assert_eq!(result, Err(Full));
+/// assert_eq!(small.0, "<p><em>");
+/// ```
+pub fn write_html_to<'a, I, W, R>(
+    writer: W,
+    iter: I,
+    options: &Options,
+    render: R,
+) -> Result<(), W::Error>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+    R: Render,
+{
+    HtmlWriter::new(iter.map(|event| (event, None)), writer, options, render).run()?;
+    Ok(())
+}
+
+/// Like [`push_html_ext`](fn.push_html_ext.html), but takes an offset
+/// iterator, so that the source ranges of events are known while rendering.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::html::{self, SourcePositions};
+/// use pulldown_cmark::Parser;
+///
+/// let input = "# Title\n\n* one\n* two\n";
+/// let options = html::Options {
+///     source_positions: Some(SourcePositions::new(input)),
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// html::push_html_with_offsets(&mut html_buf, Parser::new(input).into_offset_iter(), &options);
+/// assert_eq!(
+///     html_buf,
+///     "<h1 data-sourcepos=\"1:1-1:7\">Title</h1>\n\
+///      <ul data-sourcepos=\"3:1-4:5\">\n\
+///      <li data-sourcepos=\"3:1-3:5\">one</li>\n\
+///      <li data-sourcepos=\"4:1-4:5\">two</li>\n\
+///      </ul>\n"
+/// );
+/// ```
+pub fn push_html_with_offsets<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    HtmlWriter::new(
+        iter.map(|(event, range)| (event, Some(range))),
+        s,
+        options,
+        DefaultRender,
+    )
+    .run()
+    .unwrap();
+}
+
+/// Like [`write_html_ext`](fn.write_html_ext.html), but takes an offset
+/// iterator, so that the source ranges of events are known while rendering.
+pub fn write_html_with_offsets<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+    W: Write,
+{
+    HtmlWriter::new(
+        iter.map(|(event, range)| (event, Some(range))),
+        WriteWrapper(writer),
+        options,
+        DefaultRender,
+    )
+    .run()?;
+    Ok(())
+}
+
+/// Like [`push_html_ext`](fn.push_html_ext.html), but returns the ids given
+/// to the headings, in document order, so that a table of contents can link
+/// to them.
+///
+/// Ids are only given with
+/// [`Options::heading_ids`](struct.Options.html#structfield.heading_ids).
+/// Headings with the same text get distinct ids, like `intro` and `intro-1`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::slug::Slugger;
+/// use pulldown_cmark::{html, HeadingLevel, Parser};
+///
+/// let options = html::Options {
+///     heading_ids: Some(Slugger::default()),
+///     ..Default::default()
+/// };
+/// let mut html_buf = String::new();
+/// let parser = Parser::new("# Intro\n## Intro\n");
+/// let headings = html::push_html_with_heading_ids(&mut html_buf, parser, &options);
+/// assert_eq!(html_buf, "<h1 id=\"intro\">Intro</h1>\n<h2 id=\"intro-1\">Intro</h2>\n");
+/// assert_eq!(headings[1].level, HeadingLevel::H2);
+/// assert_eq!(headings[1].id, "intro-1");
+/// ```
+pub fn push_html_with_heading_ids<'a, I>(
+    s: &mut String,
+    iter: I,
+    options: &Options,
+) -> Vec<HeadingId>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut writer = HtmlWriter::new(iter.map(|event| (event, None)), s, options, DefaultRender);
+    writer.run().unwrap();
+    writer.headings
+}
+
+/// Like [`push_html`](fn.push_html.html), but takes an offset iterator and
+/// returns a source map of the generated HTML.
+///
+/// The source map has an entry for every event that produced output, in
+/// output order. Output ranges are byte offsets into `s`, including any text
+/// it held before. For start and end tags, the input range is the range of
+/// the whole element, as given by the offset iterator. Editor previews can
+/// use the map to find the source of a position in the rendered document,
+/// and the other way around.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+///
+/// let input = "# Title\n\n*text*\n";
+/// let mut html_buf = String::new();
+/// let source_map = html::push_html_with_source_map(&mut html_buf, Parser::new(input).into_offset_iter());
+///
+/// let text = source_map.iter().find(|m| &html_buf[m.output.clone()] == "text").unwrap();
+/// assert_eq!(&input[text.input.clone()], "text");
+/// ```
+pub fn push_html_with_source_map<'a, I>(s: &mut String, iter: I) -> Vec<SourceMapping>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let start = s.len();
+    let mut writer = HtmlWriter::new(
+        iter.map(|(event, range)| (event, Some(range))),
+        s,
+        &Options::default(),
+        DefaultRender,
+    );
+    writer.ctx.writer.count = start;
+    writer.source_map = Some(Vec::new());
+    writer.run().unwrap();
+    writer.source_map.unwrap_or_default()
+}
+
+/// Like [`write_html`](fn.write_html.html), but takes an offset iterator and
+/// returns a source map of the generated HTML. Output ranges are byte offsets
+/// from the start of the written HTML.
+pub fn write_html_with_source_map<'a, I, W>(writer: W, iter: I) -> io::Result<Vec<SourceMapping>>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+    W: Write,
+{
+    let mut writer = HtmlWriter::new(
+        iter.map(|(event, range)| (event, Some(range))),
+        WriteWrapper(writer),
+        &Options::default(),
+        DefaultRender,
+    );
+    writer.source_map = Some(Vec::new());
+    writer.run()?;
+    Ok(writer.source_map.unwrap_or_default())
 }
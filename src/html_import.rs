@@ -0,0 +1,704 @@
+//! Conversion of HTML back into events, with the `html-import` feature.
+//!
+//! This is not a full HTML parser. It understands the HTML written by the
+//! [`html`](../html/index.html) module with its default options, and the
+//! common tags that map to markdown directly: paragraphs, headings, block
+//! quotes, lists, code, tables, emphasis, links and images. Other tags and
+//! comments are kept as `Html` events, and the contents of `head`, `script`
+//! and `style` elements are dropped.
+//!
+//! Together with the [`markdown`](../markdown/index.html) writer, this
+//! converts HTML to markdown.
+//!
+//! # Examples
+//!
+//! ```
+//! use pulldown_cmark::{html_import, markdown};
+//!
+//! let html = "<h1>Notes</h1>\n<p>Some <b>bold</b>\ntext</p>\n<ul><li>one</li><li>two</li></ul>\n";
+//! let mut markdown_buf = String::new();
+//! markdown::push_markdown(&mut markdown_buf, html_import::parse_html(html).into_iter());
+//! assert_eq!(markdown_buf, "# Notes\n\nSome **bold**\ntext\n\n- one\n- two\n");
+//! ```
+
+use crate::compat::strip_prefix;
+use crate::scanners::scan_entity;
+use crate::strings::CowStr;
+use crate::utils::html_filter::{scan_tag, ScannedTag};
+use crate::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag};
+
+/// Parses `html` into events.
+///
+/// Elements that are left open are closed at the end, and closing tags
+/// without a matching open tag are ignored, so any input gives balanced
+/// events.
+pub fn parse_html(html: &str) -> Vec<Event<'_>> {
+    let mut importer = Importer::new();
+    let mut rest = html;
+    while let Some(ix) = rest.find('<') {
+        importer.text(&rest[..ix]);
+        rest = &rest[ix..];
+        let len = match scan_tag(rest) {
+            Some(ref tag) if tag.closing => {
+                importer.end_tag(&tag.name.to_ascii_lowercase(), &rest[..tag.len]);
+                tag.len
+            }
+            Some(tag) => {
+                importer.start_tag(&tag, &rest[..tag.len]);
+                tag.len
+            }
+            None => {
+                let end = if rest.starts_with("<!--") {
+                    rest.find("-->").map(|end| end + 3)
+                } else if rest[1..].starts_with(&['!', '?'][..]) {
+                    rest.find('>').map(|end| end + 1)
+                } else {
+                    None
+                };
+                match end {
+                    Some(end) => {
+                        importer.other(&rest[..end]);
+                        end
+                    }
+                    None => {
+                        importer.text("<");
+                        1
+                    }
+                }
+            }
+        };
+        rest = &rest[len..];
+    }
+    importer.text(rest);
+    importer.finish()
+}
+
+/// What an open element turns into.
+enum Kind<'a> {
+    /// A tag, ended by an `End` event.
+    Tag(Tag<'a>),
+    /// A paragraph opened for text outside of one.
+    ImplicitParagraph,
+    /// An element whose contents are read as if it wasn't there.
+    Transparent,
+    /// An element kept as raw HTML.
+    Raw,
+    /// An element whose contents are dropped.
+    Skip,
+    /// Inline code, whose text is collected.
+    Code,
+    /// A footnote reference, whose label is the link in it.
+    FootnoteReference,
+}
+
+struct Open<'a> {
+    name: String,
+    kind: Kind<'a>,
+    /// Index of the `Start` event of the element.
+    start: usize,
+}
+
+struct Importer<'a> {
+    events: Vec<Event<'a>>,
+    stack: Vec<Open<'a>>,
+    /// White space between inline content, written once more content
+    /// follows: a soft break when it holds a line break.
+    space: Option<bool>,
+    /// Whether white space is dropped, at the start of a block.
+    trim: bool,
+    /// Text of the code block or inline code being collected.
+    code: Option<String>,
+    /// Alignments of the columns of the current table, and whether its
+    /// header has been read.
+    alignments: Vec<Alignment>,
+    table_head: bool,
+    footnote_label: Option<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn new() -> Self {
+        Importer {
+            events: Vec::new(),
+            stack: Vec::new(),
+            space: None,
+            trim: true,
+            code: None,
+            alignments: Vec::new(),
+            table_head: false,
+            footnote_label: None,
+        }
+    }
+
+    fn finish(mut self) -> Vec<Event<'a>> {
+        while !self.stack.is_empty() {
+            self.close();
+        }
+        self.events
+    }
+
+    fn skipping(&self) -> bool {
+        self.stack
+            .iter()
+            .any(|open| matches!(open.kind, Kind::Skip | Kind::FootnoteReference))
+    }
+
+    fn in_pre(&self) -> bool {
+        self.stack.iter().any(|open| open.name == "pre")
+    }
+
+    fn text(&mut self, text: &'a str) {
+        if text.is_empty() || self.skipping() {
+            return;
+        }
+        let text = decode_entities(text);
+        let in_pre = self.in_pre();
+        if let Some(ref mut code) = self.code {
+            if in_pre {
+                code.push_str(&text);
+            } else {
+                // line breaks in code spans are spaces
+                code.extend(text.chars().map(|c| if c == '\n' { ' ' } else { c }));
+            }
+            return;
+        }
+        let mut rest = &*text;
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+            if end > 0 {
+                self.inline_content();
+                self.push_text(&rest[..end]);
+                rest = &rest[end..];
+            }
+            let end = rest
+                .find(|c: char| !c.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+            if end > 0 {
+                if !self.trim {
+                    let newline = rest[..end].contains('\n');
+                    self.space = Some(self.space.unwrap_or(false) || newline);
+                }
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    /// Appends text to the last text event, or starts a new one.
+    fn push_text(&mut self, text: &str) {
+        if let Some(Event::Text(last)) = self.events.last_mut() {
+            *last = format!("{}{}", last, text).into();
+        } else {
+            self.events.push(Event::Text(text.to_owned().into()));
+        }
+    }
+
+    /// Comments, processing instructions and declarations.
+    fn other(&mut self, html: &'a str) {
+        let doctype = html
+            .get(..9)
+            .map_or(false, |start| start.eq_ignore_ascii_case("<!doctype"));
+        if self.skipping() || self.code.is_some() || doctype {
+            return;
+        }
+        if self.in_block_container() {
+            self.block_boundary();
+        } else {
+            self.inline_content();
+        }
+        self.events.push(Event::Html(html.into()));
+    }
+
+    /// Prepares for inline content, opening a paragraph for it where blocks
+    /// are expected, and writing the white space before it.
+    fn inline_content(&mut self) {
+        if self.in_block_container() {
+            self.events.push(Event::Start(Tag::Paragraph));
+            self.stack.push(Open {
+                name: String::new(),
+                kind: Kind::ImplicitParagraph,
+                start: self.events.len() - 1,
+            });
+        }
+        match self.space.take() {
+            Some(true) => self.events.push(Event::SoftBreak),
+            Some(false) => self.push_text(" "),
+            None => (),
+        }
+        self.trim = false;
+    }
+
+    /// Whether the innermost element holds blocks rather than inline
+    /// content.
+    fn in_block_container(&self) -> bool {
+        let open = self
+            .stack
+            .iter()
+            .rev()
+            .find(|open| !matches!(open.kind, Kind::Transparent));
+        match open {
+            None => true,
+            Some(open) => matches!(
+                open.kind,
+                Kind::Tag(Tag::BlockQuote) | Kind::Tag(Tag::FootnoteDefinition(_))
+            ),
+        }
+    }
+
+    /// Drops the white space at the boundary of a block, and closes the
+    /// paragraph holding inline content.
+    fn block_boundary(&mut self) {
+        self.space = None;
+        self.trim = true;
+        if let Some(Open {
+            kind: Kind::ImplicitParagraph,
+            ..
+        }) = self.stack.last()
+        {
+            self.close();
+        }
+        if let Some(Open { name, .. }) = self.stack.last() {
+            if name == "p" {
+                self.close();
+            }
+        }
+    }
+
+    fn open(&mut self, name: &str, kind: Kind<'a>) {
+        let start = self.events.len();
+        if let Kind::Tag(ref tag) = kind {
+            self.events.push(Event::Start(tag.clone()));
+        }
+        self.stack.push(Open {
+            name: name.to_owned(),
+            kind,
+            start,
+        });
+    }
+
+    fn start_tag(&mut self, scanned: &ScannedTag<'a>, source: &'a str) {
+        let name = scanned.name.to_ascii_lowercase();
+        let attribute = |attribute: &str| {
+            scanned
+                .attributes
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(attribute))
+                .map(|&(_, source)| attribute_value(source))
+        };
+        let class = attribute("class").unwrap_or_else(|| "".into());
+        let has_class = |wanted: &str| class.split_ascii_whitespace().any(|class| class == wanted);
+
+        if self.skipping() {
+            if !is_void(&name) && !scanned.self_closing {
+                if let (Some(href), true) = (attribute("href"), self.in_footnote_reference()) {
+                    let href = href.trim_start_matches('#');
+                    let label = strip_prefix(href, "fn-").unwrap_or(href);
+                    self.footnote_label = Some(label.to_owned());
+                }
+                self.open(&name, Kind::Skip);
+            }
+            return;
+        }
+        if self.code.is_some() {
+            // tags in code, like the spans of highlighted code, are dropped
+            if name == "code" && self.in_pre() {
+                if let Some(lang) = class
+                    .split_ascii_whitespace()
+                    .find_map(|class| strip_prefix(class, "language-"))
+                {
+                    self.set_code_block_info(lang.to_owned());
+                }
+            }
+            return;
+        }
+
+        let kind = match name.as_str() {
+            "p" => Kind::Tag(Tag::Paragraph),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = match name.as_bytes()[1] {
+                    b'1' => HeadingLevel::H1,
+                    b'2' => HeadingLevel::H2,
+                    b'3' => HeadingLevel::H3,
+                    b'4' => HeadingLevel::H4,
+                    b'5' => HeadingLevel::H5,
+                    _ => HeadingLevel::H6,
+                };
+                Kind::Tag(Tag::Heading(level))
+            }
+            "blockquote" => Kind::Tag(Tag::BlockQuote),
+            "pre" => {
+                self.code = Some(String::new());
+                Kind::Tag(Tag::CodeBlock(CodeBlockKind::Fenced("".into())))
+            }
+            "ul" => Kind::Tag(Tag::List(None)),
+            "ol" => {
+                let start = attribute("start").and_then(|start| start.trim().parse().ok());
+                Kind::Tag(Tag::List(Some(start.unwrap_or(1))))
+            }
+            "li" => Kind::Tag(Tag::Item),
+            "div" if has_class("footnote-definition") => {
+                let label = attribute("id").unwrap_or_else(|| "".into());
+                Kind::Tag(Tag::FootnoteDefinition(label))
+            }
+            "table" => {
+                self.alignments.clear();
+                self.table_head = false;
+                Kind::Tag(Tag::Table(Vec::new()))
+            }
+            "thead" => {
+                self.table_head = true;
+                Kind::Tag(Tag::TableHead)
+            }
+            "tr" if self.stack.iter().any(|open| open.name == "thead") => Kind::Transparent,
+            "tr" if !self.table_head => {
+                self.table_head = true;
+                Kind::Tag(Tag::TableHead)
+            }
+            "tr" => Kind::Tag(Tag::TableRow),
+            "th" | "td" => {
+                if self.in_table_header_row() {
+                    self.alignments.push(alignment(&attribute));
+                }
+                Kind::Tag(Tag::TableCell)
+            }
+            "tbody" | "html" | "body" => Kind::Transparent,
+            "head" | "script" | "style" | "template" => Kind::Skip,
+            "sup" if has_class("footnote-definition-label") => Kind::Skip,
+            "sup" if has_class("footnote-reference") || has_class("footnote-ref") => {
+                Kind::FootnoteReference
+            }
+            "a" if has_class("anchor") => Kind::Skip,
+            "em" | "i" => Kind::Tag(Tag::Emphasis),
+            "strong" | "b" => Kind::Tag(Tag::Strong),
+            "del" | "s" | "strike" => Kind::Tag(Tag::Strikethrough),
+            "code" => {
+                self.code = Some(String::new());
+                Kind::Code
+            }
+            "a" => {
+                let dest = attribute("href").unwrap_or_else(|| "".into());
+                let title = attribute("title").unwrap_or_else(|| "".into());
+                Kind::Tag(Tag::Link(LinkType::Inline, dest, title))
+            }
+            "img" => {
+                let dest = attribute("src").unwrap_or_else(|| "".into());
+                let title = attribute("title").unwrap_or_else(|| "".into());
+                let tag = Tag::Image(LinkType::Inline, dest, title);
+                self.inline_content();
+                self.events.push(Event::Start(tag.clone()));
+                if let Some(alt) = attribute("alt").filter(|alt| !alt.is_empty()) {
+                    self.events.push(Event::Text(alt));
+                }
+                self.events.push(Event::End(tag));
+                return;
+            }
+            "br" => {
+                self.inline_content();
+                self.events.push(Event::HardBreak);
+                self.trim = true;
+                return;
+            }
+            "hr" => {
+                self.block_boundary();
+                self.events.push(Event::Rule);
+                return;
+            }
+            "input" if attribute("type").as_deref() == Some("checkbox") => {
+                self.events
+                    .push(Event::TaskListMarker(attribute("checked").is_some()));
+                return;
+            }
+            _ => Kind::Raw,
+        };
+
+        match kind {
+            Kind::Tag(ref tag) if is_block(tag) => {
+                self.block_boundary();
+                if let (Tag::Item, Some("li")) = (tag, self.stack.last().map(|o| &*o.name)) {
+                    self.close();
+                }
+            }
+            Kind::Tag(Tag::TableCell) => {
+                self.inline_content();
+                self.trim = true;
+            }
+            Kind::Tag(_) | Kind::Code | Kind::FootnoteReference => self.inline_content(),
+            Kind::Raw => {
+                self.inline_content();
+                self.events.push(Event::Html(source.into()));
+            }
+            Kind::ImplicitParagraph | Kind::Transparent | Kind::Skip => (),
+        }
+        if !is_void(&name) && !scanned.self_closing {
+            self.open(&name, kind);
+        }
+    }
+
+    fn end_tag(&mut self, name: &str, source: &'a str) {
+        let ix = match self.stack.iter().rposition(|open| open.name == name) {
+            Some(ix) => ix,
+            None => return,
+        };
+        if self.code.is_some() && !matches!(name, "pre" | "code") {
+            return;
+        }
+        while self.stack.len() > ix + 1 {
+            self.close();
+        }
+        if let Some(Open {
+            kind: Kind::Raw, ..
+        }) = self.stack.last()
+        {
+            self.events.push(Event::Html(source.into()));
+        }
+        self.close();
+    }
+
+    /// Closes the innermost open element.
+    fn close(&mut self) {
+        let open = match self.stack.pop() {
+            Some(open) => open,
+            None => return,
+        };
+        match open.kind {
+            Kind::Tag(Tag::CodeBlock(kind)) => {
+                let mut code = self.code.take().unwrap_or_default();
+                if !code.is_empty() && !code.ends_with('\n') {
+                    code.push('\n');
+                }
+                if !code.is_empty() {
+                    self.events.push(Event::Text(code.into()));
+                }
+                self.events.push(Event::End(Tag::CodeBlock(kind)));
+                self.block_boundary();
+            }
+            Kind::Tag(Tag::Table(_)) => {
+                let alignments = std::mem::take(&mut self.alignments);
+                self.events[open.start] = Event::Start(Tag::Table(alignments.clone()));
+                self.events.push(Event::End(Tag::Table(alignments)));
+                self.block_boundary();
+            }
+            Kind::Tag(Tag::Link(LinkType::Inline, dest, title)) => {
+                let tag = match &self.events[open.start + 1..] {
+                    [Event::Text(text)] if *text == dest => {
+                        Tag::Link(LinkType::Autolink, dest, title)
+                    }
+                    [Event::Text(text)] if strip_prefix(&dest, "mailto:") == Some(&**text) => {
+                        Tag::Link(LinkType::Email, text.clone(), title)
+                    }
+                    _ => Tag::Link(LinkType::Inline, dest, title),
+                };
+                self.events[open.start] = Event::Start(tag.clone());
+                self.events.push(Event::End(tag));
+            }
+            Kind::Tag(Tag::TableCell) => {
+                self.space = None;
+                self.events.push(Event::End(Tag::TableCell));
+                self.trim = true;
+            }
+            Kind::Tag(tag) => {
+                let block = is_block(&tag);
+                if block {
+                    self.space = None;
+                }
+                self.events.push(Event::End(tag));
+                if block {
+                    self.block_boundary();
+                }
+            }
+            Kind::ImplicitParagraph => {
+                self.space = None;
+                self.events.push(Event::End(Tag::Paragraph));
+                self.trim = true;
+            }
+            Kind::Code => {
+                let code = self.code.take().unwrap_or_default();
+                self.events.push(Event::Code(code.into()));
+            }
+            Kind::FootnoteReference => {
+                if let Some(label) = self.footnote_label.take() {
+                    self.events.push(Event::FootnoteReference(label.into()));
+                }
+            }
+            Kind::Transparent | Kind::Raw | Kind::Skip => (),
+        }
+    }
+
+    fn set_code_block_info(&mut self, info: String) {
+        if let Some(open) = self.stack.iter_mut().rev().find(|open| open.name == "pre") {
+            let kind = CodeBlockKind::Fenced(info.into());
+            open.kind = Kind::Tag(Tag::CodeBlock(kind.clone()));
+            self.events[open.start] = Event::Start(Tag::CodeBlock(kind));
+        }
+    }
+
+    fn in_footnote_reference(&self) -> bool {
+        self.stack
+            .iter()
+            .any(|open| matches!(open.kind, Kind::FootnoteReference))
+    }
+
+    fn in_table_header_row(&self) -> bool {
+        self.stack
+            .iter()
+            .any(|open| matches!(open.kind, Kind::Tag(Tag::TableHead)))
+    }
+}
+
+fn is_block(tag: &Tag<'_>) -> bool {
+    !matches!(
+        tag,
+        Tag::Emphasis
+            | Tag::Strong
+            | Tag::Strikethrough
+            | Tag::Link(..)
+            | Tag::Image(..)
+            | Tag::TableCell
+    )
+}
+
+fn is_void(name: &str) -> bool {
+    matches!(
+        name,
+        "area"
+            | "base"
+            | "br"
+            | "col"
+            | "embed"
+            | "hr"
+            | "img"
+            | "input"
+            | "link"
+            | "meta"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+/// Returns the alignment of a table cell, from its `align` attribute, its
+/// `text-align` style or a `text-align-*` class.
+fn alignment<'a>(attribute: &dyn Fn(&str) -> Option<CowStr<'a>>) -> Alignment {
+    let align = attribute("align")
+        .map(|align| align.to_ascii_lowercase())
+        .or_else(|| {
+            let style = attribute("style")?;
+            let value = style.split("text-align:").nth(1)?;
+            Some(value.split(';').next()?.trim().to_ascii_lowercase())
+        })
+        .or_else(|| {
+            let class = attribute("class")?;
+            let align = class
+                .split_ascii_whitespace()
+                .find_map(|class| strip_prefix(class, "text-align-"))?;
+            Some(align.to_owned())
+        });
+    match align.as_deref() {
+        Some("left") => Alignment::Left,
+        Some("center") => Alignment::Center,
+        Some("right") => Alignment::Right,
+        _ => Alignment::None,
+    }
+}
+
+/// Returns the value of an attribute from its source text, `name="value"`.
+fn attribute_value(source: &str) -> CowStr<'_> {
+    let value = match source.find('=') {
+        Some(ix) => source[ix + 1..].trim_start(),
+        None => return "".into(),
+    };
+    let value = match value.as_bytes().first() {
+        Some(b'"') | Some(b'\'') => &value[1..value.len() - 1],
+        _ => value,
+    };
+    decode_entities(value)
+}
+
+/// Replaces the character references in `text`.
+fn decode_entities(text: &str) -> CowStr<'_> {
+    if !text.contains('&') {
+        return text.into();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ix) = rest.find('&') {
+        decoded.push_str(&rest[..ix]);
+        rest = &rest[ix..];
+        match scan_entity(rest.as_bytes()) {
+            (len, Some(value)) => {
+                decoded.push_str(&value);
+                rest = &rest[len..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Options, Parser};
+
+    #[test]
+    fn html_writer_output() {
+        let input = "\
+# Title
+
+Some *emphasis*, **strong**, ~~struck~~ and `code &lt; x` text
+with a [link](/a \"t\"), <https://example.com>, ![img](i.png) and a note[^n].\\
+Break.
+
+> quote
+
+1. one
+2. two
+- [x] done
+
+```rust
+fn main() {}
+```
+
+| a | b |
+|:-:|--:|
+| c | d |
+
+[^n]: The note.
+";
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS;
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, Parser::new_ext(input, options));
+
+        let mut expected: Vec<Event<'_>> = Vec::new();
+        for event in Parser::new_ext(input, options) {
+            if let (Some(Event::Text(last)), Event::Text(text)) = (expected.last_mut(), &event) {
+                *last = format!("{}{}", last, text).into();
+                continue;
+            }
+            expected.push(event);
+        }
+        assert_eq!(expected, parse_html(&html_buf), "{}", html_buf);
+    }
+
+    #[test]
+    fn loose_html() {
+        let html = "<!DOCTYPE html><html><head><title>x</title></head><body>\n\
+            Text <span class=\"x\">span</span><br>\n<p>one<p>two\n<ul><li>a<li>b</ul>\n\
+            <!-- c --></body></html>";
+        let events = parse_html(html);
+        let mut s = String::new();
+        html::push_html(&mut s, events.into_iter());
+        assert_eq!(
+            "<p>Text <span class=\"x\">span</span><br />\n</p>\n<p>one</p>\n<p>two</p>\n\
+             <ul>\n<li>a</li>\n<li>b</li>\n</ul>\n<!-- c -->",
+            s
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Jira and Confluence wiki markup writer that takes an iterator of events
+//! as input.
+//!
+//! Headings become `h1.` to `h6.` lines, code blocks `{code}` macros, block
+//! quotes `{quote}` macros, lists `*` and `#` items and tables `||` header
+//! rows followed by `|` rows. Raw HTML is dropped.
+//!
+//! Wiki markup is less expressive than markdown: a line break starts a new
+//! line, so soft breaks are written as spaces, list items hold a single
+//! line, so their later paragraphs are joined with forced line breaks, and
+//! footnotes become superscript numbers.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::strings::CowStr;
+use crate::Event::*;
+use crate::{CodeBlockKind, Event, LinkType, Tag};
+
+/// Writes `s` to `w`, escaping the characters that are wiki markup.
+///
+/// Hyphens are only escaped next to white space, where they could strike
+/// text through, so that hyphenated words stay readable.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::jira::escape_jira;
+///
+/// let mut s = String::new();
+/// escape_jira(&mut s, "a *well-known* {macro} - [link]").unwrap();
+/// assert_eq!(s, "a \\*well-known\\* \\{macro\\} \\- \\[link\\]");
+/// ```
+pub fn escape_jira<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let bytes = s.as_bytes();
+    let mut mark = 0;
+    for (ix, &c) in bytes.iter().enumerate() {
+        let special = match c {
+            b'\\' | b'*' | b'_' | b'+' | b'^' | b'~' | b'{' | b'}' | b'[' | b']' | b'|' | b'!' => {
+                true
+            }
+            b'-' => {
+                let before = ix.checked_sub(1).map(|ix| bytes[ix]);
+                let after = bytes.get(ix + 1).copied();
+                before.map_or(true, |c| c.is_ascii_whitespace())
+                    || after.map_or(true, |c| c.is_ascii_whitespace())
+            }
+            _ => false,
+        };
+        if special {
+            w.write_str(&s[mark..ix])?;
+            w.write_str("\\")?;
+            mark = ix;
+        }
+    }
+    w.write_str(&s[mark..])
+}
+
+struct JiraWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Whether a blank line goes before the next block.
+    blank_line: bool,
+    /// Whether the open lists are ordered.
+    lists: Vec<bool>,
+    /// Whether an item or footnote marker was just written, which the first
+    /// block follows on the same line.
+    after_marker: bool,
+    in_code_block: bool,
+    in_table_head: bool,
+    /// Alt text of the image being written.
+    alt: Option<String>,
+    /// Nesting depth of autolinks, whose text is the destination.
+    autolinks: usize,
+    numbers: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a, I, W> JiraWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W) -> Self {
+        JiraWriter {
+            iter,
+            writer,
+            line_start: true,
+            blank_line: false,
+            lists: Vec::new(),
+            after_marker: false,
+            in_code_block: false,
+            in_table_head: false,
+            alt: None,
+            autolinks: 0,
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        while let Some(event) = self.iter.next() {
+            if let Some(ref mut alt) = self.alt {
+                match event {
+                    End(Tag::Image(..)) => (),
+                    Text(text) | Code(text) => {
+                        alt.push_str(&text);
+                        continue;
+                    }
+                    _ => continue,
+                }
+            }
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => self.write(&text)?,
+                Text(text) if self.autolinks == 0 => self.write_text(&text)?,
+                Text(_) | Html(_) => (),
+                Code(text) => {
+                    self.write("{{")?;
+                    self.write_text(&text)?;
+                    self.write("}}")?;
+                }
+                SoftBreak => self.write(" ")?,
+                HardBreak => self.write("\\\\ ")?,
+                Rule => {
+                    self.start_block()?;
+                    self.write("----")?;
+                    self.end_block()?;
+                }
+                FootnoteReference(name) => {
+                    let number = self.footnote_number(name);
+                    self.write(&format!("^{}^", number))?;
+                }
+                TaskListMarker(true) => self.write("\\[x\\] ")?,
+                TaskListMarker(false) => self.write("\\[ \\] ")?,
+            }
+        }
+        self.end_line()
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph if !self.after_marker && !self.lists.is_empty() => {
+                // later paragraphs of an item
+                self.write(" \\\\ ")
+            }
+            Tag::Paragraph => self.start_block(),
+            Tag::Heading(level) => {
+                self.start_block()?;
+                self.write(&format!("h{}. ", level as usize))
+            }
+            Tag::BlockQuote => {
+                self.start_block()?;
+                self.write("{quote}\n")
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block()?;
+                self.in_code_block = true;
+                match kind {
+                    CodeBlockKind::Fenced(ref info) if !info.is_empty() => {
+                        let lang = info.split(' ').next().unwrap();
+                        self.write(&format!("{{code:{}}}\n", lang))
+                    }
+                    // `{code}` without a language highlights as Java
+                    _ => self.write("{noformat}\n"),
+                }
+            }
+            Tag::List(ordered) => {
+                self.start_block()?;
+                self.lists.push(ordered.is_some());
+                Ok(())
+            }
+            Tag::Item => {
+                self.end_line()?;
+                let marker: String = self
+                    .lists
+                    .iter()
+                    .map(|&ordered| if ordered { '#' } else { '*' })
+                    .collect();
+                self.write(&marker)?;
+                self.write(" ")?;
+                self.after_marker = true;
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.start_block()?;
+                let number = self.footnote_number(name);
+                self.write(&format!("^{}^ ", number))?;
+                self.after_marker = true;
+                Ok(())
+            }
+            Tag::Table(_) => self.start_block(),
+            Tag::TableHead => {
+                self.in_table_head = true;
+                Ok(())
+            }
+            Tag::TableRow => Ok(()),
+            Tag::TableCell => {
+                if self.in_table_head {
+                    self.write("||")
+                } else {
+                    self.write("|")
+                }
+            }
+            Tag::Emphasis => self.write("_"),
+            Tag::Strong => self.write("*"),
+            Tag::Strikethrough => self.write("-"),
+            Tag::Link(LinkType::Autolink, dest, _) => {
+                self.autolinks += 1;
+                self.write("[")?;
+                self.write(&dest)
+            }
+            Tag::Link(LinkType::Email, dest, _) => {
+                self.autolinks += 1;
+                self.write("[mailto:")?;
+                self.write(&dest)
+            }
+            Tag::Link(..) => self.write("["),
+            Tag::Image(..) => {
+                self.alt = Some(String::new());
+                Ok(())
+            }
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph if !self.lists.is_empty() => Ok(()),
+            Tag::Paragraph | Tag::Heading(_) | Tag::FootnoteDefinition(_) | Tag::Table(_) => {
+                self.end_block()
+            }
+            Tag::BlockQuote => {
+                self.end_line()?;
+                self.write("{quote}")?;
+                self.end_block()
+            }
+            Tag::CodeBlock(kind) => {
+                self.in_code_block = false;
+                self.end_line()?;
+                match kind {
+                    CodeBlockKind::Fenced(ref info) if !info.is_empty() => self.write("{code}")?,
+                    _ => self.write("{noformat}")?,
+                }
+                self.end_block()
+            }
+            Tag::List(_) => {
+                self.lists.pop();
+                self.end_block()
+            }
+            Tag::Item => {
+                self.after_marker = false;
+                self.end_line()
+            }
+            Tag::TableHead => {
+                self.in_table_head = false;
+                self.write("||")?;
+                self.end_line()
+            }
+            Tag::TableRow => {
+                self.write("|")?;
+                self.end_line()
+            }
+            Tag::TableCell => Ok(()),
+            Tag::Emphasis => self.write("_"),
+            Tag::Strong => self.write("*"),
+            Tag::Strikethrough => self.write("-"),
+            Tag::Link(LinkType::Autolink, ..) | Tag::Link(LinkType::Email, ..) => {
+                self.autolinks -= 1;
+                self.write("]")
+            }
+            Tag::Link(_, dest, _) => {
+                self.write("|")?;
+                self.write(&dest)?;
+                self.write("]")
+            }
+            Tag::Image(_, dest, _) => {
+                let alt = self.alt.take().unwrap_or_default();
+                self.write("!")?;
+                self.write(&dest)?;
+                if !alt.is_empty() {
+                    // attribute values end at these characters
+                    let alt = alt.replace(&['|', '!', ','][..], " ");
+                    self.write("|alt=")?;
+                    self.write(alt.trim())?;
+                }
+                self.write("!")
+            }
+        }
+    }
+
+    fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    /// Ends the current line, and writes the blank line separating the
+    /// block that starts from the previous one. Blocks inside list items
+    /// start on the line of their marker.
+    fn start_block(&mut self) -> Result<(), W::Error> {
+        if self.after_marker {
+            self.after_marker = false;
+            return Ok(());
+        }
+        self.end_line()?;
+        if self.blank_line {
+            self.blank_line = false;
+            self.write("\n")?;
+        }
+        Ok(())
+    }
+
+    /// Ends a block, with a blank line before the next one outside of lists,
+    /// where it would end the list.
+    fn end_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.blank_line = self.lists.is_empty();
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.write("\n")
+        }
+    }
+
+    /// Writes escaped text, protecting a `#` at the start of a line, which
+    /// would start a list item.
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        if self.line_start && text.starts_with('#') {
+            self.write("\\")?;
+        }
+        escape_jira(&mut self.writer, text)?;
+        self.line_start = false;
+        self.after_marker = false;
+        Ok(())
+    }
+
+    fn write(&mut self, markup: &str) -> Result<(), W::Error> {
+        if markup.is_empty() {
+            return Ok(());
+        }
+        self.writer.write_str(markup)?;
+        self.line_start = markup.ends_with('\n');
+        self.after_marker = false;
+        Ok(())
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate wiki markup for each
+/// `Event`, and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{jira, Parser};
+///
+/// let markdown = "## Steps\n\n1. Run `make`\n2. See [the log](http://ci/log)\n";
+/// let mut wiki = String::new();
+/// jira::push_jira(&mut wiki, Parser::new(markdown));
+/// assert_eq!(wiki, "\
+/// h2. Steps
+///
+/// ## Run {{make}}
+/// ## See [the log|http://ci/log]
+/// ");
+/// ```
+pub fn push_jira<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    JiraWriter::new(iter, s).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate wiki markup for each
+/// `Event`, and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_jira<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    JiraWriter::new(iter, WriteWrapper(writer)).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn blocks() {
+        let input = "\
+Some **bold**, ~~old~~ and
+#2 <http://x.org/>[^n].
+
+* one
+
+  more
+  1. [x] nested
+* two
+
+> quoted
+
+| a | b |
+|---|---|
+| ![alt, *x*](e.png) | f |
+
+```
+{code}
+```
+
+[^n]: Note.
+";
+        let expected = "\
+Some *bold*, -old- and #2 [http://x.org/]^1^.
+
+* one \\\\ more
+*# \\[x\\] nested
+* two
+
+{quote}
+quoted
+{quote}
+
+||a||b||
+|!e.png|alt=alt  x!|f|
+
+{noformat}
+{code}
+{noformat}
+
+^1^ Note.
+";
+        let parser_options = crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_STRIKETHROUGH
+            | crate::Options::ENABLE_TASKLISTS;
+        let mut s = String::new();
+        push_jira(&mut s, Parser::new_ext(input, parser_options));
+        assert_eq!(expected, s);
+    }
+}
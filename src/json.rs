@@ -0,0 +1,669 @@
+//! Event streams as JSON, to cache them or to pass them between processes.
+//!
+//! [`write_events_json`](fn.write_events_json.html) writes one JSON object
+//! per line for every event, and
+//! [`read_events_json`](fn.read_events_json.html) reads them back, so that
+//! they can be replayed into any writer. The schema does not depend on the
+//! `serde` feature, and only grows in compatible ways: readers ignore the
+//! keys they don't know.
+//!
+//! Every object has an `event` key, and `start` and `end` events have a
+//! `tag` key:
+//!
+//! | `event` | `tag` | Other keys |
+//! |---------|-------|------------|
+//! | `start`, `end` | `paragraph`, `block_quote`, `item`, `table_head`, `table_row`, `table_cell`, `emphasis`, `strong`, `strikethrough` | |
+//! | `start`, `end` | `heading` | `level`: 1 to 6 |
+//! | `start`, `end` | `code_block` | `fenced`: boolean, `info`: string when fenced |
+//! | `start`, `end` | `list` | `start`: number of the first item, or `null` |
+//! | `start`, `end` | `footnote_definition` | `label`: string |
+//! | `start`, `end` | `table` | `alignments`: array of `none`, `left`, `center` and `right` |
+//! | `start`, `end` | `link`, `image` | `link_type`, `dest`: string, `title`: string |
+//! | `text`, `code`, `html` | | `text`: string |
+//! | `footnote_reference` | | `label`: string |
+//! | `soft_break`, `hard_break`, `rule` | | |
+//! | `task_list_marker` | | `checked`: boolean |
+//!
+//! The `link_type` is one of `inline`, `reference`, `reference_unknown`,
+//! `collapsed`, `collapsed_unknown`, `shortcut`, `shortcut_unknown`,
+//! `autolink`, `email` and `wikilink`. Events written with their offsets in the source
+//! also have a `range` key, holding the start and end offsets in an array.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::strings::CowStr;
+use crate::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag};
+
+/// An error in the JSON of an event stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonError {
+    /// Line of the event where the error was found, starting at 1.
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+fn write_string<W: StrWrite>(w: &mut W, s: &str) -> Result<(), W::Error> {
+    w.write_str("\"")?;
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"".to_owned(),
+            '\\' => "\\\\".to_owned(),
+            '\n' => "\\n".to_owned(),
+            '\r' => "\\r".to_owned(),
+            '\t' => "\\t".to_owned(),
+            c if c < ' ' => format!("\\u{:04x}", c as u32),
+            _ => continue,
+        };
+        w.write_str(&s[mark..ix])?;
+        w.write_str(&escaped)?;
+        mark = ix + 1;
+    }
+    w.write_str(&s[mark..])?;
+    w.write_str("\"")
+}
+
+fn write_key<W: StrWrite>(w: &mut W, key: &str) -> Result<(), W::Error> {
+    w.write_str(",\"")?;
+    w.write_str(key)?;
+    w.write_str("\":")
+}
+
+fn write_string_field<W: StrWrite>(w: &mut W, key: &str, value: &str) -> Result<(), W::Error> {
+    write_key(w, key)?;
+    write_string(w, value)
+}
+
+fn link_type_name(link_type: LinkType) -> &'static str {
+    match link_type {
+        LinkType::Inline => "inline",
+        LinkType::Reference => "reference",
+        LinkType::ReferenceUnknown => "reference_unknown",
+        LinkType::Collapsed => "collapsed",
+        LinkType::CollapsedUnknown => "collapsed_unknown",
+        LinkType::Shortcut => "shortcut",
+        LinkType::ShortcutUnknown => "shortcut_unknown",
+        LinkType::Autolink => "autolink",
+        LinkType::Email => "email",
+        LinkType::WikiLink => "wikilink",
+    }
+}
+
+fn write_tag<W: StrWrite>(w: &mut W, tag: &Tag) -> Result<(), W::Error> {
+    let name = match tag {
+        Tag::Paragraph => "paragraph",
+        Tag::Heading(_) => "heading",
+        Tag::BlockQuote => "block_quote",
+        Tag::CodeBlock(_) => "code_block",
+        Tag::List(_) => "list",
+        Tag::Item => "item",
+        Tag::FootnoteDefinition(_) => "footnote_definition",
+        Tag::Table(_) => "table",
+        Tag::TableHead => "table_head",
+        Tag::TableRow => "table_row",
+        Tag::TableCell => "table_cell",
+        Tag::Emphasis => "emphasis",
+        Tag::Strong => "strong",
+        Tag::Strikethrough => "strikethrough",
+        Tag::Link(..) => "link",
+        Tag::Image(..) => "image",
+    };
+    write_string_field(w, "tag", name)?;
+    match tag {
+        Tag::Heading(level) => {
+            write_key(w, "level")?;
+            w.write_str(&(*level as usize).to_string())
+        }
+        Tag::CodeBlock(CodeBlockKind::Indented) => {
+            write_key(w, "fenced")?;
+            w.write_str("false")
+        }
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            write_key(w, "fenced")?;
+            w.write_str("true")?;
+            write_string_field(w, "info", info)
+        }
+        Tag::List(start) => {
+            write_key(w, "start")?;
+            match start {
+                Some(start) => w.write_str(&start.to_string()),
+                None => w.write_str("null"),
+            }
+        }
+        Tag::FootnoteDefinition(label) => write_string_field(w, "label", label),
+        Tag::Table(alignments) => {
+            write_key(w, "alignments")?;
+            w.write_str("[")?;
+            for (ix, alignment) in alignments.iter().enumerate() {
+                if ix > 0 {
+                    w.write_str(",")?;
+                }
+                w.write_str(match alignment {
+                    Alignment::None => "\"none\"",
+                    Alignment::Left => "\"left\"",
+                    Alignment::Center => "\"center\"",
+                    Alignment::Right => "\"right\"",
+                })?;
+            }
+            w.write_str("]")
+        }
+        Tag::Link(link_type, dest, title) | Tag::Image(link_type, dest, title) => {
+            write_string_field(w, "link_type", link_type_name(*link_type))?;
+            write_string_field(w, "dest", dest)?;
+            write_string_field(w, "title", title)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_event<W: StrWrite>(
+    w: &mut W,
+    event: &Event,
+    range: Option<Range<usize>>,
+) -> Result<(), W::Error> {
+    let name = match event {
+        Event::Start(_) => "start",
+        Event::End(_) => "end",
+        Event::Text(_) => "text",
+        Event::Code(_) => "code",
+        Event::Html(_) => "html",
+        Event::FootnoteReference(_) => "footnote_reference",
+        Event::SoftBreak => "soft_break",
+        Event::HardBreak => "hard_break",
+        Event::Rule => "rule",
+        Event::TaskListMarker(_) => "task_list_marker",
+    };
+    w.write_str("{\"event\":\"")?;
+    w.write_str(name)?;
+    w.write_str("\"")?;
+    match event {
+        Event::Start(tag) | Event::End(tag) => write_tag(w, tag)?,
+        Event::Text(text) | Event::Code(text) | Event::Html(text) => {
+            write_string_field(w, "text", text)?
+        }
+        Event::FootnoteReference(label) => write_string_field(w, "label", label)?,
+        Event::TaskListMarker(checked) => {
+            write_key(w, "checked")?;
+            w.write_str(if *checked { "true" } else { "false" })?;
+        }
+        Event::SoftBreak | Event::HardBreak | Event::Rule => (),
+    }
+    if let Some(range) = range {
+        write_key(w, "range")?;
+        w.write_str(&format!("[{},{}]", range.start, range.end))?;
+    }
+    w.write_str("}\n")
+}
+
+/// Writes the events as JSON, one object per line.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::json::write_events_json;
+/// use pulldown_cmark::Parser;
+///
+/// let mut bytes = Vec::new();
+/// write_events_json(&mut bytes, Parser::new("# Hi")).unwrap();
+/// assert_eq!(String::from_utf8(bytes).unwrap(), "\
+/// {\"event\":\"start\",\"tag\":\"heading\",\"level\":1}
+/// {\"event\":\"text\",\"text\":\"Hi\"}
+/// {\"event\":\"end\",\"tag\":\"heading\",\"level\":1}
+/// ");
+/// ```
+pub fn write_events_json<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    let mut writer = WriteWrapper(writer);
+    for event in iter {
+        write_event(&mut writer, &event, None)?;
+    }
+    Ok(())
+}
+
+/// Like [`write_events_json`](fn.write_events_json.html), but writes the
+/// offsets of the events too, as produced by
+/// [`Parser::into_offset_iter`](../struct.Parser.html#method.into_offset_iter).
+pub fn write_events_json_with_offsets<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+    W: io::Write,
+{
+    let mut writer = WriteWrapper(writer);
+    for (event, range) in iter {
+        write_event(&mut writer, &event, Some(range))?;
+    }
+    Ok(())
+}
+
+/// A JSON value, with the strings borrowed from the input when they have
+/// no escapes.
+#[derive(Debug)]
+enum Value<'a> {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(CowStr<'a>),
+    Array(Vec<Value<'a>>),
+    Object(Vec<(CowStr<'a>, Value<'a>)>),
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    ix: usize,
+}
+
+type ParseResult<T> = Result<T, &'static str>;
+type SpannedEvent<'a> = (Event<'a>, Option<Range<usize>>);
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        let bytes = self.input.as_bytes();
+        while let Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') = bytes.get(self.ix) {
+            self.ix += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.as_bytes().get(self.ix).copied()
+    }
+
+    fn expect(&mut self, byte: u8, message: &'static str) -> ParseResult<()> {
+        if self.peek() == Some(byte) {
+            self.ix += 1;
+            Ok(())
+        } else {
+            Err(message)
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value<'a>) -> ParseResult<Value<'a>> {
+        if self.input[self.ix..].starts_with(literal) {
+            self.ix += literal.len();
+            Ok(value)
+        } else {
+            Err("invalid literal")
+        }
+    }
+
+    fn value(&mut self) -> ParseResult<Value<'a>> {
+        match self.peek() {
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'0'..=b'9') => {
+                let digits = self.input[self.ix..]
+                    .bytes()
+                    .take_while(u8::is_ascii_digit)
+                    .count();
+                let number = self.input[self.ix..self.ix + digits]
+                    .parse()
+                    .map_err(|_| "number out of range")?;
+                self.ix += digits;
+                Ok(Value::Number(number))
+            }
+            Some(b'[') => {
+                self.ix += 1;
+                let mut values = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.ix += 1;
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.ix += 1,
+                        Some(b']') => {
+                            self.ix += 1;
+                            return Ok(Value::Array(values));
+                        }
+                        _ => return Err("expected `,` or `]`"),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.ix += 1;
+                let mut fields = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.ix += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return Err("expected a key");
+                    }
+                    let key = self.string()?;
+                    self.expect(b':', "expected `:`")?;
+                    fields.push((key, self.value()?));
+                    match self.peek() {
+                        Some(b',') => self.ix += 1,
+                        Some(b'}') => {
+                            self.ix += 1;
+                            return Ok(Value::Object(fields));
+                        }
+                        _ => return Err("expected `,` or `}`"),
+                    }
+                }
+            }
+            Some(_) => Err("unexpected character"),
+            None => Err("unexpected end of input"),
+        }
+    }
+
+    /// Parses a string, starting at its opening quote.
+    fn string(&mut self) -> ParseResult<CowStr<'a>> {
+        self.ix += 1;
+        let start = self.ix;
+        let rest = &self.input[start..];
+        let end = rest.find(&['"', '\\'][..]).ok_or("unterminated string")?;
+        if rest.as_bytes()[end] == b'"' {
+            self.ix += end + 1;
+            return Ok(CowStr::Borrowed(&rest[..end]));
+        }
+        let mut s = String::from(&rest[..end]);
+        self.ix += end;
+        loop {
+            let mut chars = self.input[self.ix..].chars();
+            match chars.next().ok_or("unterminated string")? {
+                '"' => {
+                    self.ix += 1;
+                    return Ok(s.into());
+                }
+                '\\' => {
+                    let escaped = chars.next().ok_or("unterminated string")?;
+                    self.ix += 2;
+                    match escaped {
+                        '"' | '\\' | '/' => s.push(escaped),
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => s.push(self.unicode_escape()?),
+                        _ => return Err("invalid escape"),
+                    }
+                }
+                c => {
+                    s.push(c);
+                    self.ix += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Parses the code unit of a `\u` escape, and the low surrogate that
+    /// follows a high one.
+    fn unicode_escape(&mut self) -> ParseResult<char> {
+        let high = self.code_unit()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return std::char::from_u32(high).ok_or("invalid unicode escape");
+        }
+        if !self.input[self.ix..].starts_with("\\u") {
+            return Err("invalid unicode escape");
+        }
+        self.ix += 2;
+        let low = self.code_unit()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err("invalid unicode escape");
+        }
+        let c = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+        std::char::from_u32(c).ok_or("invalid unicode escape")
+    }
+
+    fn code_unit(&mut self) -> ParseResult<u32> {
+        let hex = self
+            .input
+            .get(self.ix..self.ix + 4)
+            .ok_or("invalid unicode escape")?;
+        let unit = u32::from_str_radix(hex, 16).map_err(|_| "invalid unicode escape")?;
+        self.ix += 4;
+        Ok(unit)
+    }
+}
+
+/// Fields of an event object.
+struct Fields<'a>(Vec<(CowStr<'a>, Value<'a>)>);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, key: &str) -> Option<Value<'a>> {
+        let ix = self.0.iter().position(|(k, _)| k.as_ref() == key)?;
+        Some(self.0.swap_remove(ix).1)
+    }
+
+    fn string(&mut self, key: &str) -> ParseResult<CowStr<'a>> {
+        match self.take(key) {
+            Some(Value::String(s)) => Ok(s),
+            _ => Err("expected a string"),
+        }
+    }
+
+    fn bool(&mut self, key: &str) -> ParseResult<bool> {
+        match self.take(key) {
+            Some(Value::Bool(b)) => Ok(b),
+            _ => Err("expected a boolean"),
+        }
+    }
+
+    fn tag(&mut self) -> ParseResult<Tag<'a>> {
+        let name = self.string("tag")?;
+        Ok(match name.as_ref() {
+            "paragraph" => Tag::Paragraph,
+            "heading" => {
+                let level = match self.take("level") {
+                    Some(Value::Number(level)) => level as usize,
+                    _ => return Err("expected a heading level"),
+                };
+                Tag::Heading(HeadingLevel::try_from(level).map_err(|_| "invalid heading level")?)
+            }
+            "block_quote" => Tag::BlockQuote,
+            "code_block" => {
+                if self.bool("fenced")? {
+                    Tag::CodeBlock(CodeBlockKind::Fenced(self.string("info")?))
+                } else {
+                    Tag::CodeBlock(CodeBlockKind::Indented)
+                }
+            }
+            "list" => match self.take("start") {
+                Some(Value::Number(start)) => Tag::List(Some(start)),
+                Some(Value::Null) => Tag::List(None),
+                _ => return Err("expected a list start"),
+            },
+            "item" => Tag::Item,
+            "footnote_definition" => Tag::FootnoteDefinition(self.string("label")?),
+            "table" => {
+                let values = match self.take("alignments") {
+                    Some(Value::Array(values)) => values,
+                    _ => return Err("expected alignments"),
+                };
+                let alignments = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::String(ref s) if s.as_ref() == "none" => Ok(Alignment::None),
+                        Value::String(ref s) if s.as_ref() == "left" => Ok(Alignment::Left),
+                        Value::String(ref s) if s.as_ref() == "center" => Ok(Alignment::Center),
+                        Value::String(ref s) if s.as_ref() == "right" => Ok(Alignment::Right),
+                        _ => Err("invalid alignment"),
+                    })
+                    .collect::<ParseResult<_>>()?;
+                Tag::Table(alignments)
+            }
+            "table_head" => Tag::TableHead,
+            "table_row" => Tag::TableRow,
+            "table_cell" => Tag::TableCell,
+            "emphasis" => Tag::Emphasis,
+            "strong" => Tag::Strong,
+            "strikethrough" => Tag::Strikethrough,
+            "link" | "image" => {
+                let link_type = match self.string("link_type")?.as_ref() {
+                    "inline" => LinkType::Inline,
+                    "reference" => LinkType::Reference,
+                    "reference_unknown" => LinkType::ReferenceUnknown,
+                    "collapsed" => LinkType::Collapsed,
+                    "collapsed_unknown" => LinkType::CollapsedUnknown,
+                    "shortcut" => LinkType::Shortcut,
+                    "shortcut_unknown" => LinkType::ShortcutUnknown,
+                    "autolink" => LinkType::Autolink,
+                    "email" => LinkType::Email,
+                    "wikilink" => LinkType::WikiLink,
+                    _ => return Err("invalid link type"),
+                };
+                let dest = self.string("dest")?;
+                let title = self.string("title")?;
+                if name.as_ref() == "link" {
+                    Tag::Link(link_type, dest, title)
+                } else {
+                    Tag::Image(link_type, dest, title)
+                }
+            }
+            _ => return Err("invalid tag"),
+        })
+    }
+
+    fn event(&mut self) -> ParseResult<Event<'a>> {
+        let name = self.string("event")?;
+        Ok(match name.as_ref() {
+            "start" => Event::Start(self.tag()?),
+            "end" => Event::End(self.tag()?),
+            "text" => Event::Text(self.string("text")?),
+            "code" => Event::Code(self.string("text")?),
+            "html" => Event::Html(self.string("text")?),
+            "footnote_reference" => Event::FootnoteReference(self.string("label")?),
+            "soft_break" => Event::SoftBreak,
+            "hard_break" => Event::HardBreak,
+            "rule" => Event::Rule,
+            "task_list_marker" => Event::TaskListMarker(self.bool("checked")?),
+            _ => return Err("invalid event"),
+        })
+    }
+
+    fn range(&mut self) -> ParseResult<Option<Range<usize>>> {
+        let values = match self.take("range") {
+            None => return Ok(None),
+            Some(Value::Array(values)) => values,
+            Some(_) => return Err("expected a range"),
+        };
+        match values[..] {
+            [Value::Number(start), Value::Number(end)] => Ok(Some(start as usize..end as usize)),
+            _ => Err("expected a range"),
+        }
+    }
+}
+
+/// Reads events written by
+/// [`write_events_json`](fn.write_events_json.html), borrowing their text
+/// from `input` when it has no escapes.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::json::{read_events_json, write_events_json};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let mut bytes = Vec::new();
+/// write_events_json(&mut bytes, Parser::new("*Hello* \"world\"")).unwrap();
+///
+/// let json = String::from_utf8(bytes).unwrap();
+/// let events = read_events_json(&json).unwrap();
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, events.into_iter());
+/// assert_eq!(html_buf, "<p><em>Hello</em> &quot;world&quot;</p>\n");
+/// ```
+pub fn read_events_json(input: &str) -> Result<Vec<Event<'_>>, JsonError> {
+    let events = read_events_json_with_offsets(input)?;
+    Ok(events.into_iter().map(|(event, _)| event).collect())
+}
+
+/// Like [`read_events_json`](fn.read_events_json.html), but also returns
+/// the offsets of the events that have them.
+pub fn read_events_json_with_offsets(input: &str) -> Result<Vec<SpannedEvent<'_>>, JsonError> {
+    let mut events = Vec::new();
+    for (ix, line) in input.lines().enumerate() {
+        let error = |message| JsonError {
+            line: ix + 1,
+            message,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parser = JsonParser { input: line, ix: 0 };
+        let fields = match parser.value().map_err(error)? {
+            Value::Object(fields) => fields,
+            _ => return Err(error("expected an object")),
+        };
+        if parser.peek().is_some() {
+            return Err(error("trailing characters"));
+        }
+        let mut fields = Fields(fields);
+        let event = fields.event().map_err(error)?;
+        let range = fields.range().map_err(error)?;
+        events.push((event, range));
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn round_trip() {
+        let input = "\
+# Title
+
+* [x] **a** `b` <http://x.org/> ![i](j.png \"t\")
+* ~~c~~\\
+  d[^n] <span>
+
+| a | b |
+|:-:|--:|
+| e | f |
+
+```rust
+let s = \"\\t\u{1f600}\";
+```
+---
+
+[^n]: Note.
+";
+        let options = crate::Options::all();
+        let events: Vec<_> = Parser::new_ext(input, options).into_offset_iter().collect();
+        let mut bytes = Vec::new();
+        write_events_json_with_offsets(&mut bytes, events.clone().into_iter()).unwrap();
+        let json = String::from_utf8(bytes).unwrap();
+        let read = read_events_json_with_offsets(&json).unwrap();
+        let expected: Vec<_> = events
+            .into_iter()
+            .map(|(event, range)| (event, Some(range)))
+            .collect();
+        assert_eq!(expected, read);
+    }
+
+    #[test]
+    fn escapes_and_errors() {
+        let json = "{\"event\":\"text\",\"text\":\"\\u00e9\\ud83d\\ude00\\n\",\"extra\":[1,{}]}\n";
+        let events = read_events_json(json).unwrap();
+        assert_eq!(events, vec![Event::Text("é\u{1f600}\n".into())]);
+
+        let json = "{\"event\":\"rule\"}\n\n{\"event\":\"start\",\"tag\":\"heading\",\"level\":7}";
+        let error = read_events_json(json).unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(error.message, "invalid heading level");
+    }
+}
@@ -0,0 +1,515 @@
+//! LaTeX writer that takes an iterator of events as input.
+//!
+//! Headings become sectioning commands, lists `itemize` and `enumerate`
+//! environments, code blocks `verbatim` or `lstlisting` environments, tables
+//! `tabular` environments, and footnotes `\footnotemark` and `\footnotetext`
+//! pairs. The output is the body of a document: the preamble needs the
+//! `hyperref`, `graphicx`, `ulem` and `amssymb` packages, and `listings` for
+//! [`Options::listings`](struct.Options.html#structfield.listings).
+//!
+//! Raw HTML is dropped, and so is the alt text of images.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::strings::CowStr;
+use crate::Event::*;
+use crate::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag};
+
+/// Options of the LaTeX writer.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Writes code blocks as `lstlisting` environments of the `listings`
+    /// package, with the language of fenced code blocks, instead of
+    /// `verbatim` environments.
+    pub listings: bool,
+}
+
+/// Writes `s` to `w`, escaping the characters that are special in TeX.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::latex::escape_latex;
+///
+/// let mut s = String::new();
+/// escape_latex(&mut s, "50% of $x_1 & {y}").unwrap();
+/// assert_eq!(s, "50\\% of \\$x\\_1 \\& \\{y\\}");
+/// ```
+pub fn escape_latex<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        let escaped = match c {
+            '\\' => "\\textbackslash{}",
+            '~' => "\\textasciitilde{}",
+            '^' => "\\textasciicircum{}",
+            '&' => "\\&",
+            '%' => "\\%",
+            '$' => "\\$",
+            '#' => "\\#",
+            '_' => "\\_",
+            '{' => "\\{",
+            '}' => "\\}",
+            _ => continue,
+        };
+        w.write_str(&s[mark..ix])?;
+        w.write_str(escaped)?;
+        mark = ix + 1; // all escaped characters are ASCII
+    }
+    w.write_str(&s[mark..])
+}
+
+/// Writes a URL for `\href` and `\url`, which need `#`, `%` and `\` escaped.
+fn escape_url<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        if let '#' | '%' | '\\' | '{' | '}' = c {
+            w.write_str(&s[mark..ix])?;
+            w.write_str("\\")?;
+            mark = ix;
+        }
+    }
+    w.write_str(&s[mark..])
+}
+
+struct LatexWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    options: Options,
+    /// Whether or not the last write wrote a newline.
+    end_newline: bool,
+    /// Numbers of the open ordered lists, `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Whether `\item` is still to be written, to know whether the item
+    /// starts with a task list marker.
+    pending_item: bool,
+    /// Whether the current code block is written verbatim.
+    in_code_block: bool,
+    /// Nesting depth inside images, whose contents are dropped.
+    image_depth: usize,
+    table_cell_index: usize,
+    numbers: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a, I, W> LatexWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W, options: &Options) -> Self {
+        LatexWriter {
+            iter,
+            writer,
+            options: options.clone(),
+            end_newline: true,
+            lists: Vec::new(),
+            pending_item: false,
+            in_code_block: false,
+            image_depth: 0,
+            table_cell_index: 0,
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn write(&mut self, s: &str) -> Result<(), W::Error> {
+        self.writer.write_str(s)?;
+        if !s.is_empty() {
+            self.end_newline = s.ends_with('\n');
+        }
+        Ok(())
+    }
+
+    fn write_escaped(&mut self, s: &str) -> Result<(), W::Error> {
+        escape_latex(&mut self.writer, s)?;
+        if !s.is_empty() {
+            self.end_newline = s.ends_with('\n');
+        }
+        Ok(())
+    }
+
+    fn write_url(&mut self, s: &str) -> Result<(), W::Error> {
+        escape_url(&mut self.writer, s)?;
+        self.end_newline = false;
+        Ok(())
+    }
+
+    fn ensure_newline(&mut self) -> Result<(), W::Error> {
+        if self.end_newline {
+            Ok(())
+        } else {
+            self.write("\n")
+        }
+    }
+
+    fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        while let Some(event) = self.iter.next() {
+            if self.pending_item {
+                self.pending_item = false;
+                match event {
+                    TaskListMarker(true) => {
+                        self.write("\\item[$\\boxtimes$] ")?;
+                        continue;
+                    }
+                    TaskListMarker(false) => {
+                        self.write("\\item[$\\square$] ")?;
+                        continue;
+                    }
+                    _ => self.write("\\item ")?,
+                }
+            }
+            if self.image_depth > 0 {
+                match event {
+                    Start(_) => self.image_depth += 1,
+                    End(_) => self.image_depth -= 1,
+                    _ => (),
+                }
+                continue;
+            }
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => self.write(&text)?,
+                Text(text) => self.write_escaped(&text)?,
+                Code(text) => {
+                    self.write("\\texttt{")?;
+                    self.write_escaped(&text)?;
+                    self.write("}")?;
+                }
+                Html(_) => (),
+                SoftBreak => self.write("\n")?,
+                HardBreak => self.write("\\\\\n")?,
+                Rule => {
+                    self.ensure_newline()?;
+                    self.write("\\noindent\\rule{\\textwidth}{0.4pt}\n\n")?;
+                }
+                FootnoteReference(name) => {
+                    let number = self.footnote_number(name);
+                    write!(self.writer, "\\footnotemark[{}]", number)?;
+                    self.end_newline = false;
+                }
+                TaskListMarker(true) => self.write("$\\boxtimes$ ")?,
+                TaskListMarker(false) => self.write("$\\square$ ")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.ensure_newline(),
+            Tag::Heading(level) => {
+                self.ensure_newline()?;
+                let command = match level {
+                    HeadingLevel::H1 => "section",
+                    HeadingLevel::H2 => "subsection",
+                    HeadingLevel::H3 => "subsubsection",
+                    HeadingLevel::H4 => "paragraph",
+                    HeadingLevel::H5 | HeadingLevel::H6 => "subparagraph",
+                };
+                write!(self.writer, "\\{}{{", command)?;
+                self.end_newline = false;
+                Ok(())
+            }
+            Tag::BlockQuote => {
+                self.ensure_newline()?;
+                self.write("\\begin{quote}\n")
+            }
+            Tag::CodeBlock(kind) => {
+                self.ensure_newline()?;
+                self.in_code_block = true;
+                if !self.options.listings {
+                    return self.write("\\begin{verbatim}\n");
+                }
+                let lang = match kind {
+                    CodeBlockKind::Fenced(ref info) => info.split(' ').next().unwrap(),
+                    CodeBlockKind::Indented => "",
+                };
+                if lang.is_empty() {
+                    self.write("\\begin{lstlisting}\n")
+                } else {
+                    self.write("\\begin{lstlisting}[language=")?;
+                    self.write(lang)?;
+                    self.write("]\n")
+                }
+            }
+            Tag::List(start) => {
+                self.ensure_newline()?;
+                match start {
+                    Some(start) => {
+                        self.write("\\begin{enumerate}\n")?;
+                        let depth = self.lists.iter().filter(|list| list.is_some()).count();
+                        if start != 1 && depth < 4 {
+                            let counter = ["enumi", "enumii", "enumiii", "enumiv"][depth];
+                            // The counter is stepped before each item, so it starts one
+                            // below the first number, which may take it to -1.
+                            writeln!(
+                                self.writer,
+                                "\\setcounter{{{}}}{{{}}}",
+                                counter,
+                                start as i64 - 1
+                            )?;
+                        }
+                    }
+                    None => self.write("\\begin{itemize}\n")?,
+                }
+                self.lists.push(start);
+                Ok(())
+            }
+            Tag::Item => {
+                self.ensure_newline()?;
+                self.pending_item = true;
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.ensure_newline()?;
+                let number = self.footnote_number(name);
+                write!(self.writer, "\\footnotetext[{}]{{", number)?;
+                self.end_newline = false;
+                Ok(())
+            }
+            Tag::Table(alignments) => {
+                self.ensure_newline()?;
+                self.write("\\begin{tabular}{")?;
+                for alignment in alignments {
+                    self.write(match alignment {
+                        Alignment::None | Alignment::Left => "l",
+                        Alignment::Center => "c",
+                        Alignment::Right => "r",
+                    })?;
+                }
+                self.write("}\n\\hline\n")
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.table_cell_index = 0;
+                Ok(())
+            }
+            Tag::TableCell => {
+                if self.table_cell_index > 0 {
+                    self.write(" & ")?;
+                }
+                self.table_cell_index += 1;
+                Ok(())
+            }
+            Tag::Emphasis => self.write("\\emph{"),
+            Tag::Strong => self.write("\\textbf{"),
+            Tag::Strikethrough => self.write("\\sout{"),
+            Tag::Link(LinkType::Autolink, dest, _) => {
+                self.write("\\url{")?;
+                self.write_url(&dest)?;
+                self.write("}")?;
+                // the text is the destination
+                self.image_depth = 1;
+                Ok(())
+            }
+            Tag::Link(link_type, dest, _) => {
+                self.write("\\href{")?;
+                if link_type == LinkType::Email {
+                    self.write("mailto:")?;
+                }
+                self.write_url(&dest)?;
+                self.write("}{")
+            }
+            Tag::Image(_, dest, _) => {
+                self.write("\\includegraphics{")?;
+                self.write_url(&dest)?;
+                self.write("}")?;
+                self.image_depth = 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.write("\n\n"),
+            Tag::Heading(_) => self.write("}\n\n"),
+            Tag::BlockQuote => {
+                self.ensure_newline()?;
+                self.write("\\end{quote}\n\n")
+            }
+            Tag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.ensure_newline()?;
+                if self.options.listings {
+                    self.write("\\end{lstlisting}\n\n")
+                } else {
+                    self.write("\\end{verbatim}\n\n")
+                }
+            }
+            Tag::List(start) => {
+                self.lists.pop();
+                self.ensure_newline()?;
+                if start.is_some() {
+                    self.write("\\end{enumerate}\n\n")
+                } else {
+                    self.write("\\end{itemize}\n\n")
+                }
+            }
+            Tag::Item => {
+                if self.pending_item {
+                    self.pending_item = false;
+                    self.write("\\item ")?;
+                }
+                self.ensure_newline()
+            }
+            Tag::FootnoteDefinition(_) => {
+                // a definition ending with a paragraph ends with a blank line
+                self.write("}\n\n")
+            }
+            Tag::Table(_) => self.write("\\hline\n\\end{tabular}\n\n"),
+            Tag::TableHead => self.write(" \\\\\n\\hline\n"),
+            Tag::TableRow => self.write(" \\\\\n"),
+            Tag::TableCell => Ok(()),
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) => self.write("}"),
+            // consumed with the skipped contents of images
+            Tag::Image(..) => Ok(()),
+        }
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate LaTeX for each `Event`,
+/// and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{latex, Parser};
+///
+/// let markdown = "# Intro\n\nSome *emphasis* and 100%.\n\n* one\n* two\n";
+/// let mut latex_buf = String::new();
+/// latex::push_latex(&mut latex_buf, Parser::new(markdown));
+/// assert_eq!(latex_buf, "\
+/// \\section{Intro}
+///
+/// Some \\emph{emphasis} and 100\\%.
+///
+/// \\begin{itemize}
+/// \\item one
+/// \\item two
+/// \\end{itemize}
+///
+/// ");
+/// ```
+pub fn push_latex<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    push_latex_ext(s, iter, &Options::default());
+}
+
+/// Like [`push_latex`](fn.push_latex.html), but with the given options.
+pub fn push_latex_ext<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    LatexWriter::new(iter, s, options).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate LaTeX for each `Event`,
+/// and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_latex<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    write_latex_ext(writer, iter, &Options::default())
+}
+
+/// Like [`write_latex`](fn.write_latex.html), but with the given options.
+pub fn write_latex_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    LatexWriter::new(iter, WriteWrapper(writer), options).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    fn render(input: &str, options: &Options) -> String {
+        let parser_options = crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_TASKLISTS;
+        let mut s = String::new();
+        push_latex_ext(&mut s, Parser::new_ext(input, parser_options), options);
+        s
+    }
+
+    #[test]
+    fn blocks() {
+        let input = "\
+3. three
+4. [x] four
+
+> A [link](http://x.org/#a) and <http://y.org/>[^n].
+
+| a | b |
+|---|--:|
+| `c_d` | ![alt *x*](e.png) |
+
+```rust
+fn f() {}
+```
+
+[^n]: Note.
+";
+        let expected = "\
+\\begin{enumerate}
+\\setcounter{enumi}{2}
+\\item three
+\\item[$\\boxtimes$] four
+\\end{enumerate}
+
+\\begin{quote}
+A \\href{http://x.org/\\#a}{link} and \\url{http://y.org/}\\footnotemark[1].
+
+\\end{quote}
+
+\\begin{tabular}{lr}
+\\hline
+a & b \\\\
+\\hline
+\\texttt{c\\_d} & \\includegraphics{e.png} \\\\
+\\hline
+\\end{tabular}
+
+\\begin{lstlisting}[language=rust]
+fn f() {}
+\\end{lstlisting}
+
+\\footnotetext[1]{
+Note.
+
+}
+
+";
+        let options = Options { listings: true };
+        assert_eq!(expected, render(input, &options));
+    }
+
+    #[test]
+    fn list_starting_at_zero() {
+        let expected = "\
+\\begin{enumerate}
+\\setcounter{enumi}{-1}
+\\item ok
+\\end{enumerate}
+
+";
+        assert_eq!(expected, render("0. ok\n", &Options::default()));
+    }
+}
@@ -52,27 +52,50 @@
 #![cfg_attr(rustbuild, feature(staged_api, rustc_private))]
 #![cfg_attr(rustbuild, unstable(feature = "rustc_private", issue = "27812"))]
 // Forbid unsafe code unless the SIMD feature is enabled.
-#![forbid(unsafe_code)]
-#![cfg_attr(feature = "simd", allow(unsafe_code))]
+#![cfg_attr(not(feature = "simd"), forbid(unsafe_code))]
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "syntect")]
+pub mod highlight;
 pub mod html;
+#[cfg(feature = "html-import")]
+pub mod html_import;
+pub mod jira;
+pub mod json;
+pub mod latex;
+pub mod man;
+pub mod markdown;
+pub mod org;
+#[cfg(feature = "rayon")]
+pub mod parallel;
 
+mod buffers;
+#[cfg(feature = "html-import")]
+mod compat;
 mod entities;
 pub mod escape;
 mod firstpass;
+mod lines;
 mod linklabel;
 mod parse;
+pub mod plain_text;
 mod puncttable;
+pub mod rtf;
 mod scanners;
+pub mod ssml;
 mod strings;
+pub mod telegram;
 mod tree;
+pub mod typst;
+pub mod utils;
 
 use std::{convert::TryFrom, fmt::Display};
 
-pub use crate::parse::{BrokenLink, BrokenLinkCallback, LinkDef, OffsetIter, Parser, RefDefs};
+pub use crate::parse::{
+    BrokenLink, BrokenLinkCallback, LinkDef, OffsetIter, Parser, ParserScratch, RefDefs,
+};
 pub use crate::strings::{CowStr, InlineStr};
 
 /// Codeblock kind.
@@ -207,6 +230,10 @@ pub enum LinkType {
     Autolink,
     /// Email address in autolink like `<john@example.org>`
     Email,
+    /// Wiki link like `[[Page]]` or `[[Page|label]]`, with the page name as
+    /// destination. Only produced with `Options::ENABLE_WIKILINKS`, but
+    /// matches on `LinkType` written before its addition need an arm for it.
+    WikiLink,
 }
 
 impl LinkType {
@@ -279,5 +306,6 @@ bitflags::bitflags! {
         const ENABLE_STRIKETHROUGH = 1 << 3;
         const ENABLE_TASKLISTS = 1 << 4;
         const ENABLE_SMART_PUNCTUATION = 1 << 5;
+        const ENABLE_WIKILINKS = 1 << 6;
     }
 }
@@ -0,0 +1,55 @@
+//! Line prefixes of nested blocks, shared by the writers of formats that
+//! repeat the markers of block quotes and list items on each line.
+
+use crate::escape::StrWrite;
+
+/// A block that prefixes the lines written inside it.
+pub(crate) struct Container {
+    /// Prefix of the first line, like a list marker.
+    pub(crate) first: String,
+    /// Prefix of the following lines.
+    pub(crate) rest: String,
+    pub(crate) first_written: bool,
+    /// Whether the container is a list item.
+    pub(crate) item: bool,
+}
+
+impl Container {
+    pub(crate) fn new(first: String, rest: String, item: bool) -> Self {
+        Container {
+            first,
+            rest,
+            first_written: false,
+            item,
+        }
+    }
+}
+
+/// Returns the prefix of the next line, making use of the first line
+/// prefixes of the containers.
+pub(crate) fn prefix(containers: &mut [Container]) -> String {
+    let mut prefix = String::new();
+    for container in containers {
+        if container.first_written {
+            prefix.push_str(&container.rest);
+        } else {
+            prefix.push_str(&container.first);
+            container.first_written = true;
+        }
+    }
+    prefix
+}
+
+/// Writes a line break, preceded by the prefix of the containers when
+/// nothing was written on the line.
+pub(crate) fn newline<W: StrWrite>(
+    writer: &mut W,
+    containers: &mut [Container],
+    line_start: bool,
+) -> Result<(), W::Error> {
+    if line_start {
+        let prefix = prefix(containers);
+        writer.write_str(prefix.trim_end())?;
+    }
+    writer.write_str("\n")
+}
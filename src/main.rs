@@ -22,88 +22,3009 @@
 
 #![forbid(unsafe_code)]
 
-use pulldown_cmark::{html, Options, Parser};
+mod compat;
+
+use compat::strip_prefix;
+use pulldown_cmark::{
+    html, jira, json, latex, man, markdown, org, plain_text, rtf, ssml, telegram, typst, utils,
+    Event, LinkType, Options, Parser, Tag,
+};
+use utils::anchors::check_anchors;
+use utils::front_matter::{split_front_matter, FrontMatterKind};
+use utils::links::collect_links;
+use utils::lint::Rule;
+use utils::normalize::Normalize;
+use utils::slug::{SlugStyle, Slugger};
+
+#[cfg(feature = "syntect")]
+use pulldown_cmark::highlight::SyntectHighlighter;
 
 use std::env;
-use std::io::{self, Read};
+use std::io::{self, BufRead, Read, Write};
 use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+fn dry_run(text: &str, opts: Options) {
+    let p = Parser::new_ext(text, opts);
+    let count = p.count();
+    println!("{} events", count);
+}
+
+/// Maps byte offsets of the input to lines and columns, both starting at 1.
+/// Columns count characters.
+struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(ix, _)| ix + 1))
+            .collect();
+        LineIndex { text, line_starts }
+    }
+
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line];
+        let column = self.text[start..offset].chars().count() + 1;
+        (line + 1, column)
+    }
+
+    /// The text of a line, without its line ending.
+    fn line(&self, line: usize) -> &'a str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.text.len(), |&end| end);
+        self.text[start..end].trim_end_matches(&['\n', '\r'][..])
+    }
+}
+
+/// Prints every event with its byte range and the line and column where it
+/// starts and ends, as text or as JSON lines.
+fn print_events<W: Write>(mut out: W, text: &str, opts: Options, as_json: bool) -> io::Result<()> {
+    let index = LineIndex::new(text);
+    for (event, range) in Parser::new_ext(text, opts).into_offset_iter() {
+        let (start_line, start_column) = index.position(range.start);
+        let (end_line, end_column) = index.position(range.end);
+        if as_json {
+            let mut line = Vec::new();
+            json::write_events_json_with_offsets(&mut line, std::iter::once((event, range)))?;
+            // replace the closing brace, the keys are ignored by readers
+            line.truncate(line.len() - 2);
+            out.write_all(&line)?;
+            writeln!(
+                out,
+                ",\"start_line\":{},\"start_column\":{},\"end_line\":{},\"end_column\":{}}}",
+                start_line, start_column, end_line, end_column
+            )?;
+        } else {
+            writeln!(
+                out,
+                "{}:{}-{}:{} {:?}: {:?}",
+                start_line, start_column, end_line, end_column, range, event
+            )?;
+        }
+    }
+    if !as_json {
+        writeln!(out, "EOF")?;
+    }
+    out.flush()
+}
+
+/// Exit status of errors, like unreadable files, and of failed checks.
+const EXIT_FAILURE: i32 = 1;
+/// Exit status of invalid arguments or configuration.
+const EXIT_USAGE: i32 = 2;
+/// Exit status of `--warnings` when the parser warned.
+const EXIT_WARNINGS: i32 = 3;
+/// Exit status of `lint` when it found problems.
+const EXIT_LINT: i32 = 4;
+/// Exit status of `check-links` when it found broken links.
+const EXIT_BROKEN_LINKS: i32 = 5;
+
+/// Exit statuses, with their descriptions.
+const EXIT_STATUSES: &[(i32, &str)] = &[
+    (0, "success"),
+    (
+        EXIT_FAILURE,
+        "an error, like an unreadable file, or a failed check of fmt --check \
+         or --write, --verify-roundtrip, spec, diff or build",
+    ),
+    (EXIT_USAGE, "invalid arguments or configuration"),
+    (EXIT_WARNINGS, "parse warnings, with --warnings"),
+    (EXIT_LINT, "problems found by lint"),
+    (EXIT_BROKEN_LINKS, "broken links found by check-links"),
+];
+
+/// A problem found in a file, for `print_diagnostics`.
+#[derive(Clone)]
+struct Report {
+    file: String,
+    line: usize,
+    column: usize,
+    severity: &'static str,
+    /// A stable id of the kind of problem, like a lint rule.
+    code: &'static str,
+    message: String,
+}
+
+/// Adds the `--format` option of diagnostics.
+fn add_format_flag(opts: &mut getopts::Options) {
+    opts.optopt(
+        "",
+        "format",
+        "format of the diagnostics, text (the default) or json",
+        "FORMAT",
+    );
+}
+
+/// Whether `--format` asks for JSON diagnostics.
+fn json_diagnostics(matches: &getopts::Matches) -> bool {
+    match matches.opt_str("format").as_deref() {
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(format) => {
+            eprintln!("unknown diagnostics format: {}", format);
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Prints diagnostics as `file:line:column: code message` lines, or as a
+/// JSON array of objects with these fields and the severity.
+fn print_diagnostics<W: Write>(mut out: W, json: bool, reports: &[Report]) -> io::Result<()> {
+    if !json {
+        for report in reports {
+            writeln!(
+                out,
+                "{}:{}:{}: {} {}",
+                report.file, report.line, report.column, report.code, report.message
+            )?;
+        }
+        return out.flush();
+    }
+    let reports = reports
+        .iter()
+        .map(|report| {
+            Value::Table(vec![
+                ("file".to_owned(), Value::String(report.file.clone())),
+                ("line".to_owned(), Value::Number(report.line.to_string())),
+                (
+                    "column".to_owned(),
+                    Value::Number(report.column.to_string()),
+                ),
+                (
+                    "severity".to_owned(),
+                    Value::String(report.severity.to_owned()),
+                ),
+                ("code".to_owned(), Value::String(report.code.to_owned())),
+                ("message".to_owned(), Value::String(report.message.clone())),
+            ])
+        })
+        .collect();
+    let mut json = String::new();
+    Value::List(reports).write_json(&mut json);
+    writeln!(out, "{}", json)?;
+    out.flush()
+}
+
+/// Parses `body`, the end of `input` such as its part after the front
+/// matter, and reports references to undefined link labels and footnotes.
+/// Shortcut references like `[this]` are not reported, as they are usually
+/// meant as text.
+fn parse_warnings(file: &str, input: &str, body: &str, opts: Options) -> Vec<Report> {
+    let index = LineIndex::new(input);
+    let start = input.len() - body.len();
+    let mut warnings = Vec::new();
+    let mut warn = |offset, code, message| {
+        let (line, column) = index.position(start + offset);
+        warnings.push(Report {
+            file: file.to_owned(),
+            line,
+            column,
+            severity: "warning",
+            code,
+            message,
+        });
+    };
+    let mut undefined = Vec::new();
+    let mut callback = |link: pulldown_cmark::BrokenLink| {
+        if link.link_type != LinkType::Shortcut {
+            undefined.push((link.span.start, link.reference.into_string()));
+        }
+        None
+    };
+    let mut footnotes = Vec::new();
+    let mut definitions = std::collections::HashSet::new();
+    let parser = Parser::new_with_broken_link_callback(body, opts, Some(&mut callback));
+    for (event, span) in parser.into_offset_iter() {
+        match event {
+            Event::FootnoteReference(label) => footnotes.push((span.start, label)),
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                definitions.insert(label.to_lowercase());
+            }
+            _ => (),
+        }
+    }
+    for (offset, label) in undefined {
+        warn(
+            offset,
+            "undefined-reference",
+            format!("no link definition for `{}`", label),
+        );
+    }
+    for (offset, label) in footnotes {
+        if !definitions.contains(&label.to_lowercase()) {
+            warn(
+                offset,
+                "undefined-footnote",
+                format!("no footnote definition for `{}`", label),
+            );
+        }
+    }
+    warnings.sort_by_key(|report| (report.line, report.column));
+    warnings
+}
+
+/// Output formats of `--to`, with their descriptions.
+const FORMATS: &[(&str, &str)] = &[
+    ("html", "HTML (the default)"),
+    (
+        "markdown",
+        "CommonMark, also called commonmark or roundtrip",
+    ),
+    ("mdx", "markdown that MDX reads as text"),
+    (
+        "events",
+        "the event sequence, with offsets, lines and columns",
+    ),
+    (
+        "json",
+        "the event sequence as JSON lines, with --events their positions too",
+    ),
+    ("plain", "plain text"),
+    ("latex", "LaTeX"),
+    ("typst", "Typst"),
+    ("man", "man page"),
+    ("org", "Org-mode"),
+    ("jira", "Jira and Confluence wiki markup"),
+    ("ssml", "SSML for text to speech"),
+    ("telegram", "Telegram MarkdownV2"),
+    ("rtf", "RTF"),
+];
+
+/// How the main command renders its input.
+struct Render {
+    format: String,
+    events: bool,
+    opts: Options,
+    html_options: html::Options,
+    /// Whether front matter is removed before parsing.
+    strip_front_matter: bool,
+    markdown_options: markdown::Options,
+    latex_options: latex::Options,
+    typst_options: typst::Options,
+    man_options: man::Options,
+    ssml_options: ssml::Options,
+    /// Highlighter of the code blocks of HTML output.
+    #[cfg(feature = "syntect")]
+    highlighter: Option<std::rc::Rc<SyntectHighlighter>>,
+}
+
+impl Render {
+    /// Returns the part of the input to render.
+    fn body<'a>(&self, input: &'a str) -> &'a str {
+        if self.strip_front_matter {
+            split_front_matter(input).1
+        } else {
+            input
+        }
+    }
+
+    fn render<W: Write>(&self, out: W, input: &str) -> io::Result<()> {
+        let input = self.body(input);
+        if self.events || self.format == "events" {
+            print_events(out, input, self.opts, self.format == "json")
+        } else {
+            self.write(out, &mut Parser::new_ext(input, self.opts))
+        }
+    }
+
+    /// Writes events in any format but `events`.
+    fn write<'a, W, I>(&self, out: W, iter: I) -> io::Result<()>
+    where
+        W: Write,
+        I: Iterator<Item = Event<'a>>,
+    {
+        match self.format.as_str() {
+            "markdown" | "commonmark" | "roundtrip" => {
+                markdown::write_markdown_ext(out, iter, &self.markdown_options)
+            }
+            "mdx" => {
+                let options = markdown::Options { mdx: true };
+                markdown::write_markdown_ext(out, iter, &options)
+            }
+            "json" => json::write_events_json(out, iter),
+            "plain" => plain_text::write_plain_text(out, iter),
+            "latex" => latex::write_latex_ext(out, iter, &self.latex_options),
+            "typst" => typst::write_typst_ext(out, iter, &self.typst_options),
+            "man" => man::write_man_ext(out, iter, &self.man_options),
+            "org" => org::write_org(out, iter),
+            "jira" => jira::write_jira(out, iter),
+            "ssml" => ssml::write_ssml_ext(out, iter, &self.ssml_options),
+            "telegram" => telegram::write_telegram(out, iter),
+            "rtf" => rtf::write_rtf(out, iter),
+            _ => {
+                // clones of a slugger share their slugs, so documents rendered
+                // again, as with --watch, each need a fresh one
+                let mut options = self.html_options.clone();
+                if let Some(ref slugger) = options.heading_ids {
+                    options.heading_ids = Some(Slugger::new(slugger.style()));
+                }
+                #[cfg(feature = "syntect")]
+                {
+                    if let Some(ref highlighter) = self.highlighter {
+                        let render = highlighter.render();
+                        return html::write_html_with_renderer(out, iter, &options, render);
+                    }
+                }
+                html::write_html_ext(out, iter, &options)
+            }
+        }
+    }
+}
+
+/// Adds the options of the rendering, shared by the main command and
+/// `build`.
+fn add_render_flags(opts: &mut getopts::Options) {
+    // `-T` enables tables
+    opts.optopt("t", "to", "output format, see below", "FORMAT");
+    opts.optflag(
+        "",
+        "safe",
+        "escape raw HTML and drop links with dangerous schemes, like javascript:",
+    );
+    opts.optflag("s", "standalone", "emit a complete HTML document");
+    opts.optopt("", "title", "title of the standalone document", "TITLE");
+    opts.optmulti(
+        "",
+        "css",
+        "link a style sheet from the standalone document",
+        "URL",
+    );
+    #[cfg(feature = "syntect")]
+    opts.optopt(
+        "",
+        "highlight",
+        "highlight the code blocks of HTML output with a syntect theme, or with the classes \
+         styled by --highlight-css",
+        "THEME|classes",
+    );
+}
+
+/// Reads the highlighter of `--highlight` from the command line and the
+/// configuration.
+#[cfg(feature = "syntect")]
+fn highlighter(matches: &getopts::Matches, config: &Config) -> Option<SyntectHighlighter> {
+    let theme = matches
+        .opt_str("highlight")
+        .or_else(|| config.string("html", "highlight"))?;
+    if theme == "classes" {
+        return Some(SyntectHighlighter::with_classes());
+    }
+    match SyntectHighlighter::with_theme(&theme) {
+        Some(highlighter) => Some(highlighter),
+        None => {
+            eprintln!(
+                "unknown theme: {}, expected classes or one of: {}",
+                theme,
+                SyntectHighlighter::themes().join(", ")
+            );
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Reads the rendering of `--to` and its options from the command line and
+/// the configuration, with neither events nor front matter handling.
+fn renderer(program: &str, matches: &getopts::Matches, config: &Config) -> Render {
+    let mut html_options = config.html_options();
+    if matches.opt_present("safe") {
+        html_options.safe = true;
+    }
+    if matches.opt_present("standalone") || config.bool("html", "standalone") {
+        let mut stylesheets = config.strings("html", "css");
+        stylesheets.extend(matches.opt_strs("css"));
+        html_options.document = Some(html::Document {
+            lang: config.string("html", "lang"),
+            title: matches
+                .opt_str("title")
+                .or_else(|| config.string("html", "title")),
+            stylesheets,
+        });
+    }
+
+    let ssml_code = match config.string("ssml", "code").as_deref() {
+        None | Some("literal") => ssml::CodeReading::Literal,
+        Some("characters") => ssml::CodeReading::Characters,
+        Some("skip") => ssml::CodeReading::Skip,
+        Some(_) => config.invalid("ssml", "code", "literal, characters or skip"),
+    };
+    let man_title = config.string("man", "title").map(|title| {
+        let section = config.string("man", "section");
+        (title, section.unwrap_or_else(|| "1".to_owned()))
+    });
+
+    let format = matches
+        .opt_str("to")
+        .or_else(|| config.string("", "to"))
+        .unwrap_or_else(|| "html".to_owned());
+    if !FORMATS.iter().any(|&(name, _)| name == format)
+        && !matches!(format.as_str(), "commonmark" | "roundtrip")
+    {
+        eprintln!("unknown output format: {}\n{}", format, brief(program));
+        std::process::exit(EXIT_USAGE);
+    }
+
+    Render {
+        format,
+        events: false,
+        opts: parser_options(matches, config),
+        html_options,
+        strip_front_matter: false,
+        markdown_options: markdown::Options {
+            mdx: config.bool("markdown", "mdx"),
+        },
+        latex_options: latex::Options {
+            listings: config.bool("latex", "listings"),
+        },
+        typst_options: typst::Options {
+            math: config.bool("typst", "math"),
+        },
+        man_options: man::Options { title: man_title },
+        ssml_options: ssml::Options { code: ssml_code },
+        #[cfg(feature = "syntect")]
+        highlighter: highlighter(matches, config).map(std::rc::Rc::new),
+    }
+}
+
+/// Reads and concatenates the input files.
+fn read_files(paths: &[String]) -> io::Result<String> {
+    let mut input = String::new();
+    for path in paths {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        input.push_str(&text);
+    }
+    Ok(input)
+}
+
+/// Replaces the file at `path` with `contents`, through a temporary file in
+/// the same directory, so that readers never see a partial file.
+fn write_atomically(path: &str, contents: &[u8]) -> io::Result<()> {
+    let path = std::path::Path::new(path);
+    let name = path
+        .file_name()
+        .map_or("output".into(), |name| name.to_string_lossy());
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)
+}
+
+/// Formats whose outputs for consecutive parts of a document can be joined.
+/// The outputs of all but HTML and JSON are joined with blank lines.
+const STREAM_FORMATS: &[&str] = &[
+    "html",
+    "json",
+    "markdown",
+    "commonmark",
+    "roundtrip",
+    "mdx",
+    "plain",
+    "org",
+    "jira",
+    "telegram",
+];
+
+/// Splits markdown, read line by line, into chunks of top level blocks that
+/// parse the same on their own as in the whole document.
+///
+/// A chunk ends before a line that starts at the left margin after a blank
+/// line, unless it is a list item, which could continue a list, or the blank
+/// line is inside a fenced code block or an HTML block that may contain
+/// blank lines. This is conservative: when in doubt the chunk goes on.
+#[derive(Default)]
+struct Chunker {
+    chunk: String,
+    /// Character and length of the open code fence.
+    fence: Option<(char, usize)>,
+    /// End of the open HTML block.
+    html_end: Option<&'static str>,
+    after_blank: bool,
+}
+
+impl Chunker {
+    /// Adds a line, including its line ending. Returns the previous chunk
+    /// when the line starts a new one.
+    fn push_line(&mut self, line: &str) -> Option<String> {
+        let trimmed = line.trim_start();
+        if let Some((c, len)) = self.fence {
+            let closing = trimmed.trim_end();
+            if closing.len() >= len && closing.chars().all(|x| x == c) {
+                self.fence = None;
+            }
+        } else if let Some(end) = self.html_end {
+            if line.to_ascii_lowercase().contains(end) {
+                self.html_end = None;
+            }
+        } else if trimmed.is_empty() {
+            self.after_blank = true;
+        } else {
+            let chunk = if self.after_blank && line.len() == trimmed.len() && !is_list_item(line) {
+                Some(std::mem::take(&mut self.chunk))
+            } else {
+                None
+            };
+            self.after_blank = false;
+            self.open_block(trimmed);
+            self.chunk.push_str(line);
+            return chunk.filter(|chunk| !chunk.is_empty());
+        }
+        self.chunk.push_str(line);
+        None
+    }
+
+    /// Notes the start of a fenced code block or of an HTML block that may
+    /// contain blank lines.
+    fn open_block(&mut self, line: &str) {
+        for &c in &['`', '~'] {
+            let len = line.chars().take_while(|&x| x == c).count();
+            if len >= 3 && !(c == '`' && line[len..].contains('`')) {
+                self.fence = Some((c, len));
+                return;
+            }
+        }
+        let lower = line.to_ascii_lowercase();
+        let blocks = [
+            ("<!--", "-->"),
+            ("<?", "?>"),
+            ("<![cdata[", "]]>"),
+            ("<!", ">"),
+            ("<pre", "</pre>"),
+            ("<script", "</script>"),
+            ("<style", "</style>"),
+            ("<textarea", "</textarea>"),
+        ];
+        if let Some(&(start, end)) = blocks.iter().find(|(start, _)| lower.starts_with(start)) {
+            if !lower[start.len()..].contains(end) {
+                self.html_end = Some(end);
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        self.chunk
+    }
+}
+
+/// Whether the line starts with a list marker.
+fn is_list_item(line: &str) -> bool {
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    let marker = if digits > 0 && digits < 10 {
+        &line[digits..]
+    } else {
+        line
+    };
+    let rest = match marker.as_bytes().first() {
+        Some(b'-') | Some(b'*') | Some(b'+') if digits == 0 => &marker[1..],
+        Some(b'.') | Some(b')') if digits > 0 => &marker[1..],
+        _ => return false,
+    };
+    rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace())
+}
+
+/// Renders standard input chunk by chunk, as soon as each is complete.
+///
+/// Link reference definitions of earlier chunks are put before each chunk,
+/// so that links can refer to definitions above them. Links to definitions
+/// further down are not resolved, and footnotes are rendered per chunk. The
+/// text formats may have more or fewer blank lines between blocks than when
+/// rendering the whole document.
+fn stream(render: &Render) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdio = io::stdout();
+    stream_to(render, stdin.lock(), stdio.lock())
+}
+
+/// Renders the markdown read from `input` chunk by chunk to `out`.
+fn stream_to<R: io::BufRead, W: Write>(
+    render: &Render,
+    mut input: R,
+    mut out: W,
+) -> io::Result<()> {
+    let mut chunker = Chunker::default();
+    let mut definitions = String::new();
+    let mut first = true;
+    let mut line = String::new();
+    // whether output was written, to put blank lines between the parts
+    let mut written = false;
+    let mut buffer = Vec::new();
+    let mut write_chunk = |chunk: &str, out: &mut W| -> io::Result<()> {
+        let chunk = if first { render.body(chunk) } else { chunk };
+        first = false;
+        let text = format!("{}{}", definitions, chunk);
+        let parser = Parser::new_ext(&text, render.opts);
+        for (_, definition) in parser.reference_definitions().iter() {
+            if definition.span.start >= definitions.len() {
+                definitions.push_str(&text[definition.span.clone()]);
+                // a blank line, so that the chunk can't continue the
+                // definition with a title
+                definitions.push_str("\n\n");
+            }
+        }
+        buffer.clear();
+        render.write(&mut buffer, parser)?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        if written && !matches!(render.format.as_str(), "html" | "json") {
+            out.write_all(b"\n")?;
+        }
+        written = true;
+        out.write_all(&buffer)?;
+        out.flush()
+    };
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        if let Some(chunk) = chunker.push_line(&line) {
+            write_chunk(&chunk, &mut out)?;
+        }
+    }
+    let chunk = chunker.finish();
+    if !chunk.is_empty() {
+        write_chunk(&chunk, &mut out)?;
+    }
+    Ok(())
+}
+
+/// Renders the input files to `output` every time one of them changes.
+/// Polls the modification times, as there is no portable file change
+/// notification in the standard library.
+fn watch(paths: &[String], output: &str, render: &Render) -> io::Result<()> {
+    let modified = |path: &String| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last = Vec::new();
+    loop {
+        let times: Vec<_> = paths.iter().map(modified).collect();
+        if times != last {
+            last = times;
+            match read_files(paths) {
+                Ok(input) => {
+                    let mut buffer = Vec::new();
+                    render.render(&mut buffer, &input)?;
+                    write_atomically(output, &buffer)?;
+                    eprintln!("wrote {}", output);
+                }
+                // a file may be missing for a moment while an editor saves it
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+}
+
+/// A value of the front matter subset understood by the binary.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    /// A number, as written.
+    Number(String),
+    String(String),
+    List(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => out.push_str(n),
+            Value::String(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            Value::List(items) => {
+                out.push('[');
+                for (ix, item) in items.iter().enumerate() {
+                    if ix > 0 {
+                        out.push(',');
+                    }
+                    item.write_json(out);
+                }
+                out.push(']');
+            }
+            Value::Table(fields) => {
+                out.push('{');
+                for (ix, (key, value)) in fields.iter().enumerate() {
+                    if ix > 0 {
+                        out.push(',');
+                    }
+                    Value::String(key.clone()).write_json(out);
+                    out.push(':');
+                    value.write_json(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    /// Writes fields as a YAML block mapping, with the values in JSON syntax,
+    /// which YAML accepts as flow style.
+    fn write_yaml_fields(fields: &[(String, Value)], indent: usize, out: &mut String) {
+        for (key, value) in fields {
+            out.push_str(&"  ".repeat(indent));
+            Value::String(key.clone()).write_json(out);
+            out.push(':');
+            if let Value::Table(fields) = value {
+                out.push('\n');
+                Value::write_yaml_fields(fields, indent + 1, out);
+            } else {
+                out.push(' ');
+                value.write_json(out);
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Parses a scalar or a flow list of YAML front matter.
+fn yaml_value(s: &str) -> Value {
+    let s = s.trim();
+    let quoted = |q: char| s.len() >= 2 && s.starts_with(q) && s.ends_with(q);
+    if quoted('"') {
+        return Value::String(unescape_yaml(&s[1..s.len() - 1]));
+    }
+    if quoted('\'') {
+        return Value::String(s[1..s.len() - 1].replace("''", "'"));
+    }
+    if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim();
+        if inner.is_empty() {
+            return Value::List(Vec::new());
+        }
+        return Value::List(split_items(inner).into_iter().map(yaml_value).collect());
+    }
+    match s {
+        "" | "~" | "null" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ if is_json_number(s) => Value::Number(s.to_owned()),
+        _ => Value::String(s.to_owned()),
+    }
+}
+
+/// Replaces the escape sequences of a double-quoted YAML scalar. Unknown
+/// escapes are kept as written.
+fn unescape_yaml(s: &str) -> String {
+    let mut value = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('0') => Some('\0'),
+            Some('a') => Some('\u{7}'),
+            Some('b') => Some('\u{8}'),
+            Some('t') | Some('\t') => Some('\t'),
+            Some('n') => Some('\n'),
+            Some('v') => Some('\u{b}'),
+            Some('f') => Some('\u{c}'),
+            Some('r') => Some('\r'),
+            Some('e') => Some('\u{1b}'),
+            Some('N') => Some('\u{85}'),
+            Some('_') => Some('\u{a0}'),
+            Some('L') => Some('\u{2028}'),
+            Some('P') => Some('\u{2029}'),
+            Some(c @ 'x') | Some(c @ 'u') | Some(c @ 'U') => {
+                let len = match c {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                unicode_escape(&mut chars, len).or_else(|| {
+                    value.push('\\');
+                    Some(c)
+                })
+            }
+            Some(c @ ' ') | Some(c @ '"') | Some(c @ '/') | Some(c @ '\\') => Some(c),
+            Some(c) => {
+                value.push('\\');
+                Some(c)
+            }
+            None => Some('\\'),
+        };
+        value.extend(escaped);
+    }
+    value
+}
+
+/// Reads the `len` hexadecimal digits of a `\u` escape, returning the
+/// character they give.
+fn unicode_escape(chars: &mut std::str::Chars, len: usize) -> Option<char> {
+    let rest = chars.as_str();
+    let digits = rest.get(..len)?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let code = u32::from_str_radix(digits, 16).ok()?;
+    *chars = rest[len..].chars();
+    std::char::from_u32(code)
+}
+
+/// Splits the inside of a flow list or array at the commas between its
+/// items, skipping those in quoted strings and nested lists.
+fn split_items(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut previous = ' ';
+    let mut chars = inner.char_indices();
+    while let Some((ix, c)) = chars.next() {
+        match quote {
+            Some('"') if c == '\\' => {
+                chars.next();
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            // as in `strip_comment`, quotes only start values
+            None if (c == '"' || c == '\'')
+                && (previous.is_whitespace() || "[,".contains(previous)) =>
+            {
+                quote = Some(c)
+            }
+            None if c == '[' => depth += 1,
+            None if c == ']' => depth = depth.saturating_sub(1),
+            None if c == ',' && depth == 0 => {
+                items.push(&inner[start..ix]);
+                start = ix + 1;
+            }
+            None => (),
+        }
+        previous = c;
+    }
+    items.push(&inner[start..]);
+    items
+}
+
+/// Whether `s` is a number in the syntax of JSON, which leaves out leading
+/// zeros, a leading `+` or `.`, and infinities.
+fn is_json_number(s: &str) -> bool {
+    let s = s.as_bytes();
+    let digits = |ix: usize| s[ix..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut ix = if s.first() == Some(&b'-') { 1 } else { 0 };
+    match digits(ix) {
+        0 => return false,
+        n if n > 1 && s[ix] == b'0' => return false,
+        n => ix += n,
+    }
+    if s.get(ix) == Some(&b'.') {
+        match digits(ix + 1) {
+            0 => return false,
+            n => ix += 1 + n,
+        }
+    }
+    if s.get(ix) == Some(&b'e') || s.get(ix) == Some(&b'E') {
+        ix += 1;
+        if s.get(ix) == Some(&b'+') || s.get(ix) == Some(&b'-') {
+            ix += 1;
+        }
+        match digits(ix) {
+            0 => return false,
+            n => ix += n,
+        }
+    }
+    ix == s.len()
+}
+
+/// Removes a comment from a line of front matter. Comments start with a `#`
+/// at the start of the line or after whitespace, outside of quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut chars = line.char_indices();
+    while let Some((ix, c)) = chars.next() {
+        match quote {
+            Some('"') if c == '\\' => {
+                chars.next();
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            // quotes only start values, not in the middle of plain words
+            None if (c == '"' || c == '\'')
+                && (previous.is_whitespace() || "[,=".contains(previous)) =>
+            {
+                quote = Some(c)
+            }
+            None if c == '#' && previous.is_whitespace() => return &line[..ix],
+            None => (),
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Parses YAML front matter made of `key: value` lines, where values are
+/// scalars, flow lists or block lists of scalars.
+fn parse_yaml(raw: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut fields: Vec<(String, Value)> = Vec::new();
+    for (ix, line) in raw.lines().enumerate() {
+        let line = strip_comment(line).trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("front matter line {}: {}", ix + 1, message);
+        if let Some(item) = strip_prefix(line.trim_start(), "- ") {
+            match fields.last_mut() {
+                Some((_, value)) if *value == Value::Null => *value = Value::List(Vec::new()),
+                Some((_, Value::List(_))) => (),
+                _ => return Err(error("list item without a key")),
+            }
+            if let Some((_, Value::List(items))) = fields.last_mut() {
+                items.push(yaml_value(item));
+            }
+        } else if line.starts_with(' ') {
+            return Err(error("nested mappings are not supported"));
+        } else {
+            let colon = line
+                .find(':')
+                .ok_or_else(|| error("expected `key: value`"))?;
+            let key = yaml_value(&line[..colon]);
+            let key = match key {
+                Value::String(key) | Value::Number(key) => key,
+                _ => return Err(error("expected `key: value`")),
+            };
+            fields.push((key, yaml_value(&line[colon + 1..])));
+        }
+    }
+    Ok(fields)
+}
+
+/// Parses a TOML value: a string, number, boolean or single line array.
+fn toml_value(s: &str) -> Result<Value, &'static str> {
+    let s = s.trim();
+    if let Some(rest) = strip_prefix(s, "\"") {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().trim().is_empty() => return Ok(Value::String(value)),
+                '"' => return Err("text after a string"),
+                '\\' => match chars.next() {
+                    Some('b') => value.push('\u{8}'),
+                    Some('t') => value.push('\t'),
+                    Some('n') => value.push('\n'),
+                    Some('f') => value.push('\u{c}'),
+                    Some('r') => value.push('\r'),
+                    Some(c @ '"') | Some(c @ '\\') => value.push(c),
+                    Some(c @ 'u') | Some(c @ 'U') => {
+                        let len = if c == 'u' { 4 } else { 8 };
+                        let c = unicode_escape(&mut chars, len).ok_or("invalid unicode escape")?;
+                        value.push(c);
+                    }
+                    _ => return Err("unsupported escape"),
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string");
+    }
+    if s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'') {
+        return Ok(Value::String(s[1..s.len() - 1].to_owned()));
+    }
+    if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim().trim_end_matches(',');
+        if inner.trim().is_empty() {
+            return Ok(Value::List(Vec::new()));
+        }
+        return split_items(inner)
+            .into_iter()
+            .map(toml_value)
+            .collect::<Result<_, _>>()
+            .map(Value::List);
+    }
+    match s {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => {
+            let number = s.replace('_', "");
+            let number = strip_prefix(&number, "+").unwrap_or(&number);
+            if is_json_number(number) {
+                Ok(Value::Number(number.to_owned()))
+            } else {
+                Err("unsupported value")
+            }
+        }
+    }
+}
+
+/// Parses TOML made of `key = value` lines and `[table]` headers.
+fn parse_toml(raw: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut fields: Vec<(String, Value)> = Vec::new();
+    let mut table: Option<usize> = None;
+    for (ix, line) in raw.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", ix + 1, message);
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_owned();
+            if fields.iter().any(|(key, _)| *key == name) {
+                return Err(error("duplicate table"));
+            }
+            fields.push((name, Value::Table(Vec::new())));
+            table = Some(fields.len() - 1);
+            continue;
+        }
+        let eq = line
+            .find('=')
+            .ok_or_else(|| error("expected `key = value`"))?;
+        let key = line[..eq].trim().trim_matches('"').to_owned();
+        let value = toml_value(&line[eq + 1..]).map_err(error)?;
+        let target = match table {
+            Some(ix) => match fields[ix].1 {
+                Value::Table(ref mut fields) => fields,
+                _ => unreachable!(),
+            },
+            None => &mut fields,
+        };
+        target.push((key, value));
+    }
+    Ok(fields)
+}
+
+/// Converts the front matter of `input` to JSON or YAML.
+fn front_matter_metadata(input: &str, mode: &str) -> Result<String, String> {
+    let fields = match split_front_matter(input).0 {
+        None => Vec::new(),
+        Some(front_matter) => match front_matter.kind {
+            FrontMatterKind::Yaml => parse_yaml(front_matter.raw)?,
+            FrontMatterKind::Toml => {
+                parse_toml(front_matter.raw).map_err(|e| format!("front matter {}", e))?
+            }
+        },
+    };
+    let mut out = String::new();
+    if mode == "json" {
+        Value::Table(fields).write_json(&mut out);
+        out.push('\n');
+    } else {
+        Value::write_yaml_fields(&fields, 0, &mut out);
+    }
+    Ok(out)
+}
+
+fn brief(program: &str) -> String {
+    let formats: Vec<String> = FORMATS
+        .iter()
+        .map(|(name, description)| format!("    {:10} {}", name, description))
+        .collect();
+    let statuses: Vec<String> = EXIT_STATUSES
+        .iter()
+        .map(|(status, description)| format!("    {:<10} {}", status, description))
+        .collect();
+    format!(
+        "Usage: {0} [options] [FILE...]\n       {0} fmt [options] [FILE...]\n       {0} spec [options] FILE...\n       {0} lint [options] [FILE...]\n       {0} toc [options] [FILE]\n       {0} serve [options] [PATH]\n       {0} diff [options] OLD NEW\n       {0} bench [options] FILE...\n       {0} check-links [options] [PATH...]\n       {0} build [options] PATH... -o DIR\n       {0} anchors [options] [PATH...]\n\n{1}\n\nOutput formats:\n{2}\n\nExit statuses:\n{3}",
+        program,
+        "Reads markdown from the files, or standard input when no file is given, \
+         and emits HTML, or the format given with --to. Settings are read from \
+         pulldown-cmark.toml in the current directory or its parents, if there is one.",
+        formats.join("\n"),
+        statuses.join("\n"),
+    )
+}
+
+/// An extension of the parser, with its name in flags and in the
+/// configuration file.
+struct Extension {
+    name: &'static str,
+    short: &'static str,
+    option: Options,
+    description: &'static str,
+}
+
+const EXTENSIONS: &[Extension] = &[
+    Extension {
+        name: "tables",
+        short: "T",
+        option: Options::ENABLE_TABLES,
+        description: "GitHub-style tables",
+    },
+    Extension {
+        name: "footnotes",
+        short: "F",
+        option: Options::ENABLE_FOOTNOTES,
+        description: "Hoedown-style footnotes",
+    },
+    Extension {
+        name: "strikethrough",
+        short: "S",
+        option: Options::ENABLE_STRIKETHROUGH,
+        description: "GitHub-style strikethrough",
+    },
+    Extension {
+        name: "tasklists",
+        short: "L",
+        option: Options::ENABLE_TASKLISTS,
+        description: "GitHub-style task lists",
+    },
+    Extension {
+        name: "smart-punctuation",
+        short: "P",
+        option: Options::ENABLE_SMART_PUNCTUATION,
+        description: "smart punctuation",
+    },
+    Extension {
+        name: "wikilinks",
+        short: "K",
+        option: Options::ENABLE_WIKILINKS,
+        description: "wiki links like [[Page]] and [[Page|label]]",
+    },
+];
+
+const CONFIG_FILE: &str = "pulldown-cmark.toml";
+
+/// The keys of the configuration file, by table. The empty name is the top
+/// level. The `extensions` table has the names of the extensions as keys.
+const CONFIG_KEYS: &[(&str, &[&str])] = &[
+    ("", &["to"]),
+    (
+        "html",
+        &[
+            "standalone",
+            "title",
+            "lang",
+            "css",
+            "heading-ids",
+            "lazy-images",
+            "heading-offset",
+            "safe",
+        ],
+    ),
+    ("markdown", &["mdx"]),
+    ("latex", &["listings"]),
+    ("typst", &["math"]),
+    ("man", &["title", "section"]),
+    ("ssml", &["code"]),
+    ("lint", &["disable"]),
+];
+
+/// Settings of `pulldown-cmark.toml`, which command line flags add to or
+/// override.
+#[derive(Clone, Default)]
+struct Config {
+    path: String,
+    fields: Vec<(String, Value)>,
+}
+
+impl Config {
+    /// Reads the file given with `--config`, or the first `pulldown-cmark.toml`
+    /// found in the current directory and its parents. Exits on errors.
+    fn load(matches: &getopts::Matches) -> Config {
+        let path = match matches.opt_str("config") {
+            Some(path) => path.into(),
+            None => {
+                let dir = env::current_dir().unwrap_or_default();
+                match dir
+                    .ancestors()
+                    .map(|dir| dir.join(CONFIG_FILE))
+                    .find(|path| path.is_file())
+                {
+                    Some(path) => path,
+                    None => return Config::default(),
+                }
+            }
+        };
+        let path = path.display().to_string();
+        let fail = |message: String| -> ! {
+            eprintln!("{}: {}", path, message);
+            std::process::exit(EXIT_USAGE);
+        };
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|e| fail(e.to_string()));
+        let fields = parse_toml(&text).unwrap_or_else(|e| fail(e));
+        for (key, value) in &fields {
+            let (table, keys) = match value {
+                Value::Table(keys) => (key.as_str(), keys.iter().map(|(key, _)| key).collect()),
+                _ => ("", vec![key]),
+            };
+            let known = CONFIG_KEYS.iter().find(|&&(name, _)| name == table);
+            for key in keys {
+                let known = if table == "extensions" {
+                    EXTENSIONS.iter().any(|extension| extension.name == key)
+                } else {
+                    known.map_or(false, |(_, known)| known.contains(&key.as_str()))
+                };
+                if !known {
+                    match table {
+                        "" => fail(format!("unknown key `{}`", key)),
+                        _ => fail(format!("unknown key `{}` in [{}]", key, table)),
+                    }
+                }
+            }
+        }
+        Config { path, fields }
+    }
+
+    fn get(&self, table: &str, key: &str) -> Option<&Value> {
+        let fields = if table.is_empty() {
+            &self.fields
+        } else {
+            match self.fields.iter().find(|(name, _)| name == table) {
+                Some((_, Value::Table(fields))) => fields,
+                _ => return None,
+            }
+        };
+        fields
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    fn invalid(&self, table: &str, key: &str, expected: &str) -> ! {
+        match table {
+            "" => eprintln!("{}: {} must be {}", self.path, key, expected),
+            _ => eprintln!("{}: [{}] {} must be {}", self.path, table, key, expected),
+        }
+        std::process::exit(EXIT_USAGE);
+    }
+
+    fn bool(&self, table: &str, key: &str) -> bool {
+        match self.get(table, key) {
+            None => false,
+            Some(Value::Bool(b)) => *b,
+            Some(_) => self.invalid(table, key, "a boolean"),
+        }
+    }
+
+    fn string(&self, table: &str, key: &str) -> Option<String> {
+        match self.get(table, key) {
+            None => None,
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(_) => self.invalid(table, key, "a string"),
+        }
+    }
+
+    fn number(&self, table: &str, key: &str) -> Option<usize> {
+        match self.get(table, key) {
+            None => None,
+            Some(Value::Number(n)) => match n.parse() {
+                Ok(n) => Some(n),
+                Err(_) => self.invalid(table, key, "a positive integer"),
+            },
+            Some(_) => self.invalid(table, key, "a positive integer"),
+        }
+    }
+
+    fn strings(&self, table: &str, key: &str) -> Vec<String> {
+        match self.get(table, key) {
+            None => Vec::new(),
+            Some(Value::List(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    _ => self.invalid(table, key, "a list of strings"),
+                })
+                .collect(),
+            Some(_) => self.invalid(table, key, "a list of strings"),
+        }
+    }
+}
+
+impl Config {
+    /// The options of the `[html]` table that apply to fragments too.
+    fn html_options(&self) -> html::Options {
+        html::Options {
+            heading_ids: Some(Slugger::default()).filter(|_| self.bool("html", "heading-ids")),
+            lazy_images: self.bool("html", "lazy-images"),
+            heading_offset: self.number("html", "heading-offset").unwrap_or(0),
+            safe: self.bool("html", "safe"),
+            ..Default::default()
+        }
+    }
+}
+
+fn add_config_flag(opts: &mut getopts::Options) {
+    opts.optopt(
+        "",
+        "config",
+        "read settings from this file instead of pulldown-cmark.toml",
+        "FILE",
+    );
+}
+
+fn add_extension_flags(opts: &mut getopts::Options) {
+    for extension in EXTENSIONS {
+        opts.optflag(
+            extension.short,
+            &format!("enable-{}", extension.name),
+            &format!("enable {}", extension.description),
+        );
+    }
+    opts.optflag("A", "all-extensions", "enable all extensions");
+}
+
+fn parser_options(matches: &getopts::Matches, config: &Config) -> Options {
+    let mut opts = Options::empty();
+    let all = matches.opt_present("all-extensions");
+    for extension in EXTENSIONS {
+        if all
+            || config.bool("extensions", extension.name)
+            || matches.opt_present(&format!("enable-{}", extension.name))
+        {
+            opts.insert(extension.option);
+        }
+    }
+    opts
+}
+
+/// Prints the extensions with their flags.
+fn list_extensions() {
+    for extension in EXTENSIONS {
+        println!(
+            "-{}, --enable-{:20} {}",
+            extension.short, extension.name, extension.description
+        );
+    }
+}
+
+/// Parses the arguments of a subcommand, printing its usage and exiting on
+/// errors and for `--help`.
+fn parse_args(opts: &getopts::Options, args: &[String], brief: &str) -> getopts::Matches {
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}\n{}", f, opts.usage(brief));
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    if matches.opt_present("help") {
+        println!("{}", opts.usage(brief));
+        std::process::exit(0);
+    }
+    matches
+}
+
+fn format_markdown(input: &str, opts: Options) -> String {
+    let mut output = String::new();
+    markdown::push_markdown(&mut output, Parser::new_ext(input, opts));
+    output
+}
+
+/// The `fmt` subcommand: formats markdown files with the markdown writer.
+fn fmt(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optflag(
+        "w",
+        "write",
+        "rewrite the files in place, except those whose events would change",
+    );
+    opts.optflag(
+        "c",
+        "check",
+        "only list the files that are not formatted, failing if there are any",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} fmt [options] [FILE...]\n\n{}",
+        program,
+        "Formats markdown files, or standard input when no file is given, \
+         and prints the result.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let parser_opts = parser_options(&matches, &config);
+    let write = matches.opt_present("write");
+    let check = matches.opt_present("check");
+    if write && matches.free.is_empty() {
+        eprintln!("--write needs files to rewrite\n{}", opts.usage(&brief));
+        std::process::exit(EXIT_USAGE);
+    }
+
+    let mut unformatted = false;
+    if matches.free.is_empty() {
+        let mut input = String::new();
+        io::stdin().lock().read_to_string(&mut input)?;
+        let output = format_markdown(&input, parser_opts);
+        if check {
+            unformatted = output != input;
+        } else {
+            io::stdout().write_all(output.as_bytes())?;
+        }
+    }
+    for path in &matches.free {
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        let output = format_markdown(&input, parser_opts);
+        if check {
+            if output != input {
+                println!("{}", path);
+                unformatted = true;
+            }
+        } else if write {
+            if output == input {
+                continue;
+            }
+            // files are only rewritten when they keep their events
+            let formatted = format!("{} (formatted)", path);
+            let mut difference = Vec::new();
+            if print_difference(
+                &mut difference,
+                (path, &input),
+                (&formatted, &output),
+                parser_opts,
+            )? {
+                eprintln!(
+                    "{}: not rewritten, formatting would change its events",
+                    path
+                );
+                io::stderr().write_all(&difference)?;
+                unformatted = true;
+            } else {
+                write_atomically(path, output.as_bytes())?;
+            }
+        } else {
+            io::stdout().write_all(output.as_bytes())?;
+        }
+    }
+    if unformatted {
+        std::process::exit(EXIT_FAILURE);
+    }
+    Ok(())
+}
+
+/// An example of a spec file, in the format of the CommonMark spec.
+struct SpecExample {
+    /// Line of the opening fence, starting at 1.
+    line: usize,
+    smart_punct: bool,
+    markdown: String,
+    html: String,
+}
+
+fn read_spec(spec: &str) -> Vec<SpecExample> {
+    const FENCE: &str = "````````````````````````````````";
+    let mut examples = Vec::new();
+    let mut lines = spec.lines().enumerate();
+    while let Some((ix, line)) = lines.next() {
+        let info = match strip_prefix(line, FENCE) {
+            Some(info) if info.trim_start().starts_with("example") => info.trim(),
+            _ => continue,
+        };
+        let mut example = SpecExample {
+            line: ix + 1,
+            smart_punct: info.ends_with("_smartpunct"),
+            markdown: String::new(),
+            html: String::new(),
+        };
+        let mut in_html = false;
+        for (_, line) in &mut lines {
+            if line == FENCE {
+                break;
+            } else if line == "." && !in_html {
+                in_html = true;
+                continue;
+            }
+            let part = if in_html {
+                &mut example.html
+            } else {
+                &mut example.markdown
+            };
+            part.push_str(&line.replace('→', "\t"));
+            part.push('\n');
+        }
+        examples.push(example);
+    }
+    examples
+}
+
+/// Normalizes HTML for comparisons: outside of `<pre>` elements, runs of
+/// whitespace become one space, and are removed next to tags.
+fn normalize_html(html: &str) -> String {
+    let mut normalized = String::with_capacity(html.len());
+    let mut rest = html;
+    while !rest.is_empty() {
+        let (outside, pre) = match rest.find("<pre") {
+            Some(start) => {
+                let end = rest[start..]
+                    .find("</pre>")
+                    .map_or(rest.len(), |end| start + end + "</pre>".len());
+                (&rest[..start], &rest[start..end])
+            }
+            None => (rest, ""),
+        };
+        let mut space = false;
+        for c in outside.chars() {
+            if c.is_ascii_whitespace() {
+                space = true;
+                continue;
+            }
+            if space && c != '<' && !normalized.ends_with('>') && !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            space = false;
+            normalized.push(c);
+        }
+        normalized.push_str(pre);
+        rest = &rest[outside.len() + pre.len()..];
+    }
+    normalized
+}
+
+/// Returns the lines of a diff from `old` to `new`, prefixed with `-`, `+`
+/// or a space.
+fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // lengths of the longest common subsequences of the suffixes
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(format!("+{}", new[j]));
+            j += 1;
+        } else {
+            lines.push(format!("-{}", old[i]));
+            i += 1;
+        }
+    }
+    lines
+}
+
+/// The `spec` subcommand: runs the examples of spec files.
+fn spec(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optflag(
+        "r",
+        "roundtrip",
+        "write the examples as markdown and parse them again before rendering",
+    );
+    opts.optflag("q", "quiet", "only print the statistics");
+    opts.optopt("n", "example", "only run the example with this number", "N");
+    let brief = format!(
+        "Usage: {} spec [options] FILE...\n\n{}",
+        program,
+        "Runs the examples of CommonMark-style spec files and compares the HTML \
+         with the expected one, ignoring whitespace next to tags. Tables, footnotes, strikethrough and task lists \
+         are enabled, and smart punctuation in the examples asking for it.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    if matches.free.is_empty() {
+        eprintln!("no spec file given\n{}", opts.usage(&brief));
+        std::process::exit(EXIT_USAGE);
+    }
+    let only = match matches.opt_str("example").map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) => Some(n),
+        Some(Err(e)) => {
+            eprintln!("invalid example number: {}", e);
+            std::process::exit(EXIT_USAGE);
+        }
+        None => None,
+    };
+    let roundtrip = matches.opt_present("roundtrip");
+    let quiet = matches.opt_present("quiet");
+
+    let stdio = io::stdout();
+    let mut out = io::BufWriter::new(stdio.lock());
+    let (mut passed, mut failed) = (0, 0);
+    for path in &matches.free {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        for (ix, example) in read_spec(&text).iter().enumerate() {
+            let number = ix + 1;
+            if only.map_or(false, |n| n != number) {
+                continue;
+            }
+            let mut opts = Options::ENABLE_TABLES
+                | Options::ENABLE_FOOTNOTES
+                | Options::ENABLE_STRIKETHROUGH
+                | Options::ENABLE_TASKLISTS;
+            if example.smart_punct {
+                opts.insert(Options::ENABLE_SMART_PUNCTUATION);
+            }
+            let input = if roundtrip {
+                format_markdown(&example.markdown, opts)
+            } else {
+                example.markdown.clone()
+            };
+            let mut html = String::new();
+            html::push_html(&mut html, Parser::new_ext(&input, opts));
+            if normalize_html(&html) == normalize_html(&example.html) {
+                passed += 1;
+                continue;
+            }
+            failed += 1;
+            if quiet {
+                continue;
+            }
+            writeln!(out, "example {} ({}:{}) failed", number, path, example.line)?;
+            writeln!(out, "markdown:")?;
+            write!(out, "{}", example.markdown)?;
+            if roundtrip {
+                writeln!(out, "written as:")?;
+                write!(out, "{}", input)?;
+            }
+            writeln!(out, "html:")?;
+            for line in diff_lines(&example.html, &html) {
+                writeln!(out, "{}", line)?;
+            }
+            writeln!(out)?;
+        }
+    }
+    writeln!(out, "{} passed, {} failed", passed, failed)?;
+    out.flush()?;
+    if failed > 0 {
+        std::process::exit(EXIT_FAILURE);
+    }
+    Ok(())
+}
+
+/// Reads the files given, or standard input when there are none, and calls
+/// `f` with the name and contents of each.
+fn for_each_input<F>(paths: &[String], mut f: F) -> io::Result<()>
+where
+    F: FnMut(&str, &str) -> io::Result<()>,
+{
+    if paths.is_empty() {
+        let mut input = String::new();
+        io::stdin().lock().read_to_string(&mut input)?;
+        return f("<stdin>", &input);
+    }
+    for path in paths {
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        f(path, &input)?;
+    }
+    Ok(())
+}
 
-fn dry_run(text: &str, opts: Options) {
-    let p = Parser::new_ext(text, opts);
-    let count = p.count();
-    println!("{} events", count);
+/// The `lint` subcommand: checks markdown files against style rules.
+fn lint(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optmulti("", "disable", "do not check this rule", "RULE");
+    add_format_flag(&mut opts);
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let rules: Vec<&str> = Rule::ALL.iter().map(|rule| rule.id()).collect();
+    let brief = format!(
+        "Usage: {} lint [options] [FILE...]\n\n{}\n\nRules: {}",
+        program,
+        "Checks markdown files, or standard input when no file is given, \
+         and prints the problems found. Exits with 4 when there are some.",
+        rules.join(", "),
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let mut disabled = Vec::new();
+    for id in config
+        .strings("lint", "disable")
+        .into_iter()
+        .chain(matches.opt_strs("disable"))
+    {
+        match Rule::from_id(&id) {
+            Some(rule) => disabled.push(rule),
+            None => {
+                eprintln!("unknown rule: {}\n{}", id, opts.usage(&brief));
+                std::process::exit(EXIT_USAGE);
+            }
+        }
+    }
+    let parser_opts = parser_options(&matches, &config);
+
+    let json = json_diagnostics(&matches);
+
+    let mut reports = Vec::new();
+    for_each_input(&matches.free, |path, input| {
+        let index = LineIndex::new(input);
+        let parser = Parser::new_ext(input, parser_opts).into_offset_iter();
+        for diagnostic in utils::lint::lint(input, parser) {
+            if disabled.contains(&diagnostic.rule) {
+                continue;
+            }
+            let (line, column) = index.position(diagnostic.span.start);
+            reports.push(Report {
+                file: path.to_owned(),
+                line,
+                column,
+                severity: "warning",
+                code: diagnostic.rule.id(),
+                message: diagnostic.message,
+            });
+        }
+        Ok(())
+    })?;
+    let stdio = io::stdout();
+    print_diagnostics(io::BufWriter::new(stdio.lock()), json, &reports)?;
+    if !reports.is_empty() {
+        std::process::exit(EXIT_LINT);
+    }
+    Ok(())
+}
+
+const TOC_START: &str = "<!-- toc -->";
+const TOC_END: &str = "<!-- tocstop -->";
+
+/// Parses a `--slugs` argument.
+fn slug_style(matches: &getopts::Matches) -> SlugStyle {
+    match matches.opt_str("slugs").as_deref() {
+        None | Some("github") => SlugStyle::GitHub,
+        Some("kebab") => SlugStyle::Kebab,
+        Some(style) => {
+            eprintln!("unknown slug style: {}", style);
+            std::process::exit(EXIT_USAGE);
+        }
+    }
 }
 
-fn print_events(text: &str, opts: Options) {
-    let parser = Parser::new_ext(text, opts).into_offset_iter();
-    for (event, range) in parser {
-        println!("{:?}: {:?}", range, event);
+/// Writes the table of contents of `input` as a markdown list.
+fn markdown_toc(input: &str, opts: Options, style: SlugStyle, max_level: usize) -> String {
+    let mut slugger = Slugger::new(style);
+    let entries = utils::toc::collect_toc(Parser::new_ext(input, opts), &mut slugger);
+    let mut toc = String::new();
+    // levels of the enclosing entries, so that items nest one level at a time
+    let mut open = Vec::new();
+    for entry in entries {
+        if entry.level as usize > max_level {
+            continue;
+        }
+        while open.last().map_or(false, |&level| level >= entry.level) {
+            open.pop();
+        }
+        toc.push_str(&"  ".repeat(open.len()));
+        toc.push_str("- [");
+        markdown::escape_markdown(&mut toc, &entry.text).unwrap();
+        toc.push_str("](#");
+        toc.push_str(&entry.slug);
+        toc.push_str(")\n");
+        open.push(entry.level);
     }
-    println!("EOF");
+    toc
 }
 
-fn brief(program: &str) -> String {
-    format!(
-        "Usage: {} [options]\n\n{}",
-        program, "Reads markdown from standard input and emits HTML.",
+/// Replaces the table of contents between the markers. Returns `None` when
+/// there is no start marker.
+fn insert_toc(input: &str, toc: &str) -> Option<String> {
+    let start = input.find(TOC_START)? + TOC_START.len();
+    let end = input[start..]
+        .find(TOC_END)
+        .map_or(start, |end| start + end + TOC_END.len());
+    let mut output = String::with_capacity(input.len() + toc.len());
+    output.push_str(&input[..start]);
+    output.push_str("\n\n");
+    output.push_str(toc);
+    output.push('\n');
+    output.push_str(TOC_END);
+    if end == start && !input[end..].starts_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(&input[end..]);
+    Some(output)
+}
+
+/// The `toc` subcommand: prints or updates tables of contents.
+fn toc(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optflag(
+        "i",
+        "insert",
+        "print the document with the table of contents between the markers",
+    );
+    opts.optflag("w", "write", "with --insert, rewrite the file in place");
+    opts.optopt("", "max-level", "deepest heading level listed", "N");
+    opts.optopt(
+        "",
+        "slugs",
+        "slug style, github (the default) or kebab",
+        "STYLE",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} toc [options] [FILE]\n\n{} {} and {}.",
+        program,
+        "Prints the table of contents of a markdown file, or standard input, as a \
+         markdown list. With --insert, the list replaces whatever is between the lines",
+        TOC_START,
+        TOC_END,
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let max_level = match matches.opt_str("max-level").map(|n| n.parse()) {
+        None => 6,
+        Some(Ok(n)) => n,
+        Some(Err(e)) => {
+            eprintln!("invalid level: {}", e);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    let style = slug_style(&matches);
+    let config = Config::load(&matches);
+    let parser_opts = parser_options(&matches, &config);
+    if matches.free.len() > 1 || (matches.opt_present("write") && matches.free.is_empty()) {
+        eprintln!("{}", opts.usage(&brief));
+        std::process::exit(EXIT_USAGE);
+    }
+
+    for_each_input(&matches.free, |path, input| {
+        let toc = markdown_toc(input, parser_opts, style, max_level);
+        if !matches.opt_present("insert") {
+            return io::stdout().write_all(toc.as_bytes());
+        }
+        let output = match insert_toc(input, &toc) {
+            Some(output) => output,
+            None => {
+                eprintln!("{}: no {} marker", path, TOC_START);
+                std::process::exit(EXIT_FAILURE);
+            }
+        };
+        if matches.opt_present("write") {
+            if output != input {
+                write_atomically(path, output.as_bytes())?;
+            }
+            Ok(())
+        } else {
+            io::stdout().write_all(output.as_bytes())
+        }
+    })
+}
+
+/// Script of served pages, asking the server for the modification time of
+/// the page every second and reloading when it changes.
+const RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+  var version = null;
+  setInterval(function () {
+    fetch("/__version" + location.pathname).then(function (response) {
+      return response.text();
+    }).then(function (text) {
+      if (version !== null && text !== version) location.reload();
+      version = text;
+    }).catch(function () {});
+  }, 1000);
+})();
+</script>
+"#;
+
+/// Decodes `%XX` escapes of a URL path.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let hex = bytes
+            .get(ix + 1..ix + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[ix], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                ix += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                ix += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn content_type(path: &std::path::Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn is_markdown(path: &std::path::Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("md") | Some("markdown")
     )
 }
 
+/// The files served by `serve`, and how markdown is rendered.
+struct Site {
+    root: std::path::PathBuf,
+    opts: Options,
+    config: Config,
+}
+
+impl Site {
+    /// Maps a request path to a file or directory under the root.
+    fn resolve(&self, path: &str) -> Option<std::path::PathBuf> {
+        if self.root.is_file() {
+            return Some(self.root.clone()).filter(|_| path == "/");
+        }
+        let mut resolved = self.root.clone();
+        for part in percent_decode(path).split('/') {
+            match part {
+                "" | "." => (),
+                ".." => return None,
+                part => resolved.push(part),
+            }
+        }
+        Some(resolved)
+    }
+
+    /// Returns the status, content type and body answering a GET request.
+    fn respond(&self, path: &str) -> (&'static str, &'static str, Vec<u8>) {
+        let not_found = ("404 Not Found", "text/plain", b"not found\n".to_vec());
+        if let Some(page) = strip_prefix(path, "/__version") {
+            let page = if page.is_empty() { "/" } else { page };
+            let version = self
+                .resolve(page)
+                .and_then(|file| std::fs::metadata(file).ok()?.modified().ok())
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_nanos());
+            return ("200 OK", "text/plain", version.to_string().into_bytes());
+        }
+        let file = match self.resolve(path) {
+            Some(file) => file,
+            None => return not_found,
+        };
+        if file.is_dir() {
+            if !path.ends_with('/') {
+                // so that relative links of the listing work
+                return ("301 Moved Permanently", "text/plain", Vec::new());
+            }
+            match self.listing(&file, path) {
+                Ok(page) => ("200 OK", "text/html; charset=utf-8", page.into_bytes()),
+                Err(_) => not_found,
+            }
+        } else if is_markdown(&file) {
+            match std::fs::read_to_string(&file) {
+                Ok(input) => {
+                    let title = file
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned());
+                    let page = self.render(&input, title);
+                    ("200 OK", "text/html; charset=utf-8", page.into_bytes())
+                }
+                Err(_) => not_found,
+            }
+        } else {
+            match std::fs::read(&file) {
+                Ok(bytes) => ("200 OK", content_type(&file), bytes),
+                Err(_) => not_found,
+            }
+        }
+    }
+
+    fn render(&self, input: &str, title: Option<String>) -> String {
+        let mut options = self.config.html_options();
+        options.document = Some(html::Document {
+            lang: self.config.string("html", "lang"),
+            title,
+            stylesheets: self.config.strings("html", "css"),
+        });
+        let mut page = String::new();
+        html::push_html_ext(&mut page, Parser::new_ext(input, self.opts), &options);
+        let end = page.rfind("</body>").unwrap_or(page.len());
+        page.insert_str(end, RELOAD_SCRIPT);
+        page
+    }
+
+    /// Renders a list of the markdown files and directories of `dir` as a
+    /// page.
+    fn listing(&self, dir: &std::path::Path, path: &str) -> io::Result<String> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?.path();
+            let name = match entry.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.is_dir() {
+                entries.push(format!("{}/", name));
+            } else if is_markdown(&entry) {
+                entries.push(name);
+            }
+        }
+        entries.sort();
+        let mut markdown = String::from("# ");
+        markdown::escape_markdown(&mut markdown, path).unwrap();
+        markdown.push_str("\n\n");
+        for entry in entries {
+            markdown.push_str("- [");
+            markdown::escape_markdown(&mut markdown, &entry).unwrap();
+            markdown.push_str("](<");
+            markdown.push_str(&entry.replace('<', "%3C").replace('>', "%3E"));
+            markdown.push_str(">)\n");
+        }
+        Ok(self.render(&markdown, Some(path.to_owned())))
+    }
+}
+
+/// How long `serve` waits on a client before dropping its connection.
+const CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Answers one HTTP request.
+fn handle_connection(site: &Site, stream: std::net::TcpStream) -> io::Result<()> {
+    // connections are served one at a time, so a client that stops sending
+    // or reading must not hold up the others
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut reader = io::BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // skip the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+    let path = target.split(&['?', '#'][..]).next().unwrap_or("/");
+    let (status, content_type, body) = if method == "GET" || method == "HEAD" {
+        site.respond(path)
+    } else {
+        ("405 Method Not Allowed", "text/plain", Vec::new())
+    };
+    let mut writer = io::BufWriter::new(&stream);
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if status.starts_with("301") {
+        write!(writer, "Location: {}/\r\n", path)?;
+    }
+    write!(writer, "Connection: close\r\n\r\n")?;
+    if method != "HEAD" {
+        writer.write_all(&body)?;
+    }
+    writer.flush()
+}
+
+/// The `serve` subcommand: a local preview server.
+fn serve(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optopt("", "port", "port to listen on, 8000 by default", "PORT");
+    opts.optopt(
+        "",
+        "host",
+        "address to listen on, 127.0.0.1 by default",
+        "HOST",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} serve [options] [PATH]\n\n{}",
+        program,
+        "Serves a markdown file, or the markdown files of a directory, the current \
+         one by default, rendered as HTML documents. Pages reload when their file \
+         changes. Other files of the directory, like images, are served as they are.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let root = matches.free.first().map_or(".", String::as_str);
+    let site = Site {
+        root: root.into(),
+        opts: parser_options(&matches, &config),
+        config,
+    };
+    if !site.root.exists() {
+        eprintln!("{}: no such file or directory", root);
+        std::process::exit(EXIT_USAGE);
+    }
+    let address = format!(
+        "{}:{}",
+        matches.opt_str("host").as_deref().unwrap_or("127.0.0.1"),
+        matches.opt_str("port").as_deref().unwrap_or("8000")
+    );
+    let listener = std::net::TcpListener::bind(&address)?;
+    eprintln!("serving {} on http://{}/", root, address);
+    for stream in listener.incoming() {
+        // a failed connection does not stop the server
+        if let Err(e) = stream.and_then(|stream| handle_connection(&site, stream)) {
+            eprintln!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+/// The normalized events of a document, with the byte offset in the source
+/// near which each was produced.
+fn normalized_events(input: &str, opts: Options) -> Vec<(Event<'_>, usize)> {
+    let offset = std::cell::Cell::new(0);
+    let iter = Parser::new_ext(input, opts)
+        .into_offset_iter()
+        .map(|(event, range)| {
+            offset.set(range.start);
+            event
+        });
+    Normalize::new(iter)
+        .map(|event| (event, offset.get()))
+        .collect()
+}
+
+/// Prints where the normalized events of two named documents first differ,
+/// with the source lines of the differing events. Prints nothing and
+/// returns `false` when they don't differ.
+fn print_difference<W: Write>(
+    mut out: W,
+    (old_name, old): (&str, &str),
+    (new_name, new): (&str, &str),
+    opts: Options,
+) -> io::Result<bool> {
+    let old_events = normalized_events(old, opts);
+    let new_events = normalized_events(new, opts);
+    let divergence = (0..old_events.len().max(new_events.len()))
+        .find(|&ix| old_events.get(ix).map(|e| &e.0) != new_events.get(ix).map(|e| &e.0));
+    let ix = match divergence {
+        Some(ix) => ix,
+        None => return Ok(false),
+    };
+    // the tags enclosing the difference, which are the same on both sides
+    let mut path = Vec::new();
+    for (event, _) in &old_events[..ix] {
+        match event {
+            Event::Start(tag) => path.push(format!("{:?}", tag)),
+            Event::End(_) => {
+                path.pop();
+            }
+            _ => (),
+        }
+    }
+    writeln!(out, "the events differ at event {}", ix + 1)?;
+    if !path.is_empty() {
+        writeln!(out, "inside {}", path.join(" > "))?;
+    }
+    for (name, input, events) in &[(old_name, old, &old_events), (new_name, new, &new_events)] {
+        match events.get(ix) {
+            Some((event, offset)) => {
+                let index = LineIndex::new(input);
+                let (line, column) = index.position(*offset);
+                writeln!(out, "{}:{}:{}: {:?}", name, line, column, event)?;
+                writeln!(out, "    | {}", index.line(line))?;
+            }
+            None => writeln!(out, "{}: end of document", name)?,
+        }
+    }
+    out.flush()?;
+    Ok(true)
+}
+
+/// The `diff` subcommand: compares the normalized events of two documents.
+fn diff(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optflag(
+        "",
+        "fmt",
+        "compare a single file with its output of the fmt subcommand",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {0} diff [options] OLD NEW\n       {0} diff --fmt [options] FILE\n\n{1}",
+        program,
+        "Compares the events of two markdown files, after merging adjacent text and \
+         inlining reference links, and prints where they first differ. Exits with 1 \
+         when they differ.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let parser_opts = parser_options(&matches, &config);
+    let fmt = matches.opt_present("fmt");
+    if matches.free.len() != if fmt { 1 } else { 2 } {
+        eprintln!("{}", opts.usage(&brief));
+        std::process::exit(EXIT_USAGE);
+    }
+    let old = read_files(&matches.free[..1])?;
+    let (new, new_name) = if fmt {
+        (format_markdown(&old, parser_opts), "formatted")
+    } else {
+        (read_files(&matches.free[1..])?, matches.free[1].as_str())
+    };
+    let old_name = matches.free[0].as_str();
+
+    let stdio = io::stdout();
+    let out = io::BufWriter::new(stdio.lock());
+    if !print_difference(out, (old_name, &old), (new_name, &new), parser_opts)? {
+        println!("the events are the same");
+        return Ok(());
+    }
+    std::process::exit(EXIT_FAILURE);
+}
+
+/// The `bench` subcommand: measures throughput on the given files.
+fn bench(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optopt(
+        "n",
+        "iterations",
+        "number of runs of every stage, 10 by default",
+        "N",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} bench [options] FILE...\n\n{}",
+        program,
+        "Parses and renders the files a number of times, after one warm-up run, and \
+         prints the mean time and throughput of each stage: parsing alone, parsing \
+         and writing HTML, and parsing and writing markdown.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let parser_opts = parser_options(&matches, &config);
+    let iterations = match matches.opt_str("iterations").map(|n| n.parse::<u32>()) {
+        None => 10,
+        Some(Ok(n)) if n > 0 => n,
+        _ => {
+            eprintln!("--iterations must be a positive number");
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    if matches.free.is_empty() {
+        eprintln!("no file given\n{}", opts.usage(&brief));
+        std::process::exit(EXIT_USAGE);
+    }
+    let mut inputs = Vec::new();
+    for path in &matches.free {
+        inputs.push(read_files(std::slice::from_ref(path))?);
+    }
+    let bytes: usize = inputs.iter().map(String::len).sum();
+
+    type Stage = fn(&str, Options) -> usize;
+    let stages: [(&str, Stage); 3] = [
+        ("parse", |input, opts| Parser::new_ext(input, opts).count()),
+        ("parse+html", |input, opts| {
+            let mut html = String::new();
+            html::push_html(&mut html, Parser::new_ext(input, opts));
+            html.len()
+        }),
+        ("parse+markdown", |input, opts| {
+            format_markdown(input, opts).len()
+        }),
+    ];
+    println!(
+        "{} files, {} bytes, {} iterations",
+        inputs.len(),
+        bytes,
+        iterations
+    );
+    for (name, stage) in &stages {
+        // The results of the timed runs are checked against those of the
+        // warm-up run, which keeps the work from being optimized away.
+        let expected: Vec<usize> = inputs
+            .iter()
+            .map(|input| stage(input, parser_opts))
+            .collect();
+        let mut mismatches = 0;
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            for (input, &expected) in inputs.iter().zip(&expected) {
+                if stage(input, parser_opts) != expected {
+                    mismatches += 1;
+                }
+            }
+        }
+        let mean = start.elapsed().as_secs_f64() / f64::from(iterations);
+        assert_eq!(mismatches, 0, "{} gave different results across runs", name);
+        println!(
+            "{:16} {:10.3} ms {:10.2} MB/s",
+            name,
+            mean * 1e3,
+            bytes as f64 / mean / 1e6
+        );
+    }
+    Ok(())
+}
+
+/// Collects the markdown files under `path`, skipping hidden files and
+/// directories, in sorted order.
+fn markdown_files(path: &std::path::Path, files: &mut Vec<std::path::PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        let hidden = entry
+            .file_name()
+            .map_or(false, |name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if entry.is_dir() {
+            markdown_files(&entry, files)?;
+        } else if is_markdown(&entry) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// The `check-links` subcommand: finds links to missing anchors and files.
+fn check_links(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    add_format_flag(&mut opts);
+    opts.optopt(
+        "",
+        "slugs",
+        "slug style, github (the default) or kebab",
+        "STYLE",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} check-links [options] [PATH...]\n\n{}",
+        program,
+        "Checks the links of markdown files, and of the markdown files in directories, \
+         the current one by default. Links to anchors must match a heading, a footnote \
+         or an HTML id of their document, and relative links must point to existing \
+         files, and to existing anchors in markdown files. Links starting with `/` are \
+         relative to the first directory given. Exits with 5 when links are broken.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let parser_opts = parser_options(&matches, &config);
+    let style = slug_style(&matches);
+    let json = json_diagnostics(&matches);
+    let roots = if matches.free.is_empty() {
+        vec![".".to_owned()]
+    } else {
+        matches.free.clone()
+    };
+    let site_root = roots
+        .iter()
+        .map(std::path::Path::new)
+        .find(|root| root.is_dir())
+        .unwrap_or_else(|| std::path::Path::new("."))
+        .to_owned();
+    let mut files = Vec::new();
+    for root in &roots {
+        markdown_files(std::path::Path::new(root), &mut files)?;
+    }
+
+    let mut broken = Vec::new();
+    let mut inputs = std::collections::HashMap::new();
+    // links with a fragment into other markdown files, by target
+    let mut fragments: std::collections::BTreeMap<_, Vec<_>> = Default::default();
+    for file in &files {
+        let input = std::fs::read_to_string(file)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file.display(), e)))?;
+        let index = LineIndex::new(&input);
+        let report = |span: std::ops::Range<usize>, dest: &str, code| {
+            let (line, column) = index.position(span.start);
+            let message = match code {
+                "missing-file" => format!("no such file `{}`", dest),
+                _ => format!("no such anchor `{}`", dest),
+            };
+            Report {
+                file: file.display().to_string(),
+                line,
+                column,
+                severity: "error",
+                code,
+                message,
+            }
+        };
+        let events: Vec<_> = Parser::new_ext(&input, parser_opts)
+            .into_offset_iter()
+            .collect();
+        let slugger = &mut Slugger::new(style);
+        for link in check_anchors(events.iter().cloned(), slugger) {
+            broken.push(report(link.span, &link.dest, "missing-anchor"));
+        }
+        for link in collect_links(events.into_iter()) {
+            if !link.is_local_path() {
+                continue;
+            }
+            let dest = &*link.dest;
+            let (path, fragment) = match dest.find('#') {
+                Some(ix) => (&dest[..ix], Some(&dest[ix..])),
+                None => (dest, None),
+            };
+            let path = percent_decode(path.split('?').next().unwrap_or(path));
+            let target = match strip_prefix(&path, "/") {
+                Some(path) => site_root.join(path),
+                None => file.parent().unwrap_or(std::path::Path::new("")).join(path),
+            };
+            if !target.exists() {
+                broken.push(report(link.span, dest, "missing-file"));
+            } else if let Some(fragment) = fragment.filter(|_| is_markdown(&target)) {
+                let link = report(link.span, dest, "missing-anchor");
+                fragments
+                    .entry(target)
+                    .or_default()
+                    .push((fragment.to_owned(), link));
+            }
+        }
+        inputs.insert(file.clone(), input);
+    }
+    for (target, links) in fragments {
+        let input = match inputs.get(&target) {
+            Some(input) => input.clone(),
+            None => read_files(&[target.display().to_string()])?,
+        };
+        // check the fragments as links of the target document
+        let end = input.len();
+        let events = Parser::new_ext(&input, parser_opts)
+            .into_offset_iter()
+            .chain(links.iter().enumerate().flat_map(|(ix, (fragment, _))| {
+                let tag = Tag::Link(LinkType::Inline, fragment.clone().into(), "".into());
+                vec![
+                    (Event::Start(tag.clone()), end + ix..end + ix),
+                    (Event::End(tag), end + ix..end + ix),
+                ]
+            }));
+        let unresolved = check_anchors(events, &mut Slugger::new(style));
+        // the target's own broken anchors were reported in the first pass
+        for link in unresolved.iter().filter(|link| link.span.start >= end) {
+            broken.push(links[link.span.start - end].1.clone());
+        }
+    }
+    broken.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+
+    let stdio = io::stdout();
+    print_diagnostics(io::BufWriter::new(stdio.lock()), json, &broken)?;
+    if !broken.is_empty() {
+        std::process::exit(EXIT_BROKEN_LINKS);
+    }
+    Ok(())
+}
+
+/// The file extension of outputs in `format`.
+fn output_extension(format: &str) -> &'static str {
+    match format {
+        "html" => "html",
+        "markdown" | "commonmark" | "roundtrip" => "md",
+        "mdx" => "mdx",
+        "json" => "json",
+        "latex" => "tex",
+        "typst" => "typ",
+        "man" => "1",
+        "org" => "org",
+        "ssml" => "ssml",
+        "rtf" => "rtf",
+        _ => "txt",
+    }
+}
+
+/// Renders one file of `build` to `output`, creating its directory.
+fn build_file(
+    render: &mut Render,
+    title: &Option<String>,
+    input: &std::path::Path,
+    output: &std::path::Path,
+) -> io::Result<()> {
+    let text = std::fs::read_to_string(input)?;
+    if let Some(ref mut document) = render.html_options.document {
+        document.title = title.clone().or_else(|| {
+            let stem = input.file_stem()?;
+            Some(stem.to_string_lossy().into_owned())
+        });
+    }
+    let mut rendered = Vec::new();
+    render.render(&mut rendered, &text)?;
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_atomically(&output.to_string_lossy(), &rendered)
+}
+
+/// The number of files `build` renders at once when `--jobs` is not given.
+#[cfg(has_available_parallelism)]
+#[clippy::msrv = "1.59"]
+fn available_parallelism() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+/// The number of files `build` renders at once when `--jobs` is not given.
+/// Compilers older than 1.59 can't tell the number of CPUs.
+#[cfg(not(has_available_parallelism))]
+fn available_parallelism() -> usize {
+    1
+}
+
+/// The `build` subcommand: renders trees of markdown files in parallel.
+fn build(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optopt("o", "output", "directory to write the outputs to", "DIR");
+    opts.optopt(
+        "j",
+        "jobs",
+        "number of files to render at once, the number of CPUs by default",
+        "N",
+    );
+    add_render_flags(&mut opts);
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} build [options] PATH... -o DIR\n\n{}",
+        program,
+        "Renders markdown files, and the markdown files of directories, to files in \
+         DIR. The outputs of a directory keep their paths relative to it, with the \
+         extension of the output format. A file that fails is reported and the others \
+         are still rendered; the exit status is then 1. Standalone documents are titled \
+         after their file unless --title is given.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let output = match matches.opt_str("output") {
+        Some(output) if !matches.free.is_empty() => std::path::PathBuf::from(output),
+        _ => {
+            eprintln!("build needs paths and --output\n{}", opts.usage(&brief));
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    let jobs = match matches.opt_str("jobs").map(|jobs| jobs.parse::<usize>()) {
+        None => available_parallelism(),
+        Some(Ok(jobs)) if jobs > 0 => jobs,
+        Some(_) => {
+            eprintln!("--jobs must be a positive number");
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    let config = Config::load(&matches);
+    let render = renderer(program, &matches, &config);
+    let extension = output_extension(&render.format);
+    let title = render
+        .html_options
+        .document
+        .and_then(|document| document.title);
+
+    // pairs of input and output paths
+    let mut files = Vec::new();
+    for root in &matches.free {
+        let root = std::path::Path::new(root);
+        let mut inputs = Vec::new();
+        markdown_files(root, &mut inputs)?;
+        for input in inputs {
+            let relative = match input.strip_prefix(root) {
+                Ok(relative) if root.is_dir() => relative.to_owned(),
+                _ => input.file_name().map_or(input.clone(), Into::into),
+            };
+            let target = output.join(relative).with_extension(extension);
+            files.push((input, target));
+        }
+    }
+
+    let files = Arc::new(files);
+    let next = Arc::new(AtomicUsize::new(0));
+    let failures = Arc::new(AtomicUsize::new(0));
+    let workers: Vec<_> = (0..jobs.min(files.len()))
+        .map(|_| {
+            let (files, next, failures) = (files.clone(), next.clone(), failures.clone());
+            let (program, matches, config) = (program.to_owned(), matches.clone(), config.clone());
+            let title = title.clone();
+            std::thread::spawn(move || {
+                // the renderer holds a slugger, which can't be shared
+                let mut render = renderer(&program, &matches, &config);
+                loop {
+                    let ix = next.fetch_add(1, Ordering::Relaxed);
+                    let (input, target) = match files.get(ix) {
+                        Some(file) => file,
+                        None => break,
+                    };
+                    if let Err(e) = build_file(&mut render, &title, input, target) {
+                        eprintln!("{}: {}", input.display(), e);
+                        failures.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().expect("a build thread panicked");
+    }
+    let failures = failures.load(Ordering::Relaxed);
+    eprintln!(
+        "built {} of {} files into {}",
+        files.len() - failures,
+        files.len(),
+        output.display()
+    );
+    if failures > 0 {
+        std::process::exit(EXIT_FAILURE);
+    }
+    Ok(())
+}
+
+/// The `anchors` subcommand: lists the headings of files with their slugs.
+fn anchors(program: &str, args: &[String]) -> io::Result<()> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "this help message");
+    opts.optflag("", "json", "print the headings as a JSON array");
+    opts.optopt(
+        "",
+        "slugs",
+        "slug style, github (the default) or kebab",
+        "STYLE",
+    );
+    add_extension_flags(&mut opts);
+    add_config_flag(&mut opts);
+    let brief = format!(
+        "Usage: {} anchors [options] [PATH...]\n\n{}",
+        program,
+        "Lists the headings of markdown files, and of the markdown files in \
+         directories, the current one by default, with their positions and the \
+         slugs they are given as ids.",
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let config = Config::load(&matches);
+    let parser_opts = parser_options(&matches, &config);
+    let style = slug_style(&matches);
+    let roots = if matches.free.is_empty() {
+        vec![".".to_owned()]
+    } else {
+        matches.free.clone()
+    };
+    let mut files = Vec::new();
+    for root in &roots {
+        markdown_files(std::path::Path::new(root), &mut files)?;
+    }
+
+    let stdio = io::stdout();
+    let mut out = io::BufWriter::new(stdio.lock());
+    let mut headings = Vec::new();
+    for file in &files {
+        let input = read_files(&[file.display().to_string()])?;
+        let index = LineIndex::new(&input);
+        let events: Vec<_> = Parser::new_ext(&input, parser_opts)
+            .into_offset_iter()
+            .collect();
+        let starts = events.iter().filter_map(|(event, span)| match event {
+            Event::Start(Tag::Heading(_)) => Some(span.start),
+            _ => None,
+        });
+        let entries = utils::toc::collect_toc(
+            events.iter().map(|(event, _)| event.clone()),
+            &mut Slugger::new(style),
+        );
+        for (entry, offset) in entries.into_iter().zip(starts) {
+            let (line, column) = index.position(offset);
+            if matches.opt_present("json") {
+                let number = |n: usize| Value::Number(n.to_string());
+                headings.push(Value::Table(vec![
+                    ("file".to_owned(), Value::String(file.display().to_string())),
+                    ("line".to_owned(), number(line)),
+                    ("column".to_owned(), number(column)),
+                    ("offset".to_owned(), number(offset)),
+                    ("level".to_owned(), number(entry.level as usize)),
+                    ("slug".to_owned(), Value::String(entry.slug)),
+                    ("text".to_owned(), Value::String(entry.text)),
+                ]));
+            } else {
+                writeln!(
+                    out,
+                    "{}:{}:{} (byte {}): {} #{} {}",
+                    file.display(),
+                    line,
+                    column,
+                    offset,
+                    entry.level,
+                    entry.slug,
+                    entry.text
+                )?;
+            }
+        }
+    }
+    if matches.opt_present("json") {
+        let mut json = String::new();
+        Value::List(headings).write_json(&mut json);
+        writeln!(out, "{}", json)?;
+    }
+    out.flush()
+}
+
+/// Checks that the files, or `input` read from standard input, parse to the
+/// same normalized events after being written back as markdown, and prints
+/// the first difference of those that don't. Positions in the written
+/// markdown are given with the file name followed by `(roundtrip)`.
+fn verify_roundtrip(paths: &[String], input: &str, render: &Render) -> io::Result<()> {
+    let stdio = io::stdout();
+    let mut out = io::BufWriter::new(stdio.lock());
+    let mut failed = false;
+    let mut verify = |name: &str, input: &str| -> io::Result<()> {
+        let body = render.body(input);
+        let written = format_markdown(body, render.opts);
+        let roundtrip = format!("{} (roundtrip)", name);
+        failed |= print_difference(&mut out, (name, body), (&roundtrip, &written), render.opts)?;
+        Ok(())
+    };
+    if paths.is_empty() {
+        verify("<stdin>", input)?;
+    }
+    for path in paths {
+        verify(path, &read_files(std::slice::from_ref(path))?)?;
+    }
+    out.flush()?;
+    if failed {
+        std::process::exit(EXIT_FAILURE);
+    }
+    Ok(())
+}
+
 pub fn main() -> std::io::Result<()> {
     let args: Vec<_> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("fmt") => return fmt(&args[0], &args[2..]),
+        Some("spec") => return spec(&args[0], &args[2..]),
+        Some("lint") => return lint(&args[0], &args[2..]),
+        Some("toc") => return toc(&args[0], &args[2..]),
+        Some("serve") => return serve(&args[0], &args[2..]),
+        Some("diff") => return diff(&args[0], &args[2..]),
+        Some("bench") => return bench(&args[0], &args[2..]),
+        Some("check-links") => return check_links(&args[0], &args[2..]),
+        Some("build") => return build(&args[0], &args[2..]),
+        Some("anchors") => return anchors(&args[0], &args[2..]),
+        _ => (),
+    }
     let mut opts = getopts::Options::new();
     opts.optflag("h", "help", "this help message");
     opts.optflag("d", "dry-run", "dry run, produce no output");
-    opts.optflag("e", "events", "print event sequence instead of rendering");
-    opts.optflag("T", "enable-tables", "enable GitHub-style tables");
-    opts.optflag("F", "enable-footnotes", "enable Hoedown-style footnotes");
     opts.optflag(
-        "S",
-        "enable-strikethrough",
-        "enable GitHub-style strikethrough",
+        "e",
+        "events",
+        "print event sequence with positions instead of rendering, as JSON with --to json",
+    );
+    add_render_flags(&mut opts);
+    add_extension_flags(&mut opts);
+    opts.optflag("", "list-extensions", "print the supported extensions");
+    #[cfg(feature = "syntect")]
+    opts.optopt(
+        "",
+        "highlight-css",
+        "print the style sheet of a syntect theme for --highlight classes",
+        "THEME",
+    );
+    add_config_flag(&mut opts);
+    opts.optopt(
+        "o",
+        "output",
+        "write to this file instead of standard output",
+        "FILE",
+    );
+    opts.optflag(
+        "w",
+        "watch",
+        "render the files again to --output every time they change",
+    );
+    opts.optflag(
+        "",
+        "stream",
+        "render standard input block by block as it is read, to standard output",
+    );
+    opts.optflag(
+        "",
+        "verify-roundtrip",
+        "instead of rendering, check that the markdown written from the events parses to the same events",
+    );
+    opts.optflag(
+        "W",
+        "warnings",
+        "report references to undefined links and footnotes to standard error",
+    );
+    add_format_flag(&mut opts);
+    opts.optopt(
+        "",
+        "frontmatter",
+        "remove the front matter before parsing, and with json or yaml print it",
+        "json|yaml|strip",
+    );
+    opts.optopt(
+        "",
+        "metadata",
+        "write the front matter to this file instead of standard output",
+        "FILE",
     );
-    opts.optflag("L", "enable-tasklists", "enable GitHub-style task lists");
-    opts.optflag("P", "enable-smart-punctuation", "enable smart punctuation");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(f) => {
             eprintln!("{}\n{}", f, opts.usage(&brief(&args[0])));
-            std::process::exit(1);
+            std::process::exit(EXIT_USAGE);
         }
     };
     if matches.opt_present("help") {
         println!("{}", opts.usage(&brief(&args[0])));
         return Ok(());
     }
-    let mut opts = Options::empty();
-    if matches.opt_present("enable-tables") {
-        opts.insert(Options::ENABLE_TABLES);
+    if matches.opt_present("list-extensions") {
+        list_extensions();
+        return Ok(());
     }
-    if matches.opt_present("enable-footnotes") {
-        opts.insert(Options::ENABLE_FOOTNOTES);
+    #[cfg(feature = "syntect")]
+    {
+        if let Some(theme) = matches.opt_str("highlight-css") {
+            match SyntectHighlighter::css(&theme) {
+                Some(css) => print!("{}", css),
+                None => {
+                    eprintln!(
+                        "unknown theme: {}, expected one of: {}",
+                        theme,
+                        SyntectHighlighter::themes().join(", ")
+                    );
+                    std::process::exit(EXIT_USAGE);
+                }
+            }
+            return Ok(());
+        }
     }
-    if matches.opt_present("enable-strikethrough") {
-        opts.insert(Options::ENABLE_STRIKETHROUGH);
+    let config = Config::load(&matches);
+
+    let front_matter = matches.opt_str("frontmatter");
+    if !matches!(
+        front_matter.as_deref(),
+        None | Some("json") | Some("yaml") | Some("strip")
+    ) {
+        eprintln!("--frontmatter must be json, yaml or strip");
+        std::process::exit(EXIT_USAGE);
     }
-    if matches.opt_present("enable-tasklists") {
-        opts.insert(Options::ENABLE_TASKLISTS);
+
+    let mut render = renderer(&args[0], &matches, &config);
+    render.events = matches.opt_present("events");
+    render.strip_front_matter = front_matter.is_some();
+    let output = matches.opt_str("output");
+    if matches.opt_present("stream") {
+        let supported = STREAM_FORMATS.contains(&render.format.as_str());
+        if !supported || render.events || render.html_options.document.is_some() {
+            eprintln!(
+                "--stream only supports these formats: {}, and no standalone documents",
+                STREAM_FORMATS.join(", ")
+            );
+            std::process::exit(EXIT_USAGE);
+        }
+        return stream(&render);
     }
-    if matches.opt_present("enable-smart-punctuation") {
-        opts.insert(Options::ENABLE_SMART_PUNCTUATION);
+    if matches.opt_present("watch") {
+        match output {
+            Some(ref output) if !matches.free.is_empty() => {
+                return watch(&matches.free, output, &render)
+            }
+            _ => {
+                eprintln!(
+                    "--watch needs files and --output\n{}",
+                    opts.usage(&brief(&args[0]))
+                );
+                std::process::exit(EXIT_USAGE);
+            }
+        }
     }
 
-    let mut input = String::new();
-    io::stdin().lock().read_to_string(&mut input)?;
-    if matches.opt_present("events") {
-        print_events(&input, opts);
-    } else if matches.opt_present("dry-run") {
-        dry_run(&input, opts);
+    let input = if matches.free.is_empty() {
+        let mut input = String::new();
+        io::stdin().lock().read_to_string(&mut input)?;
+        input
+    } else {
+        read_files(&matches.free)?
+    };
+    if matches.opt_present("verify-roundtrip") {
+        return verify_roundtrip(&matches.free, &input, &render);
+    }
+    if let Some(mode @ "json") | Some(mode @ "yaml") = front_matter.as_deref() {
+        let metadata = match front_matter_metadata(&input, mode) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(EXIT_FAILURE);
+            }
+        };
+        match matches.opt_str("metadata") {
+            Some(path) => write_atomically(&path, metadata.as_bytes())?,
+            None => {
+                io::stdout().write_all(metadata.as_bytes())?;
+                // the body is only rendered when it has somewhere to go
+                if output.is_none() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    let mut warned = false;
+    if matches.opt_present("warnings") {
+        // positions are given in each file rather than in their concatenation
+        let mut warnings = Vec::new();
+        if matches.free.is_empty() {
+            warnings = parse_warnings("<stdin>", &input, render.body(&input), render.opts);
+        }
+        for path in &matches.free {
+            let input = read_files(std::slice::from_ref(path))?;
+            warnings.extend(parse_warnings(
+                path,
+                &input,
+                render.body(&input),
+                render.opts,
+            ));
+        }
+        warned = !warnings.is_empty();
+        print_diagnostics(io::stderr(), json_diagnostics(&matches), &warnings)?;
+    }
+    let body = render.body(&input);
+    if matches.opt_present("dry-run") && !render.events && render.format != "events" {
+        dry_run(body, render.opts);
+    } else if let Some(output) = output {
+        let mut buffer = Vec::new();
+        render.render(&mut buffer, &input)?;
+        write_atomically(&output, &buffer)?;
+    } else if render.events || render.format == "events" {
+        let stdio = io::stdout();
+        print_events(
+            io::BufWriter::new(stdio.lock()),
+            body,
+            render.opts,
+            render.format == "json",
+        )?;
     } else {
-        let mut p = Parser::new_ext(&input, opts);
+        let mut p = Parser::new_ext(body, render.opts);
         let stdio = io::stdout();
         let buffer = std::io::BufWriter::with_capacity(1024 * 1024, stdio.lock());
-        html::write_html(buffer, &mut p)?;
+        render.write(buffer, &mut p)?;
         // Since the program will now terminate and the memory will be returned
         // to the operating system anyway, there is no point in tidely cleaning
         // up all the datastructures we have used. We shouldn't do this if we'd
@@ -111,5 +3032,149 @@ pub fn main() -> std::io::Result<()> {
         // leaking data. Skipping cleanup lets us return a bit (~5%) faster.
         mem::forget(p);
     }
+    if warned {
+        std::process::exit(EXIT_WARNINGS);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(format: &str) -> Render {
+        Render {
+            format: format.to_owned(),
+            events: false,
+            opts: Options::empty(),
+            html_options: html::Options::default(),
+            strip_front_matter: false,
+            markdown_options: markdown::Options::default(),
+            latex_options: latex::Options::default(),
+            typst_options: typst::Options::default(),
+            man_options: man::Options::default(),
+            ssml_options: ssml::Options::default(),
+            #[cfg(feature = "syntect")]
+            highlighter: None,
+        }
+    }
+
+    fn chunks(input: &str) -> Vec<String> {
+        let mut chunker = Chunker::default();
+        let mut chunks: Vec<String> = input
+            .split_terminator('\n')
+            .filter_map(|line| chunker.push_line(&format!("{}\n", line)))
+            .collect();
+        chunks.push(chunker.finish());
+        chunks
+    }
+
+    fn stream_html(input: &str) -> String {
+        let mut out = Vec::new();
+        stream_to(&render("html"), input.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    #[test]
+    fn parse_yaml_scalars() {
+        let raw = "title: \"Say \\\"hi\\\" \\u00e9\\n\"\nquote: 'it''s'\nplain: a # comment\ncount: 3\nnone: ~\n";
+        assert_eq!(
+            Ok(vec![
+                ("title".to_owned(), string("Say \"hi\" \u{e9}\n")),
+                ("quote".to_owned(), string("it's")),
+                ("plain".to_owned(), string("a")),
+                ("count".to_owned(), Value::Number("3".to_owned())),
+                ("none".to_owned(), Value::Null),
+            ]),
+            parse_yaml(raw)
+        );
+    }
+
+    #[test]
+    fn parse_yaml_lists() {
+        let raw = "tags: [\"x, y\", z, 'a, b', [c, d]]\nitems:\n  - \"e, f\"\n  - g\n";
+        assert_eq!(
+            Ok(vec![
+                (
+                    "tags".to_owned(),
+                    Value::List(vec![
+                        string("x, y"),
+                        string("z"),
+                        string("a, b"),
+                        Value::List(vec![string("c"), string("d")]),
+                    ])
+                ),
+                (
+                    "items".to_owned(),
+                    Value::List(vec![string("e, f"), string("g")])
+                ),
+            ]),
+            parse_yaml(raw)
+        );
+    }
+
+    #[test]
+    fn parse_toml_values() {
+        let raw = "title = \"Tab\\there \\\"q\\\" \\u00e9\" # comment\npath = 'C:\\dir'\ntags = [\"a, b\", 'c', ]\n\n[extra]\ncount = +1_000\n";
+        assert_eq!(
+            Ok(vec![
+                ("title".to_owned(), string("Tab\there \"q\" \u{e9}")),
+                ("path".to_owned(), string("C:\\dir")),
+                (
+                    "tags".to_owned(),
+                    Value::List(vec![string("a, b"), string("c")])
+                ),
+                (
+                    "extra".to_owned(),
+                    Value::Table(vec![("count".to_owned(), Value::Number("1000".to_owned()))])
+                ),
+            ]),
+            parse_toml(raw)
+        );
+        assert!(parse_toml("a = \"\\q\"\n").is_err());
+        assert!(parse_toml("a = \"\\u12\"\n").is_err());
+    }
+
+    #[test]
+    fn chunker_splits_top_level_blocks() {
+        assert_eq!(
+            vec!["# a\n\n", "b\nc\n\n", "d\n"],
+            chunks("# a\n\nb\nc\n\nd\n")
+        );
+    }
+
+    #[test]
+    fn chunker_keeps_lists_and_indented_lines() {
+        let input = "- a\n\n- b\n\n  c\n\nd\n";
+        assert_eq!(vec!["- a\n\n- b\n\n  c\n\n", "d\n"], chunks(input));
+    }
+
+    #[test]
+    fn chunker_keeps_blank_lines_in_blocks() {
+        let input = "```\na\n\nb\n```\n\n<pre>\na\n\nb\n</pre>\n\nc\n";
+        assert_eq!(
+            vec!["```\na\n\nb\n```\n\n", "<pre>\na\n\nb\n</pre>\n\n", "c\n"],
+            chunks(input)
+        );
+    }
+
+    #[test]
+    fn stream_resolves_earlier_definitions() {
+        assert_eq!(
+            "<p><a href=\"/url\">a</a></p>\n",
+            stream_html("[a]: /url\n\n[a]\n")
+        );
+    }
+
+    #[test]
+    fn stream_keeps_definition_titles_to_their_chunk() {
+        assert_eq!(
+            "<p>&quot;hello&quot;</p>\n<p><a href=\"/url\">a</a></p>\n",
+            stream_html("[a]: /url\n\n\"hello\"\n\n[a]\n")
+        );
+    }
+}
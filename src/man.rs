@@ -0,0 +1,487 @@
+//! Man page writer that takes an iterator of events as input.
+//!
+//! The output uses the man(7) macros: headings become `.SH` and `.SS`
+//! sections, paragraphs `.PP`, list items `.IP` paragraphs, code blocks
+//! `.EX` examples and tables tbl(1) tables, so pages are formatted with
+//! `man -l` or `groff -man -t`. Links are followed by their destination,
+//! and raw HTML and images are dropped, except for the alt text of images.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::strings::CowStr;
+use crate::Event::*;
+use crate::{Alignment, Event, HeadingLevel, LinkType, Tag};
+
+/// Options of the man page writer.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Title of the page, like the name of the command, and its section,
+    /// like `1`, written in the `.TH` header line. When `None`, the page has
+    /// no header line.
+    pub title: Option<(String, String)>,
+}
+
+/// Writes `s` to `w`, escaping the characters that are special in roff
+/// text.
+///
+/// Hyphens are written as `\-`, the minus sign that man pages use for
+/// command line options. Control characters at the start of a line are
+/// left alone.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::man::escape_man;
+///
+/// let mut s = String::new();
+/// escape_man(&mut s, "ls --color C:\\").unwrap();
+/// assert_eq!(s, "ls \\-\\-color C:\\e");
+/// ```
+pub fn escape_man<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        let escaped = match c {
+            '\\' => "\\e",
+            '-' => "\\-",
+            _ => continue,
+        };
+        w.write_str(&s[mark..ix])?;
+        w.write_str(escaped)?;
+        mark = ix + 1;
+    }
+    w.write_str(&s[mark..])
+}
+
+struct ManWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    options: Options,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Numbers of the open lists, `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Number of open list items.
+    items: usize,
+    /// Whether the current item has only its `.IP` tag so far, which starts
+    /// its first paragraph.
+    fresh_item: bool,
+    in_heading: bool,
+    /// Fonts of the open inline tags, to go back to when they end.
+    fonts: Vec<&'static str>,
+    table_cell_index: usize,
+    /// Nesting depth of autolinks, whose text is the destination.
+    autolinks: usize,
+    numbers: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a, I, W> ManWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W, options: &Options) -> Self {
+        ManWriter {
+            iter,
+            writer,
+            options: options.clone(),
+            line_start: true,
+            lists: Vec::new(),
+            items: 0,
+            fresh_item: false,
+            in_heading: false,
+            fonts: Vec::new(),
+            table_cell_index: 0,
+            autolinks: 0,
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        if let Some((title, section)) = self.options.title.take() {
+            self.request(&format!(".TH {} {}", quote(&title), quote(&section)))?;
+        }
+        while let Some(event) = self.iter.next() {
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.autolinks == 0 => self.write_text(&text)?,
+                Text(_) | Html(_) => (),
+                Code(text) => {
+                    self.write("\\f(CR")?;
+                    self.write_text(&text)?;
+                    self.write_font()?;
+                }
+                SoftBreak if self.in_heading => self.write(" ")?,
+                SoftBreak => self.write("\n")?,
+                HardBreak => self.request(".br")?,
+                Rule => {
+                    self.start_paragraph()?;
+                    self.request("\\l'\\n(.lu'")?;
+                }
+                FootnoteReference(name) => {
+                    let number = self.footnote_number(name);
+                    self.write(&format!("[{}]", number))?;
+                }
+                TaskListMarker(true) => self.write("[x] ")?,
+                TaskListMarker(false) => self.write("[ ] ")?,
+            }
+        }
+        self.end_line()
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.start_paragraph(),
+            Tag::Heading(level) => {
+                match level {
+                    HeadingLevel::H1 => self.request(".SH")?,
+                    HeadingLevel::H2 => self.request(".SS")?,
+                    _ => {
+                        self.start_paragraph()?;
+                        self.write("\\fB")?;
+                        self.fonts.push("B");
+                    }
+                }
+                self.in_heading = true;
+                Ok(())
+            }
+            Tag::BlockQuote => {
+                self.fresh_item = false;
+                self.request(".RS")
+            }
+            Tag::CodeBlock(_) => {
+                self.start_paragraph()?;
+                self.request(".EX")
+            }
+            Tag::List(start) => {
+                if self.items > 0 {
+                    self.fresh_item = false;
+                    self.request(".RS")?;
+                }
+                self.lists.push(start);
+                Ok(())
+            }
+            Tag::Item => {
+                let request = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!(".IP \"{}.\" 4", *number - 1)
+                    }
+                    _ => ".IP \\(bu 2".to_owned(),
+                };
+                self.request(&request)?;
+                self.items += 1;
+                self.fresh_item = true;
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                let number = self.footnote_number(name);
+                self.request(&format!(".IP \"[{}]\" 4", number))?;
+                self.items += 1;
+                self.fresh_item = true;
+                Ok(())
+            }
+            Tag::Table(alignments) => {
+                self.start_paragraph()?;
+                self.request(".TS")?;
+                self.request("tab(\t);")?;
+                let format: Vec<&str> = alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None | Alignment::Left => "l",
+                        Alignment::Center => "c",
+                        Alignment::Right => "r",
+                    })
+                    .collect();
+                self.request(&format!("{}.", format.join(" ")))
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.end_line()?;
+                self.table_cell_index = 0;
+                Ok(())
+            }
+            Tag::TableCell => {
+                if self.table_cell_index > 0 {
+                    self.write("\t")?;
+                }
+                self.table_cell_index += 1;
+                Ok(())
+            }
+            Tag::Emphasis => self.push_font("I"),
+            Tag::Strong => self.push_font("B"),
+            Tag::Link(LinkType::Autolink, _, _) | Tag::Link(LinkType::Email, _, _) => {
+                self.autolinks += 1;
+                Ok(())
+            }
+            Tag::Strikethrough | Tag::Link(..) | Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.end_line(),
+            Tag::Heading(level) => {
+                self.in_heading = false;
+                if level > HeadingLevel::H2 {
+                    self.pop_font()?;
+                }
+                self.end_line()
+            }
+            Tag::BlockQuote => self.request(".RE"),
+            Tag::CodeBlock(_) => self.request(".EE"),
+            Tag::List(_) => {
+                self.lists.pop();
+                if self.items > 0 {
+                    self.request(".RE")?;
+                }
+                Ok(())
+            }
+            Tag::Item | Tag::FootnoteDefinition(_) => {
+                self.items -= 1;
+                self.fresh_item = false;
+                self.end_line()
+            }
+            Tag::Table(_) => self.request(".TE"),
+            Tag::TableHead => {
+                self.table_cell_index = 0;
+                self.request("_")
+            }
+            Tag::TableRow => {
+                self.table_cell_index = 0;
+                Ok(())
+            }
+            Tag::TableCell => Ok(()),
+            Tag::Emphasis | Tag::Strong => self.pop_font(),
+            Tag::Strikethrough => Ok(()),
+            Tag::Link(LinkType::Autolink, dest, _) | Tag::Link(LinkType::Email, dest, _) => {
+                self.autolinks -= 1;
+                self.write_text(&dest)
+            }
+            Tag::Link(_, dest, _) => {
+                self.write(" \\(la")?;
+                self.write_text(&dest)?;
+                self.write("\\(ra")
+            }
+            Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    /// Starts a paragraph, keeping the indentation inside list items.
+    fn start_paragraph(&mut self) -> Result<(), W::Error> {
+        if self.fresh_item {
+            self.fresh_item = false;
+            self.end_line()
+        } else if self.items > 0 {
+            self.request(".IP")
+        } else {
+            self.request(".PP")
+        }
+    }
+
+    fn push_font(&mut self, font: &'static str) -> Result<(), W::Error> {
+        self.fonts.push(font);
+        self.write_font()
+    }
+
+    fn pop_font(&mut self) -> Result<(), W::Error> {
+        self.fonts.pop();
+        self.write_font()
+    }
+
+    /// Switches to the font of the innermost open inline tag.
+    fn write_font(&mut self) -> Result<(), W::Error> {
+        let font = self.fonts.last().copied().unwrap_or("R");
+        self.write(&format!("\\f{}", font))
+    }
+
+    /// Writes a request on a line of its own.
+    fn request(&mut self, request: &str) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.writer.write_str(request)?;
+        self.writer.write_str("\n")?;
+        self.line_start = true;
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.write("\n")
+        }
+    }
+
+    /// Writes escaped text, protecting lines that would start with a
+    /// control character.
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                self.write("\n")?;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.line_start && (line.starts_with('.') || line.starts_with('\'')) {
+                self.writer.write_str("\\&")?;
+            }
+            escape_man(&mut self.writer, line)?;
+            self.line_start = false;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, roff: &str) -> Result<(), W::Error> {
+        self.writer.write_str(roff)?;
+        if !roff.is_empty() {
+            self.line_start = roff.ends_with('\n');
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a macro argument.
+fn quote(argument: &str) -> String {
+    let mut escaped = String::new();
+    escape_man(&mut escaped, argument).unwrap();
+    format!("\"{}\"", escaped.replace('"', "\\(dq"))
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate a man page for each
+/// `Event`, and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{man, Parser};
+///
+/// let markdown = "# NAME\n\nfoo - frobnicate *files*\n\n# OPTIONS\n\n* `-v` is verbose\n";
+/// let options = man::Options {
+///     title: Some(("FOO".to_owned(), "1".to_owned())),
+/// };
+/// let mut roff = String::new();
+/// man::push_man_ext(&mut roff, Parser::new(markdown), &options);
+/// assert_eq!(roff, "\
+/// .TH \"FOO\" \"1\"
+/// .SH
+/// NAME
+/// .PP
+/// foo \\- frobnicate \\fIfiles\\fR
+/// .SH
+/// OPTIONS
+/// .IP \\(bu 2
+/// \\f(CR\\-v\\fR is verbose
+/// ");
+/// ```
+pub fn push_man<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    push_man_ext(s, iter, &Options::default());
+}
+
+/// Like [`push_man`](fn.push_man.html), but with the given options.
+pub fn push_man_ext<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    ManWriter::new(iter, s, options).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate a man page for each
+/// `Event`, and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_man<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    write_man_ext(writer, iter, &Options::default())
+}
+
+/// Like [`write_man`](fn.write_man.html), but with the given options.
+pub fn write_man_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    ManWriter::new(iter, WriteWrapper(writer), options).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn blocks() {
+        let input = "\
+Some **bold *and* more** text,
+.dot and a [link](http://x.org/)[^n].
+
+1. one
+
+   second paragraph
+   * nested
+
+> quoted
+
+```
+$ foo \\
+  -x
+```
+
+| a | b |
+|---|--:|
+| `c` | ![alt](e.png) |
+
+[^n]: Note.
+";
+        let expected = "\
+.PP
+Some \\fBbold \\fIand\\fB more\\fR text,
+\\&.dot and a link \\(lahttp://x.org/\\(ra[1].
+.IP \"1.\" 4
+one
+.IP
+second paragraph
+.RS
+.IP \\(bu 2
+nested
+.RE
+.RS
+.PP
+quoted
+.RE
+.PP
+.EX
+$ foo \\e
+  \\-x
+.EE
+.PP
+.TS
+tab(\t);
+l r.
+a\tb
+_
+\\f(CRc\\fR\talt
+.TE
+.IP \"[1]\" 4
+Note.
+";
+        let parser_options = crate::Options::ENABLE_TABLES | crate::Options::ENABLE_FOOTNOTES;
+        let mut s = String::new();
+        push_man(&mut s, Parser::new_ext(input, parser_options));
+        assert_eq!(expected, s);
+    }
+}
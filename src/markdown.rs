@@ -0,0 +1,1021 @@
+//! Markdown writer that takes an iterator of events as input.
+//!
+//! The output is CommonMark, with the GFM syntax for tables, strikethrough,
+//! task lists and footnotes, written so that parsing it again with the same
+//! options gives back the same events, up to how text is split between
+//! `Text` events. Text is escaped wherever it could be read as markup, and
+//! what the events don't keep is normalized: links other than wiki links
+//! are written inline, emphasis uses `*`, and lists use `-` and `1.`
+//! markers. Indented code blocks are fenced when they follow a list, which
+//! they would otherwise continue.
+//!
+//! With [`Options::mdx`](struct.Options.html#structfield.mdx) set, the output
+//! can also be given to MDX, which reads `<` as the start of a JSX element
+//! and `{` as the start of an expression: braces are escaped, raw HTML is
+//! written as text, autolinks are written as inline links, and indented code
+//! blocks, which MDX doesn't support, are fenced.
+
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::lines::{self, Container};
+use crate::Event::*;
+use crate::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag};
+
+/// Options of the markdown writer.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Whether to escape what MDX reads as JSX or JavaScript expressions.
+    pub mdx: bool,
+}
+
+/// Writes `s` to `w`, escaping it so that it reads as the same text at the
+/// start of a paragraph.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::markdown::escape_markdown;
+///
+/// let mut s = String::new();
+/// escape_markdown(&mut s, "1. *a* & [b]").unwrap();
+/// assert_eq!(s, "1\\. \\*a\\* & \\[b\\]");
+/// ```
+pub fn escape_markdown<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut escaped = String::new();
+    escape_text(&mut escaped, s, "", false, false);
+    w.write_str(&escaped)
+}
+
+/// Pushes `text` to `out`, escaped for a line that already holds `line`.
+fn escape_text(out: &mut String, text: &str, line: &str, heading: bool, mdx: bool) {
+    // number of digits the line holds, when it holds nothing else
+    let mut digits = if line.bytes().all(|b| b.is_ascii_digit()) {
+        Some(line.len())
+    } else {
+        None
+    };
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+        let start = digits == Some(0);
+        // line breaks would split the text, and whitespace starting a line
+        // would be stripped or start a code block
+        if c == '\n' || c == '\r' || (start && (c == ' ' || c == '\t')) {
+            out.push_str(&format!("&#{};", c as u32));
+            digits = None;
+            continue;
+        }
+        let escape = match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '|' | '~' => true,
+            // the next event may start a link
+            '!' => next.map_or(true, |next| next == '['),
+            '&' => next.map_or(false, |next| next.is_ascii_alphanumeric() || next == '#'),
+            '#' => start || heading,
+            '-' | '+' | '=' | '>' => start,
+            '.' | ')' => matches!(digits, Some(1..=9)),
+            '{' | '}' => mdx,
+            _ => false,
+        };
+        if escape {
+            out.push('\\');
+        }
+        out.push(c);
+        digits = match digits {
+            Some(len) if c.is_ascii_digit() => Some(len + 1),
+            _ => None,
+        };
+    }
+}
+
+/// Pushes `s` to `out`, escaping backslashes, the given characters and
+/// ampersands that could start an entity.
+fn escape_with(out: &mut String, s: &str, special: &[char]) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let entity = c == '&'
+            && chars
+                .peek()
+                .map_or(false, |&next| next.is_ascii_alphanumeric() || next == '#');
+        if c == '\\' || entity || special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+/// Returns the `(dest "title")` part of an inline link.
+fn link_target(dest: &str, title: &str) -> String {
+    let mut target = String::from("(");
+    if dest.is_empty() || dest.contains(' ') || dest.contains('<') {
+        target.push('<');
+        escape_with(&mut target, dest, &['<', '>']);
+        target.push('>');
+    } else {
+        escape_with(&mut target, dest, &['(', ')']);
+    }
+    if !title.is_empty() {
+        target.push_str(" \"");
+        escape_with(&mut target, title, &['"']);
+        target.push('"');
+    }
+    target.push(')');
+    target
+}
+
+/// Returns the length of the longest run of `c` in `s`.
+fn longest_run(s: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for ch in s.chars() {
+        run = if ch == c { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    longest
+}
+
+/// Returns a code span holding `code`, delimited by a run of backticks that
+/// `code` doesn't contain.
+fn code_span(code: &str, in_table: bool) -> String {
+    let mut ticks = 1;
+    while code.split(|c| c != '`').any(|run| run.len() == ticks) {
+        ticks += 1;
+    }
+    let ticks = "`".repeat(ticks);
+    // one space is stripped from each side when both sides have one
+    let pad = code.starts_with('`')
+        || code.ends_with('`')
+        || (code.starts_with(' ') && code.ends_with(' ') && code.bytes().any(|b| b != b' '));
+    let code = if in_table {
+        escape_pipes(code)
+    } else {
+        code.to_owned()
+    };
+    if pad {
+        format!("{} {} {}", ticks, code, ticks)
+    } else {
+        format!("{}{}{}", ticks, code, ticks)
+    }
+}
+
+/// Escapes the pipes of a code span in a table cell. The backslash of an
+/// escaped pipe stays in the code span when it is parsed, so pipes that
+/// already follow one are left alone.
+fn escape_pipes(code: &str) -> String {
+    let mut escaped = String::with_capacity(code.len());
+    let mut backslash = false;
+    for c in code.chars() {
+        if c == '|' && !backslash {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        backslash = c == '\\';
+    }
+    escaped
+}
+
+/// An open list.
+struct List {
+    /// Number of the next item, `None` for bullet lists.
+    number: Option<u64>,
+    /// The bullet, or the delimiter after the number.
+    marker: char,
+    /// Whether the items hold paragraphs, and are separated by blank lines.
+    loose: bool,
+}
+
+/// Cells of a table being written, the first row being the header.
+struct Table {
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<String>>,
+}
+
+struct MarkdownWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    options: Options,
+    containers: Vec<Container>,
+    lists: Vec<List>,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Text written on the current line after the container prefixes.
+    line: String,
+    /// Whether a blank line goes before the next block.
+    blank_line: bool,
+    /// Marker of the list that just ended, which a list following it must
+    /// not use to stay a separate list.
+    previous_list: Option<char>,
+    /// Inline text of a heading or table cell being collected.
+    capture: Option<String>,
+    heading: Option<HeadingLevel>,
+    table: Option<Table>,
+    /// Kind and text of the code block being collected.
+    code_block: Option<(CodeBlockKind<'a>, String)>,
+    in_html_block: bool,
+    /// Closing delimiters of the open emphasis, strong emphasis and
+    /// strikethrough.
+    delimiters: Vec<&'static str>,
+    /// The delimiter written last, if nothing was written after it, and
+    /// whether it opened.
+    last_delimiter: Option<(char, bool)>,
+    /// Nesting depth of autolinks, whose text is the destination.
+    autolinks: usize,
+    /// Label of the wiki link being written, which is written as is.
+    wikilink: Option<String>,
+    /// Text of adjacent text events, escaped together once they end.
+    text: String,
+    /// Whether the line holds a task list marker, which goes on the line
+    /// of the paragraph following it.
+    after_task_marker: bool,
+}
+
+impl<'a, I, W> MarkdownWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W, options: &Options) -> Self {
+        MarkdownWriter {
+            iter,
+            writer,
+            options: options.clone(),
+            containers: Vec::new(),
+            lists: Vec::new(),
+            line_start: true,
+            line: String::new(),
+            blank_line: false,
+            previous_list: None,
+            capture: None,
+            heading: None,
+            table: None,
+            code_block: None,
+            in_html_block: false,
+            delimiters: Vec::new(),
+            last_delimiter: None,
+            autolinks: 0,
+            wikilink: None,
+            text: String::new(),
+            after_task_marker: false,
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        while let Some(event) = self.iter.next() {
+            if self.in_html_block && !matches!(event, Html(_)) {
+                self.in_html_block = false;
+                self.end_block()?;
+            }
+            if !matches!(event, Start(Tag::Paragraph)) {
+                self.after_task_marker = false;
+            }
+            if let Text(ref text) = event {
+                if let Some(ref mut label) = self.wikilink {
+                    label.push_str(text);
+                    continue;
+                }
+                if self.code_block.is_none() && self.autolinks == 0 {
+                    self.text.push_str(text);
+                    continue;
+                }
+            }
+            let mut last_delimiter = self.last_delimiter.take();
+            if !self.text.is_empty() {
+                let text = std::mem::take(&mut self.text);
+                self.write_text(&text)?;
+                last_delimiter = None;
+            }
+            match event {
+                Start(tag) => self.start_tag(tag, last_delimiter)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) => {
+                    if let Some((_, ref mut code)) = self.code_block {
+                        code.push_str(&text);
+                    }
+                }
+                Code(code) => {
+                    let span = code_span(&code, self.table.is_some());
+                    self.inline(&span)?;
+                }
+                Html(html) => self.write_html(&html)?,
+                SoftBreak => self.line_break(false)?,
+                HardBreak => self.line_break(true)?,
+                Rule => {
+                    self.start_block()?;
+                    self.inline("***")?;
+                    self.end_block()?;
+                }
+                FootnoteReference(label) => self.inline(&format!("[^{}]", label))?,
+                TaskListMarker(checked) => {
+                    self.inline(if checked { "[x] " } else { "[ ] " })?;
+                    self.after_task_marker = true;
+                }
+            }
+        }
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.write_text(&text)?;
+        }
+        self.end_line()
+    }
+
+    fn start_tag(
+        &mut self,
+        tag: Tag<'a>,
+        last_delimiter: Option<(char, bool)>,
+    ) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => {
+                if let Some(container) = self.containers.last().filter(|c| c.item) {
+                    if let Some(list) = self.lists.last_mut().filter(|list| !list.loose) {
+                        list.loose = true;
+                        // the blocks written before in the item were taken
+                        // to be in a tight list
+                        if container.first_written && !self.after_task_marker {
+                            self.blank_line = true;
+                        }
+                    }
+                }
+                self.start_block()
+            }
+            Tag::Heading(level) => {
+                self.start_block()?;
+                self.capture = Some(String::new());
+                self.heading = Some(level);
+                Ok(())
+            }
+            Tag::BlockQuote => {
+                self.start_block()?;
+                self.push_container("> ".to_owned(), "> ".to_owned(), false);
+                Ok(())
+            }
+            Tag::CodeBlock(kind) => {
+                // an indented code block would continue the list before it
+                let kind = match kind {
+                    CodeBlockKind::Indented if self.previous_list.is_some() => {
+                        CodeBlockKind::Fenced("".into())
+                    }
+                    kind => kind,
+                };
+                self.start_block()?;
+                self.code_block = Some((kind, String::new()));
+                Ok(())
+            }
+            Tag::List(start) => {
+                let previous = self.previous_list.take();
+                self.start_block()?;
+                let marker = match (start, previous) {
+                    (None, Some('-')) => '*',
+                    (None, _) => '-',
+                    (Some(_), Some('.')) => ')',
+                    (Some(_), _) => '.',
+                };
+                self.lists.push(List {
+                    number: start,
+                    marker,
+                    loose: false,
+                });
+                Ok(())
+            }
+            Tag::Item => {
+                self.start_block()?;
+                let marker = match self.lists.last_mut() {
+                    Some(List {
+                        number: Some(number),
+                        marker,
+                        ..
+                    }) => {
+                        *number += 1;
+                        format!("{}{} ", *number - 1, marker)
+                    }
+                    Some(list) => format!("{} ", list.marker),
+                    None => "- ".to_owned(),
+                };
+                let indent = " ".repeat(marker.len());
+                self.push_container(marker, indent, true);
+                Ok(())
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.start_block()?;
+                self.push_container(format!("[^{}]: ", label), "    ".to_owned(), false);
+                Ok(())
+            }
+            Tag::Table(alignments) => {
+                self.start_block()?;
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                });
+                Ok(())
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.rows.push(Vec::new());
+                }
+                Ok(())
+            }
+            Tag::TableCell => {
+                self.capture = Some(String::new());
+                Ok(())
+            }
+            // `*` right after another `*` reads as one longer run, which
+            // only opens the same tags when it adds `**` to opening ones
+            Tag::Emphasis if last_delimiter.map_or(false, |(c, _)| c == '*') => {
+                self.open_delimiter("_")
+            }
+            Tag::Emphasis => self.open_delimiter("*"),
+            Tag::Strong if last_delimiter == Some(('*', false)) => self.open_delimiter("__"),
+            Tag::Strong => self.open_delimiter("**"),
+            Tag::Strikethrough => self.open_delimiter("~~"),
+            Tag::Link(LinkType::Autolink, ..) | Tag::Link(LinkType::Email, ..)
+                if !self.options.mdx =>
+            {
+                self.autolinks += 1;
+                Ok(())
+            }
+            Tag::Link(LinkType::WikiLink, ..) => {
+                self.wikilink = Some(String::new());
+                Ok(())
+            }
+            Tag::Link(..) => self.inline("["),
+            Tag::Image(..) => self.inline("!["),
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.end_block(),
+            Tag::Heading(level) => {
+                self.heading = None;
+                let text = self.capture.take().unwrap_or_default();
+                if text.contains('\n') {
+                    // only the levels that have a setext form keep breaks
+                    let underline = if level == HeadingLevel::H1 { "=" } else { "-" };
+                    let len = text.lines().map(|line| line.chars().count()).max();
+                    self.inline(&text)?;
+                    self.newline()?;
+                    self.inline(&underline.repeat(len.unwrap_or(0).max(3)))?;
+                } else {
+                    self.inline(&"#".repeat(level as usize))?;
+                    if !text.is_empty() {
+                        self.inline(" ")?;
+                        self.inline(&text)?;
+                    }
+                }
+                self.end_block()
+            }
+            Tag::BlockQuote | Tag::FootnoteDefinition(_) => {
+                self.pop_container()?;
+                self.end_block()
+            }
+            Tag::CodeBlock(_) => {
+                if let Some((kind, code)) = self.code_block.take() {
+                    self.write_code_block(kind, &code)?;
+                }
+                self.end_block()
+            }
+            Tag::List(_) => {
+                self.end_line()?;
+                self.previous_list = self.lists.pop().map(|list| list.marker);
+                self.end_block()
+            }
+            Tag::Item => self.pop_container(),
+            Tag::Table(_) => {
+                if let Some(table) = self.table.take() {
+                    self.write_table(table)?;
+                }
+                self.end_block()
+            }
+            Tag::TableCell => {
+                let text = self.capture.take().unwrap_or_default();
+                if let Some(row) = self.table.as_mut().and_then(|table| table.rows.last_mut()) {
+                    row.push(text);
+                }
+                Ok(())
+            }
+            Tag::TableHead | Tag::TableRow => Ok(()),
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => {
+                let delimiter = self.delimiters.pop().unwrap_or_default();
+                self.inline(delimiter)?;
+                self.last_delimiter = delimiter.chars().next().map(|c| (c, false));
+                Ok(())
+            }
+            Tag::Link(LinkType::Autolink, dest, _) | Tag::Link(LinkType::Email, dest, _)
+                if !self.options.mdx =>
+            {
+                self.autolinks -= 1;
+                self.inline(&format!("<{}>", dest))
+            }
+            Tag::Link(LinkType::WikiLink, page, _) => {
+                let label = self.wikilink.take().unwrap_or_default();
+                if label == *page {
+                    self.inline(&format!("[[{}]]", page))
+                } else {
+                    self.inline(&format!("[[{}|{}]]", page, label))
+                }
+            }
+            Tag::Link(LinkType::Email, dest, title) => {
+                self.inline("]")?;
+                self.inline(&link_target(&format!("mailto:{}", dest), &title))
+            }
+            Tag::Link(_, dest, title) | Tag::Image(_, dest, title) => {
+                self.inline("]")?;
+                self.inline(&link_target(&dest, &title))
+            }
+        }
+    }
+
+    fn push_container(&mut self, first: String, rest: String, item: bool) {
+        self.containers.push(Container::new(first, rest, item));
+    }
+
+    /// Closes the innermost container, writing its first line prefix alone
+    /// when it is empty.
+    fn pop_container(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        if self.containers.last().map_or(false, |c| !c.first_written) {
+            self.avoid_thematic_break();
+            self.newline()?;
+        }
+        self.containers.pop();
+        Ok(())
+    }
+
+    /// Changes the bullet of an empty item that would end a line of three
+    /// equal bullets, like `- - -`, which is a thematic break. Such an item
+    /// is the first of its list, so the rest of the list follows it.
+    fn avoid_thematic_break(&mut self) {
+        let bullets: Vec<char> = self
+            .containers
+            .iter()
+            .rev()
+            .take_while(|c| c.item && !c.first_written)
+            .map(|c| c.first.chars().next().unwrap_or(' '))
+            .take_while(|c| matches!(c, '-' | '*' | '+'))
+            .take(3)
+            .collect();
+        if bullets.len() < 3 || bullets.iter().any(|&c| c != bullets[0]) {
+            return;
+        }
+        let bullet = if bullets[0] == '-' { '*' } else { '-' };
+        if let Some(container) = self.containers.last_mut() {
+            container.first = format!("{} ", bullet);
+        }
+        if let Some(list) = self.lists.last_mut() {
+            list.marker = bullet;
+        }
+    }
+
+    /// Whether the blocks being written are directly inside an item of a
+    /// tight list, where they aren't separated by blank lines.
+    fn in_tight_item(&self) -> bool {
+        self.containers.last().map_or(false, |c| c.item)
+            && self.lists.last().map_or(false, |list| !list.loose)
+    }
+
+    fn open_delimiter(&mut self, delimiter: &'static str) -> Result<(), W::Error> {
+        self.inline(delimiter)?;
+        self.delimiters.push(delimiter);
+        self.last_delimiter = delimiter.chars().next().map(|c| (c, true));
+        Ok(())
+    }
+
+    /// Ends the current line, and writes the blank line separating the
+    /// block that starts from the previous one.
+    fn start_block(&mut self) -> Result<(), W::Error> {
+        self.previous_list = None;
+        if self.after_task_marker {
+            self.after_task_marker = false;
+            return Ok(());
+        }
+        self.end_line()?;
+        if self.blank_line {
+            self.blank_line = false;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.blank_line = !self.in_tight_item();
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.newline()
+        }
+    }
+
+    /// Writes a line break, preceded by the prefix of the containers when
+    /// the line is empty.
+    fn newline(&mut self) -> Result<(), W::Error> {
+        lines::newline(&mut self.writer, &mut self.containers, self.line_start)?;
+        self.line_start = true;
+        self.line.clear();
+        Ok(())
+    }
+
+    fn line_break(&mut self, hard: bool) -> Result<(), W::Error> {
+        let marker = if hard { "\\\n" } else { "\n" };
+        match self.capture {
+            // ATX headings and table cells can't hold breaks
+            Some(ref mut capture)
+                if self.heading.map_or(true, |level| level > HeadingLevel::H2) =>
+            {
+                capture.push(' ');
+                Ok(())
+            }
+            Some(ref mut capture) => {
+                capture.push_str(marker);
+                Ok(())
+            }
+            None => self.inline(marker),
+        }
+    }
+
+    /// Writes inline text, which can span several lines in code blocks.
+    fn inline(&mut self, text: &str) -> Result<(), W::Error> {
+        if let Some(ref mut capture) = self.capture {
+            capture.push_str(text);
+            return Ok(());
+        }
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                self.newline()?;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.line_start {
+                let prefix = lines::prefix(&mut self.containers);
+                self.writer.write_str(&prefix)?;
+                self.line_start = false;
+            }
+            self.writer.write_str(line)?;
+            self.line.push_str(line);
+        }
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        let line = match self.capture {
+            Some(ref capture) => &capture[capture.rfind('\n').map_or(0, |ix| ix + 1)..],
+            None => &self.line,
+        };
+        let mut escaped = String::new();
+        escape_text(
+            &mut escaped,
+            text,
+            line,
+            self.heading.is_some(),
+            self.options.mdx,
+        );
+        self.inline(&escaped)
+    }
+
+    /// Writes raw HTML, as text in MDX mode. Block HTML comes one line per
+    /// event, inline HTML without a trailing line break.
+    fn write_html(&mut self, html: &str) -> Result<(), W::Error> {
+        let block = self.capture.is_none() && html.ends_with('\n');
+        if block && !self.in_html_block {
+            self.start_block()?;
+            self.in_html_block = true;
+        }
+        if !self.options.mdx {
+            return self.inline(html);
+        }
+        if !block {
+            return self.write_text(html);
+        }
+        for line in html.lines() {
+            self.write_text(line.trim_start())?;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn write_code_block(&mut self, kind: CodeBlockKind<'a>, code: &str) -> Result<(), W::Error> {
+        let info = match kind {
+            CodeBlockKind::Indented if !self.options.mdx => {
+                self.push_container("    ".to_owned(), "    ".to_owned(), false);
+                self.inline(code)?;
+                self.end_line()?;
+                self.containers.pop();
+                return Ok(());
+            }
+            CodeBlockKind::Indented => "".into(),
+            CodeBlockKind::Fenced(info) => info,
+        };
+        // backticks can't be used when the info string has some
+        let fence_char = if info.contains('`') { '~' } else { '`' };
+        let fence = fence_char
+            .to_string()
+            .repeat(longest_run(code, fence_char).max(2) + 1);
+        let mut open = fence.clone();
+        escape_with(&mut open, &info, &[]);
+        self.inline(&open)?;
+        self.newline()?;
+        self.inline(code)?;
+        self.end_line()?;
+        self.inline(&fence)
+    }
+
+    /// Writes a table, with a delimiter row holding the alignments under the
+    /// header.
+    fn write_table(&mut self, table: Table) -> Result<(), W::Error> {
+        for (ix, row) in table.rows.iter().enumerate() {
+            let mut line = String::from("|");
+            for cell in row {
+                line.push(' ');
+                line.push_str(cell);
+                line.push_str(" |");
+            }
+            self.inline(&line)?;
+            self.newline()?;
+            if ix == 0 {
+                let mut delimiters = String::from("|");
+                for alignment in &table.alignments {
+                    delimiters.push_str(match alignment {
+                        Alignment::None => " --- |",
+                        Alignment::Left => " :-- |",
+                        Alignment::Center => " :-: |",
+                        Alignment::Right => " --: |",
+                    });
+                }
+                self.inline(&delimiters)?;
+                self.newline()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate markdown for each
+/// `Event`, and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{markdown, Parser};
+///
+/// let input = "Title\n===\n\n+ One *big*\n  [link][l]\n+ 2\\. two\n\n[l]: <https://example.com/a b>\n";
+/// let mut markdown_buf = String::new();
+/// markdown::push_markdown(&mut markdown_buf, Parser::new(input));
+/// assert_eq!(markdown_buf, "\
+/// ## Title
+///
+/// - One *big*
+///   [link](<https://example.com/a b>)
+/// - 2\\. two
+/// ");
+/// ```
+pub fn push_markdown<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    push_markdown_ext(s, iter, &Options::default());
+}
+
+/// Like [`push_markdown`](fn.push_markdown.html), but with the given options.
+pub fn push_markdown_ext<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    MarkdownWriter::new(iter, s, options).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate markdown for each
+/// `Event`, and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_markdown<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    write_markdown_ext(writer, iter, &Options::default())
+}
+
+/// Like [`write_markdown`](fn.write_markdown.html), but with the given options.
+pub fn write_markdown_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    MarkdownWriter::new(iter, WriteWrapper(writer), options).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    fn parser_options() -> crate::Options {
+        crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_STRIKETHROUGH
+            | crate::Options::ENABLE_TASKLISTS
+    }
+
+    /// Parses `input`, merging adjacent text events and dropping empty ones.
+    fn events(input: &str) -> Vec<Event<'_>> {
+        let mut events: Vec<Event<'_>> = Vec::new();
+        for event in Parser::new_ext(input, parser_options()) {
+            if let Text(ref text) = event {
+                if text.is_empty() {
+                    continue;
+                }
+            }
+            if let (Some(Text(last)), Text(text)) = (events.last_mut(), &event) {
+                *last = format!("{}{}", last, text).into();
+                continue;
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    /// Like `events`, with links and images marked inline and code blocks
+    /// fenced, as they may be written.
+    fn written_events(input: &str) -> Vec<Event<'_>> {
+        events(input)
+            .into_iter()
+            .map(|event| match event {
+                Start(Tag::CodeBlock(CodeBlockKind::Indented)) => {
+                    Start(Tag::CodeBlock(CodeBlockKind::Fenced("".into())))
+                }
+                End(Tag::CodeBlock(CodeBlockKind::Indented)) => {
+                    End(Tag::CodeBlock(CodeBlockKind::Fenced("".into())))
+                }
+                Start(Tag::Link(link_type, dest, title)) => {
+                    Start(Tag::Link(inline_link_type(link_type), dest, title))
+                }
+                End(Tag::Link(link_type, dest, title)) => {
+                    End(Tag::Link(inline_link_type(link_type), dest, title))
+                }
+                Start(Tag::Image(link_type, dest, title)) => {
+                    Start(Tag::Image(inline_link_type(link_type), dest, title))
+                }
+                End(Tag::Image(link_type, dest, title)) => {
+                    End(Tag::Image(inline_link_type(link_type), dest, title))
+                }
+                event => event,
+            })
+            .collect()
+    }
+
+    fn inline_link_type(link_type: LinkType) -> LinkType {
+        match link_type {
+            LinkType::Autolink | LinkType::Email | LinkType::WikiLink => link_type,
+            _ => LinkType::Inline,
+        }
+    }
+
+    fn render(input: &str, mdx: bool) -> String {
+        let mut s = String::new();
+        push_markdown_ext(
+            &mut s,
+            Parser::new_ext(input, parser_options()),
+            &Options { mdx },
+        );
+        s
+    }
+
+    #[test]
+    fn round_trip() {
+        let input = r#"# Title with `code` and \#
+
+Setext *heading*
+and **a break** _here_
+---
+
+Text with \*stars\*, \_lines\_, &amp; entities, <b>html</b> and a [link](/url "the title").
+1\. not a list, \+ not either, ![image](a\(b\).png)!
+Hard\
+break and `` a`b `` and ***both*** and ~~strike~~.
+
+> quote
+>
+> > nested
+> - list in quote
+
+1. one
+2. two
+
+   still two
+3) three
+
+- a
+- b
+* c
+
+- [x] done
+- [ ] to do
+
+Paragraph.
+
+    indented
+    code
+
+```rust
+fenced
+```
+
+````
+```
+````
+
+<div>
+block html
+</div>
+
+| a | b | `c` |
+|:--|:-:|--:|
+| 1 | *2* | [3](x) |
+
+Note[^1] and <https://example.com>.
+
+[^1]: The note.
+
+***
+"#;
+        let output = render(input, false);
+        assert_eq!(events(input), events(&output), "{}", output);
+        assert_eq!(output, render(&output, false));
+    }
+
+    #[test]
+    fn mdx() {
+        let input =
+            "Props {x} and <Tag> and <https://example.com> <a@b.c>.\n\n<div>\n{y}\n</div>\n\n    code\n";
+        let expected = "\
+Props \\{x\\} and \\<Tag> and [https://example.com](https://example.com) [a@b.c](mailto:a@b.c).
+
+\\<div>
+\\{y\\}
+\\</div>
+
+```
+code
+```
+";
+        assert_eq!(expected, render(input, true));
+    }
+
+    #[test]
+    fn empty_nested_items() {
+        for &input in &["- - *\n", "1. - - *\n  - b\n", "> * * -\n", "- * - *\n"] {
+            let output = render(input, false);
+            assert_eq!(events(input), events(&output), "{}", output);
+        }
+        assert_eq!("- - *\n", render("- - *\n", false));
+    }
+
+    #[test]
+    fn wikilinks() {
+        let input = "[[Page]] and [[Other page | the *label*]] and [[a|b|c]]\n";
+        let expected = "[[Page]] and [[Other page|the *label*]] and [[a|b|c]]\n";
+        let options = parser_options() | crate::Options::ENABLE_WIKILINKS;
+        let mut output = String::new();
+        push_markdown(&mut output, Parser::new_ext(input, options));
+        assert_eq!(expected, output);
+        assert_eq!(
+            Parser::new_ext(input, options).collect::<Vec<_>>(),
+            Parser::new_ext(&output, options).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn round_trip_spec() {
+        let fence = "````````````````````````````````";
+        let specs = [
+            include_str!("../third_party/CommonMark/spec.txt"),
+            include_str!("../third_party/GitHub/spec.txt"),
+        ];
+        let mut failures = Vec::new();
+        for spec in &specs {
+            let mut lines = spec.lines();
+            while let Some(line) = lines.next() {
+                if !line.starts_with(fence) || !line[fence.len()..].starts_with(" example") {
+                    continue;
+                }
+                let mut input = String::new();
+                for line in lines.by_ref().take_while(|&line| line != ".") {
+                    input.push_str(&line.replace('\u{2192}', "\t"));
+                    input.push('\n');
+                }
+                let output = render(&input, false);
+                if written_events(&input) != written_events(&output) {
+                    failures.push(format!("{:?} gave {:?}", input, output));
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -0,0 +1,598 @@
+//! Org-mode writer that takes an iterator of events as input.
+//!
+//! Headings become stars, code blocks `#+BEGIN_SRC` blocks, block quotes
+//! `#+BEGIN_QUOTE` blocks, and links and images `[[link][description]]`
+//! links. Raw HTML is kept in `#+BEGIN_EXPORT html` blocks and `@@html:@@`
+//! snippets, so that HTML export gives back the same document.
+//!
+//! Text that Org would read as markup is escaped with entities like
+//! `\ast{}`, and with a zero width space before markers at the start of a
+//! line, which is how the Org manual escapes them.
+
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::Event::*;
+use crate::{Alignment, CodeBlockKind, Event, LinkType, Tag};
+
+/// Writes `s` to `w`, escaping the characters that are Org markup anywhere
+/// in a line: the emphasis markers other than `_`, which is left alone in
+/// words like `snake_case`, and table bars.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::org::escape_org;
+///
+/// let mut s = String::new();
+/// escape_org(&mut s, "a *b* | c").unwrap();
+/// assert_eq!(s, "a \\ast{}b\\ast{} \\vert{} c");
+/// ```
+pub fn escape_org<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        let escaped = match c {
+            '*' => "\\ast{}",
+            '/' => "\\slash{}",
+            '=' => "\\equal{}",
+            '+' => "\\plus{}",
+            '~' => "\\tilde{}",
+            '|' => "\\vert{}",
+            _ => continue,
+        };
+        w.write_str(&s[mark..ix])?;
+        w.write_str(escaped)?;
+        mark = ix + 1;
+    }
+    w.write_str(&s[mark..])
+}
+
+/// Writes the destination of a link, escaping brackets.
+fn escape_link<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        if let '[' | ']' | '\\' = c {
+            w.write_str(&s[mark..ix])?;
+            w.write_str("\\")?;
+            mark = ix;
+        }
+    }
+    w.write_str(&s[mark..])
+}
+
+/// Returns whether Org would read `line` as a block, list or table marker
+/// at the start of a line.
+fn starts_with_marker(line: &str) -> bool {
+    match line.bytes().next() {
+        Some(b'#') | Some(b'-') | Some(b':') => true,
+        Some(b'0'..=b'9') => {
+            let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+            line[digits..].starts_with('.') || line[digits..].starts_with(')')
+        }
+        _ => false,
+    }
+}
+
+/// Label of a footnote, made of the characters Org allows in labels.
+fn footnote_label(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// An open list.
+struct List {
+    /// Number of the next item, `None` for bullet lists.
+    number: Option<u64>,
+    /// Whether the next item is the first one, which holds the start number.
+    first: bool,
+    /// Whether the items hold paragraphs, and are separated by blank lines.
+    loose: bool,
+}
+
+struct OrgWriter<I, W> {
+    iter: I,
+    writer: W,
+    /// Indentation of the lines inside the current list items.
+    indent: String,
+    lists: Vec<List>,
+    /// Lengths of the markers of the open items.
+    markers: Vec<usize>,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Whether a blank line goes before the next block.
+    blank_line: bool,
+    /// Whether an item or footnote marker was just written, which the first
+    /// block follows on the same line.
+    after_marker: bool,
+    in_heading: bool,
+    in_code_block: bool,
+    /// Whether raw HTML blocks are being written in an export block.
+    in_html_block: bool,
+    /// Whether inline content is being written, where HTML is a snippet
+    /// even at the start of a line.
+    in_inline: bool,
+    table_alignments: Vec<Alignment>,
+    /// Nesting depth of autolinks and images, whose text is dropped.
+    skip: usize,
+}
+
+impl<'a, I, W> OrgWriter<I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W) -> Self {
+        OrgWriter {
+            iter,
+            writer,
+            indent: String::new(),
+            lists: Vec::new(),
+            markers: Vec::new(),
+            line_start: true,
+            blank_line: false,
+            after_marker: false,
+            in_heading: false,
+            in_code_block: false,
+            in_html_block: false,
+            in_inline: false,
+            table_alignments: Vec::new(),
+            skip: 0,
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        while let Some(event) = self.iter.next() {
+            if self.in_html_block && !matches!(event, Html(_)) {
+                self.in_html_block = false;
+                self.end_line()?;
+                self.put("#+END_EXPORT")?;
+                self.end_block()?;
+            }
+            if self.skip > 0 {
+                match event {
+                    Start(_) => self.skip += 1,
+                    End(_) => self.skip -= 1,
+                    _ => (),
+                }
+                continue;
+            }
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => self.write_code(&text)?,
+                Text(text) => self.write_text(&text)?,
+                Code(text) => {
+                    let marker = if text.contains('~') { "=" } else { "~" };
+                    self.put(marker)?;
+                    self.put(&text)?;
+                    self.put(marker)?;
+                }
+                Html(html) if self.in_inline || !self.line_start => {
+                    self.put("@@html:")?;
+                    self.put(&html)?;
+                    self.put("@@")?;
+                }
+                Html(html) => {
+                    if !self.in_html_block {
+                        self.start_block()?;
+                        self.put("#+BEGIN_EXPORT html\n")?;
+                        self.in_html_block = true;
+                    }
+                    self.put(&html)?;
+                }
+                SoftBreak if self.in_heading => self.put(" ")?,
+                SoftBreak => self.put("\n")?,
+                HardBreak => self.put("\\\\\n")?,
+                Rule => {
+                    self.start_block()?;
+                    self.put("-----")?;
+                    self.end_block()?;
+                }
+                FootnoteReference(name) => {
+                    self.put(&format!("[fn:{}]", footnote_label(&name)))?;
+                }
+                TaskListMarker(true) => self.put("[X] ")?,
+                TaskListMarker(false) => self.put("[ ] ")?,
+            }
+        }
+        if self.in_html_block {
+            self.end_line()?;
+            self.put("#+END_EXPORT")?;
+        }
+        self.end_line()
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => {
+                if let Some(list) = self.lists.last_mut() {
+                    list.loose = true;
+                }
+                self.in_inline = true;
+                self.start_block()
+            }
+            Tag::Heading(level) => {
+                self.start_block()?;
+                self.in_heading = true;
+                self.in_inline = true;
+                self.put(&"*".repeat(level as usize))?;
+                self.put(" ")
+            }
+            Tag::BlockQuote => {
+                self.start_block()?;
+                self.put("#+BEGIN_QUOTE\n")
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block()?;
+                self.in_code_block = true;
+                match kind {
+                    CodeBlockKind::Fenced(ref info) if !info.is_empty() => {
+                        let lang = info.split(' ').next().unwrap();
+                        self.put(&format!("#+BEGIN_SRC {}\n", lang))
+                    }
+                    _ => self.put("#+BEGIN_EXAMPLE\n"),
+                }
+            }
+            Tag::List(start) => {
+                self.start_block()?;
+                self.lists.push(List {
+                    number: start,
+                    first: true,
+                    loose: false,
+                });
+                Ok(())
+            }
+            Tag::Item => {
+                self.start_block()?;
+                let (marker, indent) = match self.lists.last_mut() {
+                    Some(List {
+                        number: Some(number),
+                        first,
+                        ..
+                    }) => {
+                        let marker = format!("{}. ", number);
+                        let len = marker.len();
+                        let marker = if *first && *number != 1 {
+                            format!("{}[@{}] ", marker, number)
+                        } else {
+                            marker
+                        };
+                        *number += 1;
+                        *first = false;
+                        (marker, len)
+                    }
+                    _ => ("- ".to_owned(), 2),
+                };
+                self.put(&marker)?;
+                self.after_marker = true;
+                self.indent.push_str(&" ".repeat(indent));
+                self.markers.push(indent);
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.start_block()?;
+                self.put(&format!("[fn:{}] ", footnote_label(&name)))?;
+                self.after_marker = true;
+                Ok(())
+            }
+            Tag::Table(alignments) => {
+                self.start_block()?;
+                self.table_alignments = alignments;
+                Ok(())
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.in_inline = true;
+                self.put("|")
+            }
+            Tag::TableCell => self.put(" "),
+            Tag::Emphasis => self.put("/"),
+            Tag::Strong => self.put("*"),
+            Tag::Strikethrough => self.put("+"),
+            Tag::Link(LinkType::Autolink, dest, _) => {
+                self.put("[[")?;
+                self.write_link(&dest)?;
+                self.put("]]")?;
+                self.skip = 1;
+                Ok(())
+            }
+            Tag::Link(link_type, dest, _) => {
+                self.put("[[")?;
+                if link_type == LinkType::Email {
+                    self.put("mailto:")?;
+                }
+                self.write_link(&dest)?;
+                self.put("][")
+            }
+            Tag::Image(_, dest, _) => {
+                self.put("[[")?;
+                self.write_link(&dest)?;
+                self.put("]]")?;
+                self.skip = 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => {
+                self.in_inline = false;
+                self.end_block()
+            }
+            Tag::Heading(_) => {
+                self.in_heading = false;
+                self.in_inline = false;
+                self.end_block()
+            }
+            Tag::BlockQuote => {
+                self.end_line()?;
+                self.put("#+END_QUOTE")?;
+                self.end_block()
+            }
+            Tag::CodeBlock(kind) => {
+                self.in_code_block = false;
+                self.end_line()?;
+                match kind {
+                    CodeBlockKind::Fenced(ref info) if !info.is_empty() => self.put("#+END_SRC")?,
+                    _ => self.put("#+END_EXAMPLE")?,
+                }
+                self.end_block()
+            }
+            Tag::List(_) => {
+                self.end_line()?;
+                self.lists.pop();
+                // a nested list is part of its item
+                self.blank_line = self.lists.last().map_or(true, |list| list.loose);
+                Ok(())
+            }
+            Tag::Item => {
+                self.end_line()?;
+                let len = self.markers.pop().unwrap_or(0);
+                self.indent.truncate(self.indent.len() - len);
+                Ok(())
+            }
+            Tag::FootnoteDefinition(_) | Tag::Table(_) => self.end_block(),
+            Tag::TableHead => {
+                self.in_inline = false;
+                self.end_line()?;
+                let columns = self.table_alignments.len();
+                self.put(&format!("|{}|\n", vec!["---"; columns].join("+")))?;
+                if self.table_alignments.iter().any(|&a| a != Alignment::None) {
+                    let cookies: Vec<&str> = self
+                        .table_alignments
+                        .iter()
+                        .map(|alignment| match alignment {
+                            Alignment::None => " ",
+                            Alignment::Left => " <l> ",
+                            Alignment::Center => " <c> ",
+                            Alignment::Right => " <r> ",
+                        })
+                        .collect();
+                    self.put(&format!("|{}|\n", cookies.join("|")))?;
+                }
+                Ok(())
+            }
+            Tag::TableRow => {
+                self.in_inline = false;
+                self.end_line()
+            }
+            Tag::TableCell => self.put(" |"),
+            Tag::Emphasis => self.put("/"),
+            Tag::Strong => self.put("*"),
+            Tag::Strikethrough => self.put("+"),
+            Tag::Link(..) => self.put("]]"),
+            // consumed with the skipped contents of autolinks and images
+            Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn write_link(&mut self, dest: &str) -> Result<(), W::Error> {
+        escape_link(&mut self.writer, dest)?;
+        self.line_start = false;
+        Ok(())
+    }
+
+    /// Ends the current line, and writes the blank line separating the
+    /// block that starts from the previous one.
+    fn start_block(&mut self) -> Result<(), W::Error> {
+        if self.after_marker {
+            self.after_marker = false;
+            return Ok(());
+        }
+        self.end_line()?;
+        if self.blank_line {
+            self.blank_line = false;
+            self.put("\n")?;
+        }
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.blank_line = true;
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.put("\n")
+        }
+    }
+
+    /// Writes escaped text.
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        let mut markup = String::new();
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                markup.push('\n');
+            }
+            if (ix > 0 || self.line_start) && starts_with_marker(line) {
+                markup.push('\u{200b}');
+            }
+            escape_org(&mut markup, line).unwrap();
+        }
+        self.put(&markup)
+    }
+
+    /// Writes the text of a code block, escaping lines that Org would read
+    /// as headings or keywords with a comma.
+    fn write_code(&mut self, text: &str) -> Result<(), W::Error> {
+        let mut markup = String::new();
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                markup.push('\n');
+            }
+            let at_start = ix > 0 || self.line_start;
+            if at_start && (line.starts_with('*') || line.starts_with("#+")) {
+                markup.push(',');
+            }
+            markup.push_str(line);
+        }
+        self.put(&markup)
+    }
+
+    /// Writes markup, indenting the lines inside list items.
+    fn put(&mut self, markup: &str) -> Result<(), W::Error> {
+        self.after_marker = false;
+        for (ix, line) in markup.split('\n').enumerate() {
+            if ix > 0 {
+                self.writer.write_str("\n")?;
+                self.line_start = true;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.line_start {
+                self.writer.write_str(&self.indent)?;
+                self.line_start = false;
+            }
+            self.writer.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate Org markup for each
+/// `Event`, and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{org, Parser};
+///
+/// let markdown = "# Intro\n\nSome *emphasis* and a [link](https://example.com/).\n\n```rust\nfn main() {}\n```\n";
+/// let mut org_buf = String::new();
+/// org::push_org(&mut org_buf, Parser::new(markdown));
+/// assert_eq!(org_buf, "\
+/// * Intro
+///
+/// Some /emphasis/ and a [[https://example.com/][link]].
+///
+/// #+BEGIN_SRC rust
+/// fn main() {}
+/// #+END_SRC
+/// ");
+/// ```
+pub fn push_org<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    OrgWriter::new(iter, s).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate Org markup for each
+/// `Event`, and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_org<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    OrgWriter::new(iter, WriteWrapper(writer)).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn blocks() {
+        let input = "\
+3. three <b>bold</b>
+4. [x] four
+   * nested
+
+> quote with **2 \\* 3**[^a b]
+> \\- not a list
+
+| a | b |
+|---|--:|
+| `c~` | ![alt](e.png) <http://x.org/> |
+
+<div>
+raw
+</div>
+
+    * not a heading
+
+[^a b]: Note.
+";
+        let expected = "\
+3. [@3] three @@html:<b>@@bold@@html:</b>@@
+4. [X] four
+   - nested
+
+#+BEGIN_QUOTE
+quote with *2 \\ast{} 3*[fn:a_b]
+\u{200b}- not a list
+#+END_QUOTE
+
+| a | b |
+|---+---|
+| | <r> |
+| =c~= | [[e.png]] [[http://x.org/]] |
+
+#+BEGIN_EXPORT html
+<div>
+raw
+</div>
+#+END_EXPORT
+
+#+BEGIN_EXAMPLE
+,* not a heading
+#+END_EXAMPLE
+
+[fn:a_b] Note.
+";
+        let parser_options = crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_TASKLISTS;
+        let mut s = String::new();
+        push_org(&mut s, Parser::new_ext(input, parser_options));
+        assert_eq!(expected, s);
+    }
+
+    #[test]
+    fn emphasis_markers() {
+        let input = "See /path/, =a=, +b+ and ~c~ in snake_case.\n";
+        let expected =
+            "See \\slash{}path\\slash{}, \\equal{}a\\equal{}, \\plus{}b\\plus{} and \\tilde{}c\\tilde{} in snake_case.\n";
+        let mut s = String::new();
+        push_org(&mut s, Parser::new(input));
+        assert_eq!(expected, s);
+    }
+}
@@ -0,0 +1,156 @@
+//! Parsing of huge documents across threads, with the `rayon` feature.
+//!
+//! After a first pass over the whole document, the source is cut at the
+//! boundaries between top-level blocks into parts of similar length. The
+//! parts are then parsed on the rayon thread pool, each resolving reference
+//! links with the definitions of the whole document, and their events are
+//! joined back in document order. The events and their ranges are those a
+//! [`Parser`](../struct.Parser.html) gives for the whole document.
+//!
+//! This only pays off for documents of many megabytes. Shorter documents,
+//! and any document when the pool has a single thread, are parsed on the
+//! calling thread.
+//!
+//! # Examples
+//!
+//! ```
+//! use pulldown_cmark::{html, parallel, Options};
+//!
+//! let markdown_input = "# Title\n\nSee [the docs].\n\n[the docs]: https://docs.rs\n";
+//! let events = parallel::parse(markdown_input, Options::empty());
+//! let mut html_buf = String::new();
+//! html::push_html(&mut html_buf, events.into_iter());
+//! assert_eq!(
+//!     html_buf,
+//!     "<h1>Title</h1>\n<p>See <a href=\"https://docs.rs\">the docs</a>.</p>\n"
+//! );
+//! ```
+
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::buffers::Buffers;
+use crate::firstpass::run_first_pass;
+use crate::parse::RefDefs;
+use crate::{Event, Options, Parser};
+
+/// Parts are made at least this long, so that threads are not kept busy
+/// with parts too small to be worth sending to them.
+const MIN_PART_LEN: usize = 1 << 20;
+
+/// Parses `text` across the threads of the rayon pool, and returns its
+/// events in document order.
+pub fn parse(text: &str, options: Options) -> Vec<Event<'_>> {
+    parse_with_offsets(text, options)
+        .into_iter()
+        .map(|(event, _range)| event)
+        .collect()
+}
+
+/// Parses `text` across the threads of the rayon pool, and returns its
+/// events with their source ranges, as an
+/// [`OffsetIter`](../struct.OffsetIter.html) would give them.
+pub fn parse_with_offsets(text: &str, options: Options) -> Vec<(Event<'_>, Range<usize>)> {
+    let threads = rayon::current_num_threads();
+    if threads == 1 {
+        return Parser::new_ext(text, options).into_offset_iter().collect();
+    }
+    let part_len = text.len() / (4 * threads);
+    parse_in_parts(text, options, part_len.max(MIN_PART_LEN))
+}
+
+fn parse_in_parts(
+    text: &str,
+    options: Options,
+    min_part_len: usize,
+) -> Vec<(Event<'_>, Range<usize>)> {
+    let (starts, refdefs) = split(text, options, min_part_len);
+    if starts.len() == 1 {
+        return Parser::new_ext(text, options).into_offset_iter().collect();
+    }
+
+    let mut parts: Vec<Vec<(Event<'_>, Range<usize>)>> = (0..starts.len())
+        .into_par_iter()
+        .map(|i| {
+            let start = starts[i];
+            let end = starts.get(i + 1).copied().unwrap_or(text.len());
+            let mut parser = Parser::new_ext(&text[start..end], options);
+            parser.set_reference_definitions(refdefs.clone());
+            parser
+                .into_offset_iter()
+                .map(|(event, range)| (event, range.start + start..range.end + start))
+                .collect()
+        })
+        .collect();
+
+    let mut events = Vec::with_capacity(parts.iter().map(Vec::len).sum());
+    for part in &mut parts {
+        events.append(part);
+    }
+    events
+}
+
+/// Runs the first pass over `text`, and returns the start offsets of its
+/// parts along with the reference definitions of the whole document. Parts
+/// start at the beginning of the line of a top-level block, so that blocks
+/// keep their indentation.
+fn split(text: &str, options: Options, min_part_len: usize) -> (Vec<usize>, RefDefs<'_>) {
+    let mut buffers = Buffers::take();
+    let (mut tree, allocs) = run_first_pass(text, options, &mut buffers);
+    tree.reset();
+
+    let mut starts = vec![0];
+    let mut cur = tree.cur();
+    while let Some(cur_ix) = cur {
+        let block_start = tree[cur_ix].item.start;
+        let line_start = text[..block_start].rfind('\n').map_or(0, |ix| ix + 1);
+        if line_start >= starts[starts.len() - 1] + min_part_len {
+            starts.push(line_start);
+        }
+        cur = tree[cur_ix].next;
+    }
+
+    let (nodes, spine) = tree.into_storage();
+    buffers.nodes = nodes;
+    buffers.spine = spine;
+    buffers.give_back();
+    (starts, allocs.refdefs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_same_as_sequential(text: &str, options: Options) {
+        let expected: Vec<_> = Parser::new_ext(text, options).into_offset_iter().collect();
+        assert_eq!(parse_in_parts(text, options, 1), expected);
+    }
+
+    #[test]
+    fn spec_in_parts() {
+        assert_same_as_sequential(
+            include_str!("../third_party/CommonMark/spec.txt"),
+            Options::empty(),
+        );
+        assert_same_as_sequential(
+            include_str!("../third_party/GitHub/spec.txt"),
+            Options::all(),
+        );
+    }
+
+    #[test]
+    fn references_across_parts() {
+        let text = "[a] and [b][]\n\n  ```\n  code\n  ```\n\n[a]: /a\n\n- [b]\n\n[b]: /b 'B'\n";
+        assert_same_as_sequential(text, Options::empty());
+        let (starts, _) = split(text, Options::empty(), 1);
+        assert_eq!(starts.len(), 3);
+    }
+
+    #[test]
+    fn short_text_in_one_part() {
+        let (starts, refdefs) = split("# a\n\n[b]: /c\n\nd\n", Options::empty(), MIN_PART_LEN);
+        assert_eq!(starts, vec![0]);
+        assert!(refdefs.get("b").is_some());
+    }
+}
@@ -27,6 +27,7 @@ use std::ops::{Index, Range};
 
 use unicase::UniCase;
 
+use crate::buffers::Buffers;
 use crate::firstpass::run_first_pass;
 use crate::linklabel::{scan_link_label_rest, LinkLabel, ReferenceLabel};
 use crate::scanners::*;
@@ -140,6 +141,44 @@ pub struct Parser<'input, 'callback> {
     // used by inline passes. store them here for reuse
     inline_stack: InlineStack,
     link_stack: LinkStack,
+    code_delims: CodeDelims,
+    // whether the storage above came from a `ParserScratch`, and so is kept
+    // for `into_scratch` instead of going back to the buffers of the thread
+    from_scratch: bool,
+}
+
+/// Storage for the temporaries of parsers, kept by the caller between parses.
+///
+/// Parsers normally recycle their storage through buffers kept per thread.
+/// A service parsing many small documents can hold the storage itself
+/// instead, passing it to each parser with
+/// [`new_with_scratch`](struct.Parser.html#method.new_with_scratch) and taking
+/// it back with [`into_scratch`](struct.Parser.html#method.into_scratch).
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Options, Parser, ParserScratch};
+///
+/// let mut scratch = ParserScratch::new();
+/// let mut html_buf = String::new();
+/// for doc in &["*one*", "**two**"] {
+///     let mut parser = Parser::new_with_scratch(doc, Options::empty(), scratch);
+///     html::push_html(&mut html_buf, &mut parser);
+///     scratch = parser.into_scratch();
+/// }
+/// assert_eq!(html_buf, "<p><em>one</em></p>\n<p><strong>two</strong></p>\n");
+/// ```
+#[derive(Default)]
+pub struct ParserScratch {
+    buffers: Buffers,
+}
+
+impl ParserScratch {
+    /// Creates empty storage, which allocates on first use.
+    pub fn new() -> Self {
+        Self::default()
+    }
 }
 
 impl<'input, 'callback> Parser<'input, 'callback> {
@@ -163,10 +202,26 @@ impl<'input, 'callback> Parser<'input, 'callback> {
         options: Options,
         broken_link_callback: BrokenLinkCallback<'input, 'callback>,
     ) -> Self {
-        let (mut tree, allocs) = run_first_pass(text, options);
+        Parser::with_buffers(text, options, broken_link_callback, Buffers::take(), false)
+    }
+
+    /// Creates a new event iterator for a markdown string with given options,
+    /// which takes its storage from `scratch` rather than from the buffers of
+    /// the thread. The storage is given back by
+    /// [`into_scratch`](#method.into_scratch).
+    pub fn new_with_scratch(text: &'input str, options: Options, scratch: ParserScratch) -> Self {
+        Parser::with_buffers(text, options, None, scratch.buffers, true)
+    }
+
+    fn with_buffers(
+        text: &'input str,
+        options: Options,
+        broken_link_callback: BrokenLinkCallback<'input, 'callback>,
+        mut buffers: Buffers,
+        from_scratch: bool,
+    ) -> Self {
+        let (mut tree, allocs) = run_first_pass(text, options, &mut buffers);
         tree.reset();
-        let inline_stack = Default::default();
-        let link_stack = Default::default();
         let html_scan_guard = Default::default();
         Parser {
             text,
@@ -174,18 +229,58 @@ impl<'input, 'callback> Parser<'input, 'callback> {
             tree,
             allocs,
             broken_link_callback,
-            inline_stack,
-            link_stack,
+            inline_stack: buffers.inline_stack,
+            link_stack: buffers.link_stack,
+            code_delims: buffers.code_delims,
             html_scan_guard,
+            from_scratch,
+        }
+    }
+
+    /// Gives the storage of the tree and of the inline passes back to the
+    /// buffers of the thread, once the document is exhausted. Storage from a
+    /// `ParserScratch` stays with the parser.
+    fn recycle(&mut self) {
+        if !self.from_scratch {
+            self.take_buffers().give_back();
         }
     }
 
+    fn take_buffers(&mut self) -> Buffers {
+        let tree = std::mem::replace(&mut self.tree, Tree::detached());
+        let (nodes, spine) = tree.into_storage();
+        Buffers {
+            nodes,
+            spine,
+            inline_stack: std::mem::take(&mut self.inline_stack),
+            link_stack: std::mem::take(&mut self.link_stack),
+            code_delims: std::mem::take(&mut self.code_delims),
+        }
+    }
+
+    /// Stops parsing and returns the storage of the parser, cleared, for the
+    /// next one. A parser made with `new` rather than
+    /// [`new_with_scratch`](#method.new_with_scratch) only has storage to
+    /// return before its document is exhausted.
+    pub fn into_scratch(mut self) -> ParserScratch {
+        let mut buffers = self.take_buffers();
+        buffers.clear();
+        ParserScratch { buffers }
+    }
+
     /// Returns a reference to the internal `RefDefs` object, which provides access
     /// to the internal map of reference definitions.
-    pub fn reference_definitions(&self) -> &RefDefs {
+    pub fn reference_definitions(&self) -> &RefDefs<'input> {
         &self.allocs.refdefs
     }
 
+    /// Replaces the reference definitions the parser resolves links with, so
+    /// that a part of a document can be parsed with those of the whole.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn set_reference_definitions(&mut self, refdefs: RefDefs<'input>) {
+        self.allocs.refdefs = refdefs;
+    }
+
     /// Handle inline markup.
     ///
     /// When the parser encounters any item indicating potential inline markup, all
@@ -203,7 +298,7 @@ impl<'input, 'callback> Parser<'input, 'callback> {
     /// the same precedence. It also handles links, even though they have lower
     /// precedence, because the URL of links must not be processed.
     fn handle_inline_pass1(&mut self) {
-        let mut code_delims = CodeDelims::new();
+        self.code_delims.clear();
         let mut cur = self.tree.cur();
         let mut prev = None;
 
@@ -280,10 +375,10 @@ impl<'input, 'callback> Parser<'input, 'callback> {
                         }
                     }
 
-                    if code_delims.is_populated() {
+                    if self.code_delims.is_populated() {
                         // we have previously scanned all codeblock delimiters,
                         // so we can reuse that work
-                        if let Some(scan_ix) = code_delims.find(cur_ix, search_count) {
+                        if let Some(scan_ix) = self.code_delims.find(cur_ix, search_count) {
                             self.make_code_span(cur_ix, scan_ix, preceded_by_backslash);
                         } else {
                             self.tree[cur_ix].item.body = ItemBody::Text;
@@ -302,10 +397,10 @@ impl<'input, 'callback> Parser<'input, 'callback> {
                             {
                                 if search_count == delim_count {
                                     self.make_code_span(cur_ix, scan_ix, preceded_by_backslash);
-                                    code_delims.clear();
+                                    self.code_delims.clear();
                                     break;
                                 } else {
-                                    code_delims.insert(delim_count, scan_ix);
+                                    self.code_delims.insert(delim_count, scan_ix);
                                 }
                             }
                             scan = self.tree[scan_ix].next;
@@ -316,6 +411,13 @@ impl<'input, 'callback> Parser<'input, 'callback> {
                     }
                 }
                 ItemBody::MaybeLinkOpen => {
+                    if self.options.contains(Options::ENABLE_WIKILINKS)
+                        && self.make_wikilink(block_text, cur_ix)
+                    {
+                        prev = cur;
+                        cur = self.tree[cur_ix].next;
+                        continue;
+                    }
                     self.tree[cur_ix].item.body = ItemBody::Text;
                     self.link_stack.push(LinkStackEl {
                         node: cur_ix,
@@ -568,14 +670,7 @@ impl<'input, 'callback> Parser<'input, 'callback> {
                             cur = self.tree[cur_ix + match_count - 1].next;
                             self.tree[prev_ix].next = cur;
 
-                            if el.count > match_count {
-                                self.inline_stack.push(InlineEl {
-                                    start: el.start,
-                                    count: el.count - match_count,
-                                    c: el.c,
-                                    both,
-                                })
-                            }
+                            self.inline_stack.consume(match_count, both);
                             count -= match_count;
                             if count > 0 {
                                 cur_ix = cur.unwrap();
@@ -738,6 +833,70 @@ impl<'input, 'callback> Parser<'input, 'callback> {
         None
     }
 
+    /// Make a wiki link of `[[page]]` or `[[page|label]]`, returning whether
+    /// there is one.
+    ///
+    /// `open` is a MaybeLinkOpen item. All four brackets must be link items,
+    /// so that escaped brackets do not delimit wiki links.
+    fn make_wikilink(&mut self, block_text: &'input str, open: TreeIndex) -> bool {
+        let tree = &self.tree;
+        let body_at = |node: Option<TreeIndex>, ix: usize| {
+            node.filter(|&node| tree[node].item.start == ix)
+                .map(|node| tree[node].item.body)
+        };
+        let second_open = tree[open].next;
+        let inner_start = tree[open].item.end + 1;
+        if body_at(second_open, inner_start - 1) != Some(ItemBody::MaybeLinkOpen) {
+            return false;
+        }
+        let inner_end = match block_text[inner_start..].find(&['[', ']', '\n', '\r'][..]) {
+            Some(len) if block_text[inner_start + len..].starts_with("]]") => inner_start + len,
+            _ => return false,
+        };
+        let first_close = scan_nodes_to_ix(tree, second_open, inner_end);
+        let second_close = first_close.and_then(|node| tree[node].next);
+        let is_close = |body| matches!(body, Some(ItemBody::MaybeLinkClose(..)));
+        if !is_close(body_at(first_close, inner_end))
+            || !is_close(body_at(second_close, inner_end + 1))
+        {
+            return false;
+        }
+
+        let trimmed = |start: usize, end: usize| {
+            let text = &block_text[start..end];
+            let start = start + text.len() - text.trim_start().len();
+            (start, start + text.trim().len())
+        };
+        let bar = block_text[inner_start..inner_end].find('|');
+        let page = trimmed(inner_start, bar.map_or(inner_end, |bar| inner_start + bar));
+        let label = match bar {
+            Some(bar) => trimmed(inner_start + bar + 1, inner_end),
+            None => page,
+        };
+        if page.0 == page.1 {
+            return false;
+        }
+        let (label_start, label_end) = if label.0 == label.1 { page } else { label };
+
+        let after = self.tree[second_close.unwrap()].next;
+        let url = CowStr::Borrowed(&block_text[page.0..page.1]);
+        let link_ix = self
+            .allocs
+            .allocate_link(LinkType::WikiLink, url, "".into());
+        let label_node = self.tree.create_node(Item {
+            start: label_start,
+            end: label_end,
+            body: ItemBody::Text,
+        });
+        self.tree[open].item.body = ItemBody::Link(link_ix);
+        self.tree[open].item.end = inner_end + 2;
+        self.tree[open].child = Some(label_node);
+        self.tree[open].next = after;
+        // links do not contain other links
+        self.link_stack.disable_all_links();
+        true
+    }
+
     /// Make a code span.
     ///
     /// Both `open` and `close` are matching MaybeCode items.
@@ -912,7 +1071,7 @@ struct InlineEl {
 }
 
 #[derive(Debug, Clone, Default)]
-struct InlineStack {
+pub(crate) struct InlineStack {
     stack: Vec<InlineEl>,
     // Lower bounds for matching indices in the stack. For example
     // a strikethrough delimiter will never match with any element
@@ -931,6 +1090,15 @@ impl InlineStack {
     const TILDES: usize = 5;
     const UNDERSCORE_BOTH: usize = 6;
 
+    pub(crate) fn clear(&mut self) {
+        self.stack.clear();
+        self.lower_bounds = [0; 7];
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.stack.capacity()
+    }
+
     fn pop_all(&mut self, tree: &mut Tree<Item>) {
         for el in self.stack.drain(..) {
             for i in 0..el.count {
@@ -987,29 +1155,40 @@ impl InlineStack {
         both: bool,
     ) -> Option<InlineEl> {
         let lowerbound = min(self.stack.len(), self.get_lowerbound(c, count, both));
-        let res = self.stack[lowerbound..]
-            .iter()
-            .cloned()
-            .enumerate()
-            .rfind(|(_, el)| {
-                el.c == c && (!both && !el.both || (count + el.count) % 3 != 0 || count % 3 == 0)
-            });
+        let res = self.stack[lowerbound..].iter().rposition(|el| {
+            el.c == c && (!both && !el.both || (count + el.count) % 3 != 0 || count % 3 == 0)
+        });
 
-        if let Some((matching_ix, matching_el)) = res {
+        if let Some(matching_ix) = res {
             let matching_ix = matching_ix + lowerbound;
             for el in &self.stack[(matching_ix + 1)..] {
                 for i in 0..el.count {
                     tree[el.start + i].item.body = ItemBody::Text;
                 }
             }
-            self.stack.truncate(matching_ix);
-            Some(matching_el)
+            self.stack.truncate(matching_ix + 1);
+            Some(self.stack[matching_ix])
         } else {
             self.set_lowerbound(c, count, both, self.stack.len());
             None
         }
     }
 
+    /// Takes `count` delimiters off the element returned by the last call to
+    /// `find_match`, which is at the top of the stack, and pops it once none
+    /// are left. What remains of the element takes on `both` from the
+    /// closer that matched it.
+    fn consume(&mut self, count: usize, both: bool) {
+        let top = self.stack.len() - 1;
+        let el = &mut self.stack[top];
+        if el.count > count {
+            el.count -= count;
+            el.both = both;
+        } else {
+            self.stack.pop();
+        }
+    }
+
     fn push(&mut self, el: InlineEl) {
         self.stack.push(el)
     }
@@ -1093,7 +1272,7 @@ fn scan_reference<'a, 'b>(
 }
 
 #[derive(Clone, Default)]
-struct LinkStack {
+pub(crate) struct LinkStack {
     inner: Vec<LinkStackEl>,
     disabled_ix: usize,
 }
@@ -1109,11 +1288,15 @@ impl LinkStack {
         el
     }
 
-    fn clear(&mut self) {
+    pub(crate) fn clear(&mut self) {
         self.inner.clear();
         self.disabled_ix = 0;
     }
 
+    pub(crate) fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
     fn disable_all_links(&mut self) {
         for el in &mut self.inner[self.disabled_ix..] {
             if el.ty == LinkStackTy::Link {
@@ -1147,19 +1330,13 @@ pub struct LinkDef<'a> {
 
 /// Tracks tree indices of code span delimiters of each length. It should prevent
 /// quadratic scanning behaviours by providing (amortized) constant time lookups.
-struct CodeDelims {
+#[derive(Default)]
+pub(crate) struct CodeDelims {
     inner: HashMap<usize, VecDeque<TreeIndex>>,
     seen_first: bool,
 }
 
 impl CodeDelims {
-    fn new() -> Self {
-        Self {
-            inner: Default::default(),
-            seen_first: false,
-        }
-    }
-
     fn insert(&mut self, count: usize, ix: TreeIndex) {
         if self.seen_first {
             self.inner
@@ -1186,10 +1363,14 @@ impl CodeDelims {
         None
     }
 
-    fn clear(&mut self) {
+    pub(crate) fn clear(&mut self) {
         self.inner.clear();
         self.seen_first = false;
     }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -1232,7 +1413,7 @@ impl<'a> Allocations<'a> {
     pub fn new() -> Self {
         Self {
             refdefs: RefDefs::default(),
-            links: Vec::with_capacity(128),
+            links: Vec::new(),
             cows: Vec::new(),
             alignments: Vec::new(),
         }
@@ -1309,9 +1490,15 @@ pub struct OffsetIter<'a, 'b> {
 
 impl<'a, 'b> OffsetIter<'a, 'b> {
     /// Returns a reference to the internal reference definition tracker.
-    pub fn reference_definitions(&self) -> &RefDefs {
+    pub fn reference_definitions(&self) -> &RefDefs<'a> {
         self.inner.reference_definitions()
     }
+
+    /// Stops parsing and returns the storage of the parser, cleared, for the
+    /// next one. See [`Parser::into_scratch`](struct.Parser.html#method.into_scratch).
+    pub fn into_scratch(self) -> ParserScratch {
+        self.inner.into_scratch()
+    }
 }
 
 impl<'a, 'b> Iterator for OffsetIter<'a, 'b> {
@@ -1320,7 +1507,13 @@ impl<'a, 'b> Iterator for OffsetIter<'a, 'b> {
     fn next(&mut self) -> Option<Self::Item> {
         match self.inner.tree.cur() {
             None => {
-                let ix = self.inner.tree.pop()?;
+                let ix = match self.inner.tree.pop() {
+                    Some(ix) => ix,
+                    None => {
+                        self.inner.recycle();
+                        return None;
+                    }
+                };
                 let tag = item_to_tag(&self.inner.tree[ix].item, &self.inner.allocs);
                 self.inner.tree.next_sibling(ix);
                 let span = self.inner.tree[ix].item.start..self.inner.tree[ix].item.end;
@@ -1443,7 +1636,13 @@ impl<'a, 'b> Iterator for Parser<'a, 'b> {
     fn next(&mut self) -> Option<Event<'a>> {
         match self.tree.cur() {
             None => {
-                let ix = self.tree.pop()?;
+                let ix = match self.tree.pop() {
+                    Some(ix) => ix,
+                    None => {
+                        self.recycle();
+                        return None;
+                    }
+                };
                 let tag = item_to_tag(&self.tree[ix].item, &self.allocs);
                 self.tree.next_sibling(ix);
                 Some(Event::End(tag))
@@ -1583,6 +1782,34 @@ mod test {
         parser_with_extensions("*__#_#__*").count();
     }
 
+    #[test]
+    fn partially_matched_opener_closes_again() {
+        let mut s = String::new();
+        crate::html::push_html(&mut s, parser_with_extensions("***a* b** ~~c ~~d~~ e~~"));
+        assert_eq!(
+            "<p><strong><em>a</em> b</strong> <del>c <del>d</del> e</del></p>\n",
+            s
+        );
+    }
+
+    #[test]
+    fn scratch_reused_across_parsers() {
+        let input = "*a* [b](c) `d`\n\n# e\n";
+        let expected: Vec<_> = Parser::new(input).into_offset_iter().collect();
+
+        let mut parser = Parser::new_with_scratch(input, Options::empty(), ParserScratch::new());
+        assert_eq!(expected.len(), (&mut parser).count());
+        let scratch = parser.into_scratch();
+        let nodes = scratch.buffers.nodes.as_ptr();
+        assert!(scratch.buffers.nodes.capacity() > 0);
+
+        let mut iter =
+            Parser::new_with_scratch(input, Options::empty(), scratch).into_offset_iter();
+        let events: Vec<_> = (&mut iter).collect();
+        assert_eq!(expected, events);
+        assert_eq!(iter.into_scratch().buffers.nodes.as_ptr(), nodes);
+    }
+
     #[test]
     fn offset_iter() {
         let event_offsets: Vec<_> = Parser::new("*hello* world")
@@ -1660,6 +1887,28 @@ mod test {
         assert_eq!(expected_offsets, event_offsets);
     }
 
+    #[test]
+    fn wikilink_offsets() {
+        let events: Vec<_> = Parser::new_ext("a [[b | c]] \\[\\[d]]", Options::ENABLE_WIKILINKS)
+            .into_offset_iter()
+            .collect();
+        let link = Tag::Link(LinkType::WikiLink, "b".into(), "".into());
+        let expected = vec![
+            (Event::Start(Tag::Paragraph), 0..19),
+            (Event::Text("a ".into()), 0..2),
+            (Event::Start(link.clone()), 2..11),
+            (Event::Text("c".into()), 8..9),
+            (Event::End(link), 2..11),
+            (Event::Text(" ".into()), 11..12),
+            (Event::Text("[".into()), 13..14),
+            (Event::Text("[d".into()), 15..17),
+            (Event::Text("]".into()), 17..18),
+            (Event::Text("]".into()), 18..19),
+            (Event::End(Tag::Paragraph), 0..19),
+        ];
+        assert_eq!(expected, events);
+    }
+
     #[test]
     fn offset_iter_issue_404() {
         let event_offsets: Vec<_> = Parser::new("###\n")
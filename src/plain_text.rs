@@ -0,0 +1,475 @@
+//! Plain text renderer that takes an iterator of events as input.
+//!
+//! The output is meant to be read as is, for example as the text part of an
+//! email: headings are underlined or prefixed with `#`, list items are
+//! indented under their markers, links are followed by their destination
+//! like `text <url>`, and tables are laid out in aligned columns. Inline
+//! formatting and raw HTML are dropped.
+//!
+//! [`utils::plain::PlainText`](../utils/plain/struct.PlainText.html) flattens
+//! a document to text events instead, without any layout.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::lines::{self, Container};
+use crate::strings::CowStr;
+use crate::Event::*;
+use crate::{Alignment, Event, HeadingLevel, LinkType, Tag};
+
+/// An open list.
+struct List {
+    /// Number of the next item, `None` for bullet lists.
+    number: Option<u64>,
+    /// Whether the items hold paragraphs, and are separated by blank lines.
+    loose: bool,
+}
+
+/// Cells of a table being rendered.
+struct Table {
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<String>>,
+    /// Number of header rows.
+    head: usize,
+}
+
+struct PlainTextWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    containers: Vec<Container>,
+    lists: Vec<List>,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Whether a blank line goes before the next block.
+    blank_line: bool,
+    /// Inline text of a heading or table cell being collected.
+    capture: Option<String>,
+    table: Option<Table>,
+    /// Nesting depth of autolinks, whose text is the destination.
+    autolinks: usize,
+    numbers: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a, I, W> PlainTextWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W) -> Self {
+        PlainTextWriter {
+            iter,
+            writer,
+            containers: Vec::new(),
+            lists: Vec::new(),
+            line_start: true,
+            blank_line: false,
+            capture: None,
+            table: None,
+            autolinks: 0,
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        while let Some(event) = self.iter.next() {
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) | Code(text) if self.autolinks == 0 => self.inline(&text)?,
+                Text(_) | Code(_) | Html(_) => (),
+                SoftBreak | HardBreak => self.line_break()?,
+                Rule => {
+                    self.start_block()?;
+                    self.inline("---")?;
+                    self.end_block()?;
+                }
+                FootnoteReference(name) => {
+                    let marker = format!("[{}]", self.footnote_number(name));
+                    self.inline(&marker)?;
+                }
+                TaskListMarker(true) => self.inline("[x] ")?,
+                TaskListMarker(false) => self.inline("[ ] ")?,
+            }
+        }
+        self.end_line()
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => {
+                if let Some(list) = self.lists.last_mut() {
+                    list.loose = true;
+                }
+                self.start_block()
+            }
+            Tag::Heading(_) => {
+                self.start_block()?;
+                self.capture = Some(String::new());
+                Ok(())
+            }
+            Tag::BlockQuote => {
+                self.start_block()?;
+                self.push_container("> ".to_owned(), "> ".to_owned());
+                Ok(())
+            }
+            Tag::CodeBlock(_) => {
+                self.start_block()?;
+                self.push_container("    ".to_owned(), "    ".to_owned());
+                Ok(())
+            }
+            Tag::List(start) => {
+                self.start_block()?;
+                self.lists.push(List {
+                    number: start,
+                    loose: false,
+                });
+                Ok(())
+            }
+            Tag::Item => {
+                self.start_block()?;
+                let number = self.lists.last_mut().and_then(|list| list.number.as_mut());
+                let marker = match number {
+                    Some(number) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    None => "* ".to_owned(),
+                };
+                let indent = " ".repeat(marker.len());
+                self.push_container(marker, indent);
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.start_block()?;
+                let marker = format!("[{}] ", self.footnote_number(name));
+                let indent = " ".repeat(marker.len());
+                self.push_container(marker, indent);
+                Ok(())
+            }
+            Tag::Table(alignments) => {
+                self.start_block()?;
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                    head: 0,
+                });
+                Ok(())
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.rows.push(Vec::new());
+                }
+                Ok(())
+            }
+            Tag::TableCell => {
+                self.capture = Some(String::new());
+                Ok(())
+            }
+            Tag::Link(LinkType::Autolink, ..) | Tag::Link(LinkType::Email, ..) => {
+                self.autolinks += 1;
+                Ok(())
+            }
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..) => {
+                Ok(())
+            }
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.end_block(),
+            Tag::Heading(level) => {
+                let text = self.capture.take().unwrap_or_default();
+                let underline = match level {
+                    HeadingLevel::H1 => '=',
+                    HeadingLevel::H2 => '-',
+                    _ => {
+                        let hashes = "#".repeat(level as usize);
+                        self.inline(&format!("{} {}", hashes, text))?;
+                        return self.end_block();
+                    }
+                };
+                let len = text.chars().count();
+                self.inline(&text)?;
+                self.line_break()?;
+                self.inline(&underline.to_string().repeat(len.max(3)))?;
+                self.end_block()
+            }
+            Tag::BlockQuote | Tag::CodeBlock(_) | Tag::FootnoteDefinition(_) => {
+                self.end_line()?;
+                self.containers.pop();
+                self.blank_line = true;
+                Ok(())
+            }
+            Tag::List(_) => {
+                self.end_line()?;
+                self.lists.pop();
+                // a nested list is part of its item
+                self.blank_line = self.lists.last().map_or(true, |list| list.loose);
+                Ok(())
+            }
+            Tag::Item => {
+                self.end_line()?;
+                self.containers.pop();
+                Ok(())
+            }
+            Tag::Table(_) => {
+                if let Some(table) = self.table.take() {
+                    self.write_table(table)?;
+                }
+                self.end_block()
+            }
+            Tag::TableHead => {
+                if let Some(table) = self.table.as_mut() {
+                    table.head = table.rows.len();
+                }
+                Ok(())
+            }
+            Tag::TableCell => {
+                let text = self.capture.take().unwrap_or_default();
+                if let Some(row) = self.table.as_mut().and_then(|table| table.rows.last_mut()) {
+                    row.push(text);
+                }
+                Ok(())
+            }
+            Tag::Link(LinkType::Autolink, dest, _) | Tag::Link(LinkType::Email, dest, _) => {
+                self.autolinks -= 1;
+                self.inline(&format!("<{}>", dest))
+            }
+            Tag::Link(_, dest, _) | Tag::Image(_, dest, _) => self.inline(&format!(" <{}>", dest)),
+            Tag::TableRow | Tag::Emphasis | Tag::Strong | Tag::Strikethrough => Ok(()),
+        }
+    }
+
+    fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    fn push_container(&mut self, first: String, rest: String) {
+        self.containers.push(Container::new(first, rest, false));
+    }
+
+    /// Ends the current line, and writes the blank line separating the
+    /// block that starts from the previous one.
+    fn start_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        if self.blank_line {
+            self.blank_line = false;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.blank_line = true;
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.newline()
+        }
+    }
+
+    /// Writes a line break, preceded by the prefix of the containers when
+    /// the line is empty.
+    fn newline(&mut self) -> Result<(), W::Error> {
+        lines::newline(&mut self.writer, &mut self.containers, self.line_start)?;
+        self.line_start = true;
+        Ok(())
+    }
+
+    fn line_break(&mut self) -> Result<(), W::Error> {
+        match self.capture {
+            Some(ref mut capture) => {
+                capture.push(' ');
+                Ok(())
+            }
+            None => self.newline(),
+        }
+    }
+
+    /// Writes inline text, which can span several lines in code blocks.
+    fn inline(&mut self, text: &str) -> Result<(), W::Error> {
+        if let Some(ref mut capture) = self.capture {
+            capture.push_str(text);
+            return Ok(());
+        }
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                self.newline()?;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.line_start {
+                let prefix = lines::prefix(&mut self.containers);
+                self.writer.write_str(&prefix)?;
+                self.line_start = false;
+            }
+            self.writer.write_str(line)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a table with its columns padded to the same width, and a line
+    /// of dashes under the header.
+    fn write_table(&mut self, table: Table) -> Result<(), W::Error> {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in &table.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for (ix, row) in table.rows.iter().enumerate() {
+            if ix == table.head && ix > 0 {
+                let dashes: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+                self.inline(dashes.join("  ").trim_end())?;
+                self.newline()?;
+            }
+            let mut line = String::new();
+            for (column, &width) in widths.iter().enumerate() {
+                if column > 0 {
+                    line.push_str("  ");
+                }
+                let cell = row.get(column).map_or("", String::as_str);
+                let padding = width - cell.chars().count();
+                let left = match table.alignments.get(column) {
+                    Some(Alignment::Right) => padding,
+                    Some(Alignment::Center) => padding / 2,
+                    _ => 0,
+                };
+                line.push_str(&" ".repeat(left));
+                line.push_str(cell);
+                line.push_str(&" ".repeat(padding - left));
+            }
+            self.inline(line.trim_end())?;
+            self.newline()?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders an iterator of events as plain text, and pushes it to a
+/// `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{plain_text, Options, Parser};
+///
+/// let markdown = "Title\n=====\n\n* See [the docs](https://example.com/).\n\n| a | b |\n|--:|---|\n| 10 | x |\n";
+/// let mut text = String::new();
+/// plain_text::push_plain_text(&mut text, Parser::new_ext(markdown, Options::ENABLE_TABLES));
+/// assert_eq!(text, "\
+/// Title
+/// =====
+///
+/// * See the docs <https://example.com/>.
+///
+///  a  b
+/// --  -
+/// 10  x
+/// ");
+/// ```
+pub fn push_plain_text<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    PlainTextWriter::new(iter, s).run().unwrap();
+}
+
+/// Renders an iterator of events as plain text, and writes it out to a
+/// writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_plain_text<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    PlainTextWriter::new(iter, WriteWrapper(writer)).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    fn render(input: &str) -> String {
+        let mut s = String::new();
+        let options = Options::ENABLE_FOOTNOTES | Options::ENABLE_TASKLISTS;
+        push_plain_text(&mut s, Parser::new_ext(input, options));
+        s
+    }
+
+    #[test]
+    fn blocks() {
+        let input = "\
+## Sub *title*
+
+> quoted
+> text
+
+1. one
+2. two
+
+   more
+   - nested
+   - [x] done
+3. three
+
+para
+
+    code
+
+    line
+
+#### Small
+
+Note[^n] <https://example.com>.
+
+[^n]: The note.
+";
+        let expected = "\
+Sub title
+---------
+
+> quoted
+> text
+
+1. one
+
+2. two
+
+   more
+
+   * nested
+   * [x] done
+
+3. three
+
+para
+
+    code
+
+    line
+
+#### Small
+
+Note[1] <https://example.com>.
+
+[1] The note.
+";
+        assert_eq!(expected, render(input));
+    }
+}
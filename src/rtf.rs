@@ -0,0 +1,464 @@
+//! RTF writer that takes an iterator of events as input.
+//!
+//! The output is a complete RTF document, like the rich text put on the
+//! clipboard by desktop applications. Headings become bold paragraphs in a
+//! larger size, code is set in a monospaced font, block quotes and lists are
+//! indented, and links become `HYPERLINK` fields. Tables become RTF tables
+//! with columns of equal width. Footnotes are numbered in superscript, with
+//! their text where they are defined. Raw HTML is dropped, and images are
+//! written as their alt text.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::strings::CowStr;
+use crate::Event::*;
+use crate::{Alignment, Event, HeadingLevel, LinkType, Tag};
+
+/// Width of a table, in twips.
+const TABLE_WIDTH: usize = 9000;
+
+/// Indentation of a block quote or list level, in twips.
+const INDENT: u32 = 720;
+
+/// Writes `s` to `w`, escaping backslashes and braces, and writing
+/// characters outside of ASCII as `\u` control words.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::rtf::escape_rtf;
+///
+/// let mut s = String::new();
+/// escape_rtf(&mut s, "{a} \\ café").unwrap();
+/// assert_eq!(s, "\\{a\\} \\\\ caf\\u233?");
+/// ```
+pub fn escape_rtf<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        if c.is_ascii() && !matches!(c, '\\' | '{' | '}' | '\n' | '\r' | '\t') {
+            continue;
+        }
+        w.write_str(&s[mark..ix])?;
+        mark = ix + c.len_utf8();
+        match c {
+            '\\' | '{' | '}' => {
+                w.write_str("\\")?;
+                w.write_str(&s[ix..mark])?;
+            }
+            '\n' | '\r' => w.write_str(" ")?,
+            '\t' => w.write_str("\\tab ")?,
+            _ => {
+                // RTF takes signed 16 bit code units, followed by a
+                // replacement for readers without Unicode support
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(w, "\\u{}?", *unit as i16)?;
+                }
+            }
+        }
+    }
+    w.write_str(&s[mark..])
+}
+
+/// An open table.
+struct Table {
+    alignments: Vec<Alignment>,
+    in_head: bool,
+    /// Index of the next cell in the row.
+    cell: usize,
+}
+
+struct RtfWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    /// Whether a paragraph group is open.
+    in_paragraph: bool,
+    /// Left indentation of the paragraphs, in twips.
+    indent: u32,
+    /// Numbers of the next items of the open lists, `None` for bullet
+    /// lists.
+    lists: Vec<Option<u64>>,
+    /// Marker of the item whose first paragraph hasn't been opened yet.
+    marker: Option<String>,
+    in_code_block: bool,
+    /// Whether the code block has a line already.
+    after_code_line: bool,
+    table: Option<Table>,
+    /// Number of the footnote whose first paragraph hasn't been opened yet.
+    footnote: Option<usize>,
+    numbers: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a, I, W> RtfWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W) -> Self {
+        RtfWriter {
+            iter,
+            writer,
+            in_paragraph: false,
+            indent: 0,
+            lists: Vec::new(),
+            marker: None,
+            in_code_block: false,
+            after_code_line: false,
+            table: None,
+            footnote: None,
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        self.writer.write_str(
+            "{\\rtf1\\ansi\\deff0\n\
+             {\\fonttbl{\\f0\\fswiss Helvetica;}{\\f1\\fmodern Courier New;}}\n\
+             \\fs24\n",
+        )?;
+        while let Some(event) = self.iter.next() {
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => self.write_code(&text)?,
+                Text(text) => self.write_text(&text)?,
+                Code(text) => {
+                    self.open_paragraph()?;
+                    self.writer.write_str("{\\f1 ")?;
+                    escape_rtf(&mut self.writer, &text)?;
+                    self.writer.write_str("}")?;
+                }
+                Html(_) => (),
+                SoftBreak => self.write_text(" ")?,
+                HardBreak => {
+                    self.open_paragraph()?;
+                    self.writer.write_str("\\line ")?;
+                }
+                Rule => {
+                    self.close_paragraph()?;
+                    writeln!(
+                        self.writer,
+                        "{{\\pard\\li{}\\brdrb\\brdrs\\brdrw10\\brsp20\\sa180 \\par}}",
+                        self.indent
+                    )?;
+                }
+                FootnoteReference(name) => {
+                    let number = self.footnote_number(name);
+                    self.open_paragraph()?;
+                    write!(self.writer, "{{\\super {}}}", number)?;
+                }
+                TaskListMarker(true) => self.write_text("\u{2611} ")?,
+                TaskListMarker(false) => self.write_text("\u{2610} ")?,
+            }
+        }
+        self.close_paragraph()?;
+        self.writer.write_str("}\n")
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.close_paragraph(),
+            Tag::Heading(level) => {
+                self.close_paragraph()?;
+                let size = match level {
+                    HeadingLevel::H1 => 36,
+                    HeadingLevel::H2 => 32,
+                    HeadingLevel::H3 => 28,
+                    HeadingLevel::H4 => 26,
+                    HeadingLevel::H5 | HeadingLevel::H6 => 24,
+                };
+                write!(
+                    self.writer,
+                    "{{\\pard\\li{}\\sb240\\sa120\\keepn\\b\\fs{} ",
+                    self.indent, size
+                )?;
+                self.in_paragraph = true;
+                Ok(())
+            }
+            Tag::BlockQuote => {
+                self.close_paragraph()?;
+                self.indent += INDENT;
+                Ok(())
+            }
+            Tag::CodeBlock(_) => {
+                self.close_paragraph()?;
+                self.in_code_block = true;
+                self.after_code_line = false;
+                self.open_paragraph()?;
+                self.writer.write_str("\\f1\\fs20 ")
+            }
+            Tag::List(start) => {
+                self.close_paragraph()?;
+                self.lists.push(start);
+                self.indent += INDENT;
+                Ok(())
+            }
+            Tag::Item => {
+                self.close_paragraph()?;
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}.", *number - 1)
+                    }
+                    _ => "\\bullet".to_owned(),
+                };
+                self.marker = Some(marker);
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.close_paragraph()?;
+                self.footnote = Some(self.footnote_number(name));
+                Ok(())
+            }
+            Tag::Table(alignments) => {
+                self.close_paragraph()?;
+                self.table = Some(Table {
+                    alignments,
+                    in_head: false,
+                    cell: 0,
+                });
+                Ok(())
+            }
+            Tag::TableHead | Tag::TableRow => {
+                let table = match self.table.as_mut() {
+                    Some(table) => table,
+                    None => return Ok(()),
+                };
+                table.in_head = tag == Tag::TableHead;
+                table.cell = 0;
+                let columns = table.alignments.len().max(1);
+                write!(self.writer, "\\trowd\\trgaph108\\trleft{}", self.indent)?;
+                for column in 1..=columns {
+                    let right = self.indent as usize + column * TABLE_WIDTH / columns;
+                    write!(self.writer, "\\cellx{}", right)?;
+                }
+                self.writer.write_str("\n")
+            }
+            Tag::TableCell => {
+                let (alignment, in_head) = match self.table.as_ref() {
+                    Some(table) => (table.alignments.get(table.cell).copied(), table.in_head),
+                    None => return Ok(()),
+                };
+                self.writer.write_str("\\pard\\intbl")?;
+                self.writer.write_str(match alignment {
+                    Some(Alignment::Center) => "\\qc",
+                    Some(Alignment::Right) => "\\qr",
+                    _ => "\\ql",
+                })?;
+                self.writer.write_str(if in_head { "{\\b " } else { "{" })
+            }
+            Tag::Emphasis => self.open_group("{\\i "),
+            Tag::Strong => self.open_group("{\\b "),
+            Tag::Strikethrough => self.open_group("{\\strike "),
+            Tag::Link(link_type, dest, _) => {
+                self.open_paragraph()?;
+                self.writer
+                    .write_str("{\\field{\\*\\fldinst{HYPERLINK \"")?;
+                if link_type == LinkType::Email {
+                    self.writer.write_str("mailto:")?;
+                }
+                escape_rtf(&mut self.writer, &dest.replace('"', "%22"))?;
+                self.writer.write_str("\"}}{\\fldrslt{\\ul ")
+            }
+            Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph | Tag::Heading(_) | Tag::FootnoteDefinition(_) => self.close_paragraph(),
+            Tag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.close_paragraph()
+            }
+            Tag::BlockQuote | Tag::List(_) => {
+                self.close_paragraph()?;
+                if let Tag::List(_) = tag {
+                    self.lists.pop();
+                }
+                self.indent -= INDENT;
+                Ok(())
+            }
+            Tag::Item => {
+                // an empty item still shows its marker
+                if self.marker.is_some() {
+                    self.open_paragraph()?;
+                }
+                self.close_paragraph()
+            }
+            Tag::Table(_) => {
+                self.table = None;
+                self.writer.write_str("\\pard\n")
+            }
+            Tag::TableHead | Tag::TableRow => self.writer.write_str("\\row\n"),
+            Tag::TableCell => {
+                if let Some(table) = self.table.as_mut() {
+                    table.cell += 1;
+                }
+                self.writer.write_str("}\\cell\n")
+            }
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => self.writer.write_str("}"),
+            Tag::Link(..) => self.writer.write_str("}}}"),
+            Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    fn open_group(&mut self, group: &str) -> Result<(), W::Error> {
+        self.open_paragraph()?;
+        self.writer.write_str(group)
+    }
+
+    /// Opens a paragraph group, unless one is open or the text goes in a
+    /// table cell. The first paragraph of a list item starts with its
+    /// marker, hanging in the indentation, and the first paragraph of a
+    /// footnote with its number.
+    fn open_paragraph(&mut self) -> Result<(), W::Error> {
+        if self.in_paragraph || self.table.is_some() {
+            return Ok(());
+        }
+        self.in_paragraph = true;
+        write!(self.writer, "{{\\pard\\li{}\\sa180", self.indent)?;
+        match self.marker.take() {
+            Some(marker) => write!(
+                self.writer,
+                "\\fi-{}\\tx{} {}\\tab ",
+                INDENT / 2,
+                self.indent,
+                marker
+            )?,
+            None => self.writer.write_str(" ")?,
+        }
+        match self.footnote.take() {
+            Some(number) => write!(self.writer, "{{\\super {}}} ", number),
+            None => Ok(()),
+        }
+    }
+
+    fn close_paragraph(&mut self) -> Result<(), W::Error> {
+        if !self.in_paragraph {
+            return Ok(());
+        }
+        self.in_paragraph = false;
+        self.writer.write_str("\\par}\n")
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        self.open_paragraph()?;
+        escape_rtf(&mut self.writer, text)
+    }
+
+    /// Writes the text of a code block, with its lines separated by line
+    /// breaks.
+    fn write_code(&mut self, text: &str) -> Result<(), W::Error> {
+        for line in text.lines() {
+            if self.after_code_line {
+                self.writer.write_str("\\line\n")?;
+            }
+            self.after_code_line = true;
+            let line = line.replace('\t', "    ");
+            escape_rtf(&mut self.writer, &line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate RTF for each `Event`,
+/// and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{rtf, Parser};
+///
+/// let markdown = "# Hello\n\nSome *italic* and `code`.\n";
+/// let mut rtf_buf = String::new();
+/// rtf::push_rtf(&mut rtf_buf, Parser::new(markdown));
+/// assert_eq!(rtf_buf, r"{\rtf1\ansi\deff0
+/// {\fonttbl{\f0\fswiss Helvetica;}{\f1\fmodern Courier New;}}
+/// \fs24
+/// {\pard\li0\sb240\sa120\keepn\b\fs36 Hello\par}
+/// {\pard\li0\sa180 Some {\i italic} and {\f1 code}.\par}
+/// }
+/// ");
+/// ```
+pub fn push_rtf<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    RtfWriter::new(iter, s).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate RTF for each `Event`,
+/// and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_rtf<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    RtfWriter::new(iter, WriteWrapper(writer)).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn blocks() {
+        let input = "\
+> **Note** see [docs](http://x/\"y\")[^n]
+
+1. one
+2. ~~two~~
+
+```
+a\\b
+  c
+```
+
+| a | b |
+|---|--:|
+| ![c](c.png) | 😀 |
+
+[^n]: Note.
+";
+        let expected = r#"{\rtf1\ansi\deff0
+{\fonttbl{\f0\fswiss Helvetica;}{\f1\fmodern Courier New;}}
+\fs24
+{\pard\li720\sa180 {\b Note} see {\field{\*\fldinst{HYPERLINK "http://x/%22y%22"}}{\fldrslt{\ul docs}}}{\super 1}\par}
+{\pard\li720\sa180\fi-360\tx720 1.\tab one\par}
+{\pard\li720\sa180\fi-360\tx720 2.\tab {\strike two}\par}
+{\pard\li0\sa180 \f1\fs20 a\\b\line
+  c\par}
+\trowd\trgaph108\trleft0\cellx4500\cellx9000
+\pard\intbl\ql{\b a}\cell
+\pard\intbl\qr{\b b}\cell
+\row
+\trowd\trgaph108\trleft0\cellx4500\cellx9000
+\pard\intbl\ql{c}\cell
+\pard\intbl\qr{\u-10179?\u-8704?}\cell
+\row
+\pard
+{\pard\li0\sa180 {\super 1} Note.\par}
+}
+"#;
+        let parser_options = crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_STRIKETHROUGH;
+        let mut s = String::new();
+        push_rtf(&mut s, Parser::new_ext(input, parser_options));
+        assert_eq!(expected, s);
+    }
+}
@@ -29,7 +29,7 @@ use crate::strings::CowStr;
 use crate::{entities, HeadingLevel};
 use crate::{Alignment, LinkType};
 
-use memchr::memchr;
+use memchr::{memchr, memchr3};
 
 // sorted for binary search
 const HTML_TAGS: [&str; 62] = [
@@ -961,7 +961,10 @@ pub(crate) fn unescape(input: &str) -> CowStr<'_> {
     let mut mark = 0;
     let mut i = 0;
     let bytes = input.as_bytes();
-    while i < bytes.len() {
+    // memchr skips the plain text between candidates with SIMD where the
+    // target has it
+    while let Some(offset) = memchr3(b'\\', b'&', b'\r', &bytes[i..]) {
+        i += offset;
         match bytes[i] {
             b'\\' if i + 1 < bytes.len() && is_ascii_punctuation(bytes[i + 1]) => {
                 result.push_str(&input[mark..i]);
@@ -0,0 +1,325 @@
+//! SSML writer that takes an iterator of events as input, for text to
+//! speech engines.
+//!
+//! The output is a `<speak>` document. Paragraphs, list items, table rows
+//! and headings become `<p>` elements, emphasis `<emphasis>` elements, and
+//! line breaks and thematic breaks `<break>` elements. Links are read as
+//! their text and images as their alt text, while raw HTML and footnote
+//! references are left out.
+
+use std::io;
+
+use crate::escape::{escape_html, StrWrite, WriteWrapper};
+use crate::Event::*;
+use crate::{Event, Tag};
+
+/// How code is read.
+#[derive(Clone, Copy, Debug)]
+pub enum CodeReading {
+    /// Code is read like the text around it.
+    Literal,
+    /// Code is read character by character, with
+    /// `<say-as interpret-as="characters">`.
+    Characters,
+    /// Code is left out.
+    Skip,
+}
+
+impl Default for CodeReading {
+    fn default() -> Self {
+        CodeReading::Literal
+    }
+}
+
+/// Options of the SSML writer.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// How inline code and code blocks are read.
+    pub code: CodeReading,
+}
+
+struct SsmlWriter<I, W> {
+    iter: I,
+    writer: W,
+    options: Options,
+    /// Whether a `<p>` element is open.
+    in_paragraph: bool,
+    in_code_block: bool,
+    /// Whether a table cell was written in the current row.
+    after_cell: bool,
+}
+
+impl<'a, I, W> SsmlWriter<I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W, options: &Options) -> Self {
+        SsmlWriter {
+            iter,
+            writer,
+            options: options.clone(),
+            in_paragraph: false,
+            in_code_block: false,
+            after_cell: false,
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        self.writer.write_str("<speak>\n")?;
+        while let Some(event) = self.iter.next() {
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => self.write_code(&text)?,
+                Text(text) => self.write_text(&text)?,
+                Code(text) => self.write_code(&text)?,
+                Html(_) | FootnoteReference(_) => (),
+                SoftBreak => self.write_text(" ")?,
+                HardBreak => {
+                    self.open_paragraph()?;
+                    self.writer.write_str("<break/>")?;
+                }
+                Rule => {
+                    self.close_paragraph()?;
+                    self.writer.write_str("<break strength=\"x-strong\"/>\n")?;
+                }
+                TaskListMarker(true) => self.write_text("done: ")?,
+                TaskListMarker(false) => self.write_text("to do: ")?,
+            }
+        }
+        self.close_paragraph()?;
+        self.writer.write_str("</speak>\n")
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph | Tag::Item | Tag::TableHead | Tag::TableRow => {
+                self.after_cell = false;
+                self.close_paragraph()
+            }
+            Tag::Heading(_) => {
+                self.close_paragraph()?;
+                self.open_paragraph()?;
+                self.writer.write_str("<emphasis level=\"strong\">")
+            }
+            Tag::CodeBlock(_) => {
+                self.close_paragraph()?;
+                self.in_code_block = true;
+                Ok(())
+            }
+            Tag::TableCell => {
+                if self.after_cell {
+                    self.open_paragraph()?;
+                    self.writer.write_str("<break strength=\"weak\"/>")?;
+                }
+                self.after_cell = true;
+                Ok(())
+            }
+            Tag::Emphasis => {
+                self.open_paragraph()?;
+                self.writer.write_str("<emphasis>")
+            }
+            Tag::Strong => {
+                self.open_paragraph()?;
+                self.writer.write_str("<emphasis level=\"strong\">")
+            }
+            Tag::BlockQuote
+            | Tag::List(_)
+            | Tag::FootnoteDefinition(_)
+            | Tag::Table(_)
+            | Tag::Strikethrough
+            | Tag::Link(..)
+            | Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Heading(_) => {
+                self.writer.write_str("</emphasis>")?;
+                self.close_paragraph()
+            }
+            Tag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.close_paragraph()
+            }
+            Tag::Emphasis | Tag::Strong => self.writer.write_str("</emphasis>"),
+            Tag::Paragraph
+            | Tag::BlockQuote
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::FootnoteDefinition(_)
+            | Tag::Table(_)
+            | Tag::TableHead
+            | Tag::TableRow => self.close_paragraph(),
+            Tag::TableCell | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..) => Ok(()),
+        }
+    }
+
+    fn open_paragraph(&mut self) -> Result<(), W::Error> {
+        if self.in_paragraph {
+            return Ok(());
+        }
+        self.in_paragraph = true;
+        self.writer.write_str("<p>")
+    }
+
+    fn close_paragraph(&mut self) -> Result<(), W::Error> {
+        if !self.in_paragraph {
+            return Ok(());
+        }
+        self.in_paragraph = false;
+        self.writer.write_str("</p>\n")
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        self.open_paragraph()?;
+        escape_html(&mut self.writer, text)
+    }
+
+    /// Writes inline code, or the text of a code block, read line by line.
+    fn write_code(&mut self, text: &str) -> Result<(), W::Error> {
+        if !self.in_code_block {
+            return self.write_code_line(text);
+        }
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            self.write_code_line(line)?;
+            if let CodeReading::Literal | CodeReading::Characters = self.options.code {
+                self.writer.write_str("<break/>")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_code_line(&mut self, code: &str) -> Result<(), W::Error> {
+        match self.options.code {
+            CodeReading::Literal => self.write_text(code),
+            CodeReading::Characters => {
+                self.open_paragraph()?;
+                self.writer
+                    .write_str("<say-as interpret-as=\"characters\">")?;
+                escape_html(&mut self.writer, code)?;
+                self.writer.write_str("</say-as>")
+            }
+            CodeReading::Skip => Ok(()),
+        }
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate SSML for each `Event`,
+/// and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{ssml, Parser};
+///
+/// let markdown = "# Hello\n\nIt is *very* `cool` & quiet.\n\n- one\n- two\n";
+/// let mut ssml_buf = String::new();
+/// ssml::push_ssml(&mut ssml_buf, Parser::new(markdown));
+/// assert_eq!(ssml_buf, "\
+/// <speak>
+/// <p><emphasis level=\"strong\">Hello</emphasis></p>
+/// <p>It is <emphasis>very</emphasis> cool &amp; quiet.</p>
+/// <p>one</p>
+/// <p>two</p>
+/// </speak>
+/// ");
+/// ```
+pub fn push_ssml<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    push_ssml_ext(s, iter, &Options::default());
+}
+
+/// Like [`push_ssml`](fn.push_ssml.html), but with the given options.
+pub fn push_ssml_ext<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    SsmlWriter::new(iter, s, options).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate SSML for each `Event`,
+/// and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_ssml<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    write_ssml_ext(writer, iter, &Options::default())
+}
+
+/// Like [`write_ssml`](fn.write_ssml.html), but with the given options.
+pub fn write_ssml_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    SsmlWriter::new(iter, WriteWrapper(writer), options).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    fn render(input: &str, code: CodeReading) -> String {
+        let parser_options = crate::Options::ENABLE_TABLES | crate::Options::ENABLE_TASKLISTS;
+        let mut s = String::new();
+        push_ssml_ext(
+            &mut s,
+            Parser::new_ext(input, parser_options),
+            &Options { code },
+        );
+        s
+    }
+
+    #[test]
+    fn blocks() {
+        let input = "\
+Run `ls`,\\
+then **stop**.
+
+---
+
+* [x] tested
+
+| a | b |
+|---|---|
+| c | ![d](e.png) |
+
+```
+ls
+cd
+```
+";
+        let expected = "\
+<speak>
+<p>Run <say-as interpret-as=\"characters\">ls</say-as>,<break/>then <emphasis level=\"strong\">stop</emphasis>.</p>
+<break strength=\"x-strong\"/>
+<p>done: tested</p>
+<p>a<break strength=\"weak\"/>b</p>
+<p>c<break strength=\"weak\"/>d</p>
+<p><say-as interpret-as=\"characters\">ls</say-as><break/><say-as interpret-as=\"characters\">cd</say-as><break/></p>
+</speak>
+";
+        assert_eq!(expected, render(input, CodeReading::Characters));
+    }
+
+    #[test]
+    fn skipped_code() {
+        let input = "Run `ls` now.\n\n    ls\n";
+        assert_eq!(
+            "<speak>\n<p>Run  now.</p>\n</speak>\n",
+            render(input, CodeReading::Skip)
+        );
+    }
+}
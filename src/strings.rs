@@ -3,20 +3,55 @@ use std::convert::{AsRef, TryFrom};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::rc::Rc;
 use std::str::from_utf8;
+use std::sync::Arc;
 
+#[cfg(has_multi_variant_niche)]
 const MAX_INLINE_STR_LEN: usize = 3 * std::mem::size_of::<isize>() - 1;
+#[cfg(not(has_multi_variant_niche))]
+const MAX_INLINE_STR_LEN: usize = 3 * std::mem::size_of::<isize>() - 2;
+
+/// Length of the text stored in an `InlineStr`.
+///
+/// Being an enum rather than a plain `u8` leaves the values past the
+/// largest possible length free for the compiler to use as the `CowStr`
+/// discriminant, so the whole remaining word can be spent on text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+#[rustfmt::skip]
+enum InlineLen {
+    L0, L1, L2, L3, L4, L5, L6, L7, L8, L9, L10, L11,
+    L12, L13, L14, L15, L16, L17, L18, L19, L20, L21, L22, L23,
+}
+
+impl InlineLen {
+    #[rustfmt::skip]
+    const ALL: [InlineLen; 24] = {
+        use InlineLen::*;
+        [
+            L0, L1, L2, L3, L4, L5, L6, L7, L8, L9, L10, L11,
+            L12, L13, L14, L15, L16, L17, L18, L19, L20, L21, L22, L23,
+        ]
+    };
+
+    fn new(len: usize) -> InlineLen {
+        debug_assert!(len <= MAX_INLINE_STR_LEN);
+        InlineLen::ALL[len]
+    }
+}
 
 /// Returned when trying to convert a `&str` into a `InlineStr`
 /// but it fails because it doesn't fit.
 #[derive(Debug)]
 pub struct StringTooLongError;
 
-/// An inline string that can contain almost three words
-/// of utf-8 text.
+/// An inline string that can contain all but a byte or two of three
+/// words of utf-8 text.
 #[derive(Debug, Clone, Copy, Eq)]
 pub struct InlineStr {
     inner: [u8; MAX_INLINE_STR_LEN],
+    len: InlineLen,
 }
 
 impl<'a> AsRef<str> for InlineStr {
@@ -35,8 +70,8 @@ impl From<char> for InlineStr {
     fn from(c: char) -> Self {
         let mut inner = [0u8; MAX_INLINE_STR_LEN];
         c.encode_utf8(&mut inner);
-        inner[MAX_INLINE_STR_LEN - 1] = c.len_utf8() as u8;
-        Self { inner }
+        let len = InlineLen::new(c.len_utf8());
+        Self { inner, len }
     }
 }
 
@@ -51,11 +86,11 @@ impl TryFrom<&str> for InlineStr {
 
     fn try_from(s: &str) -> Result<InlineStr, StringTooLongError> {
         let len = s.len();
-        if len < MAX_INLINE_STR_LEN {
+        if len <= MAX_INLINE_STR_LEN {
             let mut inner = [0u8; MAX_INLINE_STR_LEN];
             inner[..len].copy_from_slice(s.as_bytes());
-            inner[MAX_INLINE_STR_LEN - 1] = len as u8;
-            Ok(Self { inner })
+            let len = InlineLen::new(len);
+            Ok(Self { inner, len })
         } else {
             Err(StringTooLongError)
         }
@@ -66,7 +101,7 @@ impl Deref for InlineStr {
     type Target = str;
 
     fn deref(&self) -> &str {
-        let len = self.inner[MAX_INLINE_STR_LEN - 1] as usize;
+        let len = self.len as usize;
         from_utf8(&self.inner[..len]).unwrap()
     }
 }
@@ -176,6 +211,22 @@ impl<'a> From<String> for CowStr<'a> {
     }
 }
 
+/// Copies the string, as `CowStr` has no variant that shares it: the
+/// contents are inlined when short enough, and boxed otherwise.
+impl<'a> From<Arc<str>> for CowStr<'a> {
+    fn from(s: Arc<str>) -> Self {
+        CowStr::copied(&s)
+    }
+}
+
+/// Copies the string, as `CowStr` has no variant that shares it: the
+/// contents are inlined when short enough, and boxed otherwise.
+impl<'a> From<Rc<str>> for CowStr<'a> {
+    fn from(s: Rc<str>) -> Self {
+        CowStr::copied(&s)
+    }
+}
+
 impl<'a> From<char> for CowStr<'a> {
     fn from(c: char) -> Self {
         CowStr::Inlined(c.into())
@@ -233,6 +284,14 @@ impl<'a> CowStr<'a> {
             CowStr::Inlined(s) => s.deref().to_owned(),
         }
     }
+
+    /// Returns an owned copy of `s`, inlined when short enough.
+    fn copied(s: &str) -> Self {
+        match InlineStr::try_from(s) {
+            Ok(inline) => CowStr::Inlined(inline),
+            Err(..) => CowStr::Boxed(s.into()),
+        }
+    }
 }
 
 impl<'a> fmt::Display for CowStr<'a> {
@@ -281,20 +340,34 @@ mod test_special_string {
     }
 
     #[test]
-    #[cfg(target_pointer_width = "64")]
-    fn inlinestr_fits_twentytwo() {
-        let s = "0123456789abcdefghijkl";
+    fn inline_len_covers_max_inline_str_len() {
+        assert!(MAX_INLINE_STR_LEN < InlineLen::ALL.len());
+    }
+
+    #[test]
+    #[cfg(all(target_pointer_width = "64", has_multi_variant_niche))]
+    fn inlinestr_fits_twentythree() {
+        let s = "0123456789abcdefghijklm";
         let stack_str = InlineStr::try_from(s).unwrap();
         assert_eq!(stack_str.deref(), s);
     }
 
     #[test]
     #[cfg(target_pointer_width = "64")]
-    fn inlinestr_not_fits_twentythree() {
-        let s = "0123456789abcdefghijklm";
+    fn inlinestr_not_fits_twentyfour() {
+        let s = "0123456789abcdefghijklmn";
         let _stack_str = InlineStr::try_from(s).unwrap_err();
     }
 
+    #[test]
+    #[cfg(all(target_pointer_width = "64", not(has_multi_variant_niche)))]
+    fn inlinestr_fits_twentytwo() {
+        let s = "0123456789abcdefghijkl";
+        let stack_str = InlineStr::try_from(s).unwrap();
+        assert_eq!(stack_str.deref(), s);
+        InlineStr::try_from("0123456789abcdefghijklm").unwrap_err();
+    }
+
     #[test]
     #[cfg(target_pointer_width = "64")]
     fn small_boxed_str_clones_to_stack() {
@@ -350,6 +423,19 @@ mod test_special_string {
         assert!(variant_eq(&actual, &expected));
     }
 
+    #[test]
+    fn shared_to_cow_str() {
+        let shared: Arc<str> = Arc::from("a string that is shared by several owners");
+        let actual = CowStr::from(shared.clone());
+        assert!(variant_eq(&actual, &CowStr::Boxed("".into())));
+        assert_eq!(&*actual, &*shared);
+
+        let short: Rc<str> = Rc::from("short");
+        let actual = CowStr::from(short);
+        assert!(variant_eq(&actual, &CowStr::Inlined(InlineStr::from('s'))));
+        assert_eq!("short", &*actual);
+    }
+
     #[test]
     fn cow_char_to_cow_str() {
         let c = 'c';
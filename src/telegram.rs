@@ -0,0 +1,497 @@
+//! Telegram MarkdownV2 writer that takes an iterator of events as input.
+//!
+//! MarkdownV2 is the markup of the Telegram Bot API. It has bold, italic,
+//! strikethrough, links, code and block quotes, but no headings, lists or
+//! tables: headings are written as lines in bold, list items as lines
+//! starting with `•` or their number, and table rows as lines of cells
+//! separated by `|`. Raw HTML is dropped, and images are written as links
+//! to the image.
+//!
+//! Every character the API reserves has to be escaped outside of code, even
+//! where it couldn't be read as markup, or the message is rejected. Inside
+//! code only backticks and backslashes are, and inside link destinations
+//! only closing parentheses and backslashes.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::lines::{self, Container};
+use crate::strings::CowStr;
+use crate::Event::*;
+use crate::{CodeBlockKind, Event, LinkType, Tag};
+
+/// Writes `s` to `w`, escaping all the characters MarkdownV2 reserves.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::telegram::escape_telegram;
+///
+/// let mut s = String::new();
+/// escape_telegram(&mut s, "1. Costs $5 (or less)!").unwrap();
+/// assert_eq!(s, "1\\. Costs $5 \\(or less\\)\\!");
+/// ```
+pub fn escape_telegram<W: StrWrite>(w: W, s: &str) -> Result<(), W::Error> {
+    escape_chars(w, s, b"_*[]()~`>#+-=|{}.!\\")
+}
+
+/// Writes the text of inline code or of a code block.
+fn escape_code<W: StrWrite>(w: W, s: &str) -> Result<(), W::Error> {
+    escape_chars(w, s, b"`\\")
+}
+
+/// Writes the destination of a link.
+fn escape_url<W: StrWrite>(w: W, s: &str) -> Result<(), W::Error> {
+    escape_chars(w, s, b")\\")
+}
+
+fn escape_chars<W: StrWrite>(mut w: W, s: &str, special: &[u8]) -> Result<(), W::Error> {
+    let mut mark = 0;
+    for (ix, c) in s.bytes().enumerate() {
+        if special.contains(&c) {
+            w.write_str(&s[mark..ix])?;
+            w.write_str("\\")?;
+            mark = ix;
+        }
+    }
+    w.write_str(&s[mark..])
+}
+
+/// An open list.
+struct List {
+    /// Number of the next item, `None` for bullet lists.
+    number: Option<u64>,
+    /// Whether the items hold paragraphs, and are separated by blank lines.
+    loose: bool,
+}
+
+struct TelegramWriter<'a, I, W> {
+    iter: I,
+    writer: W,
+    containers: Vec<Container>,
+    lists: Vec<List>,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Whether a blank line goes before the next block.
+    blank_line: bool,
+    in_code_block: bool,
+    /// Number of open elements written in bold, which can't be nested.
+    bold: usize,
+    in_table_head: bool,
+    /// Whether a table cell was written in the current row.
+    after_cell: bool,
+    /// Whether the last thing written was an underscore delimiter.
+    after_underscore: bool,
+    /// Nesting depth of email autolinks, whose text is written alone.
+    emails: usize,
+    numbers: HashMap<CowStr<'a>, usize>,
+}
+
+impl<'a, I, W> TelegramWriter<'a, I, W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: StrWrite,
+{
+    fn new(iter: I, writer: W) -> Self {
+        TelegramWriter {
+            iter,
+            writer,
+            containers: Vec::new(),
+            lists: Vec::new(),
+            line_start: true,
+            blank_line: false,
+            in_code_block: false,
+            bold: 0,
+            in_table_head: false,
+            after_cell: false,
+            after_underscore: false,
+            emails: 0,
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<(), W::Error> {
+        while let Some(event) = self.iter.next() {
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => {
+                    escape_code(&mut self.writer, &text)?;
+                    self.line_start = text.ends_with('\n');
+                }
+                Text(text) => self.write_text(&text)?,
+                Code(text) => {
+                    self.write("`")?;
+                    escape_code(&mut self.writer, &text)?;
+                    self.write("`")?;
+                }
+                Html(_) => (),
+                SoftBreak => self.write(" ")?,
+                HardBreak => self.newline()?,
+                Rule => {
+                    self.start_block()?;
+                    self.write("\\-\\-\\-")?;
+                    self.end_block()?;
+                }
+                FootnoteReference(name) => {
+                    let marker = format!("\\[{}\\]", self.footnote_number(name));
+                    self.write(&marker)?;
+                }
+                TaskListMarker(true) => self.write("\\[x\\] ")?,
+                TaskListMarker(false) => self.write("\\[ \\] ")?,
+            }
+        }
+        self.end_line()
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => {
+                if let Some(list) = self.lists.last_mut() {
+                    list.loose = true;
+                }
+                self.start_block()
+            }
+            Tag::Heading(_) => {
+                self.start_block()?;
+                self.open_bold()
+            }
+            Tag::BlockQuote => {
+                self.start_block()?;
+                // quotes can't be nested
+                let prefix = if self.in_quote() { "" } else { ">" };
+                self.push_container(prefix.to_owned(), prefix.to_owned());
+                Ok(())
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block()?;
+                self.write("```")?;
+                if let CodeBlockKind::Fenced(info) = kind {
+                    let lang = info.split(' ').next().unwrap();
+                    escape_code(&mut self.writer, lang)?;
+                }
+                self.writer.write_str("\n")?;
+                // the lines of code are written without prefixes, which
+                // would be part of the code
+                self.line_start = true;
+                self.in_code_block = true;
+                Ok(())
+            }
+            Tag::List(start) => {
+                self.start_block()?;
+                self.lists.push(List {
+                    number: start,
+                    loose: false,
+                });
+                Ok(())
+            }
+            Tag::Item => {
+                self.start_block()?;
+                let number = self.lists.last_mut().and_then(|list| list.number.as_mut());
+                let marker = match number {
+                    Some(number) => {
+                        *number += 1;
+                        format!("{}\\. ", *number - 1)
+                    }
+                    None => "• ".to_owned(),
+                };
+                let width = marker.chars().filter(|&c| c != '\\').count();
+                self.push_container(marker, " ".repeat(width));
+                Ok(())
+            }
+            Tag::FootnoteDefinition(name) => {
+                self.start_block()?;
+                let number = self.footnote_number(name).to_string();
+                let indent = " ".repeat(number.len() + 3);
+                self.push_container(format!("\\[{}\\] ", number), indent);
+                Ok(())
+            }
+            Tag::Table(_) => self.start_block(),
+            Tag::TableHead => {
+                self.in_table_head = true;
+                self.after_cell = false;
+                Ok(())
+            }
+            Tag::TableRow => {
+                self.after_cell = false;
+                Ok(())
+            }
+            Tag::TableCell => {
+                if self.after_cell {
+                    self.write(" \\| ")?;
+                }
+                self.after_cell = true;
+                if self.in_table_head {
+                    self.open_bold()?;
+                }
+                Ok(())
+            }
+            Tag::Emphasis => self.write_underscore(),
+            Tag::Strong => self.open_bold(),
+            Tag::Strikethrough => self.write("~"),
+            Tag::Link(LinkType::Email, ..) => {
+                self.emails += 1;
+                Ok(())
+            }
+            Tag::Link(..) | Tag::Image(..) if self.emails > 0 => Ok(()),
+            Tag::Link(..) | Tag::Image(..) => self.write("["),
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph | Tag::Table(_) => self.end_block(),
+            Tag::Heading(_) => {
+                self.close_bold()?;
+                self.end_block()
+            }
+            Tag::BlockQuote | Tag::FootnoteDefinition(_) => {
+                self.end_line()?;
+                self.containers.pop();
+                self.blank_line = true;
+                Ok(())
+            }
+            Tag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.writer.write_str("```")?;
+                self.line_start = false;
+                self.end_block()
+            }
+            Tag::List(_) => {
+                self.end_line()?;
+                self.lists.pop();
+                // a nested list is part of its item
+                self.blank_line = self.lists.last().map_or(true, |list| list.loose);
+                Ok(())
+            }
+            Tag::Item => {
+                self.end_line()?;
+                self.containers.pop();
+                Ok(())
+            }
+            Tag::TableHead => {
+                self.in_table_head = false;
+                self.end_line()
+            }
+            Tag::TableRow => self.end_line(),
+            Tag::TableCell if self.in_table_head => self.close_bold(),
+            Tag::TableCell => Ok(()),
+            Tag::Emphasis => self.write_underscore(),
+            Tag::Strong => self.close_bold(),
+            Tag::Strikethrough => self.write("~"),
+            Tag::Link(LinkType::Email, ..) => {
+                self.emails -= 1;
+                Ok(())
+            }
+            Tag::Link(..) | Tag::Image(..) if self.emails > 0 => Ok(()),
+            Tag::Link(_, dest, _) | Tag::Image(_, dest, _) => {
+                self.write("](")?;
+                escape_url(&mut self.writer, &dest)?;
+                self.write(")")
+            }
+        }
+    }
+
+    fn footnote_number(&mut self, name: CowStr<'a>) -> usize {
+        let len = self.numbers.len() + 1;
+        *self.numbers.entry(name).or_insert(len)
+    }
+
+    fn push_container(&mut self, first: String, rest: String) {
+        self.containers.push(Container::new(first, rest, false));
+    }
+
+    fn in_quote(&self) -> bool {
+        self.containers
+            .iter()
+            .any(|container| container.rest == ">")
+    }
+
+    fn open_bold(&mut self) -> Result<(), W::Error> {
+        self.bold += 1;
+        if self.bold == 1 {
+            self.write("*")?;
+        }
+        Ok(())
+    }
+
+    fn close_bold(&mut self) -> Result<(), W::Error> {
+        self.bold -= 1;
+        if self.bold == 0 {
+            self.write("*")?;
+        }
+        Ok(())
+    }
+
+    /// Writes an italic delimiter. Two underscores in a row are read as an
+    /// underline delimiter, unless a carriage return, which Telegram
+    /// ignores, separates them.
+    fn write_underscore(&mut self) -> Result<(), W::Error> {
+        if self.after_underscore {
+            self.write("\r")?;
+        }
+        self.write("_")?;
+        self.after_underscore = true;
+        Ok(())
+    }
+
+    /// Ends the current line, and writes the blank line separating the
+    /// block that starts from the previous one.
+    fn start_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        if self.blank_line {
+            self.blank_line = false;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.blank_line = true;
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.newline()
+        }
+    }
+
+    /// Writes a line break, preceded by the prefix of the containers when
+    /// the line is empty.
+    fn newline(&mut self) -> Result<(), W::Error> {
+        lines::newline(&mut self.writer, &mut self.containers, self.line_start)?;
+        self.line_start = true;
+        self.after_underscore = false;
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write("")?;
+        escape_telegram(&mut self.writer, text)
+    }
+
+    /// Writes markup, preceded by the prefix of the containers at the start
+    /// of a line.
+    fn write(&mut self, markup: &str) -> Result<(), W::Error> {
+        if self.line_start {
+            let prefix = lines::prefix(&mut self.containers);
+            self.writer.write_str(&prefix)?;
+            self.line_start = false;
+        }
+        self.after_underscore = false;
+        self.writer.write_str(markup)
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate Telegram MarkdownV2 for
+/// each `Event`, and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{telegram, Parser};
+///
+/// let markdown = "# Build 1.2\n\n- *All* tests pass.\n- See [the log](http://ci/log_(1)).\n";
+/// let mut message = String::new();
+/// telegram::push_telegram(&mut message, Parser::new(markdown));
+/// assert_eq!(message, "\
+/// *Build 1\\.2*
+///
+/// • _All_ tests pass\\.
+/// • See [the log](http://ci/log_(1\\))\\.
+/// ");
+/// ```
+pub fn push_telegram<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    TelegramWriter::new(iter, s).run().unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate Telegram MarkdownV2 for
+/// each `Event`, and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_telegram<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    TelegramWriter::new(iter, WriteWrapper(writer)).run()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn blocks() {
+        let input = "\
+Some **bold _and_ italic**, *a**b***, ~~old~~ and `` a`b ``[^n].
+
+> quoted
+>
+> > nested
+
+1. one
+2. two
+   - [x] done
+
+| a | b |
+|---|---|
+| ![c](c.png) | <d@e.org> |
+
+```rust
+let s = \"`\\\\`\";
+```
+
+[^n]: Note.
+";
+        let expected = "\
+Some *bold _and_ italic*, _a*b*_, ~old~ and `a\\`b`\\[1\\]\\.
+
+>quoted
+>
+>nested
+
+1\\. one
+2\\. two
+   • \\[x\\] done
+
+*a* \\| *b*
+[c](c.png) \\| d@e\\.org
+
+```rust
+let s = \"\\`\\\\\\\\\\`\";
+```
+
+\\[1\\] Note\\.
+";
+        let parser_options = crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_STRIKETHROUGH
+            | crate::Options::ENABLE_TASKLISTS;
+        let mut s = String::new();
+        push_telegram(&mut s, Parser::new_ext(input, parser_options));
+        assert_eq!(expected, s);
+    }
+
+    #[test]
+    fn adjacent_italics() {
+        let mut s = String::new();
+        push_telegram(&mut s, Parser::new("*a*_b_ and _*c*_"));
+        assert_eq!("_a_\r_b_ and _\r_c_\r_\n", s);
+    }
+}
@@ -59,8 +59,14 @@ impl<T: Default> Tree<T> {
     // Indices start at one, so we place a dummy value at index zero.
     // The alternative would be subtracting one from every TreeIndex
     // every time we convert it to usize to index our nodes.
-    pub(crate) fn with_capacity(cap: usize) -> Tree<T> {
-        let mut nodes = Vec::with_capacity(cap);
+    // The given storage, which must be empty, is reused.
+    pub(crate) fn with_storage(
+        mut nodes: Vec<Node<T>>,
+        spine: Vec<TreeIndex>,
+        cap: usize,
+    ) -> Tree<T> {
+        debug_assert!(nodes.is_empty() && spine.is_empty());
+        nodes.reserve(cap);
         nodes.push(Node {
             child: None,
             next: None,
@@ -68,11 +74,28 @@ impl<T: Default> Tree<T> {
         });
         Tree {
             nodes,
+            spine,
+            cur: None,
+        }
+    }
+
+    /// A tree without even the dummy node, which does not allocate. It has
+    /// no focus, and no node can be created in it.
+    pub(crate) fn detached() -> Tree<T> {
+        Tree {
+            nodes: Vec::new(),
             spine: Vec::new(),
             cur: None,
         }
     }
 
+    /// Returns the storage of the nodes and the spine, emptied.
+    pub(crate) fn into_storage(mut self) -> (Vec<Node<T>>, Vec<TreeIndex>) {
+        self.nodes.clear();
+        self.spine.clear();
+        (self.nodes, self.spine)
+    }
+
     /// Returns the index of the element currently in focus.
     pub(crate) fn cur(&self) -> Option<TreeIndex> {
         self.cur
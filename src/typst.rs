@@ -0,0 +1,659 @@
+//! Typst writer that takes an iterator of events as input.
+//!
+//! Headings, lists, emphasis and raw blocks use the Typst markup syntax,
+//! while tables, links, images and footnotes become calls to the `table`,
+//! `link`, `image` and `footnote` functions. Raw HTML is dropped.
+//!
+//! The parser has no math syntax, so math is passed through by
+//! [`Options::math`](struct.Options.html#structfield.math) instead: text
+//! between dollar signs, as found by
+//! [`RenderMath`](../utils/math/struct.RenderMath.html), is then written as
+//! is, to be read as Typst math.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::escape::{StrWrite, WriteWrapper};
+use crate::strings::CowStr;
+use crate::utils::math::{MathDisplay, RenderMath};
+use crate::Event::*;
+use crate::{Alignment, CodeBlockKind, Event, LinkType, Tag};
+
+/// Options of the Typst writer.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Writes text between dollar signs as is, as Typst math, and drops
+    /// raw HTML earlier, since the math ends up in `Html` events.
+    pub math: bool,
+}
+
+/// Writes `s` to `w`, escaping the characters that are special in Typst
+/// markup anywhere in a line.
+///
+/// Markers that are only special at the start of a line, like `=` and
+/// `-`, are left alone.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::typst::escape_typst;
+///
+/// let mut s = String::new();
+/// escape_typst(&mut s, "#1 *of* [two] // 50$").unwrap();
+/// assert_eq!(s, "\\#1 \\*of\\* \\[two\\] \\/\\/ 50\\$");
+/// ```
+pub fn escape_typst<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    let bytes = s.as_bytes();
+    let mut mark = 0;
+    for (ix, &c) in bytes.iter().enumerate() {
+        let next = bytes.get(ix + 1).copied();
+        let special = match c {
+            b'\\' | b'*' | b'_' | b'`' | b'#' | b'$' | b'[' | b']' | b'<' | b'>' | b'@' | b'~' => {
+                true
+            }
+            // comments and dashes
+            b'/' => next == Some(b'/') || next == Some(b'*') || ix > 0 && bytes[ix - 1] == b'/',
+            b'-' => next == Some(b'-'),
+            _ => false,
+        };
+        if special {
+            w.write_str(&s[mark..ix])?;
+            w.write_str("\\")?;
+            mark = ix;
+        }
+    }
+    w.write_str(&s[mark..])
+}
+
+/// Writes `s` as a Typst string literal.
+fn write_string<W: StrWrite>(mut w: W, s: &str) -> Result<(), W::Error> {
+    w.write_str("\"")?;
+    let mut mark = 0;
+    for (ix, c) in s.char_indices() {
+        if let '"' | '\\' = c {
+            w.write_str(&s[mark..ix])?;
+            w.write_str("\\")?;
+            mark = ix;
+        }
+    }
+    w.write_str(&s[mark..])?;
+    w.write_str("\"")
+}
+
+/// Returns the length of the prefix of `line` that Typst would read as a
+/// heading or list marker at the start of a line.
+fn line_marker_len(line: &str) -> Option<usize> {
+    match line.bytes().next()? {
+        b'=' | b'-' | b'+' | b'/' => Some(0),
+        b'0'..=b'9' => {
+            let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+            if line[digits..].starts_with('.') {
+                Some(digits)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Pushes `event` to `events`, merging adjacent text so that line start
+/// markers are found in text split by the parser.
+fn push_merged<'a>(events: &mut Vec<Event<'a>>, event: Event<'a>) {
+    if let (Some(Text(last)), Text(text)) = (events.last_mut(), &event) {
+        let mut merged = last.to_string();
+        merged.push_str(text);
+        *last = merged.into();
+        return;
+    }
+    events.push(event);
+}
+
+/// A footnote definition, rendered up front since Typst writes footnotes
+/// where they are referenced.
+struct Footnote {
+    content: String,
+    number: usize,
+    /// Whether the footnote is referenced more than once, and gets a label
+    /// for the later references.
+    labeled: bool,
+    written: bool,
+}
+
+struct TypstWriter<'a, W> {
+    writer: W,
+    options: Options,
+    /// Indentation of the lines inside the current list items.
+    indent: String,
+    /// Numbers of the open lists, `None` for bullet lists, and whether they
+    /// are loose.
+    lists: Vec<(Option<u64>, bool)>,
+    /// Whether nothing has been written on the current line yet.
+    line_start: bool,
+    /// Whether a blank line goes before the next block.
+    blank_line: bool,
+    in_heading: bool,
+    in_code_block: bool,
+    table_cell_index: usize,
+    /// Alt text of the image being written.
+    alt: Option<String>,
+    /// Nesting depth of autolinks, whose text is the destination.
+    autolinks: usize,
+    footnotes: HashMap<CowStr<'a>, Footnote>,
+}
+
+impl<'a, W> TypstWriter<'a, W>
+where
+    W: StrWrite,
+{
+    fn new(writer: W, options: &Options) -> Self {
+        TypstWriter {
+            writer,
+            options: options.clone(),
+            indent: String::new(),
+            lists: Vec::new(),
+            line_start: true,
+            blank_line: false,
+            in_heading: false,
+            in_code_block: false,
+            table_cell_index: 0,
+            alt: None,
+            autolinks: 0,
+            footnotes: HashMap::new(),
+        }
+    }
+
+    /// Takes the footnote definitions out of the events, and renders them.
+    fn collect_footnotes<I>(&mut self, iter: I) -> Vec<Event<'a>>
+    where
+        I: Iterator<Item = Event<'a>>,
+    {
+        let mut events = Vec::new();
+        let mut definitions = Vec::new();
+        let mut references = HashMap::new();
+        let mut definition: Option<(CowStr<'a>, Vec<Event<'a>>)> = None;
+        let iter: Box<dyn Iterator<Item = Event<'a>>> = if self.options.math {
+            let text = iter.filter(|event| !matches!(event, Html(_)));
+            Box::new(RenderMath::new(text, |tex, display| match display {
+                MathDisplay::Inline => format!("${}$", tex),
+                MathDisplay::Block => format!("$ {} $", tex),
+            }))
+        } else {
+            Box::new(iter)
+        };
+        for event in iter {
+            if let FootnoteReference(ref name) = event {
+                *references.entry(name.clone()).or_insert(0) += 1;
+            }
+            match (event, definition.as_mut().map(|(_, events)| events)) {
+                (Start(Tag::FootnoteDefinition(name)), _) => definition = Some((name, Vec::new())),
+                (End(Tag::FootnoteDefinition(_)), _) => definitions.extend(definition.take()),
+                (event, Some(definition)) => push_merged(definition, event),
+                (event, None) => push_merged(&mut events, event),
+            }
+        }
+        for (name, events) in definitions {
+            let mut content = String::new();
+            TypstWriter::new(&mut content, &self.options)
+                .write_events(events.into_iter())
+                .unwrap();
+            let number = self.footnotes.len() + 1;
+            let footnote = Footnote {
+                content: content.trim_end().to_owned(),
+                number,
+                labeled: references.get(&name).map_or(false, |&count| count > 1),
+                written: false,
+            };
+            self.footnotes.insert(name, footnote);
+        }
+        events
+    }
+
+    fn run<I>(mut self, iter: I) -> Result<(), W::Error>
+    where
+        I: Iterator<Item = Event<'a>>,
+    {
+        let events = self.collect_footnotes(iter);
+        self.write_events(events.into_iter())
+    }
+
+    fn write_events<I>(&mut self, iter: I) -> Result<(), W::Error>
+    where
+        I: Iterator<Item = Event<'a>>,
+    {
+        for event in iter {
+            if let Some(ref mut alt) = self.alt {
+                match event {
+                    End(Tag::Image(..)) => (),
+                    Text(text) | Code(text) => {
+                        alt.push_str(&text);
+                        continue;
+                    }
+                    _ => continue,
+                }
+            }
+            match event {
+                Start(tag) => self.start_tag(tag)?,
+                End(tag) => self.end_tag(tag)?,
+                Text(text) if self.in_code_block => self.put(&text)?,
+                Text(text) if self.autolinks == 0 => self.write_text(&text)?,
+                Html(math) if self.options.math => self.put(&math)?,
+                Text(_) | Html(_) => (),
+                Code(text) => {
+                    if text.contains('`') {
+                        self.put("#raw(")?;
+                        write_string(&mut self.writer, &text)?;
+                        self.put(")")?;
+                    } else {
+                        self.put("`")?;
+                        self.put(&text)?;
+                        self.put("`")?;
+                    }
+                }
+                SoftBreak if self.in_heading => self.put(" ")?,
+                SoftBreak => self.put("\n")?,
+                HardBreak => self.put("\\\n")?,
+                Rule => {
+                    self.start_block()?;
+                    self.put("#line(length: 100%)")?;
+                    self.end_block()?;
+                }
+                FootnoteReference(name) => self.write_footnote(name)?,
+                TaskListMarker(true) => self.put("☒ ")?,
+                TaskListMarker(false) => self.put("☐ ")?,
+            }
+        }
+        self.end_line()
+    }
+
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => {
+                if let Some(list) = self.lists.last_mut() {
+                    list.1 = true;
+                }
+                self.start_block()
+            }
+            Tag::Heading(level) => {
+                self.start_block()?;
+                self.in_heading = true;
+                self.put(&"=".repeat(level as usize))?;
+                self.put(" ")
+            }
+            Tag::BlockQuote => {
+                self.start_block()?;
+                self.put("#quote(block: true)[\n")
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block()?;
+                self.in_code_block = true;
+                let lang = match kind {
+                    CodeBlockKind::Fenced(ref info) => info.split(' ').next().unwrap(),
+                    CodeBlockKind::Indented => "",
+                };
+                self.put("```")?;
+                self.put(lang)?;
+                self.put("\n")
+            }
+            Tag::List(start) => {
+                self.start_block()?;
+                self.lists.push((start, false));
+                Ok(())
+            }
+            Tag::Item => {
+                self.start_block()?;
+                let marker = match self.lists.last_mut() {
+                    Some((Some(number), _)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_owned(),
+                };
+                self.put(&marker)?;
+                self.indent.push_str(&" ".repeat(marker.len()));
+                Ok(())
+            }
+            // taken out of the events by `collect_footnotes`
+            Tag::FootnoteDefinition(_) => Ok(()),
+            Tag::Table(alignments) => {
+                self.start_block()?;
+                self.put(&format!("#table(\n  columns: {},\n", alignments.len()))?;
+                if alignments
+                    .iter()
+                    .any(|&alignment| alignment != Alignment::None)
+                {
+                    let alignments: Vec<&str> = alignments
+                        .iter()
+                        .map(|alignment| match alignment {
+                            Alignment::None => "auto",
+                            Alignment::Left => "left",
+                            Alignment::Center => "center",
+                            Alignment::Right => "right",
+                        })
+                        .collect();
+                    let comma = if alignments.len() == 1 { "," } else { "" };
+                    self.put(&format!("  align: ({}{}),\n", alignments.join(", "), comma))?;
+                }
+                Ok(())
+            }
+            Tag::TableHead => {
+                self.table_cell_index = 0;
+                self.put("  table.header(")
+            }
+            Tag::TableRow => {
+                self.table_cell_index = 0;
+                self.put("  ")
+            }
+            Tag::TableCell => {
+                if self.table_cell_index > 0 {
+                    self.put(", ")?;
+                }
+                self.table_cell_index += 1;
+                self.put("[")
+            }
+            Tag::Emphasis => self.put("_"),
+            Tag::Strong => self.put("*"),
+            Tag::Strikethrough => self.put("#strike["),
+            Tag::Link(LinkType::Autolink, dest, _) => {
+                self.autolinks += 1;
+                self.put("#link(")?;
+                write_string(&mut self.writer, &dest)?;
+                self.put(")")
+            }
+            Tag::Link(link_type, dest, _) => {
+                self.put("#link(")?;
+                if link_type == LinkType::Email {
+                    write_string(&mut self.writer, &format!("mailto:{}", dest))?;
+                } else {
+                    write_string(&mut self.writer, &dest)?;
+                }
+                self.put(")[")
+            }
+            Tag::Image(..) => {
+                self.alt = Some(String::new());
+                Ok(())
+            }
+        }
+    }
+
+    fn end_tag(&mut self, tag: Tag<'a>) -> Result<(), W::Error> {
+        match tag {
+            Tag::Paragraph => self.end_block(),
+            Tag::Heading(_) => {
+                self.in_heading = false;
+                self.end_block()
+            }
+            Tag::BlockQuote => {
+                self.end_line()?;
+                self.put("]")?;
+                self.end_block()
+            }
+            Tag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.end_line()?;
+                self.put("```")?;
+                self.end_block()
+            }
+            Tag::List(_) => {
+                self.end_line()?;
+                self.lists.pop();
+                // a nested list is part of its item
+                self.blank_line = self.lists.last().map_or(true, |list| list.1);
+                Ok(())
+            }
+            Tag::Item => {
+                self.end_line()?;
+                let len = self.indent.len();
+                let marker_len = match self.lists.last() {
+                    Some((Some(number), _)) => format!("{}. ", number - 1).len(),
+                    _ => 2,
+                };
+                self.indent.truncate(len - marker_len);
+                Ok(())
+            }
+            Tag::FootnoteDefinition(_) => Ok(()),
+            Tag::Table(_) => {
+                self.put(")")?;
+                self.end_block()
+            }
+            Tag::TableHead => self.put("),\n"),
+            Tag::TableRow => self.put(",\n"),
+            Tag::TableCell => self.put("]"),
+            Tag::Emphasis => self.put("_"),
+            Tag::Strong => self.put("*"),
+            Tag::Strikethrough => self.put("]"),
+            Tag::Link(LinkType::Autolink, ..) => {
+                self.autolinks -= 1;
+                Ok(())
+            }
+            Tag::Link(..) => self.put("]"),
+            Tag::Image(_, dest, _) => {
+                let alt = self.alt.take().unwrap_or_default();
+                self.put("#image(")?;
+                write_string(&mut self.writer, &dest)?;
+                if !alt.is_empty() {
+                    self.put(", alt: ")?;
+                    write_string(&mut self.writer, &alt)?;
+                }
+                self.put(")")
+            }
+        }
+    }
+
+    fn write_footnote(&mut self, name: CowStr<'a>) -> Result<(), W::Error> {
+        let footnote = match self.footnotes.get_mut(&name) {
+            Some(footnote) => footnote,
+            None => return self.write_text(&format!("[^{}]", name)),
+        };
+        let markup = if !footnote.written {
+            footnote.written = true;
+            let label = if footnote.labeled {
+                format!(" <fn-{}>", footnote.number)
+            } else {
+                String::new()
+            };
+            format!("#footnote[{}]{}", footnote.content, label)
+        } else if footnote.labeled {
+            format!("#footnote(<fn-{}>)", footnote.number)
+        } else {
+            format!("#footnote[{}]", footnote.content)
+        };
+        self.put(&markup)
+    }
+
+    /// Ends the current line, and writes the blank line separating the
+    /// block that starts from the previous one.
+    fn start_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        if self.blank_line {
+            self.blank_line = false;
+            self.put("\n")?;
+        }
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> Result<(), W::Error> {
+        self.end_line()?;
+        self.blank_line = true;
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.line_start {
+            Ok(())
+        } else {
+            self.put("\n")
+        }
+    }
+
+    /// Writes escaped text.
+    fn write_text(&mut self, text: &str) -> Result<(), W::Error> {
+        let mut markup = String::new();
+        for (ix, line) in text.split('\n').enumerate() {
+            let mut line = line;
+            if ix > 0 || self.line_start {
+                if ix > 0 {
+                    markup.push('\n');
+                }
+                if let Some(len) = line_marker_len(line) {
+                    markup.push_str(&line[..len]);
+                    markup.push('\\');
+                    line = &line[len..];
+                }
+            }
+            escape_typst(&mut markup, line).unwrap();
+        }
+        self.put(&markup)
+    }
+
+    /// Writes markup, indenting the lines inside list items.
+    fn put(&mut self, markup: &str) -> Result<(), W::Error> {
+        for (ix, line) in markup.split('\n').enumerate() {
+            if ix > 0 {
+                self.writer.write_str("\n")?;
+                self.line_start = true;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.line_start {
+                self.writer.write_str(&self.indent)?;
+                self.line_start = false;
+            }
+            self.writer.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate Typst markup for each
+/// `Event`, and push it to a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{typst, Parser};
+///
+/// let markdown = "# Intro\n\nSome *emphasis*, 50% of #1.\n\n1. one\n2. two\n";
+/// let mut typst_buf = String::new();
+/// typst::push_typst(&mut typst_buf, Parser::new(markdown));
+/// assert_eq!(typst_buf, "\
+/// = Intro
+///
+/// Some _emphasis_, 50% of \\#1.
+///
+/// 1. one
+/// 2. two
+/// ");
+/// ```
+pub fn push_typst<'a, I>(s: &mut String, iter: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    push_typst_ext(s, iter, &Options::default());
+}
+
+/// Like [`push_typst`](fn.push_typst.html), but with the given options.
+pub fn push_typst_ext<'a, I>(s: &mut String, iter: I, options: &Options)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    TypstWriter::new(s, options).run(iter).unwrap();
+}
+
+/// Iterate over an `Iterator` of `Event`s, generate Typst markup for each
+/// `Event`, and write it out to a writable stream.
+///
+/// **Note**: using this function with an unbuffered writer like a file or
+/// socket will result in poor performance. Wrap these in a
+/// [`BufWriter`](https://doc.rust-lang.org/std/io/struct.BufWriter.html) to
+/// prevent unnecessary slowdowns.
+pub fn write_typst<'a, I, W>(writer: W, iter: I) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    write_typst_ext(writer, iter, &Options::default())
+}
+
+/// Like [`write_typst`](fn.write_typst.html), but with the given options.
+pub fn write_typst_ext<'a, I, W>(writer: W, iter: I, options: &Options) -> io::Result<()>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: io::Write,
+{
+    TypstWriter::new(WriteWrapper(writer), options).run(iter)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    fn render(input: &str, options: &Options) -> String {
+        let parser_options = crate::Options::ENABLE_TABLES
+            | crate::Options::ENABLE_FOOTNOTES
+            | crate::Options::ENABLE_TASKLISTS
+            | crate::Options::ENABLE_STRIKETHROUGH;
+        let mut s = String::new();
+        push_typst_ext(&mut s, Parser::new_ext(input, parser_options), options);
+        s
+    }
+
+    #[test]
+    fn blocks() {
+        let input = "\
+* [x] done
+* nested
+  - ~~old~~ `` a`b ``
+
+> A [link](http://x.org/\"a\") and <http://y.org/>[^n], again[^n].
+> 2019\\. was a year
+
+| a | b |
+|---|--:|
+| `c` | ![alt *x*](e.png) |
+
+```rust
+fn f() {}
+```
+
+[^n]: Note.
+";
+        let expected = "\
+- ☒ done
+- nested
+  - #strike[old] #raw(\"a`b\")
+
+#quote(block: true)[
+A #link(\"http://x.org/\\\"a\\\"\")[link] and #link(\"http://y.org/\")#footnote[Note.] <fn-1>, again#footnote(<fn-1>).
+2019\\. was a year
+]
+
+#table(
+  columns: 2,
+  align: (auto, right),
+  table.header([a], [b]),
+  [`c`], [#image(\"e.png\", alt: \"alt x\")],
+)
+
+```rust
+fn f() {}
+```
+";
+        assert_eq!(expected, render(input, &Options::default()));
+    }
+
+    #[test]
+    fn math() {
+        let input = "Area $pi r^2$ for *r*, $5 and $10.\n\n$$sum_i x_i$$\n";
+        assert_eq!(
+            "Area $pi r^2$ for _r_, \\$5 and \\$10.\n\n$ sum_i x_i $\n",
+            render(input, &Options { math: true })
+        );
+        assert_eq!(
+            "Area \\$pi r^2\\$ for _r_, \\$5 and \\$10.\n\n\\$\\$sum\\_i x\\_i\\$\\$\n",
+            render(input, &Options::default())
+        );
+    }
+}
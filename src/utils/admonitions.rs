@@ -0,0 +1,299 @@
+//! Admonitions written as block quotes.
+
+use std::collections::VecDeque;
+
+use crate::strings::CowStr;
+use crate::{Event, Tag};
+
+/// The kind of an admonition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdmonitionKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl AdmonitionKind {
+    /// Returns the kind named by a label such as `Note` or `warning`.
+    ///
+    /// Besides the names of the kinds, `Info` is accepted for notes, `Hint`
+    /// for tips and `Danger` for cautions.
+    pub fn from_label(label: &str) -> Option<AdmonitionKind> {
+        let kind = match label.to_ascii_lowercase().as_str() {
+            "note" | "info" => AdmonitionKind::Note,
+            "tip" | "hint" => AdmonitionKind::Tip,
+            "important" => AdmonitionKind::Important,
+            "warning" => AdmonitionKind::Warning,
+            "caution" | "danger" => AdmonitionKind::Caution,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    fn from_emoji(emoji: char) -> Option<AdmonitionKind> {
+        let kind = match emoji {
+            'ℹ' | '📝' => AdmonitionKind::Note,
+            '💡' => AdmonitionKind::Tip,
+            '❗' => AdmonitionKind::Important,
+            '⚠' => AdmonitionKind::Warning,
+            '🛑' | '⛔' => AdmonitionKind::Caution,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// The CSS class of the kind, e.g. `warning`.
+    pub fn class(self) -> &'static str {
+        match self {
+            AdmonitionKind::Note => "note",
+            AdmonitionKind::Tip => "tip",
+            AdmonitionKind::Important => "important",
+            AdmonitionKind::Warning => "warning",
+            AdmonitionKind::Caution => "caution",
+        }
+    }
+
+    /// The title shown for the kind, e.g. `Warning`.
+    pub fn title(self) -> &'static str {
+        match self {
+            AdmonitionKind::Note => "Note",
+            AdmonitionKind::Tip => "Tip",
+            AdmonitionKind::Important => "Important",
+            AdmonitionKind::Warning => "Warning",
+            AdmonitionKind::Caution => "Caution",
+        }
+    }
+}
+
+/// Iterator adapter that turns block quotes following an admonition
+/// convention into admonition containers.
+///
+/// A block quote is an admonition when its first paragraph starts with one
+/// of these markers:
+///
+/// * a bold label, like `> **Note:** text` or `> **Warning**: text`,
+/// * an emoji, like `> ⚠️ text` or `> 💡 text`,
+/// * a GitHub style label, like `> [!NOTE]`.
+///
+/// The marker is removed and the block quote tags are replaced by raw HTML:
+///
+/// ```html
+/// <div class="admonition warning">
+/// <p class="admonition-title">Warning</p>
+/// ...
+/// </div>
+/// ```
+///
+/// Labels are recognized by
+/// [`AdmonitionKind::from_label`](enum.AdmonitionKind.html#method.from_label).
+/// Other block quotes are passed through unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::admonitions::Admonitions;
+/// use pulldown_cmark::{html, Parser};
+///
+/// let parser = Parser::new("> **Note:** Mind the gap.\n");
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, Admonitions::new(parser));
+/// assert_eq!(
+///     html_buf,
+///     "<div class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n\
+///      <p>Mind the gap.</p>\n</div>\n"
+/// );
+/// ```
+pub struct Admonitions<'a, I> {
+    iter: I,
+    pending: VecDeque<Event<'a>>,
+}
+
+impl<'a, I> Admonitions<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I) -> Self {
+        Admonitions {
+            iter,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Buffers a block quote and converts it if it is an admonition.
+    fn block_quote(&mut self, start: Event<'a>) {
+        let mut events = vec![start];
+        let mut depth = 0;
+        for event in self.iter.by_ref() {
+            match event {
+                Event::Start(Tag::BlockQuote) => depth += 1,
+                Event::End(Tag::BlockQuote) if depth == 0 => {
+                    events.push(event);
+                    break;
+                }
+                Event::End(Tag::BlockQuote) => depth -= 1,
+                _ => (),
+            }
+            events.push(event);
+        }
+
+        let mut end = events.pop();
+        if let Some(kind) = strip_marker(&mut events) {
+            let open = format!(
+                "<div class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n",
+                kind.class(),
+                kind.title()
+            );
+            events[0] = Event::Html(open.into());
+            end = Some(Event::Html("</div>\n".into()));
+        }
+        let mut events = events.into_iter();
+        self.pending.extend(events.next());
+        // nested block quotes can be admonitions too
+        self.pending.extend(Admonitions::new(events));
+        self.pending.extend(end);
+    }
+}
+
+impl<'a, I> Iterator for Admonitions<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        match self.iter.next()? {
+            start @ Event::Start(Tag::BlockQuote) => {
+                self.block_quote(start);
+                self.pending.pop_front()
+            }
+            event => Some(event),
+        }
+    }
+}
+
+/// Removes the admonition marker from a buffered block quote, returning the
+/// kind of admonition it denotes.
+fn strip_marker(events: &mut Vec<Event>) -> Option<AdmonitionKind> {
+    if events.get(1) != Some(&Event::Start(Tag::Paragraph)) {
+        return None;
+    }
+    // number of events making up the marker, and the text following it
+    let (kind, len, rest) = match &events[2..] {
+        [Event::Start(Tag::Strong), Event::Text(label), Event::End(Tag::Strong), tail @ ..] => {
+            let (kind, rest) = match tail.first() {
+                Some(Event::Text(text)) if text.starts_with(':') => {
+                    (AdmonitionKind::from_label(label)?, &text[1..])
+                }
+                next if label.ends_with(':') => {
+                    let kind = AdmonitionKind::from_label(&label[..label.len() - 1])?;
+                    match next {
+                        Some(Event::Text(text)) => (kind, &text[..]),
+                        _ => (kind, ""),
+                    }
+                }
+                _ => return None,
+            };
+            // the text following the label is replaced as well
+            let len = if let Some(Event::Text(..)) = tail.first() {
+                4
+            } else {
+                3
+            };
+            (kind, len, rest.to_owned())
+        }
+        [Event::Text(text), ..] => {
+            let first = text.chars().next()?;
+            if let Some(kind) = AdmonitionKind::from_emoji(first) {
+                // skip a variation selector following the emoji
+                let rest = text[first.len_utf8()..].trim_start_matches('\u{fe0f}');
+                (kind, 1, rest.to_owned())
+            } else {
+                // the parser may split the label into several text events
+                let mut label = String::new();
+                let mut len = 0;
+                for event in &events[2..] {
+                    match event {
+                        Event::Text(text) if !label.contains(']') => {
+                            label.push_str(text);
+                            len += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                let close = label.find(']')?;
+                if !label.starts_with("[!") {
+                    return None;
+                }
+                let kind = AdmonitionKind::from_label(&label[2..close])?;
+                (kind, len, label[close + 1..].to_owned())
+            }
+        }
+        _ => return None,
+    };
+
+    let rest = rest.trim_start();
+    let mut replacement: Vec<Event> = Vec::new();
+    if !rest.is_empty() {
+        replacement.push(Event::Text(CowStr::from(rest.to_owned())));
+    }
+    events.splice(2..2 + len, replacement.clone());
+    if replacement.is_empty() {
+        // drop a line break left at the start of the paragraph
+        if let Some(Event::SoftBreak) | Some(Event::HardBreak) = events.get(2) {
+            events.remove(2);
+        }
+        if events.get(2) == Some(&Event::End(Tag::Paragraph)) {
+            events.drain(1..3);
+        }
+    }
+    Some(kind)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Parser};
+
+    fn render(input: &str) -> String {
+        let mut s = String::new();
+        html::push_html(&mut s, Admonitions::new(Parser::new(input)));
+        s
+    }
+
+    #[test]
+    fn markers() {
+        let warning =
+            "<div class=\"admonition warning\">\n<p class=\"admonition-title\">Warning</p>\n";
+        assert_eq!(
+            format!("{}<p>Hot <em>surface</em>.</p>\n</div>\n", warning),
+            render("> **Warning**: Hot *surface*.")
+        );
+        assert_eq!(
+            format!("{}<p>Hot</p>\n</div>\n", warning),
+            render("> ⚠️ Hot")
+        );
+        assert_eq!(
+            format!("{}<p>Hot</p>\n<p>More</p>\n</div>\n", warning),
+            render("> [!WARNING]\n> Hot\n>\n> More")
+        );
+        assert_eq!(
+            format!("{}<ul>\n<li>Hot</li>\n</ul>\n</div>\n", warning),
+            render("> **Warning:**\n>\n> * Hot")
+        );
+    }
+
+    #[test]
+    fn plain_and_nested_quotes() {
+        assert_eq!(
+            "<blockquote>\n<p><strong>Hello:</strong> world</p>\n\
+             <div class=\"admonition tip\">\n<p class=\"admonition-title\">Tip</p>\n\
+             <p>nested</p>\n</div>\n</blockquote>\n",
+            render("> **Hello:** world\n>\n> > 💡 nested")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Checking of links to anchors within a document.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::strings::CowStr;
+use crate::utils::push_event_text;
+use crate::utils::slug::Slugger;
+use crate::{Event, Tag};
+
+/// A link to an anchor that does not exist in the document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnresolvedAnchor<'a> {
+    /// The link destination, including the leading `#`.
+    pub dest: CowStr<'a>,
+    /// The link's range in the source.
+    pub span: Range<usize>,
+}
+
+/// Checks that every `#fragment` link of an offset event stream points to an
+/// anchor of the same document.
+///
+/// Anchors are the heading slugs generated by the given slugger, the labels
+/// of footnote definitions, and `id` and `name` attributes in raw HTML.
+/// Fragments are percent-decoded before comparison. Links are returned in
+/// document order; an empty result means all anchors resolve.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::anchors::check_anchors;
+/// use pulldown_cmark::utils::slug::Slugger;
+/// use pulldown_cmark::Parser;
+///
+/// let input = "# Getting started\n\nSee [setup](#getting-started) and [faq](#faq).";
+/// let unresolved = check_anchors(Parser::new(input).into_offset_iter(), &mut Slugger::default());
+///
+/// assert_eq!(unresolved.len(), 1);
+/// assert_eq!(&*unresolved[0].dest, "#faq");
+/// assert_eq!(&input[unresolved[0].span.clone()], "[faq](#faq)");
+/// ```
+pub fn check_anchors<'a, I>(iter: I, slugger: &mut Slugger) -> Vec<UnresolvedAnchor<'a>>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut anchors = HashSet::new();
+    let mut links = Vec::new();
+    let mut heading: Option<String> = None;
+
+    for (event, span) in iter {
+        match event {
+            Event::Start(Tag::Heading(..)) => heading = Some(String::new()),
+            Event::End(Tag::Heading(..)) => {
+                if let Some(text) = heading.take() {
+                    anchors.insert(slugger.slug(&text));
+                }
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                anchors.insert(label.to_string());
+            }
+            Event::Start(Tag::Link(_, dest, _)) if dest.starts_with('#') => {
+                links.push(UnresolvedAnchor { dest, span });
+            }
+            Event::Html(html) => html_ids(&html, &mut anchors),
+            ref event => {
+                if let Some(ref mut text) = heading {
+                    push_event_text(text, event);
+                }
+            }
+        }
+    }
+
+    links.retain(|link| !anchors.contains(&percent_decode(&link.dest[1..])));
+    links
+}
+
+/// Adds the values of `id` and `name` attributes in `html` to `anchors`.
+fn html_ids(html: &str, anchors: &mut HashSet<String>) {
+    let bytes = html.as_bytes();
+    for (ix, _) in html.match_indices('=') {
+        let name_end = html[..ix].trim_end().len();
+        let name_start = html[..name_end]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .map_or(0, |ix| ix + 1);
+        let name = &html[name_start..name_end];
+        if name_start == 0
+            || !bytes[name_start - 1].is_ascii_whitespace()
+            || !(name.eq_ignore_ascii_case("id") || name.eq_ignore_ascii_case("name"))
+        {
+            continue;
+        }
+        let value = html[ix + 1..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ '"') | Some(quote @ '\'') => value[1..].split(quote).next(),
+            _ => value
+                .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .next(),
+        };
+        if let Some(value) = value {
+            anchors.insert(value.to_owned());
+        }
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let hex = bytes
+            .get(ix + 1..ix + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) if bytes[ix] == b'%' => {
+                decoded.push(byte);
+                ix += 3;
+            }
+            _ => {
+                decoded.push(bytes[ix]);
+                ix += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn anchor_sources() {
+        let input = "# Café\n\n# Café\n\n<a name=top></a>\n<div id=\"box\" data-id=\"x\">\n\n\
+                     text[^note]\n\n[^note]: note\n\n\
+                     [a](#caf%C3%A9-1) [b](#top) [c](#box) [d](#note) [e](#x) [f](#caf%C3%A9-2) [g](other#x)\n";
+        let parser = Parser::new_ext(input, Options::ENABLE_FOOTNOTES).into_offset_iter();
+        let unresolved: Vec<_> = check_anchors(parser, &mut Slugger::default())
+            .into_iter()
+            .map(|link| link.dest)
+            .collect();
+        assert_eq!(vec![CowStr::from("#x"), "#caf%C3%A9-2".into()], unresolved);
+    }
+}
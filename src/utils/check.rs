@@ -0,0 +1,291 @@
+//! Well-formedness checks for event streams.
+//!
+//! Event streams produced by the parser are always well-formed, but streams
+//! built or transformed by hand may not be. The writers assume well-formed
+//! input, so checking a stream up front gives a proper error instead of
+//! garbled output or a panic.
+
+use std::fmt;
+use std::mem::discriminant;
+
+use crate::{Event, Tag};
+
+/// A problem found in an event stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error<'a> {
+    /// Index of the offending event in the stream.
+    pub index: usize,
+    pub kind: ErrorKind<'a>,
+}
+
+/// The kind of a well-formedness problem.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind<'a> {
+    /// An end tag was found while no element was open.
+    UnexpectedEnd(Tag<'a>),
+    /// An end tag does not match the innermost open element.
+    MismatchedEnd { open: Tag<'a>, end: Tag<'a> },
+    /// An element was still open at the end of the stream.
+    Unclosed(Tag<'a>),
+    /// An event may not appear inside its parent. A parent of `None` stands
+    /// for the top level of the document.
+    IllegalChild {
+        parent: Option<Tag<'a>>,
+        child: Event<'a>,
+    },
+    /// A table row has a different number of cells than the table has columns.
+    CellCount { expected: usize, found: usize },
+}
+
+impl<'a> fmt::Display for Error<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "event {}: ", self.index)?;
+        match &self.kind {
+            ErrorKind::UnexpectedEnd(tag) => write!(f, "unexpected end of {:?}", tag),
+            ErrorKind::MismatchedEnd { open, end } => {
+                write!(f, "end of {:?} while {:?} is open", end, open)
+            }
+            ErrorKind::Unclosed(tag) => write!(f, "{:?} is never closed", tag),
+            ErrorKind::IllegalChild {
+                parent: Some(parent),
+                child,
+            } => write!(f, "{:?} is not allowed inside {:?}", child, parent),
+            ErrorKind::IllegalChild {
+                parent: None,
+                child,
+            } => write!(f, "{:?} is not allowed at the top level", child),
+            ErrorKind::CellCount { expected, found } => {
+                write!(f, "table row has {} cells, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+/// Checks that an event stream is well-formed.
+///
+/// This verifies that start and end tags are balanced, that every event is
+/// allowed inside its parent (for example, list items only appear in lists,
+/// and links do not nest) and that every table row has as many cells as the
+/// table has columns. All problems found are returned.
+pub fn check_events<'a, I>(iter: I) -> Result<(), Vec<Error<'a>>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut errors = Vec::new();
+    let mut stack: Vec<Tag<'a>> = Vec::new();
+    // column count and cells seen in the current row, per open table
+    let mut tables: Vec<(usize, usize)> = Vec::new();
+    let mut index = 0;
+
+    for event in iter {
+        let mut error = |kind| errors.push(Error { index, kind });
+        if !allowed_in(stack.last(), &event) || nested_link(&stack, &event) {
+            error(ErrorKind::IllegalChild {
+                parent: stack.last().cloned(),
+                child: event.clone(),
+            });
+        }
+        match event {
+            Event::Start(tag) => {
+                match tag {
+                    Tag::Table(ref alignments) => tables.push((alignments.len(), 0)),
+                    Tag::TableHead | Tag::TableRow => {
+                        if let Some(table) = tables.last_mut() {
+                            table.1 = 0;
+                        }
+                    }
+                    Tag::TableCell => {
+                        if let Some(table) = tables.last_mut() {
+                            table.1 += 1;
+                        }
+                    }
+                    _ => (),
+                }
+                stack.push(tag);
+            }
+            Event::End(tag) => {
+                match tag {
+                    Tag::Table(..) => {
+                        tables.pop();
+                    }
+                    Tag::TableHead | Tag::TableRow => {
+                        if let Some(&(expected, found)) = tables.last() {
+                            if expected != found {
+                                error(ErrorKind::CellCount { expected, found });
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                match stack.pop() {
+                    None => error(ErrorKind::UnexpectedEnd(tag)),
+                    Some(open) => {
+                        if discriminant(&open) != discriminant(&tag) {
+                            error(ErrorKind::MismatchedEnd { open, end: tag });
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+        index += 1;
+    }
+
+    for tag in stack {
+        errors.push(Error {
+            index,
+            kind: ErrorKind::Unclosed(tag),
+        });
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn is_block(event: &Event) -> bool {
+    match event {
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Paragraph
+                | Tag::Heading(..)
+                | Tag::BlockQuote
+                | Tag::CodeBlock(..)
+                | Tag::List(..)
+                | Tag::FootnoteDefinition(..)
+                | Tag::Table(..)
+        ),
+        Event::Html(..) | Event::Rule => true,
+        _ => false,
+    }
+}
+
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+        ),
+        Event::Text(..)
+        | Event::Code(..)
+        | Event::Html(..)
+        | Event::FootnoteReference(..)
+        | Event::SoftBreak
+        | Event::HardBreak => true,
+        _ => false,
+    }
+}
+
+fn allowed_in(parent: Option<&Tag>, event: &Event) -> bool {
+    if let Event::End(..) = event {
+        return true;
+    }
+    match parent {
+        None | Some(Tag::BlockQuote) | Some(Tag::FootnoteDefinition(..)) => is_block(event),
+        Some(Tag::Item) => {
+            is_block(event) || is_inline(event) || matches!(event, Event::TaskListMarker(..))
+        }
+        Some(Tag::List(..)) => matches!(event, Event::Start(Tag::Item)),
+        Some(Tag::Table(..)) => matches!(
+            event,
+            Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow)
+        ),
+        Some(Tag::TableHead) | Some(Tag::TableRow) => {
+            matches!(event, Event::Start(Tag::TableCell))
+        }
+        Some(Tag::CodeBlock(..)) => matches!(event, Event::Text(..)),
+        Some(Tag::Paragraph)
+        | Some(Tag::Heading(..))
+        | Some(Tag::TableCell)
+        | Some(Tag::Emphasis)
+        | Some(Tag::Strong)
+        | Some(Tag::Strikethrough)
+        | Some(Tag::Link(..))
+        | Some(Tag::Image(..)) => is_inline(event),
+    }
+}
+
+fn nested_link(stack: &[Tag], event: &Event) -> bool {
+    matches!(event, Event::Start(Tag::Link(..)))
+        && stack.iter().any(|tag| matches!(tag, Tag::Link(..)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn parser_output_is_well_formed() {
+        let input = "# h\n\n> * [ ] a\n>   ```\n>   x\n>   ```\n\n|a|b|\n|-|-|\n|1|\n\n[*l*](u) ![i](s)[^f]\n\n[^f]: <b>x</b>\n\n---\n";
+        assert_eq!(Ok(()), check_events(Parser::new_ext(input, Options::all())));
+    }
+
+    #[test]
+    fn unbalanced_tags() {
+        let events = vec![
+            Event::Start(Tag::Paragraph),
+            Event::Start(Tag::Emphasis),
+            Event::Text("x".into()),
+            Event::End(Tag::Paragraph),
+            Event::End(Tag::Strong),
+            Event::Start(Tag::BlockQuote),
+        ];
+        assert_eq!(
+            Err(vec![
+                Error {
+                    index: 3,
+                    kind: ErrorKind::MismatchedEnd {
+                        open: Tag::Emphasis,
+                        end: Tag::Paragraph
+                    },
+                },
+                Error {
+                    index: 4,
+                    kind: ErrorKind::MismatchedEnd {
+                        open: Tag::Paragraph,
+                        end: Tag::Strong
+                    },
+                },
+                Error {
+                    index: 6,
+                    kind: ErrorKind::Unclosed(Tag::BlockQuote),
+                },
+            ]),
+            check_events(events.into_iter())
+        );
+    }
+
+    #[test]
+    fn illegal_children_and_arity() {
+        let events = vec![
+            Event::Text("loose".into()),
+            Event::Start(Tag::List(None)),
+            Event::Start(Tag::Paragraph),
+            Event::Start(Tag::Link(crate::LinkType::Inline, "a".into(), "".into())),
+            Event::Start(Tag::Link(crate::LinkType::Inline, "b".into(), "".into())),
+            Event::End(Tag::Link(crate::LinkType::Inline, "b".into(), "".into())),
+            Event::End(Tag::Link(crate::LinkType::Inline, "a".into(), "".into())),
+            Event::End(Tag::Paragraph),
+            Event::End(Tag::List(None)),
+            Event::Start(Tag::Table(vec![crate::Alignment::None; 2])),
+            Event::Start(Tag::TableHead),
+            Event::Start(Tag::TableCell),
+            Event::End(Tag::TableCell),
+            Event::End(Tag::TableHead),
+            Event::End(Tag::Table(vec![crate::Alignment::None; 2])),
+        ];
+        let errors = check_events(events.into_iter()).unwrap_err();
+        let summary: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "event 0: Text(Borrowed(\"loose\")) is not allowed at the top level",
+                "event 2: Start(Paragraph) is not allowed inside List(None)",
+                "event 4: Start(Link(Inline, Borrowed(\"b\"), Borrowed(\"\"))) is not allowed inside Link(Inline, Borrowed(\"a\"), Borrowed(\"\"))",
+                "event 13: table row has 1 cells, expected 2",
+            ],
+            summary
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Truncated previews of documents.
+
+use std::collections::VecDeque;
+use std::iter::Peekable;
+
+use crate::strings::CowStr;
+use crate::{Event, Tag};
+
+/// How much of a document an [`Excerpt`](struct.Excerpt.html) keeps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExcerptLength {
+    /// Keep the first `n` top-level blocks, such as paragraphs or lists.
+    Blocks(usize),
+    /// Keep the first `n` words of text.
+    Words(usize),
+}
+
+/// Iterator adapter that cuts an event stream short, for previews.
+///
+/// Once the limit is reached the stream ends, with end tags emitted for every
+/// element still open, so the result is well-formed. If an ellipsis is set,
+/// it is appended as text where the document was cut; nothing is appended
+/// when the whole document fit within the limit.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::excerpt::{Excerpt, ExcerptLength};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let parser = Parser::new("Some *long winded* text.\n\nMore.");
+/// let excerpt = Excerpt::new(parser, ExcerptLength::Words(2)).ellipsis("…");
+///
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, excerpt);
+/// assert_eq!(html_buf, "<p>Some <em>long…</em></p>\n");
+/// ```
+pub struct Excerpt<'a, I: Iterator<Item = Event<'a>>> {
+    iter: Peekable<I>,
+    length: ExcerptLength,
+    ellipsis: Option<CowStr<'a>>,
+    stack: Vec<Tag<'a>>,
+    pending: VecDeque<Event<'a>>,
+    blocks: usize,
+    words: usize,
+    done: bool,
+    truncated: bool,
+}
+
+impl<'a, I> Excerpt<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I, length: ExcerptLength) -> Self {
+        Excerpt {
+            iter: iter.peekable(),
+            length,
+            ellipsis: None,
+            stack: Vec::new(),
+            pending: VecDeque::new(),
+            blocks: 0,
+            words: 0,
+            done: false,
+            truncated: false,
+        }
+    }
+
+    /// Sets the text appended where the document is cut.
+    pub fn ellipsis<S: Into<CowStr<'a>>>(mut self, ellipsis: S) -> Self {
+        self.ellipsis = Some(ellipsis.into());
+        self
+    }
+
+    /// Returns true if the document has been cut short so far.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Ends the stream: queues the ellipsis and closes all open elements.
+    fn truncate(&mut self) {
+        self.done = true;
+        self.truncated = true;
+        if let Some(ellipsis) = self.ellipsis.take() {
+            match self.stack.last() {
+                Some(Tag::BlockQuote) | Some(Tag::FootnoteDefinition(..)) | None => {
+                    self.pending.push_back(Event::Start(Tag::Paragraph));
+                    self.pending.push_back(Event::Text(ellipsis));
+                    self.pending.push_back(Event::End(Tag::Paragraph));
+                }
+                Some(Tag::List(..)) | Some(Tag::Table(..)) | Some(Tag::TableHead)
+                | Some(Tag::TableRow) => (),
+                Some(_) => self.pending.push_back(Event::Text(ellipsis)),
+            }
+        }
+        while let Some(tag) = self.stack.pop() {
+            self.pending.push_back(Event::End(tag));
+        }
+    }
+
+    fn next_blocks(&mut self, event: Event<'a>, limit: usize) -> Option<Event<'a>> {
+        if self.stack.is_empty() && self.blocks >= limit {
+            self.truncate();
+            return self.pending.pop_front();
+        }
+        match event {
+            Event::Start(ref tag) => self.stack.push(tag.clone()),
+            Event::End(..) => {
+                if self.stack.len() == 1 {
+                    self.blocks += 1;
+                    if self.blocks == limit && self.iter.peek().is_some() {
+                        self.truncate();
+                        return self.pending.pop_front();
+                    }
+                }
+                self.stack.pop();
+            }
+            Event::Rule if self.stack.is_empty() => self.blocks += 1,
+            _ => (),
+        }
+        Some(event)
+    }
+
+    fn next_words(&mut self, event: Event<'a>, limit: usize) -> Option<Event<'a>> {
+        match event {
+            Event::Start(ref tag) if self.words >= limit && !is_inline(tag) => {
+                self.truncate();
+                self.pending.pop_front()
+            }
+            Event::Rule if self.words >= limit => {
+                self.truncate();
+                self.pending.pop_front()
+            }
+            Event::Text(ref text) | Event::Code(ref text) if count_words(text) > 0 => {
+                let remaining = limit.saturating_sub(self.words);
+                self.words += count_words(text);
+                if self.words <= limit {
+                    return Some(event);
+                }
+                let end = end_of_words(text, remaining);
+                self.truncate();
+                match event {
+                    _ if end == 0 => self.pending.pop_front(),
+                    Event::Code(text) => Some(Event::Code(prefix(text, end))),
+                    Event::Text(text) => Some(Event::Text(prefix(text, end))),
+                    _ => unreachable!(),
+                }
+            }
+            Event::Start(ref tag) => {
+                self.stack.push(tag.clone());
+                Some(event)
+            }
+            Event::End(..) => {
+                self.stack.pop();
+                Some(event)
+            }
+            event => Some(event),
+        }
+    }
+}
+
+impl<'a, I> Iterator for Excerpt<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        if self.done {
+            return None;
+        }
+        let event = self.iter.next()?;
+        match self.length {
+            ExcerptLength::Blocks(limit) => self.next_blocks(event, limit),
+            ExcerptLength::Words(limit) => self.next_words(event, limit),
+        }
+    }
+}
+
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+    )
+}
+
+fn count_words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Returns the byte offset just past the `n`th word of `text`.
+fn end_of_words(text: &str, n: usize) -> usize {
+    if n == 0 {
+        return 0;
+    }
+    let mut seen = 0;
+    let mut in_word = false;
+    for (ix, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if in_word && seen == n {
+                return ix;
+            }
+            in_word = false;
+        } else if !in_word {
+            in_word = true;
+            seen += 1;
+        }
+    }
+    text.len()
+}
+
+fn prefix(text: CowStr, end: usize) -> CowStr {
+    match text {
+        CowStr::Borrowed(s) => CowStr::Borrowed(&s[..end]),
+        text => text[..end].to_owned().into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Parser};
+
+    fn render(input: &str, length: ExcerptLength) -> String {
+        let mut s = String::new();
+        html::push_html(
+            &mut s,
+            Excerpt::new(Parser::new(input), length).ellipsis("..."),
+        );
+        s
+    }
+
+    #[test]
+    fn first_blocks() {
+        let input = "# Title\n\nFirst *para*.\n\n* a\n* b\n";
+        assert_eq!(
+            "<h1>Title</h1>\n<p>First <em>para</em>....</p>\n",
+            render(input, ExcerptLength::Blocks(2))
+        );
+        assert_eq!(
+            "<h1>Title</h1>\n<p>First <em>para</em>.</p>\n<ul>\n<li>a</li>\n<li>b</li>\n</ul>\n",
+            render(input, ExcerptLength::Blocks(3))
+        );
+    }
+
+    #[test]
+    fn first_words() {
+        let input = "> one `two three`\n>\n> * four\n\nfive\n";
+        assert_eq!(
+            "<blockquote>\n<p>one <code>two</code>...</p>\n</blockquote>\n",
+            render(input, ExcerptLength::Words(2))
+        );
+        assert_eq!(
+            "<blockquote>\n<p>one <code>two three</code></p>\n<p>...</p>\n</blockquote>\n",
+            render(input, ExcerptLength::Words(3))
+        );
+
+        let mut excerpt = Excerpt::new(Parser::new(input), ExcerptLength::Words(5));
+        assert_eq!(Parser::new(input).count(), excerpt.by_ref().count());
+        assert!(!excerpt.truncated());
+    }
+}
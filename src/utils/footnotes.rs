@@ -0,0 +1,266 @@
+//! Footnote renumbering.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::strings::CowStr;
+use crate::utils::ids::IdAllocator;
+use crate::{Event, Tag};
+
+/// Where [`RenumberFootnotes`](struct.RenumberFootnotes.html) places the
+/// footnote definitions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FootnotePlacement {
+    /// Definitions stay where they appear in the document.
+    InPlace,
+    /// Definitions are moved to the end of the document, ordered by number.
+    End,
+}
+
+/// Iterator adapter that relabels footnotes with sequential numbers.
+///
+/// Footnotes are numbered in the order of their first reference, starting at
+/// one, and both references and definitions are relabeled with that number.
+/// Definitions that are never referenced are numbered after all referenced
+/// ones. Because the writers only ever see the new labels, the numbers shown
+/// in the output are the same whichever writer renders the stream.
+///
+/// The HTML writer uses footnote labels as element ids. To keep them from
+/// clashing with other generated ids, like heading anchors, pass a shared
+/// allocator to [`ids`](#method.ids).
+///
+/// The whole stream is buffered on the first call to `next`.
+pub struct RenumberFootnotes<'a, I> {
+    iter: Option<I>,
+    placement: FootnotePlacement,
+    ids: Option<IdAllocator>,
+    events: VecDeque<Event<'a>>,
+    labels: Vec<(CowStr<'a>, CowStr<'a>)>,
+}
+
+impl<'a, I> RenumberFootnotes<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I, placement: FootnotePlacement) -> Self {
+        RenumberFootnotes {
+            iter: Some(iter),
+            placement,
+            ids: None,
+            events: VecDeque::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Allocates the new labels from `ids`. A number that is already taken
+    /// gets a suffix, so footnote 1 may be labeled `1-1`.
+    pub fn ids(mut self, ids: IdAllocator) -> Self {
+        self.ids = Some(ids);
+        self
+    }
+
+    /// Returns the mapping from original to new labels, ordered by number.
+    pub fn labels(&mut self) -> &[(CowStr<'a>, CowStr<'a>)] {
+        self.fill();
+        &self.labels
+    }
+
+    fn fill(&mut self) {
+        let iter = match self.iter.take() {
+            Some(iter) => iter,
+            None => return,
+        };
+        let events: Vec<_> = iter.collect();
+
+        let order = footnote_order(events.iter());
+        let numbers: HashMap<CowStr<'a>, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(ix, label)| (label.clone(), ix + 1))
+            .collect();
+        let ids = &self.ids;
+        let new_labels: Vec<CowStr<'a>> = (1..=order.len())
+            .map(|number| match ids {
+                Some(ids) => ids.allocate(&number.to_string()).into(),
+                None => number.to_string().into(),
+            })
+            .collect();
+        let relabel = |label: &CowStr<'a>| new_labels[numbers[label] - 1].clone();
+
+        let mut definitions: Vec<(usize, Vec<Event<'a>>)> = Vec::new();
+        let mut depth = 0;
+        for event in events {
+            let event = match event {
+                Event::FootnoteReference(label) => Event::FootnoteReference(relabel(&label)),
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    depth += 1;
+                    if self.placement == FootnotePlacement::End && depth == 1 {
+                        definitions.push((numbers[&label], Vec::new()));
+                    }
+                    Event::Start(Tag::FootnoteDefinition(relabel(&label)))
+                }
+                Event::End(Tag::FootnoteDefinition(label)) => {
+                    depth -= 1;
+                    let event = Event::End(Tag::FootnoteDefinition(relabel(&label)));
+                    if self.placement == FootnotePlacement::End && depth == 0 {
+                        definitions.last_mut().unwrap().1.push(event);
+                        continue;
+                    }
+                    event
+                }
+                event => event,
+            };
+            match definitions.last_mut() {
+                Some((_, definition)) if depth > 0 => definition.push(event),
+                _ => self.events.push_back(event),
+            }
+        }
+
+        definitions.sort_by_key(|&(number, _)| number);
+        for (_, definition) in definitions {
+            self.events.extend(definition);
+        }
+        self.labels = order.into_iter().zip(new_labels).collect();
+    }
+}
+
+/// Returns the footnote labels of a document in the order they are numbered
+/// by [`RenumberFootnotes`](struct.RenumberFootnotes.html): by first
+/// reference, followed by the labels of definitions that are never
+/// referenced. The number of a label is its index plus one.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::footnotes::footnote_order;
+/// use pulldown_cmark::{Event, Options, Parser};
+///
+/// let input = "[^x]: Unused.\n\nText[^b][^a][^b].\n";
+/// let events: Vec<Event> = Parser::new_ext(input, Options::ENABLE_FOOTNOTES).collect();
+/// let order: Vec<String> = footnote_order(events.iter()).iter().map(|l| l.to_string()).collect();
+/// assert_eq!(order, vec!["b", "a", "x"]);
+/// ```
+pub fn footnote_order<'a, 'e, I>(events: I) -> Vec<CowStr<'a>>
+where
+    'a: 'e,
+    I: Iterator<Item = &'e Event<'a>> + Clone,
+{
+    let mut order: Vec<CowStr<'a>> = Vec::new();
+    let mut seen = HashSet::new();
+    let references = events.clone().filter_map(|event| match event {
+        Event::FootnoteReference(label) => Some(label),
+        _ => None,
+    });
+    let definitions = events.filter_map(|event| match event {
+        Event::Start(Tag::FootnoteDefinition(label)) => Some(label),
+        _ => None,
+    });
+    for label in references.chain(definitions) {
+        if seen.insert(label) {
+            order.push(label.clone());
+        }
+    }
+    order
+}
+
+impl<'a, I> Iterator for RenumberFootnotes<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        self.fill();
+        self.events.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::slug::{AnchorStyle, HeadingAnchors, SlugStyle, Slugger};
+    use crate::{html, Options, Parser};
+
+    const INPUT: &str =
+        "[^b]: Bee.\n\nText[^a] and[^b], again[^a].\n\n[^a]: Ay.\n\n[^c]: Unused.\n";
+
+    #[test]
+    fn renumber_in_place() {
+        let parser = Parser::new_ext(INPUT, Options::ENABLE_FOOTNOTES);
+        let mut renumbered = RenumberFootnotes::new(parser, FootnotePlacement::InPlace);
+        let labels: Vec<_> = renumbered
+            .labels()
+            .iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("a".to_owned(), "1".to_owned()),
+                ("b".to_owned(), "2".to_owned()),
+                ("c".to_owned(), "3".to_owned()),
+            ],
+            labels
+        );
+
+        let events: Vec<_> = renumbered.collect();
+        assert_eq!(Event::Start(Tag::FootnoteDefinition("2".into())), events[0]);
+    }
+
+    #[test]
+    fn move_definitions_to_end() {
+        let parser = Parser::new_ext(INPUT, Options::ENABLE_FOOTNOTES);
+        let events: Vec<_> = RenumberFootnotes::new(parser, FootnotePlacement::End).collect();
+        let labels: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::FootnoteReference(label) => Some(format!("ref {}", label)),
+                Event::Start(Tag::FootnoteDefinition(label)) => Some(format!("def {}", label)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec!["ref 1", "ref 2", "ref 1", "def 1", "def 2", "def 3"],
+            labels
+        );
+        assert_eq!(Event::Start(Tag::Paragraph), events[0]);
+        assert_eq!(
+            Some(&Event::End(Tag::FootnoteDefinition("3".into()))),
+            events.last()
+        );
+
+        let mut s = String::new();
+        html::push_html(&mut s, events.into_iter());
+        assert_eq!(
+            "<p>Text<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup> \
+             and<sup class=\"footnote-reference\"><a href=\"#2\">2</a></sup>, \
+             again<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup>.</p>\n\
+             <div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+             <p>Ay.</p>\n</div>\n\
+             <div class=\"footnote-definition\" id=\"2\"><sup class=\"footnote-definition-label\">2</sup>\n\
+             <p>Bee.</p>\n</div>\n\
+             <div class=\"footnote-definition\" id=\"3\"><sup class=\"footnote-definition-label\">3</sup>\n\
+             <p>Unused.</p>\n</div>\n",
+            s
+        );
+    }
+
+    #[test]
+    fn share_ids_with_heading_anchors() {
+        let input = "# 1\n\nText[^x]\n\n[^x]: Note.\n";
+        let ids = IdAllocator::new();
+        let parser = Parser::new_ext(input, Options::ENABLE_FOOTNOTES);
+        let renumbered = RenumberFootnotes::new(parser, FootnotePlacement::End).ids(ids.clone());
+        let slugger = Slugger::with_ids(SlugStyle::GitHub, ids);
+        let events = HeadingAnchors::with_slugger(renumbered, slugger, AnchorStyle::Attribute);
+        let heading_ids = events.heading_ids();
+
+        let mut s = String::new();
+        html::push_html_with_renderer(&mut s, events, &html::Options::default(), heading_ids);
+        assert_eq!(
+            "<h1 id=\"1-1\">1</h1>\n\
+             <p>Text<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup></p>\n\
+             <div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+             <p>Note.</p>\n</div>\n",
+            s
+        );
+    }
+}
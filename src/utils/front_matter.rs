@@ -0,0 +1,145 @@
+//! Splitting of front matter from documents.
+//!
+//! The parser has no notion of front matter, so a metadata block at the top
+//! of a document would be parsed as markdown: YAML front matter as a thematic
+//! break followed by a setext heading, TOML front matter as a paragraph.
+//! These functions separate it from the source text before parsing.
+
+use crate::{Options, Parser};
+
+/// The format of a front matter block, as indicated by its fences.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrontMatterKind {
+    /// Fenced by `---` lines. The closing fence can also be `...`.
+    Yaml,
+    /// Fenced by `+++` lines.
+    Toml,
+}
+
+/// A front matter block found at the start of a document.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrontMatter<'a> {
+    pub kind: FrontMatterKind,
+    /// The metadata between the fences, without the fences themselves.
+    pub raw: &'a str,
+    /// Offset of the body in the document. Add it to the offsets of events
+    /// parsed from the body to get offsets in the whole document.
+    pub body_offset: usize,
+}
+
+/// Splits the front matter from the start of `input`.
+///
+/// Returns the front matter, if the document starts with one, and the rest
+/// of the document. A document whose opening fence is never closed has no
+/// front matter.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::front_matter::{split_front_matter, FrontMatterKind};
+///
+/// let input = "---\ntitle: Hello\n---\n# Hello\n";
+/// let (front_matter, body) = split_front_matter(input);
+/// let front_matter = front_matter.unwrap();
+///
+/// assert_eq!(front_matter.kind, FrontMatterKind::Yaml);
+/// assert_eq!(front_matter.raw, "title: Hello\n");
+/// assert_eq!(body, "# Hello\n");
+/// ```
+pub fn split_front_matter(input: &str) -> (Option<FrontMatter<'_>>, &str) {
+    let (first, mut ix) = match next_line(input, 0) {
+        Some(line) => line,
+        None => return (None, input),
+    };
+    let kind = match first {
+        "---" => FrontMatterKind::Yaml,
+        "+++" => FrontMatterKind::Toml,
+        _ => return (None, input),
+    };
+    let raw_start = ix;
+    while let Some((line, next)) = next_line(input, ix) {
+        let closes = match kind {
+            FrontMatterKind::Yaml => line == "---" || line == "...",
+            FrontMatterKind::Toml => line == "+++",
+        };
+        if closes {
+            let front_matter = FrontMatter {
+                kind,
+                raw: &input[raw_start..ix],
+                body_offset: next,
+            };
+            return (Some(front_matter), &input[next..]);
+        }
+        ix = next;
+    }
+    (None, input)
+}
+
+/// Returns the line starting at `ix` without trailing whitespace, and the
+/// offset of the next line.
+fn next_line(input: &str, ix: usize) -> Option<(&str, usize)> {
+    if ix >= input.len() {
+        return None;
+    }
+    let rest = &input[ix..];
+    let end = rest.find('\n').map_or(rest.len(), |end| end + 1);
+    Some((rest[..end].trim_end(), ix + end))
+}
+
+/// Splits the front matter from `input` and creates a parser for the body.
+///
+/// This is a shortcut for [`split_front_matter`](fn.split_front_matter.html)
+/// followed by `Parser::new_ext`. The metadata is returned raw, ready to be
+/// handed to a YAML or TOML deserializer.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::front_matter::parse_with_front_matter;
+/// use pulldown_cmark::{html, Options};
+///
+/// let input = "+++\ntitle = \"Hello\"\n+++\n*Hi*\n";
+/// let (metadata, parser) = parse_with_front_matter(input, Options::empty());
+///
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, parser);
+/// assert_eq!(metadata, Some("title = \"Hello\"\n"));
+/// assert_eq!(html_buf, "<p><em>Hi</em></p>\n");
+/// ```
+pub fn parse_with_front_matter(
+    input: &str,
+    options: Options,
+) -> (Option<&str>, Parser<'_, 'static>) {
+    let (front_matter, body) = split_front_matter(input);
+    (
+        front_matter.map(|front_matter| front_matter.raw),
+        Parser::new_ext(body, options),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fences() {
+        let input = "---  \r\na: 1\n...\r\nbody";
+        let (front_matter, body) = split_front_matter(input);
+        assert_eq!(
+            Some(FrontMatter {
+                kind: FrontMatterKind::Yaml,
+                raw: "a: 1\n",
+                body_offset: 17,
+            }),
+            front_matter
+        );
+        assert_eq!("body", body);
+        assert_eq!(body, &input[17..]);
+
+        let (front_matter, _) = split_front_matter("---\n---\n");
+        assert_eq!(Some(""), front_matter.map(|f| f.raw));
+        assert_eq!((None, "+++\na\n---\n"), split_front_matter("+++\na\n---\n"));
+        assert_eq!((None, "text\n---\n"), split_front_matter("text\n---\n"));
+        assert_eq!((None, " ---\n---\n"), split_front_matter(" ---\n---\n"));
+    }
+}
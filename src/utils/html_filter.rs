@@ -0,0 +1,361 @@
+//! Filtering of raw HTML by an allowlist.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::escape::escape_html;
+use crate::html::is_dangerous_url;
+use crate::utils::rewrite::LinkKind;
+use crate::Event;
+
+/// What happens to HTML tags that are not allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisallowedHtml {
+    /// The tag is escaped, so it shows up as text.
+    Escape,
+    /// The tag is removed.
+    Drop,
+}
+
+/// The HTML tags and attributes let through by
+/// [`FilterHtml`](struct.FilterHtml.html).
+#[derive(Clone, Debug, Default)]
+pub struct HtmlAllowlist {
+    tags: HashMap<String, HashSet<String>>,
+}
+
+impl HtmlAllowlist {
+    /// Creates an allowlist allowing nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows a tag with the given attributes. Names are matched
+    /// case-insensitively.
+    pub fn allow_tag(mut self, tag: &str, attributes: &[&str]) -> Self {
+        self.tags
+            .entry(tag.to_ascii_lowercase())
+            .or_default()
+            .extend(attributes.iter().map(|attr| attr.to_ascii_lowercase()));
+        self
+    }
+
+    /// Creates an allowlist of the tags commonly allowed in comments:
+    /// inline formatting, line breaks, links and images.
+    pub fn basic() -> Self {
+        let formatting = [
+            "b", "br", "code", "del", "em", "i", "ins", "kbd", "mark", "s", "small", "strong",
+            "sub", "sup", "u",
+        ];
+        formatting
+            .iter()
+            .fold(HtmlAllowlist::new(), |allowlist, tag| {
+                allowlist.allow_tag(tag, &[])
+            })
+            .allow_tag("abbr", &["title"])
+            .allow_tag("a", &["href", "title"])
+            .allow_tag("img", &["src", "alt", "title", "width", "height"])
+    }
+
+    fn attributes(&self, tag: &str) -> Option<&HashSet<String>> {
+        self.tags.get(&tag.to_ascii_lowercase())
+    }
+}
+
+/// Iterator adapter that restricts raw HTML to an allowlist.
+///
+/// Every tag in `Event::Html` events is checked against the allowlist.
+/// Allowed tags are kept, minus any attributes that are not allowed for
+/// them. Other tags, comments, processing instructions and declarations are
+/// escaped or dropped. Text between tags is passed through.
+///
+/// URL attributes like `href` and `src` are also removed when their value
+/// is a `javascript:` URL or another URL that can run code. Other attribute
+/// values are not inspected, so this does not replace a full HTML sanitizer
+/// when untrusted attributes like `style` are allowed.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::html_filter::{DisallowedHtml, FilterHtml, HtmlAllowlist};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let allowlist = HtmlAllowlist::new().allow_tag("sup", &[]).allow_tag("br", &[]);
+/// let parser = Parser::new("E = mc<sup onclick=\"x()\">2</sup><br><script>");
+/// let filtered = FilterHtml::new(parser, allowlist, DisallowedHtml::Escape);
+///
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, filtered);
+/// assert_eq!(html_buf, "<p>E = mc<sup>2</sup><br>&lt;script&gt;</p>\n");
+/// ```
+pub struct FilterHtml<I> {
+    iter: I,
+    allowlist: HtmlAllowlist,
+    disallowed: DisallowedHtml,
+}
+
+impl<'a, I> FilterHtml<I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I, allowlist: HtmlAllowlist, disallowed: DisallowedHtml) -> Self {
+        FilterHtml {
+            iter,
+            allowlist,
+            disallowed,
+        }
+    }
+
+    fn filter_html(&self, html: &str) -> String {
+        let mut out = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(ix) = rest.find('<') {
+            out.push_str(&rest[..ix]);
+            rest = &rest[ix..];
+            let len = match scan_tag(rest) {
+                Some(tag) => {
+                    match self.allowlist.attributes(tag.name) {
+                        Some(allowed) => tag.write_allowed(&mut out, allowed),
+                        None => self.disallow(&mut out, &rest[..tag.len]),
+                    }
+                    tag.len
+                }
+                None => {
+                    // comments, declarations and anything that isn't a tag
+                    let len = match rest.find('>') {
+                        Some(end) if rest[1..].starts_with(&['!', '?'][..]) => end + 1,
+                        _ => 1,
+                    };
+                    if len == 1 {
+                        out.push_str("&lt;");
+                    } else {
+                        self.disallow(&mut out, &rest[..len]);
+                    }
+                    len
+                }
+            };
+            rest = &rest[len..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn disallow(&self, out: &mut String, html: &str) {
+        if self.disallowed == DisallowedHtml::Escape {
+            escape_html(out, html).unwrap();
+        }
+    }
+}
+
+impl<'a, I> Iterator for FilterHtml<I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            match self.iter.next()? {
+                Event::Html(html) => {
+                    let filtered = self.filter_html(&html);
+                    if filtered == *html {
+                        return Some(Event::Html(html));
+                    } else if !filtered.is_empty() {
+                        return Some(Event::Html(filtered.into()));
+                    }
+                }
+                event => return Some(event),
+            }
+        }
+    }
+}
+
+/// An open or closing tag found in raw HTML.
+pub(crate) struct ScannedTag<'h> {
+    pub(crate) name: &'h str,
+    pub(crate) closing: bool,
+    pub(crate) self_closing: bool,
+    /// Attribute names, each with its full source text.
+    pub(crate) attributes: Vec<(&'h str, &'h str)>,
+    pub(crate) len: usize,
+}
+
+impl<'h> ScannedTag<'h> {
+    fn write_allowed(&self, out: &mut String, allowed: &HashSet<String>) {
+        out.push('<');
+        if self.closing {
+            out.push('/');
+        }
+        out.push_str(self.name);
+        for &(name, source) in &self.attributes {
+            let name = name.to_ascii_lowercase();
+            if allowed.contains(&name) && !is_dangerous_attribute(&name, source) {
+                out.push(' ');
+                out.push_str(source);
+            }
+        }
+        if self.self_closing {
+            out.push_str(" /");
+        }
+        out.push('>');
+    }
+}
+
+/// Scans a tag at the start of `html`, which starts with `<`.
+pub(crate) fn scan_tag(html: &str) -> Option<ScannedTag<'_>> {
+    let bytes = html.as_bytes();
+    let mut ix = 1;
+    let closing = bytes.get(ix) == Some(&b'/');
+    if closing {
+        ix += 1;
+    }
+    let name_start = ix;
+    if !bytes.get(ix)?.is_ascii_alphabetic() {
+        return None;
+    }
+    while matches!(bytes.get(ix), Some(&b) if b.is_ascii_alphanumeric() || b == b'-') {
+        ix += 1;
+    }
+    let name = &html[name_start..ix];
+
+    let mut attributes = Vec::new();
+    loop {
+        let ws_start = ix;
+        while bytes.get(ix)?.is_ascii_whitespace() {
+            ix += 1;
+        }
+        match bytes[ix] {
+            b'>' => {
+                return Some(ScannedTag {
+                    name,
+                    closing,
+                    self_closing: false,
+                    attributes,
+                    len: ix + 1,
+                })
+            }
+            b'/' if bytes.get(ix + 1) == Some(&b'>') && !closing => {
+                return Some(ScannedTag {
+                    name,
+                    closing,
+                    self_closing: true,
+                    attributes,
+                    len: ix + 2,
+                })
+            }
+            _ if closing || ix == ws_start => return None,
+            _ => (),
+        }
+
+        let attr_start = ix;
+        if !(bytes[ix].is_ascii_alphabetic() || bytes[ix] == b'_' || bytes[ix] == b':') {
+            return None;
+        }
+        while matches!(bytes.get(ix), Some(&b) if is_attribute_name_byte(b)) {
+            ix += 1;
+        }
+        let attr_name = &html[attr_start..ix];
+        let mut value_ix = ix;
+        while bytes.get(value_ix)?.is_ascii_whitespace() {
+            value_ix += 1;
+        }
+        if bytes[value_ix] == b'=' {
+            value_ix += 1;
+            while bytes.get(value_ix)?.is_ascii_whitespace() {
+                value_ix += 1;
+            }
+            match bytes[value_ix] {
+                quote @ b'"' | quote @ b'\'' => {
+                    let len = html[value_ix + 1..].find(quote as char)?;
+                    ix = value_ix + len + 2;
+                }
+                _ => {
+                    let start = value_ix;
+                    while matches!(bytes.get(value_ix), Some(&b) if is_unquoted_value_byte(b)) {
+                        value_ix += 1;
+                    }
+                    if value_ix == start {
+                        return None;
+                    }
+                    ix = value_ix;
+                }
+            }
+        }
+        attributes.push((attr_name, &html[attr_start..ix]));
+    }
+}
+
+/// Returns whether an attribute holds a URL that can run code. URLs with
+/// character references in their scheme are treated as dangerous, since
+/// browsers decode them before looking at the scheme.
+fn is_dangerous_attribute(name: &str, source: &str) -> bool {
+    let kind = match name {
+        "src" | "poster" => LinkKind::Image,
+        "href" | "cite" | "action" | "formaction" | "xlink:href" => LinkKind::Link,
+        _ => return false,
+    };
+    let value = match source.find('=') {
+        Some(ix) => source[ix + 1..].trim_start().trim_matches(&['"', '\''][..]),
+        None => return false,
+    };
+    let scheme_end = value.find(&[':', '/', '?', '#'][..]).unwrap_or(value.len());
+    value[..scheme_end].contains('&') || is_dangerous_url(kind, value)
+}
+
+fn is_attribute_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"_.:-".contains(&b)
+}
+
+fn is_unquoted_value_byte(b: u8) -> bool {
+    !b.is_ascii_whitespace() && !b"\"'=<>`".contains(&b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Parser};
+
+    fn render(input: &str, disallowed: DisallowedHtml) -> String {
+        let allowlist = HtmlAllowlist::new()
+            .allow_tag("a", &["href", "title"])
+            .allow_tag("BR", &[]);
+        let mut s = String::new();
+        html::push_html(
+            &mut s,
+            FilterHtml::new(Parser::new(input), allowlist, disallowed),
+        );
+        s
+    }
+
+    #[test]
+    fn attributes_are_filtered() {
+        assert_eq!(
+            "<p><a href=\"x\" title='t'>y</a> <br /> 1 &lt; 2</p>\n",
+            render(
+                "<a href=\"x\" onclick=\"evil()\" title='t' data-x=y>y</a> <br/> 1 < 2",
+                DisallowedHtml::Drop
+            )
+        );
+    }
+
+    #[test]
+    fn dangerous_urls_are_removed() {
+        assert_eq!(
+            "<p><a title=\"t\">x</a><a>y</a><a href=\"/a?b&amp;c\">z</a></p>\n",
+            render(
+                "<a href=\" JavaScript:evil()\" title=\"t\">x</a>\
+                 <a href='jav&#x61;script:evil()'>y</a><a href=\"/a?b&amp;c\">z</a>",
+                DisallowedHtml::Drop
+            )
+        );
+    }
+
+    #[test]
+    fn disallowed_blocks() {
+        let input = "<div class=\"x\">\n<!-- hidden -->\n<br>\n</div>\n";
+        assert_eq!("\n\n<br>\n\n", render(input, DisallowedHtml::Drop));
+        assert_eq!(
+            "&lt;div class=&quot;x&quot;&gt;\n&lt;!-- hidden --&gt;\n<br>\n&lt;/div&gt;\n",
+            render(input, DisallowedHtml::Escape)
+        );
+    }
+}
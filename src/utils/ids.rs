@@ -0,0 +1,116 @@
+//! Allocation of unique element ids.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Hands out ids that are unique within a document.
+///
+/// When an id has been handed out before, a numeric suffix is appended, so
+/// that allocating `intro` twice yields `intro` and `intro-1`. Allocation is
+/// deterministic: the same sequence of requests always yields the same ids.
+///
+/// An `IdAllocator` is a handle, and its clones share the set of allocated
+/// ids. Giving clones of one allocator to several adapters stacked on the
+/// same event stream, like a [`Slugger`](../slug/struct.Slugger.html) and
+/// [`RenumberFootnotes`](../footnotes/struct.RenumberFootnotes.html), keeps
+/// the ids unique over the whole document. Use [`fork`](#method.fork) to get
+/// an independent copy.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::ids::IdAllocator;
+///
+/// let ids = IdAllocator::new();
+/// let shared = ids.clone();
+/// assert_eq!(ids.allocate("intro"), "intro");
+/// assert_eq!(shared.allocate("intro"), "intro-1");
+///
+/// let fork = ids.fork();
+/// assert_eq!(fork.allocate("intro"), "intro-2");
+/// assert_eq!(ids.allocate("intro"), "intro-2");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct IdAllocator {
+    state: Rc<RefCell<State>>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    used: HashSet<String>,
+    counters: HashMap<String, usize>,
+}
+
+impl IdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an id derived from `base` that has not been handed out before.
+    pub fn allocate(&self, base: &str) -> String {
+        let mut state = self.state.borrow_mut();
+        let mut id = base.to_owned();
+        if state.used.contains(&id) {
+            let mut counter = state.counters.get(base).copied().unwrap_or(0);
+            loop {
+                counter += 1;
+                let candidate = format!("{}-{}", base, counter);
+                if !state.used.contains(&candidate) {
+                    id = candidate;
+                    break;
+                }
+            }
+            state.counters.insert(base.to_owned(), counter);
+        }
+        state.used.insert(id.clone());
+        id
+    }
+
+    /// Marks `id` as handed out, so that it is never allocated.
+    ///
+    /// Returns `false` if the id was already taken.
+    pub fn reserve(&self, id: &str) -> bool {
+        self.state.borrow_mut().used.insert(id.to_owned())
+    }
+
+    /// Returns whether `id` has been handed out or reserved.
+    pub fn contains(&self, id: &str) -> bool {
+        self.state.borrow().used.contains(id)
+    }
+
+    /// Returns an allocator that starts out with the ids handed out so far,
+    /// but does not share later allocations with this one.
+    pub fn fork(&self) -> IdAllocator {
+        IdAllocator {
+            state: Rc::new(RefCell::new(self.state.borrow().clone())),
+        }
+    }
+
+    /// Forgets all ids handed out so far, for this allocator and all of its
+    /// clones.
+    pub fn reset(&self) {
+        let mut state = self.state.borrow_mut();
+        state.used.clear();
+        state.counters.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suffixes_and_reservations() {
+        let ids = IdAllocator::new();
+        assert!(ids.reserve("a-1"));
+        assert!(!ids.reserve("a-1"));
+        assert_eq!("a", ids.allocate("a"));
+        assert_eq!("a-2", ids.allocate("a"));
+        assert_eq!("a-3", ids.allocate("a"));
+        assert!(ids.contains("a-3"));
+        ids.reset();
+        assert!(!ids.contains("a-3"));
+        assert_eq!("a", ids.allocate("a"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Extra HTML attributes for images.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use crate::escape::{escape_href, escape_html};
+use crate::{Event, Tag};
+
+/// Attributes added to an image's `<img>` element.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageAttributes {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Space separated class names.
+    pub class: Option<String>,
+    /// Value of the `loading` attribute, like `lazy` or `eager`.
+    pub loading: Option<String>,
+    /// Value of the `decoding` attribute, like `async` or `sync`.
+    pub decoding: Option<String>,
+}
+
+impl ImageAttributes {
+    /// Attributes letting browsers load and decode the image lazily:
+    /// `loading="lazy"` and `decoding="async"`.
+    pub fn lazy() -> Self {
+        ImageAttributes {
+            loading: Some("lazy".to_owned()),
+            decoding: Some("async".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == ImageAttributes::default()
+    }
+}
+
+/// Iterator adapter that lets a callback add attributes to images.
+///
+/// The callback is called for every image with its source, its title and the
+/// events making up its alt text. When it returns attributes, the image is
+/// replaced by an `Event::Html` holding the complete `<img>` element, written
+/// the same way the HTML renderer writes it plus the extra attributes.
+/// Images the callback returns `None` or no attributes for are passed through
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::images::{ImageAttributes, InjectImageAttributes};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let parser = Parser::new("![A *cat*](cat.png) ![](dog.png)");
+/// let images = InjectImageAttributes::new(parser, |src, _title, _alt| {
+///     if src == "cat.png" {
+///         Some(ImageAttributes { width: Some(640), height: Some(480), ..Default::default() })
+///     } else {
+///         Some(ImageAttributes::lazy())
+///     }
+/// });
+///
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, images);
+/// assert_eq!(
+///     html_buf,
+///     "<p><img src=\"cat.png\" alt=\"A cat\" width=\"640\" height=\"480\" /> \
+///      <img src=\"dog.png\" alt=\"\" loading=\"lazy\" decoding=\"async\" /></p>\n"
+/// );
+/// ```
+pub struct InjectImageAttributes<'a, I, F> {
+    iter: I,
+    callback: F,
+    pending: VecDeque<Event<'a>>,
+}
+
+impl<'a, I, F> InjectImageAttributes<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: FnMut(&str, &str, &[Event<'a>]) -> Option<ImageAttributes>,
+{
+    pub fn new(iter: I, callback: F) -> Self {
+        InjectImageAttributes {
+            iter,
+            callback,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<'a, I, F> Iterator for InjectImageAttributes<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: FnMut(&str, &str, &[Event<'a>]) -> Option<ImageAttributes>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        let start = match self.iter.next()? {
+            start @ Event::Start(Tag::Image(..)) => start,
+            event => return Some(event),
+        };
+        let mut alt = Vec::new();
+        let mut end = None;
+        let mut nest = 0;
+        for event in self.iter.by_ref() {
+            match event {
+                Event::Start(..) => nest += 1,
+                Event::End(..) if nest == 0 => {
+                    end = Some(event);
+                    break;
+                }
+                Event::End(..) => nest -= 1,
+                _ => (),
+            }
+            alt.push(event);
+        }
+
+        let attrs = match start {
+            Event::Start(Tag::Image(_, ref dest, ref title)) => (self.callback)(dest, title, &alt)
+                .filter(|attrs| !attrs.is_empty())
+                .map(|attrs| img_html(dest, title, &alt, &attrs)),
+            _ => unreachable!(),
+        };
+        match attrs {
+            Some(html) => Some(Event::Html(html.into())),
+            None => {
+                self.pending.extend(alt);
+                self.pending.extend(end);
+                Some(start)
+            }
+        }
+    }
+}
+
+fn img_html(dest: &str, title: &str, alt: &[Event], attrs: &ImageAttributes) -> String {
+    // writing to a string can't fail
+    let mut s = String::new();
+    s.push_str("<img src=\"");
+    escape_href(&mut s, dest).unwrap();
+    s.push_str("\" alt=\"");
+    for event in alt {
+        match event {
+            Event::Html(text) | Event::Code(text) | Event::Text(text) => {
+                escape_html(&mut s, text).unwrap()
+            }
+            Event::SoftBreak | Event::HardBreak | Event::Rule => s.push(' '),
+            Event::FootnoteReference(name) => {
+                s.push('[');
+                escape_html(&mut s, name).unwrap();
+                s.push(']');
+            }
+            Event::TaskListMarker(true) => s.push_str("[x]"),
+            Event::TaskListMarker(false) => s.push_str("[ ]"),
+            Event::Start(..) | Event::End(..) => (),
+        }
+    }
+    if !title.is_empty() {
+        s.push_str("\" title=\"");
+        escape_html(&mut s, title).unwrap();
+    }
+    s.push('"');
+    if let Some(width) = attrs.width {
+        write!(s, " width=\"{}\"", width).unwrap();
+    }
+    if let Some(height) = attrs.height {
+        write!(s, " height=\"{}\"", height).unwrap();
+    }
+    let strings = [
+        ("class", &attrs.class),
+        ("loading", &attrs.loading),
+        ("decoding", &attrs.decoding),
+    ];
+    for (name, value) in strings.iter() {
+        if let Some(value) = value {
+            write!(s, " {}=\"", name).unwrap();
+            escape_html(&mut s, value).unwrap();
+            s.push('"');
+        }
+    }
+    s.push_str(" />");
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Parser};
+
+    #[test]
+    fn attributes_and_passthrough() {
+        let input = "![one](a.png \"T\") ![two `x`](b.png)";
+        let mut alts = Vec::new();
+        let images = InjectImageAttributes::new(Parser::new(input), |src, title, alt| {
+            alts.push(alt.len());
+            if src == "a.png" {
+                assert_eq!("T", title);
+                Some(ImageAttributes {
+                    class: Some("wide \"x\"".to_owned()),
+                    ..Default::default()
+                })
+            } else {
+                Some(ImageAttributes::default())
+            }
+        });
+        let mut s = String::new();
+        html::push_html(&mut s, images);
+        assert_eq!(
+            "<p><img src=\"a.png\" alt=\"one\" title=\"T\" class=\"wide &quot;x&quot;\" /> \
+             <img src=\"b.png\" alt=\"two x\" /></p>\n",
+            s
+        );
+        assert_eq!(vec![1, 2], alts);
+    }
+}
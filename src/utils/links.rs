@@ -0,0 +1,201 @@
+//! Collection of links, images and footnote references.
+
+use std::ops::Range;
+
+use crate::strings::CowStr;
+use crate::utils::{has_scheme, push_event_text};
+use crate::{Event, LinkType, Tag};
+
+/// The kind of a collected reference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkItemKind {
+    /// A link. The destination is the link URL.
+    Link,
+    /// An image. The destination is the image source.
+    Image,
+    /// A footnote reference. The destination is the footnote label.
+    FootnoteReference,
+}
+
+/// A link, image or footnote reference found in a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkItem<'a> {
+    pub kind: LinkItemKind,
+    /// The link type, or `None` for footnote references.
+    pub link_type: Option<LinkType>,
+    pub dest: CowStr<'a>,
+    pub title: CowStr<'a>,
+    /// The text of the link, or the alt text of the image.
+    pub text: String,
+    /// The plain text of the innermost block containing the item.
+    pub context: String,
+    /// The item's range in the source.
+    pub span: Range<usize>,
+}
+
+impl<'a> LinkItem<'a> {
+    /// Whether the item is a link or image to a path relative to the
+    /// document or its site, like `guide.md#usage` or `/logo.png`, rather
+    /// than to a URL, an email address or an anchor of the document.
+    pub fn is_local_path(&self) -> bool {
+        let dest = &*self.dest;
+        self.kind != LinkItemKind::FootnoteReference
+            && self.link_type != Some(LinkType::Email)
+            && !dest.is_empty()
+            && !dest.starts_with('#')
+            && !dest.starts_with("//")
+            && !has_scheme(dest)
+    }
+}
+
+/// Walks an offset event stream and returns all links, images and footnote
+/// references in document order.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::links::{collect_links, LinkItemKind};
+/// use pulldown_cmark::Parser;
+///
+/// let input = "See [the docs](docs.html) and ![a logo](logo.png).";
+/// let links = collect_links(Parser::new(input).into_offset_iter());
+///
+/// assert_eq!(links.len(), 2);
+/// assert_eq!(links[0].kind, LinkItemKind::Link);
+/// assert_eq!(&*links[0].dest, "docs.html");
+/// assert_eq!(links[0].text, "the docs");
+/// assert_eq!(links[1].context, "See the docs and a logo.");
+/// assert_eq!(&input[links[1].span.clone()], "![a logo](logo.png)");
+/// ```
+pub fn collect_links<'a, I>(iter: I) -> Vec<LinkItem<'a>>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut items: Vec<LinkItem<'a>> = Vec::new();
+    // indices into `items` of the links and images currently open
+    let mut open: Vec<usize> = Vec::new();
+    // text of the enclosing blocks, with the index of their first item
+    let mut blocks: Vec<(usize, String)> = Vec::new();
+
+    for (event, span) in iter {
+        match event {
+            Event::Start(Tag::Link(link_type, dest, title)) => {
+                open.push(items.len());
+                items.push(new_item(LinkItemKind::Link, link_type, dest, title, span));
+            }
+            Event::Start(Tag::Image(link_type, dest, title)) => {
+                open.push(items.len());
+                items.push(new_item(LinkItemKind::Image, link_type, dest, title, span));
+            }
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => {
+                open.pop();
+            }
+            Event::FootnoteReference(label) => {
+                items.push(LinkItem {
+                    kind: LinkItemKind::FootnoteReference,
+                    link_type: None,
+                    dest: label,
+                    title: "".into(),
+                    text: String::new(),
+                    context: String::new(),
+                    span,
+                });
+            }
+            Event::Start(ref tag) if is_block(tag) => blocks.push((items.len(), String::new())),
+            Event::End(ref tag) if is_block(tag) => {
+                if let Some((first, text)) = blocks.pop() {
+                    for item in &mut items[first..] {
+                        if item.context.is_empty() {
+                            item.context.push_str(&text);
+                        }
+                    }
+                    if let Some((_, outer)) = blocks.last_mut() {
+                        outer.push_str(&text);
+                    }
+                }
+            }
+            ref event => {
+                for &ix in &open {
+                    push_event_text(&mut items[ix].text, event);
+                }
+                if let Some((_, block)) = blocks.last_mut() {
+                    match event {
+                        Event::SoftBreak | Event::HardBreak => block.push(' '),
+                        _ => push_event_text(block, event),
+                    }
+                }
+            }
+        }
+    }
+    items
+}
+
+fn new_item<'a>(
+    kind: LinkItemKind,
+    link_type: LinkType,
+    dest: CowStr<'a>,
+    title: CowStr<'a>,
+    span: Range<usize>,
+) -> LinkItem<'a> {
+    LinkItem {
+        kind,
+        link_type: Some(link_type),
+        dest,
+        title,
+        text: String::new(),
+        context: String::new(),
+        span,
+    }
+}
+
+fn is_block(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph | Tag::Heading(..) | Tag::Item | Tag::TableCell
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn nested_and_footnotes() {
+        let input =
+            "# [![icon](i.png) Home](/)\n\n* item[^1]\n\n[^1]: a [link][l]\n\n[l]: x \"T\"\n";
+        let parser = Parser::new_ext(input, Options::ENABLE_FOOTNOTES).into_offset_iter();
+        let links = collect_links(parser);
+
+        let summary: Vec<_> = links
+            .iter()
+            .map(|l| (l.kind, l.dest.as_ref(), l.text.as_str(), l.context.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (LinkItemKind::Link, "/", "icon Home", "icon Home"),
+                (LinkItemKind::Image, "i.png", "icon", "icon Home"),
+                (LinkItemKind::FootnoteReference, "1", "", "item"),
+                (LinkItemKind::Link, "x", "link", "a link"),
+            ],
+            summary
+        );
+        assert_eq!(Some(LinkType::Reference), links[3].link_type);
+        assert_eq!("T", links[3].title.as_ref());
+        assert_eq!("[^1]", &input[links[2].span.clone()]);
+    }
+
+    #[test]
+    fn local_paths() {
+        let input = "[a](guide.md#usage) ![b](/logo.png) [c](#top) [d](https://x.org) \
+                     [e](//x.org/y) [f](mailto:a@b.c) <g@h.com> <https://i.org> [^1]\n\n\
+                     [^1]: note\n";
+        let parser = Parser::new_ext(input, Options::ENABLE_FOOTNOTES).into_offset_iter();
+        let local: Vec<_> = collect_links(parser)
+            .into_iter()
+            .filter(LinkItem::is_local_path)
+            .map(|link| link.dest.into_string())
+            .collect();
+        assert_eq!(vec!["guide.md#usage", "/logo.png"], local);
+    }
+}
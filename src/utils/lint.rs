@@ -0,0 +1,272 @@
+//! Style checks for markdown sources.
+//!
+//! [`lint`](fn.lint.html) walks an offset event stream together with its
+//! source and reports constructs that render fine but are usually
+//! unintended or inconsistent. Every [`Rule`](enum.Rule.html) has a stable
+//! id, for configuration and for tools reading the diagnostics.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::utils::push_event_text;
+use crate::{Event, HeadingLevel, Tag};
+
+/// A style rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// Bullet list items use a different marker than the first bullet list
+    /// item of the document.
+    BulletStyle,
+    /// A heading is more than one level deeper than the previous heading.
+    HeadingIncrement,
+    /// A URL appears in text without being a link.
+    BareUrl,
+    /// A hard line break is made of trailing spaces, which are invisible in
+    /// most editors, rather than a backslash.
+    TrailingSpaceBreak,
+    /// A heading has the same text as an earlier heading.
+    DuplicateHeading,
+}
+
+impl Rule {
+    /// All rules, in the order of their ids.
+    pub const ALL: [Rule; 5] = [
+        Rule::BareUrl,
+        Rule::BulletStyle,
+        Rule::DuplicateHeading,
+        Rule::HeadingIncrement,
+        Rule::TrailingSpaceBreak,
+    ];
+
+    /// The id of the rule, in kebab case.
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::BulletStyle => "bullet-style",
+            Rule::HeadingIncrement => "heading-increment",
+            Rule::BareUrl => "bare-url",
+            Rule::TrailingSpaceBreak => "trailing-space-break",
+            Rule::DuplicateHeading => "duplicate-heading",
+        }
+    }
+
+    /// Returns the rule with the given id.
+    pub fn from_id(id: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.id() == id)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// A rule violation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    /// The offending range of the source.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// Checks an offset event stream of `source` against all rules.
+///
+/// Diagnostics are returned in document order.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::lint::{lint, Rule};
+/// use pulldown_cmark::Parser;
+///
+/// let input = "# Title\n\n### Details\n\nSee https://example.com.\n";
+/// let diagnostics = lint(input, Parser::new(input).into_offset_iter());
+///
+/// let rules: Vec<_> = diagnostics.iter().map(|d| d.rule).collect();
+/// assert_eq!(rules, [Rule::HeadingIncrement, Rule::BareUrl]);
+/// assert_eq!(&input[diagnostics[1].span.clone()], "https://example.com");
+/// ```
+pub fn lint<'a, I>(source: &str, iter: I) -> Vec<Diagnostic>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut diagnostics = Vec::new();
+    // whether each open list is a bullet list
+    let mut lists = Vec::new();
+    let mut bullet = None;
+    let mut previous_level: Option<HeadingLevel> = None;
+    let mut heading: Option<(String, Range<usize>)> = None;
+    // lines of the first headings with each text
+    let mut headings = HashMap::new();
+    // a line number and the offset it was counted up to
+    let mut line = (1, 0);
+    // depth of links, images and code blocks, in which URLs are fine
+    let mut literal = 0;
+
+    for (event, span) in iter {
+        match event {
+            Event::Start(Tag::List(start)) => lists.push(start.is_none()),
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Item) if lists.last() == Some(&true) => {
+                let marker = source[span.clone()].trim_start().chars().next();
+                match (bullet, marker) {
+                    (None, Some(marker)) => bullet = Some(marker),
+                    (Some(expected), Some(marker)) if marker != expected => {
+                        let start = span.start + source[span.clone()].find(marker).unwrap_or(0);
+                        diagnostics.push(Diagnostic {
+                            rule: Rule::BulletStyle,
+                            span: start..start + 1,
+                            message: format!("bullet `{}` instead of `{}`", marker, expected),
+                        });
+                    }
+                    _ => (),
+                }
+            }
+            Event::Start(Tag::Heading(level)) => {
+                if let Some(previous) = previous_level {
+                    if level as usize > previous as usize + 1 {
+                        diagnostics.push(Diagnostic {
+                            rule: Rule::HeadingIncrement,
+                            span: span.clone(),
+                            message: format!(
+                                "level {} heading after a level {} heading",
+                                level as usize, previous as usize
+                            ),
+                        });
+                    }
+                }
+                previous_level = Some(level);
+                heading = Some((String::new(), span));
+            }
+            Event::End(Tag::Heading(_)) => {
+                if let Some((text, span)) = heading.take() {
+                    let key = text.trim().to_lowercase();
+                    if let Some(first) = headings.get(&key) {
+                        diagnostics.push(Diagnostic {
+                            rule: Rule::DuplicateHeading,
+                            span,
+                            message: format!("same heading as on line {}", first),
+                        });
+                    } else {
+                        if span.start < line.1 {
+                            line = (1, 0);
+                        }
+                        line.0 += source[line.1..span.start].matches('\n').count();
+                        line.1 = span.start;
+                        headings.insert(key, line.0);
+                    }
+                }
+            }
+            Event::Start(Tag::Link(..)) | Event::Start(Tag::Image(..)) => literal += 1,
+            Event::Start(Tag::CodeBlock(_)) => literal += 1,
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => literal -= 1,
+            Event::End(Tag::CodeBlock(_)) => literal -= 1,
+            Event::HardBreak if source[span.clone()].starts_with(' ') => {
+                diagnostics.push(Diagnostic {
+                    rule: Rule::TrailingSpaceBreak,
+                    span,
+                    message: "hard break made of trailing spaces, use a backslash".to_owned(),
+                });
+            }
+            Event::Text(ref text) if literal == 0 => {
+                bare_urls(source, text, span, &mut diagnostics);
+            }
+            _ => (),
+        }
+        if let Some((ref mut text, _)) = heading {
+            push_event_text(text, &event);
+        }
+    }
+    diagnostics
+}
+
+/// Reports the URLs in a text event at `span`.
+fn bare_urls(source: &str, text: &str, span: Range<usize>, diagnostics: &mut Vec<Diagnostic>) {
+    let mut rest = text;
+    while let Some(start) = ["http://", "https://"]
+        .iter()
+        .filter_map(|scheme| rest.find(scheme))
+        .min()
+    {
+        let len = rest[start..]
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .unwrap_or(rest.len() - start);
+        let url = rest[start..start + len].trim_end_matches(|c| ".,:;!?)'\"".contains(c));
+        rest = &rest[start + len..];
+        if !url.contains("://") || url.ends_with("://") {
+            continue;
+        }
+        // the text is a slice of the source unless it had escapes
+        let url_span = source[span.clone()].find(url).map_or(span.clone(), |ix| {
+            span.start + ix..span.start + ix + url.len()
+        });
+        diagnostics.push(Diagnostic {
+            rule: Rule::BareUrl,
+            span: url_span,
+            message: format!("bare URL, write it as <{}>", url),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn rules() {
+        let input = "\
+# Title
+
+- one
+* two
+- three
+
+## Setup
+
+Line  \n\
+break\\
+and http://example.com/a, [https://example.com/b](https://example.com/b)
+and <https://example.com/c>.
+
+    https://example.com/d
+
+#### Title
+";
+        let diagnostics = lint(
+            input,
+            Parser::new_ext(input, Options::empty()).into_offset_iter(),
+        );
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.rule.id(), &input[d.span.clone()]))
+            .collect();
+        let duplicate = diagnostics.last().map(|d| d.message.as_str());
+        assert_eq!(
+            found,
+            [
+                ("bullet-style", "*"),
+                ("trailing-space-break", "  \n"),
+                ("bare-url", "http://example.com/a"),
+                ("heading-increment", "#### Title\n"),
+                ("duplicate-heading", "#### Title\n"),
+            ]
+        );
+        assert_eq!(duplicate, Some("same heading as on line 1"));
+    }
+
+    #[test]
+    fn duplicate_heading_line() {
+        let input = "Intro\n\n# One\n\nText\n\n## Two\n\n# one\n\n## Two\n";
+        let diagnostics = lint(input, Parser::new(input).into_offset_iter());
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["same heading as on line 3", "same heading as on line 7"]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! TeX math written between dollar signs.
+
+use std::collections::VecDeque;
+
+use crate::escape::escape_html;
+use crate::strings::CowStr;
+use crate::{Event, Tag};
+
+/// Whether math is part of the text or shown as a block of its own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MathDisplay {
+    /// Math between single dollar signs, `$x^2$`.
+    Inline,
+    /// Math between double dollar signs, `$$\sum_i x_i$$`.
+    Block,
+}
+
+/// Iterator adapter that hands TeX math in text to a renderer.
+///
+/// The parser has no notion of math, so math arrives as part of `Text`
+/// events. This adapter finds it and replaces it by an `Event::Html` holding
+/// what the callback returns for the TeX source, for example the MathML
+/// produced by a KaTeX binding. [`math_spans`](fn.math_spans.html) wraps the
+/// math for client side rendering instead.
+///
+/// Math follows the rules of Pandoc: an opening `$` must not be followed by
+/// white space, and a closing `$` must not be preceded by white space nor
+/// followed by a digit, so that `$5 and $10` stays text. Double dollar signs
+/// delimit display math, which can span several lines. Math inside code is
+/// left alone.
+///
+/// The source has been parsed as markdown before the adapter sees it, so
+/// characters that are markdown syntax, like `*` or `_` pairs, need to be
+/// escaped inside math.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::math::{MathDisplay, RenderMath};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let parser = Parser::new("Euler: $e^{i\\pi} + 1 = 0$");
+/// let math = RenderMath::new(parser, |tex, display| {
+///     assert_eq!(display, MathDisplay::Inline);
+///     format!("<math>{}</math>", tex)
+/// });
+///
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, math);
+/// assert_eq!(html_buf, "<p>Euler: <math>e^{i\\pi} + 1 = 0</math></p>\n");
+/// ```
+pub struct RenderMath<'a, I, F> {
+    iter: I,
+    callback: F,
+    pending: VecDeque<Event<'a>>,
+    in_code_block: bool,
+}
+
+impl<'a, I, F> RenderMath<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: FnMut(&str, MathDisplay) -> String,
+{
+    /// Creates an adapter calling `callback` with the TeX source of every
+    /// piece of math.
+    pub fn new(iter: I, callback: F) -> Self {
+        RenderMath {
+            iter,
+            callback,
+            pending: VecDeque::new(),
+            in_code_block: false,
+        }
+    }
+
+    /// Collects a run of text and soft breaks starting with `first`, and
+    /// queues it with its math rendered.
+    fn text_run(&mut self, first: CowStr<'a>) {
+        let mut run = first.into_string();
+        let mut after = None;
+        for event in self.iter.by_ref() {
+            match event {
+                Event::Text(text) => run.push_str(&text),
+                Event::SoftBreak => run.push('\n'),
+                event => {
+                    after = Some(event);
+                    break;
+                }
+            }
+        }
+
+        let mut rest = &run[..];
+        while let Some((start, end, display)) = find_math(rest) {
+            self.push_text(&rest[..start]);
+            let delimiter = match display {
+                MathDisplay::Inline => 1,
+                MathDisplay::Block => 2,
+            };
+            let html = (self.callback)(&rest[start + delimiter..end - delimiter], display);
+            self.pending.push_back(Event::Html(html.into()));
+            rest = &rest[end..];
+        }
+        self.push_text(rest);
+        self.pending.extend(after);
+    }
+
+    /// Queues text, turning line endings back into soft breaks.
+    fn push_text(&mut self, text: &str) {
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                self.pending.push_back(Event::SoftBreak);
+            }
+            if !line.is_empty() {
+                self.pending
+                    .push_back(Event::Text(CowStr::from(line.to_owned())));
+            }
+        }
+    }
+}
+
+impl<'a, I, F> Iterator for RenderMath<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: FnMut(&str, MathDisplay) -> String,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        let event = match self.pending.pop_front() {
+            Some(event) => event,
+            None => match self.iter.next()? {
+                Event::Text(text) if !self.in_code_block && text.contains('$') => {
+                    self.text_run(text);
+                    self.pending.pop_front()?
+                }
+                event => event,
+            },
+        };
+        match event {
+            Event::Start(Tag::CodeBlock(..)) => self.in_code_block = true,
+            Event::End(Tag::CodeBlock(..)) => self.in_code_block = false,
+            _ => (),
+        }
+        Some(event)
+    }
+}
+
+/// Returns the start and end of the first piece of math in `text`,
+/// delimiters included.
+fn find_math(text: &str) -> Option<(usize, usize, MathDisplay)> {
+    let bytes = text.as_bytes();
+    let mut ix = 0;
+    while let Some(offset) = text[ix..].find('$') {
+        let start = ix + offset;
+        if bytes.get(start + 1) == Some(&b'$') {
+            if let Some(len) = text[start + 2..].find("$$") {
+                if len > 0 {
+                    return Some((start, start + 2 + len + 2, MathDisplay::Block));
+                }
+            }
+            ix = start + 2;
+            continue;
+        }
+        ix = start + 1;
+        match bytes.get(ix) {
+            Some(b) if !b.is_ascii_whitespace() => (),
+            _ => continue,
+        }
+        let mut close = ix;
+        while let Some(offset) = text[close + 1..].find('$') {
+            close += 1 + offset;
+            let closes = !bytes[close - 1].is_ascii_whitespace()
+                && !bytes.get(close + 1).map_or(false, u8::is_ascii_digit);
+            if closes && !text[ix..close].contains('\n') {
+                return Some((start, close + 1, MathDisplay::Inline));
+            }
+        }
+    }
+    None
+}
+
+/// Wraps math in `<span class="math inline">\(...\)</span>` and
+/// `<span class="math display">\[...\]</span>`, the markup expected by the
+/// client side renderers of KaTeX and MathJax.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::math::math_spans;
+/// use pulldown_cmark::{html, Parser};
+///
+/// let mut html_buf = String::new();
+/// html::push_html(&mut html_buf, math_spans(Parser::new("$$a < b$$")));
+/// assert_eq!(
+///     html_buf,
+///     "<p><span class=\"math display\">\\[a &lt; b\\]</span></p>\n"
+/// );
+/// ```
+pub fn math_spans<'a, I>(iter: I) -> RenderMath<'a, I, impl FnMut(&str, MathDisplay) -> String>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    RenderMath::new(iter, |tex, display| {
+        let (class, open, close) = match display {
+            MathDisplay::Inline => ("inline", "\\(", "\\)"),
+            MathDisplay::Block => ("display", "\\[", "\\]"),
+        };
+        // writing to a string can't fail
+        let mut s = format!("<span class=\"math {}\">{}", class, open);
+        escape_html(&mut s, tex).unwrap();
+        s.push_str(close);
+        s.push_str("</span>");
+        s
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Parser};
+
+    fn render(input: &str) -> String {
+        let mut s = String::new();
+        html::push_html(&mut s, math_spans(Parser::new(input)));
+        s
+    }
+
+    #[test]
+    fn delimiters() {
+        assert_eq!("<p>$5 and $10, $ x $</p>\n", render("$5 and $10, $ x $"));
+        assert_eq!(
+            "<p>a <span class=\"math inline\">\\(x_[i]\\)</span> b</p>\n",
+            render("a $x_[i]$ b")
+        );
+        assert_eq!(
+            "<p><span class=\"math display\">\\[\nx\n\\]</span>\nafter</p>\n",
+            render("$$\nx\n$$\nafter")
+        );
+        assert_eq!(
+            "<pre><code>$x$\n</code></pre>\n<p><code>$y$</code></p>\n",
+            render("    $x$\n\n`$y$`")
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Merging several documents into one.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::strings::CowStr;
+use crate::utils::slug::{AnchorStyle, HeadingAnchors, HeadingIds, Slugger};
+use crate::utils::toc::collect_toc;
+use crate::{Event, Tag};
+
+/// Concatenates documents, keeping their labels apart.
+///
+/// Documents written separately, like the chapters of a book, often reuse
+/// footnote labels and heading names. The merger renames footnote labels
+/// that were already used by an earlier document and anchors every heading
+/// with a slug that is unique over the merged document. Links to a heading
+/// of the same document (`[see above](#intro)`) are updated to the renamed
+/// slug.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::merge::Merger;
+/// use pulldown_cmark::utils::slug::{AnchorStyle, Slugger};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let mut merger = Merger::new(Slugger::default(), AnchorStyle::Attribute);
+/// merger.push(Parser::new("# Intro\n"));
+/// merger.push(Parser::new("# Intro\n\n[top](#intro)\n"));
+///
+/// let ids = merger.heading_ids();
+/// let events = merger.finish().into_iter();
+/// let mut html_buf = String::new();
+/// html::push_html_with_renderer(&mut html_buf, events, &html::Options::default(), ids);
+/// assert_eq!(
+///     html_buf,
+///     "<h1 id=\"intro\">Intro</h1>\n<h1 id=\"intro-1\">Intro</h1>\n\
+///      <p><a href=\"#intro-1\">top</a></p>\n"
+/// );
+/// ```
+pub struct Merger<'a> {
+    slugger: Slugger,
+    anchors: AnchorStyle,
+    heading_ids: HeadingIds,
+    footnotes: HashSet<String>,
+    events: Vec<Event<'a>>,
+}
+
+impl<'a> Merger<'a> {
+    /// Creates a merger anchoring headings with the given slugger and style.
+    pub fn new(slugger: Slugger, anchors: AnchorStyle) -> Self {
+        Merger {
+            slugger,
+            anchors,
+            heading_ids: HeadingIds::new(),
+            footnotes: HashSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Appends a document.
+    pub fn push<I>(&mut self, doc: I)
+    where
+        I: IntoIterator<Item = Event<'a>>,
+    {
+        let events: Vec<_> = doc.into_iter().collect();
+
+        // slugs the document would have on its own, and in the merged document
+        let local = collect_toc(
+            events.iter().cloned(),
+            &mut Slugger::new(self.slugger.style()),
+        );
+        let global = collect_toc(events.iter().cloned(), &mut self.slugger.fork());
+        let slugs: HashMap<String, String> = local
+            .into_iter()
+            .zip(global)
+            .filter(|(local, global)| local.slug != global.slug)
+            .map(|(local, global)| (format!("#{}", local.slug), format!("#{}", global.slug)))
+            .collect();
+
+        let mut labels: HashMap<CowStr<'a>, CowStr<'a>> = HashMap::new();
+        for event in &events {
+            match event {
+                Event::FootnoteReference(label) | Event::Start(Tag::FootnoteDefinition(label))
+                    if !labels.contains_key(label) =>
+                {
+                    let renamed = self.unique_label(label);
+                    labels.insert(label.clone(), renamed);
+                }
+                _ => (),
+            }
+        }
+
+        let relabel = |label: CowStr<'a>| labels[&label].clone();
+        let relink = |dest: CowStr<'a>| match slugs.get(&*dest) {
+            Some(slug) => slug.clone().into(),
+            None => dest,
+        };
+        let events = events.into_iter().map(|event| match event {
+            Event::FootnoteReference(label) => Event::FootnoteReference(relabel(label)),
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                Event::Start(Tag::FootnoteDefinition(relabel(label)))
+            }
+            Event::End(Tag::FootnoteDefinition(label)) => {
+                Event::End(Tag::FootnoteDefinition(relabel(label)))
+            }
+            Event::Start(Tag::Link(link_type, dest, title)) => {
+                Event::Start(Tag::Link(link_type, relink(dest), title))
+            }
+            Event::End(Tag::Link(link_type, dest, title)) => {
+                Event::End(Tag::Link(link_type, relink(dest), title))
+            }
+            event => event,
+        });
+
+        let slugger = std::mem::take(&mut self.slugger);
+        let mut anchored = HeadingAnchors::with_slugger(events, slugger, self.anchors)
+            .with_heading_ids(self.heading_ids.clone());
+        self.events.extend(&mut anchored);
+        self.slugger = anchored.into_slugger();
+    }
+
+    /// Returns the slugs of `AnchorStyle::Attribute` for every document
+    /// pushed, to render the merged document with.
+    pub fn heading_ids(&self) -> HeadingIds {
+        self.heading_ids.clone()
+    }
+
+    /// Returns the merged document.
+    pub fn finish(self) -> Vec<Event<'a>> {
+        self.events
+    }
+
+    fn unique_label(&mut self, label: &CowStr<'a>) -> CowStr<'a> {
+        if self.footnotes.insert(label.to_string()) {
+            return label.clone();
+        }
+        let mut n = 1;
+        loop {
+            n += 1;
+            let candidate = format!("{}-{}", label, n);
+            if self.footnotes.insert(candidate.clone()) {
+                return candidate.into();
+            }
+        }
+    }
+}
+
+/// Merges documents, anchoring headings with GitHub style slugs.
+pub fn merge_documents<'a, D, I>(docs: D) -> Vec<Event<'a>>
+where
+    D: IntoIterator<Item = I>,
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut merger = Merger::new(Slugger::default(), AnchorStyle::Link);
+    for doc in docs {
+        merger.push(doc);
+    }
+    merger.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn footnote_labels_are_renamed() {
+        let docs = vec![
+            "a[^n]\n\n[^n]: first\n",
+            "b[^n] c[^m]\n\n[^n]: second\n\n[^m]: third\n",
+            "d[^n]\n\n[^n]: fourth\n",
+        ];
+        let events = merge_documents(
+            docs.into_iter()
+                .map(|doc| Parser::new_ext(doc, Options::ENABLE_FOOTNOTES)),
+        );
+        let labels: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::FootnoteReference(label) => Some(format!("ref {}", label)),
+                Event::Start(Tag::FootnoteDefinition(label)) => Some(format!("def {}", label)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec!["ref n", "def n", "ref n-2", "ref m", "def n-2", "def m", "ref n-3", "def n-3"],
+            labels
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Utilities that operate on event streams.
+//!
+//! This module contains iterator adapters that transform a stream of
+//! [`Event`](../enum.Event.html)s, as well as functions that walk a stream and
+//! extract information from it. None of them depend on a particular writer, so
+//! they can be combined freely with the [HTML renderer](../html/index.html) or
+//! with custom code.
+
+pub mod admonitions;
+pub mod anchors;
+pub mod check;
+pub mod excerpt;
+pub mod footnotes;
+pub mod front_matter;
+pub mod html_filter;
+pub mod ids;
+pub mod images;
+pub mod links;
+pub mod lint;
+pub mod math;
+pub mod merge;
+pub mod normalize;
+pub mod plain;
+pub mod query;
+pub mod references;
+pub mod rewrite;
+pub mod search;
+pub mod sections;
+pub mod slug;
+pub mod stats;
+pub mod tables;
+pub mod tasks;
+pub mod toc;
+
+use crate::Event;
+
+/// Appends the textual content of an event to `buf`, if it has any.
+///
+/// Only `Text` and `Code` events contribute; markup is ignored.
+pub(crate) fn push_event_text(buf: &mut String, event: &Event) {
+    match event {
+        Event::Text(text) | Event::Code(text) => buf.push_str(text),
+        _ => (),
+    }
+}
+
+/// Whether a destination starts with a URL scheme, like `https:`. Single
+/// letters are taken for Windows drives.
+pub(crate) fn has_scheme(dest: &str) -> bool {
+    match dest.find(':') {
+        Some(colon) => {
+            let scheme = &dest[..colon];
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,156 @@
+//! Canonical form of event streams.
+
+use std::iter::Peekable;
+
+use crate::strings::CowStr;
+use crate::utils::references::InlineReferences;
+use crate::{Event, Tag};
+
+/// Iterator adapter producing a canonical event sequence.
+///
+/// Two documents that render the same usually produce the same events after
+/// normalization, even when the parser splits text differently:
+///
+/// * adjacent `Text` events are merged into one, and empty ones removed,
+/// * spaces and tabs around soft and hard breaks are removed,
+/// * empty paragraphs are removed,
+/// * reference, collapsed and shortcut links and images become inline ones,
+///   as done by
+///   [`InlineReferences`](../references/struct.InlineReferences.html).
+///
+/// This makes normalized streams suitable for comparisons, for example as
+/// cache keys or in tests.
+pub struct Normalize<'a, I: Iterator<Item = Event<'a>>> {
+    iter: Peekable<InlineReferences<I>>,
+    after_break: bool,
+}
+
+impl<'a, I> Normalize<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I) -> Self {
+        Normalize {
+            iter: InlineReferences::new(iter).peekable(),
+            after_break: false,
+        }
+    }
+
+    fn next_text(&mut self, first: CowStr<'a>) -> Option<CowStr<'a>> {
+        let mut text = first;
+        while let Some(Event::Text(..)) = self.iter.peek() {
+            if let Some(Event::Text(next)) = self.iter.next() {
+                text = concat(text, &next);
+            }
+        }
+        let end = match self.iter.peek() {
+            Some(Event::SoftBreak) | Some(Event::HardBreak) => {
+                text.trim_end_matches(is_blank).len()
+            }
+            _ => text.len(),
+        };
+        let start = if self.after_break {
+            text.len() - text.trim_start_matches(is_blank).len()
+        } else {
+            0
+        };
+        if start >= end {
+            None
+        } else if start == 0 && end == text.len() {
+            Some(text)
+        } else {
+            Some(substring(text, start, end))
+        }
+    }
+}
+
+impl<'a, I> Iterator for Normalize<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            let event = match self.iter.next()? {
+                Event::Text(text) => match self.next_text(text) {
+                    Some(text) => Event::Text(text),
+                    None => continue,
+                },
+                Event::Start(Tag::Paragraph) => {
+                    if let Some(Event::End(Tag::Paragraph)) = self.iter.peek() {
+                        self.iter.next();
+                        continue;
+                    }
+                    Event::Start(Tag::Paragraph)
+                }
+                event => event,
+            };
+            self.after_break = matches!(event, Event::SoftBreak | Event::HardBreak);
+            return Some(event);
+        }
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn concat<'a>(text: CowStr<'a>, next: &str) -> CowStr<'a> {
+    let mut s = text.into_string();
+    s.push_str(next);
+    s.into()
+}
+
+fn substring(text: CowStr, start: usize, end: usize) -> CowStr {
+    match text {
+        CowStr::Borrowed(s) => CowStr::Borrowed(&s[start..end]),
+        text => text[start..end].to_owned().into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    fn normalized(input: &str) -> Vec<Event<'_>> {
+        Normalize::new(Parser::new(input)).collect()
+    }
+
+    #[test]
+    fn text_is_merged_and_trimmed() {
+        assert_eq!(
+            vec![
+                Event::Start(Tag::Paragraph),
+                Event::Text("a [b] c".into()),
+                Event::SoftBreak,
+                Event::Text("d".into()),
+                Event::HardBreak,
+                Event::Text("e".into()),
+                Event::End(Tag::Paragraph),
+            ],
+            normalized("a [b] c \nd  \ne")
+        );
+    }
+
+    #[test]
+    fn equivalent_documents() {
+        assert_eq!(
+            normalized("[x](/u) *a*&amp;b\n"),
+            normalized("[x] *a*&b   \n\n[x]: /u\n")
+        );
+        let events = vec![
+            Event::Start(Tag::Paragraph),
+            Event::End(Tag::Paragraph),
+            Event::Start(Tag::Paragraph),
+            Event::Text("".into()),
+            Event::Text("t".into()),
+            Event::End(Tag::Paragraph),
+        ];
+        assert_eq!(
+            normalized("t"),
+            Normalize::new(events.into_iter()).collect::<Vec<_>>()
+        );
+    }
+}
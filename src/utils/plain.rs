@@ -0,0 +1,133 @@
+//! Flattening of documents to plain text.
+
+use std::collections::HashMap;
+
+use crate::strings::CowStr;
+use crate::{Event, Tag};
+
+/// Iterator adapter that flattens a document into plain text events.
+///
+/// Only `Event::Text` events are produced. All inline formatting is removed,
+/// inline code becomes text, raw HTML is dropped, footnote references become
+/// markers like `[1]`, numbered in order of first appearance, and task list
+/// markers become `[ ]` or `[x]`. Every block ends with a newline, and table
+/// cells are separated by tabs. Soft breaks become spaces, hard breaks
+/// newlines.
+///
+/// Text events are passed through one by one rather than merged, so their
+/// granularity is kept.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::plain::PlainText;
+/// use pulldown_cmark::{Options, Parser};
+///
+/// let input = "# Title\n\nSome *emphasis*[^1] and `code`.\n\n[^1]: A note.\n";
+/// let parser = Parser::new_ext(input, Options::ENABLE_FOOTNOTES);
+/// let text: String = PlainText::new(parser).map(|event| match event {
+///     pulldown_cmark::Event::Text(text) => text.into_string(),
+///     _ => unreachable!(),
+/// }).collect();
+/// assert_eq!(text, "Title\nSome emphasis[1] and code.\n[1] A note.\n");
+/// ```
+pub struct PlainText<'a, I> {
+    iter: I,
+    numbers: HashMap<CowStr<'a>, usize>,
+    line_start: bool,
+}
+
+impl<'a, I> PlainText<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I) -> Self {
+        PlainText {
+            iter,
+            numbers: HashMap::new(),
+            line_start: true,
+        }
+    }
+
+    fn footnote_marker(&mut self, label: CowStr<'a>) -> CowStr<'a> {
+        let len = self.numbers.len() + 1;
+        let number = *self.numbers.entry(label).or_insert(len);
+        format!("[{}]", number).into()
+    }
+
+    fn end_line(&self) -> Option<CowStr<'a>> {
+        if self.line_start {
+            None
+        } else {
+            Some("\n".into())
+        }
+    }
+}
+
+impl<'a, I> Iterator for PlainText<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            let text = match self.iter.next()? {
+                Event::Text(text) | Event::Code(text) => Some(text),
+                Event::FootnoteReference(label) => Some(self.footnote_marker(label)),
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    Some(format!("{} ", self.footnote_marker(label)).into())
+                }
+                Event::TaskListMarker(true) => Some("[x] ".into()),
+                Event::TaskListMarker(false) => Some("[ ] ".into()),
+                Event::SoftBreak => Some(" ".into()),
+                Event::HardBreak => Some("\n".into()),
+                Event::Rule => self.end_line(),
+                Event::End(Tag::TableCell) => Some("\t".into()),
+                Event::End(tag) if !is_inline(&tag) => self.end_line(),
+                Event::Start(..) | Event::End(..) | Event::Html(..) => None,
+            };
+            match text {
+                Some(text) if !text.is_empty() => {
+                    self.line_start = text.ends_with('\n');
+                    return Some(Event::Text(text));
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    fn flatten(input: &str) -> Vec<String> {
+        PlainText::new(Parser::new_ext(input, Options::all()))
+            .map(|event| match event {
+                Event::Text(text) => text.into_string(),
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blocks_and_granularity() {
+        let input = "> * [x] a <b>b</b>\n>   c\n\n```\nx\n```\n\n|a|b|\n|-|-|\n|1|2|\n\n***\n![alt *text*](i.png)\n";
+        assert_eq!(
+            vec![
+                "[x] ", "a ", "b", " ", "c", "\n", "x\n", "a", "\t", "b", "\t", "\n", "1", "\t",
+                "2", "\t", "\n", "alt ", "text", "\n"
+            ],
+            flatten(input)
+        );
+    }
+}
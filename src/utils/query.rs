@@ -0,0 +1,468 @@
+//! Selector queries over event streams.
+//!
+//! Selectors use a small subset of CSS syntax. A selector is a sequence of
+//! compound selectors separated by combinators: whitespace matches any
+//! descendant, `>` only direct children. A compound selector is an element
+//! name, or `*` for any element, followed by any number of attribute
+//! conditions in brackets:
+//!
+//! * `[name]` holds when the element has the attribute,
+//! * `[name=value]` when it has exactly that value,
+//! * `[name^=value]`, `[name$=value]` and `[name*=value]` when the value
+//!   starts with, ends with or contains the given string.
+//!
+//! Values can be quoted with `"` or `'`. The element names and their
+//! attributes are:
+//!
+//! | Name | Element | Attributes |
+//! |------|---------|------------|
+//! | `paragraph` | paragraph | |
+//! | `heading` | heading | `level` |
+//! | `blockquote` | block quote | |
+//! | `codeblock` | code block | `lang`, `fenced` |
+//! | `list` | list | `ordered`, `start` |
+//! | `item` | list item | |
+//! | `footnote` | footnote definition | `label` |
+//! | `table`, `thead`, `tr`, `td` | table parts | |
+//! | `em`, `strong`, `del` | emphasis, strong emphasis, strikethrough | |
+//! | `link`, `image` | link, image | `dest`, `title`, `type` |
+//! | `text`, `code`, `html` | text, inline code, raw HTML | `content` |
+//! | `footnote-ref` | footnote reference | `label` |
+//! | `softbreak`, `hardbreak`, `rule` | breaks, thematic break | |
+//! | `task` | task list marker | `checked` |
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::{CodeBlockKind, Event, LinkType, Tag};
+
+/// An error in the syntax of a selector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectorError {
+    /// Byte offset in the selector where the error was found.
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AttrOp {
+    Exists,
+    Equals,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct AttrCondition {
+    name: String,
+    op: AttrOp,
+    value: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Compound {
+    // `None` matches any element
+    name: Option<String>,
+    conditions: Vec<AttrCondition>,
+}
+
+/// A parsed selector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selector {
+    // compounds from left to right; each but the first has the combinator
+    // relating it to the one before
+    compounds: Vec<(Combinator, Compound)>,
+}
+
+impl Selector {
+    /// Parses a selector.
+    pub fn parse(selector: &str) -> Result<Selector, SelectorError> {
+        SelectorParser {
+            input: selector,
+            ix: 0,
+        }
+        .parse()
+    }
+
+    /// Returns true if `event` matches the selector, given the start events
+    /// of its ancestors, outermost first.
+    fn matches(&self, event: &Event, ancestors: &[Event]) -> bool {
+        let (_, last) = self.compounds.last().unwrap();
+        last.matches(event) && self.matches_ancestors(self.compounds.len() - 1, ancestors)
+    }
+
+    /// Matches the compounds before `ix` against `ancestors`.
+    fn matches_ancestors(&self, ix: usize, ancestors: &[Event]) -> bool {
+        if ix == 0 {
+            return true;
+        }
+        let (combinator, _) = self.compounds[ix];
+        let (_, ref compound) = self.compounds[ix - 1];
+        match combinator {
+            Combinator::Child => match ancestors.split_last() {
+                Some((parent, rest)) => {
+                    compound.matches(parent) && self.matches_ancestors(ix - 1, rest)
+                }
+                None => false,
+            },
+            Combinator::Descendant => (0..ancestors.len()).rev().any(|depth| {
+                compound.matches(&ancestors[depth])
+                    && self.matches_ancestors(ix - 1, &ancestors[..depth])
+            }),
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, event: &Event) -> bool {
+        let name = match element_name(event) {
+            Some(name) => name,
+            None => return false,
+        };
+        if let Some(ref wanted) = self.name {
+            if wanted != name {
+                return false;
+            }
+        }
+        self.conditions.iter().all(|condition| {
+            let value = match attribute(event, &condition.name) {
+                Some(value) => value,
+                None => return false,
+            };
+            match condition.op {
+                AttrOp::Exists => true,
+                AttrOp::Equals => value == condition.value,
+                AttrOp::Prefix => value.starts_with(&condition.value),
+                AttrOp::Suffix => value.ends_with(&condition.value),
+                AttrOp::Contains => value.contains(&condition.value),
+            }
+        })
+    }
+}
+
+struct SelectorParser<'s> {
+    input: &'s str,
+    ix: usize,
+}
+
+impl<'s> SelectorParser<'s> {
+    fn error<T>(&self, message: &'static str) -> Result<T, SelectorError> {
+        Err(SelectorError {
+            position: self.ix,
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.ix..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = &self.input[self.ix..];
+        let trimmed = rest.trim_start();
+        self.ix += rest.len() - trimmed.len();
+        rest.len() != trimmed.len()
+    }
+
+    fn ident(&mut self) -> &'s str {
+        let rest = &self.input[self.ix..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(rest.len());
+        self.ix += len;
+        &rest[..len]
+    }
+
+    fn parse(mut self) -> Result<Selector, SelectorError> {
+        let mut compounds = Vec::new();
+        let mut combinator = Combinator::Descendant;
+        self.skip_whitespace();
+        loop {
+            compounds.push((combinator, self.compound()?));
+            let had_whitespace = self.skip_whitespace();
+            combinator = match self.peek() {
+                None => break,
+                Some('>') => {
+                    self.ix += 1;
+                    self.skip_whitespace();
+                    Combinator::Child
+                }
+                Some(_) if had_whitespace => Combinator::Descendant,
+                Some(_) => return self.error("unexpected character"),
+            };
+        }
+        Ok(Selector { compounds })
+    }
+
+    fn compound(&mut self) -> Result<Compound, SelectorError> {
+        let name = if self.peek() == Some('*') {
+            self.ix += 1;
+            None
+        } else {
+            match self.ident() {
+                "" if self.peek() != Some('[') => return self.error("expected element name"),
+                "" => None,
+                name => Some(name.to_owned()),
+            }
+        };
+        let mut conditions = Vec::new();
+        while self.peek() == Some('[') {
+            self.ix += 1;
+            self.skip_whitespace();
+            let attr = self.ident();
+            if attr.is_empty() {
+                return self.error("expected attribute name");
+            }
+            self.skip_whitespace();
+            let rest = &self.input[self.ix..];
+            let (op, len) = if rest.starts_with(']') {
+                (AttrOp::Exists, 0)
+            } else if rest.starts_with('=') {
+                (AttrOp::Equals, 1)
+            } else if rest.starts_with("^=") {
+                (AttrOp::Prefix, 2)
+            } else if rest.starts_with("$=") {
+                (AttrOp::Suffix, 2)
+            } else if rest.starts_with("*=") {
+                (AttrOp::Contains, 2)
+            } else {
+                return self.error("expected `]` or an operator");
+            };
+            self.ix += len;
+            self.skip_whitespace();
+            let value = if op == AttrOp::Exists {
+                String::new()
+            } else {
+                self.value()?
+            };
+            self.skip_whitespace();
+            if self.peek() != Some(']') {
+                return self.error("expected `]`");
+            }
+            self.ix += 1;
+            conditions.push(AttrCondition {
+                name: attr.to_owned(),
+                op,
+                value,
+            });
+        }
+        Ok(Compound { name, conditions })
+    }
+
+    fn value(&mut self) -> Result<String, SelectorError> {
+        match self.peek() {
+            Some(quote @ '"') | Some(quote @ '\'') => {
+                let rest = &self.input[self.ix + 1..];
+                match rest.find(quote) {
+                    Some(len) => {
+                        self.ix += len + 2;
+                        Ok(rest[..len].to_owned())
+                    }
+                    None => self.error("unterminated string"),
+                }
+            }
+            _ => {
+                let rest = &self.input[self.ix..];
+                let len = rest
+                    .find(|c: char| c == ']' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return self.error("expected attribute value");
+                }
+                self.ix += len;
+                Ok(rest[..len].to_owned())
+            }
+        }
+    }
+}
+
+fn element_name(event: &Event) -> Option<&'static str> {
+    let name = match event {
+        Event::Start(tag) => match tag {
+            Tag::Paragraph => "paragraph",
+            Tag::Heading(..) => "heading",
+            Tag::BlockQuote => "blockquote",
+            Tag::CodeBlock(..) => "codeblock",
+            Tag::List(..) => "list",
+            Tag::Item => "item",
+            Tag::FootnoteDefinition(..) => "footnote",
+            Tag::Table(..) => "table",
+            Tag::TableHead => "thead",
+            Tag::TableRow => "tr",
+            Tag::TableCell => "td",
+            Tag::Emphasis => "em",
+            Tag::Strong => "strong",
+            Tag::Strikethrough => "del",
+            Tag::Link(..) => "link",
+            Tag::Image(..) => "image",
+        },
+        Event::End(..) => return None,
+        Event::Text(..) => "text",
+        Event::Code(..) => "code",
+        Event::Html(..) => "html",
+        Event::FootnoteReference(..) => "footnote-ref",
+        Event::SoftBreak => "softbreak",
+        Event::HardBreak => "hardbreak",
+        Event::Rule => "rule",
+        Event::TaskListMarker(..) => "task",
+    };
+    Some(name)
+}
+
+fn attribute(event: &Event, name: &str) -> Option<String> {
+    let flag = |set: bool| if set { Some(String::new()) } else { None };
+    match (event, name) {
+        (Event::Start(Tag::Heading(level)), "level") => Some((*level as usize).to_string()),
+        (Event::Start(Tag::CodeBlock(kind)), "lang") => match kind {
+            CodeBlockKind::Fenced(info) => info.split_whitespace().next().map(str::to_owned),
+            CodeBlockKind::Indented => None,
+        },
+        (Event::Start(Tag::CodeBlock(kind)), "fenced") => {
+            flag(matches!(kind, CodeBlockKind::Fenced(..)))
+        }
+        (Event::Start(Tag::List(start)), "ordered") => flag(start.is_some()),
+        (Event::Start(Tag::List(start)), "start") => start.map(|start| start.to_string()),
+        (Event::Start(Tag::FootnoteDefinition(label)), "label")
+        | (Event::FootnoteReference(label), "label") => Some(label.to_string()),
+        (Event::Start(Tag::Link(_, dest, _)), "dest")
+        | (Event::Start(Tag::Image(_, dest, _)), "dest") => Some(dest.to_string()),
+        (Event::Start(Tag::Link(_, _, title)), "title")
+        | (Event::Start(Tag::Image(_, _, title)), "title") => Some(title.to_string()),
+        (Event::Start(Tag::Link(link_type, ..)), "type")
+        | (Event::Start(Tag::Image(link_type, ..)), "type") => {
+            Some(link_type_name(*link_type).to_owned())
+        }
+        (Event::Text(text), "content")
+        | (Event::Code(text), "content")
+        | (Event::Html(text), "content") => Some(text.to_string()),
+        (Event::TaskListMarker(checked), "checked") => flag(*checked),
+        _ => None,
+    }
+}
+
+fn link_type_name(link_type: LinkType) -> &'static str {
+    match link_type {
+        LinkType::Inline => "inline",
+        LinkType::Reference | LinkType::ReferenceUnknown => "reference",
+        LinkType::Collapsed | LinkType::CollapsedUnknown => "collapsed",
+        LinkType::Shortcut | LinkType::ShortcutUnknown => "shortcut",
+        LinkType::Autolink => "autolink",
+        LinkType::Email => "email",
+        LinkType::WikiLink => "wikilink",
+    }
+}
+
+/// An element matched by a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryMatch<'a> {
+    /// The start event of the element, or the event itself for elements
+    /// without children such as text.
+    pub event: Event<'a>,
+    /// The element's range in the source.
+    pub span: Range<usize>,
+}
+
+/// Returns all elements of an offset event stream matching the selector, in
+/// document order.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::query::{query, Selector};
+/// use pulldown_cmark::Parser;
+///
+/// let input = "# Intro\n\n## Usage\n\nSee [docs](https://docs.rs).\n\n## *Notes*\n";
+/// let selector = Selector::parse("heading[level=2] > text").unwrap();
+/// let matches = query(Parser::new(input).into_offset_iter(), &selector);
+///
+/// let spans: Vec<_> = matches.iter().map(|m| &input[m.span.clone()]).collect();
+/// assert_eq!(spans, vec!["Usage"]);
+///
+/// let selector = Selector::parse("paragraph link[dest^=https]").unwrap();
+/// assert_eq!(query(Parser::new(input).into_offset_iter(), &selector).len(), 1);
+/// ```
+pub fn query<'a, I>(iter: I, selector: &Selector) -> Vec<QueryMatch<'a>>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut matches = Vec::new();
+    let mut ancestors: Vec<Event<'a>> = Vec::new();
+    for (event, span) in iter {
+        if let Event::End(..) = event {
+            ancestors.pop();
+            continue;
+        }
+        if selector.matches(&event, &ancestors) {
+            matches.push(QueryMatch {
+                event: event.clone(),
+                span,
+            });
+        }
+        if let Event::Start(..) = event {
+            ancestors.push(event);
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    fn query_spans<'a>(input: &'a str, selector: &str) -> Vec<&'a str> {
+        let selector = Selector::parse(selector).unwrap();
+        query(
+            Parser::new_ext(input, Options::all()).into_offset_iter(),
+            &selector,
+        )
+        .into_iter()
+        .map(|m| &input[m.span])
+        .collect()
+    }
+
+    #[test]
+    fn combinators_and_attributes() {
+        let input = "* a\n  * [ ] b\n  * [x] c\n\n> ```rust\n> x\n> ```\n\n1. `d`\n";
+        assert_eq!(vec!["a", "b", "c"], query_spans(input, "list item > text"));
+        assert_eq!(vec!["b", "c"], query_spans(input, "item item text"));
+        assert_eq!(vec!["[x]"], query_spans(input, "task[checked]"));
+        assert_eq!(vec!["`d`"], query_spans(input, "list[ordered] code"));
+        assert_eq!(
+            vec!["```rust\n> x\n> ```"],
+            query_spans(input, "blockquote > [lang='rust']")
+        );
+        assert!(query_spans(input, "paragraph > item").is_empty());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            Err(SelectorError {
+                position: 15,
+                message: "expected `]`",
+            }),
+            Selector::parse("heading[level=2")
+        );
+        assert_eq!(
+            "expected attribute name at offset 5",
+            Selector::parse("link[]").unwrap_err().to_string()
+        );
+        assert!(Selector::parse("a >").is_err());
+    }
+}
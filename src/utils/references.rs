@@ -0,0 +1,234 @@
+//! Conversion between reference and inline links.
+
+use std::collections::VecDeque;
+
+use crate::parse::RefDefs;
+use crate::{Event, LinkType, Tag};
+
+/// Iterator adapter that turns reference links into inline links.
+///
+/// The parser already resolves reference, collapsed and shortcut links,
+/// including those resolved by a broken link callback, so their destination
+/// and title are kept and only the link type changes to
+/// [`LinkType::Inline`](../../enum.LinkType.html#variant.Inline). Writers that
+/// look at the link type then render them as `[text](dest "title")`.
+pub struct InlineReferences<I> {
+    iter: I,
+}
+
+impl<'a, I> InlineReferences<I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I) -> Self {
+        InlineReferences { iter }
+    }
+}
+
+impl<'a, I> Iterator for InlineReferences<I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        let event = match self.iter.next()? {
+            Event::Start(Tag::Link(link_type, dest, title)) if is_reference(link_type) => {
+                Event::Start(Tag::Link(LinkType::Inline, dest, title))
+            }
+            Event::End(Tag::Link(link_type, dest, title)) if is_reference(link_type) => {
+                Event::End(Tag::Link(LinkType::Inline, dest, title))
+            }
+            Event::Start(Tag::Image(link_type, dest, title)) if is_reference(link_type) => {
+                Event::Start(Tag::Image(LinkType::Inline, dest, title))
+            }
+            Event::End(Tag::Image(link_type, dest, title)) if is_reference(link_type) => {
+                Event::End(Tag::Image(LinkType::Inline, dest, title))
+            }
+            event => event,
+        };
+        Some(event)
+    }
+}
+
+fn is_reference(link_type: LinkType) -> bool {
+    matches!(
+        link_type,
+        LinkType::Reference
+            | LinkType::ReferenceUnknown
+            | LinkType::Collapsed
+            | LinkType::CollapsedUnknown
+            | LinkType::Shortcut
+            | LinkType::ShortcutUnknown
+    )
+}
+
+/// Iterator adapter that turns inline links into shortcut reference links
+/// where a reference definition allows it.
+///
+/// An inline link or image becomes a
+/// [`LinkType::Shortcut`](../../enum.LinkType.html#variant.Shortcut) when its
+/// text is plain and matches the label of a definition with the same
+/// destination and title, so that writing it as `[text]` refers to that
+/// definition. Other links are passed through unchanged.
+///
+/// The reference definitions are borrowed, so clone them out of the parser
+/// before iterating it.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::references::ShortcutReferences;
+/// use pulldown_cmark::{Event, LinkType, Parser, Tag};
+///
+/// let parser = Parser::new("[docs](/docs) and [home](/)\n\n[docs]: /docs\n");
+/// let refdefs = parser.reference_definitions().clone();
+/// let types: Vec<_> = ShortcutReferences::new(parser, &refdefs)
+///     .filter_map(|event| match event {
+///         Event::Start(Tag::Link(link_type, ..)) => Some(link_type),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(types, vec![LinkType::Shortcut, LinkType::Inline]);
+/// ```
+pub struct ShortcutReferences<'a, 'r, I> {
+    iter: I,
+    refdefs: &'r RefDefs<'a>,
+    buffer: VecDeque<Event<'a>>,
+}
+
+impl<'a, 'r, I> ShortcutReferences<'a, 'r, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(iter: I, refdefs: &'r RefDefs<'a>) -> Self {
+        ShortcutReferences {
+            iter,
+            refdefs,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Buffers the events of a link up to its end tag and converts it if
+    /// possible.
+    fn convert(&mut self, start: Event<'a>) {
+        self.buffer.push_back(start);
+        let mut label = Some(String::new());
+        let mut depth = 0;
+        for event in self.iter.by_ref() {
+            match event {
+                Event::Start(..) => {
+                    depth += 1;
+                    label = None;
+                }
+                Event::End(..) if depth == 0 => {
+                    self.buffer.push_back(event);
+                    break;
+                }
+                Event::End(..) => depth -= 1,
+                Event::Text(ref text) => {
+                    if let Some(label) = label.as_mut() {
+                        label.push_str(text);
+                    }
+                }
+                _ => label = None,
+            }
+            self.buffer.push_back(event);
+        }
+
+        let matches = match (label.as_ref(), self.buffer.front()) {
+            (Some(label), Some(Event::Start(Tag::Link(_, dest, title))))
+            | (Some(label), Some(Event::Start(Tag::Image(_, dest, title)))) => {
+                match self.refdefs.get(label) {
+                    Some(def) => {
+                        def.dest == *dest
+                            && def.title.as_ref().map_or("", |title| title.as_ref()) == &**title
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+        if matches {
+            for ix in [0, self.buffer.len() - 1].iter() {
+                if let Event::Start(Tag::Link(link_type, ..))
+                | Event::End(Tag::Link(link_type, ..))
+                | Event::Start(Tag::Image(link_type, ..))
+                | Event::End(Tag::Image(link_type, ..)) = &mut self.buffer[*ix]
+                {
+                    *link_type = LinkType::Shortcut;
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'r, I> Iterator for ShortcutReferences<'a, 'r, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.buffer.pop_front() {
+            return Some(event);
+        }
+        match self.iter.next()? {
+            event @ Event::Start(Tag::Link(LinkType::Inline, ..))
+            | event @ Event::Start(Tag::Image(LinkType::Inline, ..)) => {
+                self.convert(event);
+                self.buffer.pop_front()
+            }
+            event => Some(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    fn link_types<'a>(iter: impl Iterator<Item = Event<'a>>) -> Vec<LinkType> {
+        iter.filter_map(|event| match event {
+            Event::Start(Tag::Link(link_type, ..))
+            | Event::End(Tag::Link(link_type, ..))
+            | Event::Start(Tag::Image(link_type, ..))
+            | Event::End(Tag::Image(link_type, ..)) => Some(link_type),
+            _ => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn references_to_inline() {
+        let input = "[a][x] [x][] [x] ![i][x] <http://y.org>\n\n[x]: /x\n";
+        let types = link_types(InlineReferences::new(Parser::new(input)));
+        let mut expected = vec![LinkType::Inline; 8];
+        expected.extend(vec![LinkType::Autolink; 2]);
+        assert_eq!(expected, types);
+    }
+
+    #[test]
+    fn inline_to_shortcut() {
+        let input = "[Foo](/f \"t\") [foo](/f) [*foo*](/f \"t\") ![foo](/f \"t\") [bar](/f \"t\")\n\n[foo]: /f \"t\"\n";
+        let parser = Parser::new(input);
+        let refdefs = parser.reference_definitions().clone();
+        let types = link_types(ShortcutReferences::new(parser, &refdefs));
+        assert_eq!(
+            vec![
+                LinkType::Shortcut,
+                LinkType::Shortcut,
+                LinkType::Inline,
+                LinkType::Inline,
+                LinkType::Inline,
+                LinkType::Inline,
+                LinkType::Shortcut,
+                LinkType::Shortcut,
+                LinkType::Inline,
+                LinkType::Inline,
+            ],
+            types
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Link and image destination rewriting.
+
+use crate::strings::CowStr;
+use crate::utils::has_scheme;
+use crate::{Event, LinkType, Tag};
+
+/// The kind of element whose destination is being rewritten.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkKind {
+    Link,
+    Image,
+}
+
+/// Iterator adapter that rewrites the destinations of links and images.
+///
+/// The callback is invoked once per link or image, when its start tag is
+/// seen. The matching end tag receives the same rewritten destination, so
+/// consumers always see balanced tags. Email autolinks are passed through
+/// untouched.
+pub struct RewriteLinks<'a, I, F> {
+    iter: I,
+    callback: F,
+    stack: Vec<CowStr<'a>>,
+}
+
+impl<'a, I, F> RewriteLinks<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: FnMut(LinkKind, CowStr<'a>) -> CowStr<'a>,
+{
+    /// Creates an adapter calling `callback` with every destination.
+    pub fn new(iter: I, callback: F) -> Self {
+        RewriteLinks {
+            iter,
+            callback,
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl<'a, I, F> Iterator for RewriteLinks<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: FnMut(LinkKind, CowStr<'a>) -> CowStr<'a>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        let event = match self.iter.next()? {
+            Event::Start(Tag::Link(link_type, dest, title)) if link_type != LinkType::Email => {
+                let dest = (self.callback)(LinkKind::Link, dest);
+                self.stack.push(dest.clone());
+                Event::Start(Tag::Link(link_type, dest, title))
+            }
+            Event::Start(Tag::Image(link_type, dest, title)) => {
+                let dest = (self.callback)(LinkKind::Image, dest);
+                self.stack.push(dest.clone());
+                Event::Start(Tag::Image(link_type, dest, title))
+            }
+            Event::End(Tag::Link(link_type, dest, title)) if link_type != LinkType::Email => {
+                let dest = self.stack.pop().unwrap_or(dest);
+                Event::End(Tag::Link(link_type, dest, title))
+            }
+            Event::End(Tag::Image(link_type, dest, title)) => {
+                let dest = self.stack.pop().unwrap_or(dest);
+                Event::End(Tag::Image(link_type, dest, title))
+            }
+            event => event,
+        };
+        Some(event)
+    }
+}
+
+/// Resolves relative destinations against a base URL.
+#[derive(Clone, Debug)]
+pub struct BaseUrl {
+    base: String,
+    md_to_html: bool,
+}
+
+impl BaseUrl {
+    /// Creates a resolver for the given base, e.g. `https://example.com/docs/`.
+    ///
+    /// As in a browser, the last path segment of the base is replaced when it
+    /// does not end with a `/`.
+    pub fn new(base: &str) -> Self {
+        BaseUrl {
+            base: base.to_owned(),
+            md_to_html: false,
+        }
+    }
+
+    /// When enabled, relative link destinations ending in `.md` are rewritten
+    /// to end in `.html` before being resolved. Image sources are unaffected.
+    pub fn md_to_html(mut self, enabled: bool) -> Self {
+        self.md_to_html = enabled;
+        self
+    }
+
+    /// Returns the resolved destination.
+    pub fn resolve<'a>(&self, kind: LinkKind, dest: CowStr<'a>) -> CowStr<'a> {
+        if dest.is_empty() || dest.starts_with('#') || has_scheme(&dest) {
+            return dest;
+        }
+        if self.md_to_html && kind == LinkKind::Link {
+            let path_end = dest.find(&['?', '#'][..]).unwrap_or(dest.len());
+            let path = &dest[..path_end];
+            if path.len() > 3 && path[path.len() - 3..].eq_ignore_ascii_case(".md") {
+                let rewritten = format!("{}.html{}", &path[..path.len() - 3], &dest[path_end..]);
+                return resolve_url(&self.base, &rewritten).into();
+            }
+        }
+        resolve_url(&self.base, &dest).into()
+    }
+}
+
+/// Rewrites all link and image destinations of the stream against a base URL.
+pub fn resolve_links<'a, I>(
+    iter: I,
+    base: BaseUrl,
+) -> RewriteLinks<'a, I, impl FnMut(LinkKind, CowStr<'a>) -> CowStr<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    RewriteLinks::new(iter, move |kind, dest| base.resolve(kind, dest))
+}
+
+/// Resolves `relative` against `base`, following RFC 3986 for the common
+/// cases. Destinations that carry their own scheme are returned unchanged.
+pub fn resolve_url(base: &str, relative: &str) -> String {
+    if has_scheme(relative) {
+        return relative.to_owned();
+    }
+    // strip fragment and query from the base
+    let base = &base[..base.find('#').unwrap_or(base.len())];
+    let (base, base_query) = base.split_at(base.find('?').unwrap_or(base.len()));
+    let (scheme, rest) = match base.find("//") {
+        Some(ix) if has_scheme(base) || ix == 0 => (&base[..ix], &base[ix + 2..]),
+        _ => ("", base),
+    };
+    if relative.starts_with("//") {
+        return format!("{}{}", scheme, relative);
+    }
+    let (authority, base_path) = if scheme.is_empty() && !base.starts_with("//") {
+        ("", rest)
+    } else {
+        let path_start = rest.find('/').unwrap_or(rest.len());
+        (&rest[..path_start], &rest[path_start..])
+    };
+    let prefix = if scheme.is_empty() && authority.is_empty() {
+        String::new()
+    } else {
+        format!("{}//{}", scheme, authority)
+    };
+
+    let suffix_start = relative.find(&['?', '#'][..]).unwrap_or(relative.len());
+    let (rel_path, suffix) = relative.split_at(suffix_start);
+    if rel_path.is_empty() {
+        // the base path is kept, and so is the base query unless there is
+        // another one
+        let query = if suffix.starts_with('?') {
+            ""
+        } else {
+            base_query
+        };
+        return format!("{}{}{}{}", prefix, base_path, query, suffix);
+    }
+    let path = if rel_path.starts_with('/') {
+        rel_path.to_owned()
+    } else if base_path.is_empty() && !prefix.is_empty() {
+        format!("/{}", rel_path)
+    } else {
+        let dir = &base_path[..base_path.rfind('/').map_or(0, |ix| ix + 1)];
+        format!("{}{}", dir, rel_path)
+    };
+    format!("{}{}{}", prefix, remove_dot_segments(&path), suffix)
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/') {
+        trailing_slash = false;
+        match segment {
+            "." => trailing_slash = true,
+            ".." => {
+                segments.pop();
+                trailing_slash = true;
+            }
+            "" => (),
+            segment => segments.push(segment),
+        }
+    }
+    let mut result = String::with_capacity(path.len());
+    if absolute {
+        result.push('/');
+    }
+    result.push_str(&segments.join("/"));
+    if (path.ends_with('/') || trailing_slash) && !segments.is_empty() {
+        result.push('/');
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, Parser};
+
+    #[test]
+    fn resolve_relative_urls() {
+        let base = "https://example.com/docs/guide/index.html?x=1";
+        assert_eq!(
+            "https://example.com/docs/guide/intro.html",
+            resolve_url(base, "intro.html")
+        );
+        assert_eq!(
+            "https://example.com/docs/img/a.png",
+            resolve_url(base, "../img/./a.png")
+        );
+        assert_eq!(
+            "https://example.com/root#top",
+            resolve_url(base, "/root#top")
+        );
+        assert_eq!("https://cdn.org/x", resolve_url(base, "//cdn.org/x"));
+        assert_eq!("mailto:a@b.c", resolve_url(base, "mailto:a@b.c"));
+        assert_eq!("/docs/a/b", resolve_url("/docs/", "a/b"));
+        assert_eq!(
+            "https://example.com/a",
+            resolve_url("https://example.com", "a")
+        );
+    }
+
+    #[test]
+    fn resolve_query_and_fragment_only() {
+        let base = "https://example.com/docs/index.html?x=1#old";
+        assert_eq!(
+            "https://example.com/docs/index.html?q=2",
+            resolve_url(base, "?q=2")
+        );
+        assert_eq!(
+            "https://example.com/docs/index.html?q#top",
+            resolve_url(base, "?q#top")
+        );
+        assert_eq!(
+            "https://example.com/docs/index.html?x=1#top",
+            resolve_url(base, "#top")
+        );
+        assert_eq!(
+            "https://example.com/docs/index.html?x=1",
+            resolve_url(base, "")
+        );
+        assert_eq!(
+            "https://example.com?q",
+            resolve_url("https://example.com", "?q")
+        );
+        assert_eq!("/docs/page#a", resolve_url("/docs/page", "#a"));
+    }
+
+    #[test]
+    fn rewrite_start_and_end_tags() {
+        let input = "[a](one.md#part) ![b](pic.png) [c](https://x.org/y.md) <me@x.org>";
+        let base = BaseUrl::new("https://example.com/book/").md_to_html(true);
+        let events: Vec<_> = resolve_links(Parser::new(input), base).collect();
+        let dests: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link(_, dest, _))
+                | Event::End(Tag::Link(_, dest, _))
+                | Event::Start(Tag::Image(_, dest, _))
+                | Event::End(Tag::Image(_, dest, _)) => Some(dest.as_ref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "https://example.com/book/one.html#part",
+                "https://example.com/book/one.html#part",
+                "https://example.com/book/pic.png",
+                "https://example.com/book/pic.png",
+                "https://x.org/y.md",
+                "https://x.org/y.md",
+                "me@x.org",
+                "me@x.org",
+            ],
+            dests
+        );
+
+        let mut s = String::new();
+        html::push_html(
+            &mut s,
+            RewriteLinks::new(Parser::new("[a](b)"), |_, dest| format!("/{}", dest).into()),
+        );
+        assert_eq!("<p><a href=\"/b\">a</a></p>\n", s);
+    }
+}
@@ -0,0 +1,147 @@
+//! Plain text extraction for search indexes.
+
+use std::ops::Range;
+
+use crate::strings::CowStr;
+use crate::utils::slug::Slugger;
+use crate::{Event, Tag};
+
+/// Where a run of text appears in the document.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextRunKind {
+    /// Text of a heading.
+    Heading,
+    /// Inline code or the contents of a code block.
+    Code,
+    /// Any other text.
+    Body,
+}
+
+/// A run of plain text, as fed to a search index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun<'a> {
+    /// The text, with markup and escapes removed.
+    pub text: CowStr<'a>,
+    pub kind: TextRunKind,
+    /// The slug of the nearest heading at or before the text, or `None`
+    /// for text preceding the first heading.
+    pub section: Option<String>,
+    /// The range in the source the text was parsed from.
+    pub span: Range<usize>,
+}
+
+/// Walks an offset event stream and returns its text runs in document order.
+///
+/// Every text and inline code event becomes one run; raw HTML is skipped.
+/// Sections are identified by the slugs the given slugger generates, so they
+/// match the anchors added by
+/// [`HeadingAnchors`](../slug/struct.HeadingAnchors.html) when both use the
+/// same slug style.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::search::{collect_text_runs, TextRunKind};
+/// use pulldown_cmark::utils::slug::Slugger;
+/// use pulldown_cmark::Parser;
+///
+/// let input = "Intro\n\n## Setup\n\nRun `make`.";
+/// let runs = collect_text_runs(Parser::new(input).into_offset_iter(), &mut Slugger::default());
+///
+/// assert_eq!(runs[0].section, None);
+/// assert_eq!(runs[1].kind, TextRunKind::Heading);
+/// assert_eq!(runs[3].section.as_deref(), Some("setup"));
+/// assert_eq!(&input[runs[3].span.clone()], "`make`");
+/// ```
+pub fn collect_text_runs<'a, I>(iter: I, slugger: &mut Slugger) -> Vec<TextRun<'a>>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut runs: Vec<TextRun<'a>> = Vec::new();
+    let mut section: Option<String> = None;
+    // index of the first run and text of the heading currently open
+    let mut heading: Option<(usize, String)> = None;
+    let mut code_block = false;
+
+    for (event, span) in iter {
+        let (text, kind) = match event {
+            Event::Start(Tag::Heading(..)) => {
+                heading = Some((runs.len(), String::new()));
+                continue;
+            }
+            Event::End(Tag::Heading(..)) => {
+                if let Some((first, text)) = heading.take() {
+                    let slug = slugger.slug(&text);
+                    for run in &mut runs[first..] {
+                        run.section = Some(slug.clone());
+                    }
+                    section = Some(slug);
+                }
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(..)) => {
+                code_block = true;
+                continue;
+            }
+            Event::End(Tag::CodeBlock(..)) => {
+                code_block = false;
+                continue;
+            }
+            Event::Text(text) => {
+                let kind = if heading.is_some() {
+                    TextRunKind::Heading
+                } else if code_block {
+                    TextRunKind::Code
+                } else {
+                    TextRunKind::Body
+                };
+                (text, kind)
+            }
+            Event::Code(text) if heading.is_some() => (text, TextRunKind::Heading),
+            Event::Code(text) => (text, TextRunKind::Code),
+            _ => continue,
+        };
+        if let Some((_, ref mut heading_text)) = heading {
+            heading_text.push_str(&text);
+        }
+        runs.push(TextRun {
+            text,
+            kind,
+            section: section.clone(),
+            span,
+        });
+    }
+    runs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn sections_follow_headings() {
+        let input = "# A *b*\n\n```\ncode\n```\n\n# A b\n\ntext &amp; more\n";
+        let runs = collect_text_runs(
+            Parser::new(input).into_offset_iter(),
+            &mut Slugger::default(),
+        );
+        let summary: Vec<_> = runs
+            .iter()
+            .map(|run| (run.text.as_ref(), run.kind, run.section.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("A ", TextRunKind::Heading, Some("a-b")),
+                ("b", TextRunKind::Heading, Some("a-b")),
+                ("code\n", TextRunKind::Code, Some("a-b")),
+                ("A b", TextRunKind::Heading, Some("a-b-1")),
+                ("text ", TextRunKind::Body, Some("a-b-1")),
+                ("&", TextRunKind::Body, Some("a-b-1")),
+                (" more", TextRunKind::Body, Some("a-b-1")),
+            ],
+            summary
+        );
+        assert_eq!("&amp;", &input[runs[5].span.clone()]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Splitting documents into sections by heading.
+
+use std::ops::Range;
+
+use crate::utils::push_event_text;
+use crate::{Event, HeadingLevel, Tag};
+
+/// A part of a document introduced by a heading.
+///
+/// A section contains everything up to the next heading of the same or a
+/// higher level. Headings of a lower level start subsections, which are
+/// stored in `children` rather than in `events`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section<'a> {
+    /// The level of the heading, or `None` for the root section returned by
+    /// [`split_sections`](fn.split_sections.html).
+    pub level: Option<HeadingLevel>,
+    /// The plain text of the heading.
+    pub title: String,
+    /// The events of this section, starting with its heading, without those
+    /// of subsections.
+    pub events: Vec<Event<'a>>,
+    /// The section's range in the source, including subsections. Sections
+    /// end where the next section starts.
+    pub span: Range<usize>,
+    pub children: Vec<Section<'a>>,
+}
+
+impl<'a> Section<'a> {
+    fn new(level: Option<HeadingLevel>, start: usize) -> Self {
+        Section {
+            level,
+            title: String::new(),
+            events: Vec::new(),
+            span: start..start,
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the events of this section followed by those of its
+    /// subsections, in document order.
+    pub fn all_events(&self) -> Vec<Event<'a>> {
+        let mut events = self.events.clone();
+        for child in &self.children {
+            events.extend(child.all_events());
+        }
+        events
+    }
+}
+
+/// Splits an offset event stream into nested sections.
+///
+/// The returned root section holds the content before the first heading,
+/// with the top-level sections as its children. Only headings at the top
+/// level of the document start sections; headings inside block quotes or
+/// lists do not.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::sections::split_sections;
+/// use pulldown_cmark::Parser;
+///
+/// let input = "Intro\n\n# One\n\n## Detail\n\n# Two\n";
+/// let root = split_sections(Parser::new(input).into_offset_iter());
+///
+/// assert_eq!(root.children.len(), 2);
+/// assert_eq!(root.children[0].title, "One");
+/// assert_eq!(root.children[0].children[0].title, "Detail");
+/// assert_eq!(&input[root.children[0].span.clone()], "# One\n\n## Detail\n\n");
+/// ```
+pub fn split_sections<'a, I>(iter: I) -> Section<'a>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut stack = vec![Section::new(None, 0)];
+    let mut depth = 0;
+    let mut in_heading = false;
+
+    for (event, span) in iter {
+        match event {
+            Event::Start(Tag::Heading(level)) if depth == 0 => {
+                while stack.len() > 1 && stack.last().unwrap().level >= Some(level) {
+                    close_section(&mut stack, span.start);
+                }
+                stack.push(Section::new(Some(level), span.start));
+                in_heading = true;
+            }
+            Event::End(Tag::Heading(..)) if depth == 1 => in_heading = false,
+            ref event if in_heading => push_event_text(&mut stack.last_mut().unwrap().title, event),
+            _ => (),
+        }
+        match event {
+            Event::Start(..) => depth += 1,
+            Event::End(..) => depth -= 1,
+            _ => (),
+        }
+        let section = stack.last_mut().unwrap();
+        if section.events.is_empty() && section.level.is_none() {
+            section.span.start = span.start;
+        }
+        section.span.end = section.span.end.max(span.end);
+        section.events.push(event);
+    }
+    while stack.len() > 1 {
+        let end = stack.last().unwrap().span.end;
+        close_section(&mut stack, end);
+    }
+    stack.pop().unwrap()
+}
+
+/// Ends the innermost section at `end` and adds it to its parent.
+fn close_section(stack: &mut Vec<Section>, end: usize) {
+    let mut section = stack.pop().unwrap();
+    section.span.end = end;
+    let parent = stack.last_mut().unwrap();
+    parent.span.end = end;
+    parent.children.push(section);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn nesting_follows_levels() {
+        let input = "## A\n\n#### B\n\n> # quoted\n\ntext\n\n### C\n\n# D *e*\n";
+        let root = split_sections(Parser::new(input).into_offset_iter());
+        assert!(root.events.is_empty());
+
+        let a = &root.children[0];
+        assert_eq!(Some(HeadingLevel::H2), a.level);
+        let titles: Vec<_> = a.children.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(vec!["B", "C"], titles);
+        assert_eq!(
+            "#### B\n\n> # quoted\n\ntext\n\n",
+            &input[a.children[0].span.clone()]
+        );
+        assert_eq!(Event::Start(Tag::BlockQuote), a.children[0].events[3]);
+
+        let d = &root.children[1];
+        assert_eq!("D e", d.title);
+        assert!(d.children.is_empty());
+        assert_eq!(Parser::new(input).collect::<Vec<_>>(), root.all_events());
+    }
+}
@@ -0,0 +1,369 @@
+//! Heading slugs and anchors.
+//!
+//! A [`Slugger`](struct.Slugger.html) turns heading text into identifiers that
+//! are unique within a document. The [`HeadingAnchors`](struct.HeadingAnchors.html)
+//! adapter uses it to attach anchors to every heading in an event stream.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::escape::escape_html;
+use crate::html::{Attributes, Render};
+use crate::utils::ids::IdAllocator;
+use crate::utils::push_event_text;
+use crate::{Event, Tag};
+
+/// Slugification scheme used to derive an identifier from heading text.
+#[derive(Clone, Copy)]
+pub enum SlugStyle {
+    /// The scheme used by GitHub: lowercase, spaces replaced by `-` and all
+    /// punctuation except `-` and `_` removed.
+    GitHub,
+    /// Lowercase, with every run of non-alphanumeric characters collapsed into
+    /// a single `-`, and no leading or trailing `-`.
+    Kebab,
+    /// A user supplied slugification function.
+    Custom(fn(&str) -> String),
+}
+
+// Written out because function pointers taking references only implement
+// `Debug` on newer compilers.
+impl fmt::Debug for SlugStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlugStyle::GitHub => f.write_str("GitHub"),
+            SlugStyle::Kebab => f.write_str("Kebab"),
+            SlugStyle::Custom(func) => f
+                .debug_tuple("Custom")
+                .field(&(*func as *const ()))
+                .finish(),
+        }
+    }
+}
+
+impl SlugStyle {
+    /// Computes the slug for the given text. The result is not deduplicated.
+    pub fn slugify(&self, text: &str) -> String {
+        match self {
+            SlugStyle::GitHub => github_slug(text),
+            SlugStyle::Kebab => kebab_slug(text),
+            SlugStyle::Custom(f) => f(text),
+        }
+    }
+}
+
+/// Slugifies text the way GitHub does for heading anchors.
+pub fn github_slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.trim().chars() {
+        if c == ' ' {
+            slug.push('-');
+        } else if c.is_alphanumeric() || c == '-' || c == '_' {
+            slug.extend(c.to_lowercase());
+        }
+    }
+    slug
+}
+
+/// Slugifies text into lowercase words separated by single dashes.
+pub fn kebab_slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut pending_dash = false;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if pending_dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_dash = false;
+            slug.extend(c.to_lowercase());
+        } else {
+            pending_dash = true;
+        }
+    }
+    slug
+}
+
+/// Generates slugs that are unique within a document.
+///
+/// When a slug has been handed out before, a numeric suffix is appended, so
+/// that two headings named "Intro" get the slugs `intro` and `intro-1`.
+///
+/// Uniqueness is tracked by an [`IdAllocator`](../ids/struct.IdAllocator.html).
+/// Clones of a slugger share it, as do sluggers created with clones of the
+/// same allocator; [`fork`](#method.fork) gives an independent copy.
+#[derive(Clone, Debug)]
+pub struct Slugger {
+    style: SlugStyle,
+    ids: IdAllocator,
+}
+
+impl Slugger {
+    /// Creates a new slugger using the given scheme.
+    pub fn new(style: SlugStyle) -> Self {
+        Self::with_ids(style, IdAllocator::new())
+    }
+
+    /// Creates a slugger using the given scheme that allocates its slugs from
+    /// `ids`.
+    pub fn with_ids(style: SlugStyle, ids: IdAllocator) -> Self {
+        Slugger { style, ids }
+    }
+
+    /// Returns the slugification scheme of this slugger.
+    pub fn style(&self) -> SlugStyle {
+        self.style
+    }
+
+    /// Returns the allocator the slugs are taken from.
+    pub fn ids(&self) -> &IdAllocator {
+        &self.ids
+    }
+
+    /// Returns a slugger with the same scheme and a fork of the allocator, so
+    /// that slugs generated by either do not affect the other.
+    pub fn fork(&self) -> Slugger {
+        Self::with_ids(self.style, self.ids.fork())
+    }
+
+    /// Returns a slug for the given text that has not been returned before.
+    pub fn slug(&mut self, text: &str) -> String {
+        self.ids.allocate(&self.style.slugify(text))
+    }
+
+    /// Forgets all slugs handed out so far.
+    pub fn reset(&mut self) {
+        self.ids.reset();
+    }
+}
+
+impl Default for Slugger {
+    fn default() -> Self {
+        Slugger::new(SlugStyle::GitHub)
+    }
+}
+
+/// How [`HeadingAnchors`](struct.HeadingAnchors.html) attaches anchors to headings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnchorStyle {
+    /// Inserts `<a class="anchor" id="slug" href="#slug"></a>` as the first
+    /// element of the heading contents.
+    Link,
+    /// Leaves the headings as they are and queues their slugs in the
+    /// adapter's [`HeadingIds`](struct.HeadingIds.html), which gives the
+    /// heading element itself an `id="slug"` attribute when rendered with it.
+    Attribute,
+}
+
+/// Gives headings the slugs chosen by
+/// [`HeadingAnchors`](struct.HeadingAnchors.html) with
+/// `AnchorStyle::Attribute` as `id` attributes, when used as the
+/// [`Render`](../../html/trait.Render.html) of the HTML writer.
+///
+/// Slugs are handed out in the order they were queued, one per heading.
+/// Clones share the queue.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::slug::{AnchorStyle, HeadingAnchors, Slugger};
+/// use pulldown_cmark::{html, Parser};
+///
+/// let parser = Parser::new("# Intro\n\n# Intro\n");
+/// let anchors = HeadingAnchors::with_slugger(parser, Slugger::default(), AnchorStyle::Attribute);
+/// let ids = anchors.heading_ids();
+///
+/// let mut html_buf = String::new();
+/// html::push_html_with_renderer(&mut html_buf, anchors, &html::Options::default(), ids);
+/// assert_eq!(html_buf, "<h1 id=\"intro\">Intro</h1>\n<h1 id=\"intro-1\">Intro</h1>\n");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HeadingIds {
+    queue: Rc<RefCell<VecDeque<String>>>,
+}
+
+impl HeadingIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, id: String) {
+        self.queue.borrow_mut().push_back(id);
+    }
+}
+
+impl Render for HeadingIds {
+    fn attributes(&mut self, tag: &Tag) -> Attributes {
+        let id = match tag {
+            Tag::Heading(..) => self.queue.borrow_mut().pop_front(),
+            _ => None,
+        };
+        match id {
+            Some(id) => Attributes::new().attr("id", &id),
+            None => Attributes::new(),
+        }
+    }
+}
+
+/// Iterator adapter that attaches an anchor to every heading.
+///
+/// Headings are buffered until their end tag so that the slug can be computed
+/// from their full text. All other events are passed through unchanged.
+pub struct HeadingAnchors<'a, I> {
+    iter: I,
+    slugger: Slugger,
+    style: AnchorStyle,
+    ids: HeadingIds,
+    buffer: VecDeque<Event<'a>>,
+}
+
+impl<'a, I> HeadingAnchors<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    /// Creates an adapter using GitHub style slugs and `AnchorStyle::Link`.
+    pub fn new(iter: I) -> Self {
+        Self::with_slugger(iter, Slugger::default(), AnchorStyle::Link)
+    }
+
+    /// Creates an adapter with the given slugger and anchor style.
+    pub fn with_slugger(iter: I, slugger: Slugger, style: AnchorStyle) -> Self {
+        HeadingAnchors {
+            iter,
+            slugger,
+            style,
+            ids: HeadingIds::new(),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Queues the slugs of `AnchorStyle::Attribute` in `ids` rather than in
+    /// a queue of its own, like one shared by the adapters of several
+    /// documents rendered together.
+    pub fn with_heading_ids(mut self, ids: HeadingIds) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Returns the queue the slugs of `AnchorStyle::Attribute` are added to,
+    /// to render the headings with.
+    pub fn heading_ids(&self) -> HeadingIds {
+        self.ids.clone()
+    }
+
+    /// Consumes the adapter, returning the slugger with all slugs handed out.
+    pub fn into_slugger(self) -> Slugger {
+        self.slugger
+    }
+
+    fn anchor_heading(&mut self, start: Event<'a>) -> Event<'a> {
+        let mut text = String::new();
+        let mut end = None;
+        for event in &mut self.iter {
+            if let Event::End(Tag::Heading(..)) = event {
+                end = Some(event);
+                break;
+            }
+            push_event_text(&mut text, &event);
+            self.buffer.push_back(event);
+        }
+        let slug = self.slugger.slug(&text);
+        match self.style {
+            AnchorStyle::Link => {
+                let mut escaped = String::with_capacity(slug.len());
+                escape_html(&mut escaped, &slug).unwrap();
+                let anchor = format!("<a class=\"anchor\" id=\"{0}\" href=\"#{0}\"></a>", escaped);
+                self.buffer.push_front(Event::Html(anchor.into()));
+            }
+            AnchorStyle::Attribute => self.ids.push(slug),
+        }
+        self.buffer.extend(end);
+        start
+    }
+}
+
+impl<'a, I> Iterator for HeadingAnchors<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.buffer.pop_front() {
+            return Some(event);
+        }
+        match self.iter.next()? {
+            event @ Event::Start(Tag::Heading(..)) => Some(self.anchor_heading(event)),
+            event => Some(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{html, markdown, Parser};
+
+    #[test]
+    fn github_slugs() {
+        assert_eq!("hello-world", github_slug("Hello World"));
+        assert_eq!("whats-new-in-v2_0", github_slug("What's new in v2_0?"));
+        assert_eq!("jalapeño--peppers", github_slug("Jalapeño & peppers"));
+    }
+
+    #[test]
+    fn kebab_slugs() {
+        assert_eq!("hello-world", kebab_slug("  Hello,   World! "));
+        assert_eq!("a-b", kebab_slug("--a--b--"));
+    }
+
+    #[test]
+    fn duplicate_slugs() {
+        let mut slugger = Slugger::default();
+        assert_eq!("intro", slugger.slug("Intro"));
+        assert_eq!("intro-1", slugger.slug("Intro"));
+        assert_eq!("intro-1-1", slugger.slug("Intro 1"));
+        assert_eq!("intro-2", slugger.slug("Intro"));
+    }
+
+    #[test]
+    fn anchor_styles() {
+        let input = "# Hello `code`\n\n## Hello code\n";
+
+        let mut s = String::new();
+        html::push_html(&mut s, HeadingAnchors::new(Parser::new(input)));
+        assert_eq!(
+            "<h1><a class=\"anchor\" id=\"hello-code\" href=\"#hello-code\"></a>Hello <code>code</code></h1>\n\
+             <h2><a class=\"anchor\" id=\"hello-code-1\" href=\"#hello-code-1\"></a>Hello code</h2>\n",
+            s
+        );
+
+        let mut s = String::new();
+        let slugger = Slugger::new(SlugStyle::Kebab);
+        let events =
+            HeadingAnchors::with_slugger(Parser::new(input), slugger, AnchorStyle::Attribute);
+        let ids = events.heading_ids();
+        html::push_html_with_renderer(&mut s, events, &html::Options::default(), ids);
+        assert_eq!(
+            "<h1 id=\"hello-code\">Hello <code>code</code></h1>\n<h2 id=\"hello-code-1\">Hello code</h2>\n",
+            s
+        );
+    }
+
+    #[test]
+    fn attribute_style_keeps_headings() {
+        let input = "# Intro\n\ntext\n";
+        let events: Vec<_> = HeadingAnchors::with_slugger(
+            Parser::new(input),
+            Slugger::default(),
+            AnchorStyle::Attribute,
+        )
+        .collect();
+        assert_eq!(events, Parser::new(input).collect::<Vec<_>>());
+
+        let mut s = String::new();
+        markdown::push_markdown(&mut s, events.into_iter());
+        assert_eq!("# Intro\n\ntext\n", s);
+    }
+}
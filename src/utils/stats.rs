@@ -0,0 +1,175 @@
+//! Word and character counts over rendered text.
+
+use std::time::Duration;
+
+use crate::{Event, Tag};
+
+/// Counts of the text a document renders to.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextStats {
+    /// Number of words.
+    pub words: usize,
+    /// Number of characters, including whitespace.
+    pub chars: usize,
+    /// Number of characters, excluding whitespace.
+    pub non_whitespace_chars: usize,
+}
+
+impl TextStats {
+    /// Estimates the time needed to read the text at the given speed.
+    pub fn reading_time(&self, words_per_minute: u32) -> Duration {
+        if words_per_minute == 0 {
+            return Duration::from_secs(0);
+        }
+        let word_secs = self.words as u64 * 60;
+        let wpm = u64::from(words_per_minute);
+        let mut secs = word_secs / wpm;
+        if secs * wpm < word_secs {
+            secs += 1;
+        }
+        Duration::from_secs(secs)
+    }
+}
+
+/// Counts words and characters of the text rendered from an event stream.
+///
+/// Markup, raw HTML and footnote references are not counted. Word boundaries
+/// follow the rules of Unicode text segmentation closely enough for
+/// statistics: runs of letters and digits form a word, possibly joined by an
+/// apostrophe or a decimal separator (`don't`, `3.14`), while every
+/// ideographic or kana character counts as a word by itself.
+///
+/// When `skip_code_blocks` is true, the contents of code blocks are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::stats::text_stats;
+/// use pulldown_cmark::Parser;
+///
+/// let stats = text_stats(Parser::new("*Don't* [count](http://x.org/a-b) `syntax`."), false);
+/// assert_eq!(stats.words, 3);
+/// ```
+pub fn text_stats<'a, I>(iter: I, skip_code_blocks: bool) -> TextStats
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut counter = WordCounter::default();
+    let mut code_block = false;
+    for event in iter {
+        match event {
+            Event::Start(Tag::CodeBlock(..)) => {
+                counter.boundary();
+                code_block = true;
+            }
+            Event::End(Tag::CodeBlock(..)) => {
+                counter.boundary();
+                code_block = false;
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if !(code_block && skip_code_blocks) {
+                    counter.feed(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => counter.feed(" "),
+            Event::Start(tag) | Event::End(tag) => {
+                if !is_inline(&tag) {
+                    counter.boundary();
+                }
+            }
+            Event::Html(..) | Event::FootnoteReference(..) | Event::Rule => counter.boundary(),
+            Event::TaskListMarker(..) => (),
+        }
+    }
+    counter.boundary();
+    counter.stats
+}
+
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+    )
+}
+
+#[derive(Default)]
+struct WordCounter {
+    stats: TextStats,
+    in_word: bool,
+    // a separator that continues the current word if a letter follows it
+    pending_joiner: bool,
+}
+
+impl WordCounter {
+    fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            self.stats.chars += 1;
+            if !c.is_whitespace() {
+                self.stats.non_whitespace_chars += 1;
+            }
+
+            if is_ideographic(c) {
+                self.boundary();
+                self.stats.words += 1;
+            } else if c.is_alphanumeric() {
+                if !self.in_word {
+                    self.stats.words += 1;
+                    self.in_word = true;
+                }
+                self.pending_joiner = false;
+            } else if self.in_word && !self.pending_joiner && is_joiner(c) {
+                self.pending_joiner = true;
+            } else {
+                self.boundary();
+            }
+        }
+    }
+
+    fn boundary(&mut self) {
+        self.in_word = false;
+        self.pending_joiner = false;
+    }
+}
+
+fn is_joiner(c: char) -> bool {
+    matches!(c, '\'' | '\u{2019}' | '.' | ',' | '_')
+}
+
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF // hiragana and katakana
+        | 0x3400..=0x4DBF // CJK extension A
+        | 0x4E00..=0x9FFF // CJK unified ideographs
+        | 0xF900..=0xFAFF // CJK compatibility ideographs
+        | 0x20000..=0x3134F // CJK extensions B to G
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn words_across_events() {
+        let stats = text_stats(Parser::new("un*believ*able, it's *3.14*.\n"), false);
+        assert_eq!(2 + 1, stats.words);
+        assert_eq!("unbelievable, it's 3.14.".len(), stats.chars);
+    }
+
+    #[test]
+    fn blocks_separate_words() {
+        let input = "# Title\nword\n\n```\nlet x = 1;\n```\n\n漢字かな\n";
+        assert_eq!(2 + 3 + 4, text_stats(Parser::new(input), false).words);
+        assert_eq!(2 + 4, text_stats(Parser::new(input), true).words);
+    }
+
+    #[test]
+    fn reading_time() {
+        let stats = TextStats {
+            words: 401,
+            ..Default::default()
+        };
+        assert_eq!(Duration::from_secs(121), stats.reading_time(200));
+    }
+}
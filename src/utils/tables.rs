@@ -0,0 +1,125 @@
+//! Extraction of table contents.
+
+use std::collections::HashMap;
+
+use crate::strings::CowStr;
+use crate::{Alignment, Event, Tag};
+
+/// The contents of a table as plain text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableData<'a> {
+    /// The alignment of each column.
+    pub alignments: Vec<Alignment>,
+    /// The header cells.
+    pub header: Vec<CowStr<'a>>,
+    /// The body rows. Like the header, every row has one cell per column.
+    pub rows: Vec<Vec<CowStr<'a>>>,
+}
+
+impl<'a> TableData<'a> {
+    /// Returns the body rows as maps from header cell to row cell.
+    ///
+    /// When several columns have the same header, the last one wins.
+    pub fn records(&self) -> Vec<HashMap<&str, &str>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                self.header
+                    .iter()
+                    .zip(row)
+                    .map(|(key, value)| (key.as_ref(), value.as_ref()))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Collects all tables of an event stream, in document order.
+///
+/// Cell contents are reduced to their text, without formatting. The parser
+/// pads short rows with empty cells and drops excess ones, so all rows have
+/// as many cells as there are entries in `alignments`.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::tables::collect_tables;
+/// use pulldown_cmark::{Alignment, Options, Parser};
+///
+/// let input = "| Name | Stars |\n|------|------:|\n| *a*  | 12    |\n| b    |\n";
+/// let tables = collect_tables(Parser::new_ext(input, Options::ENABLE_TABLES));
+///
+/// assert_eq!(tables[0].alignments, vec![Alignment::None, Alignment::Right]);
+/// assert_eq!(tables[0].rows[1], vec!["b".into(), "".into()] as Vec<pulldown_cmark::CowStr>);
+/// assert_eq!(tables[0].records()[0]["Stars"], "12");
+/// ```
+pub fn collect_tables<'a, I>(iter: I) -> Vec<TableData<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut tables = Vec::new();
+    let mut table: Option<TableData<'a>> = None;
+    let mut row: Vec<CowStr<'a>> = Vec::new();
+    let mut cell: Option<CowStr<'a>> = None;
+
+    for event in iter {
+        match event {
+            Event::Start(Tag::Table(alignments)) => {
+                table = Some(TableData {
+                    alignments,
+                    header: Vec::new(),
+                    rows: Vec::new(),
+                })
+            }
+            Event::End(Tag::Table(..)) => tables.extend(table.take()),
+            Event::Start(Tag::TableCell) => cell = Some("".into()),
+            Event::End(Tag::TableCell) => row.extend(cell.take()),
+            Event::End(Tag::TableHead) => {
+                if let Some(ref mut table) = table {
+                    table.header = std::mem::take(&mut row);
+                }
+            }
+            Event::End(Tag::TableRow) => {
+                if let Some(ref mut table) = table {
+                    table.rows.push(std::mem::take(&mut row));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(ref mut cell) = cell {
+                    *cell = if cell.is_empty() {
+                        text
+                    } else {
+                        let mut joined = std::mem::replace(cell, "".into()).into_string();
+                        joined.push_str(&text);
+                        joined.into()
+                    };
+                }
+            }
+            _ => (),
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn cells_and_records() {
+        let input = "a|b|a\n:-|:-:|-\n`x` **y**|\\||z|extra\n\n|c|\n|-|\n";
+        let tables = collect_tables(Parser::new_ext(input, Options::ENABLE_TABLES));
+        assert_eq!(2, tables.len());
+        assert_eq!(
+            vec![Alignment::Left, Alignment::Center, Alignment::None],
+            tables[0].alignments
+        );
+        let row: Vec<_> = tables[0].rows[0].iter().map(|cell| cell.as_ref()).collect();
+        assert_eq!(vec!["x y", "|", "z"], row);
+        let records = tables[0].records();
+        assert_eq!("z", records[0]["a"]);
+        assert_eq!("|", records[0]["b"]);
+        assert!(tables[1].rows.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! Task list progress.
+
+use std::ops::Range;
+
+use crate::{Event, Tag};
+
+/// Number of completed and total tasks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TaskProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl TaskProgress {
+    fn add(&mut self, other: TaskProgress) {
+        self.done += other.done;
+        self.total += other.total;
+    }
+}
+
+/// The tasks of one list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListTasks {
+    /// The list's range in the source.
+    pub span: Range<usize>,
+    /// Nesting depth of the list, zero for lists not inside another list.
+    pub depth: usize,
+    /// Tasks that are items of this list.
+    pub own: TaskProgress,
+    /// Tasks of this list and of all lists nested in it.
+    pub nested: TaskProgress,
+}
+
+/// Task counts of a document.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TaskSummary {
+    /// All lists containing tasks, directly or in nested lists, in document
+    /// order.
+    pub lists: Vec<ListTasks>,
+    /// All tasks of the document.
+    pub total: TaskProgress,
+}
+
+/// Counts the task list items of an offset event stream.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::utils::tasks::task_progress;
+/// use pulldown_cmark::{Options, Parser};
+///
+/// let input = "- [x] write\n- [ ] review\n  - [x] read\n  - [ ] comment\n";
+/// let parser = Parser::new_ext(input, Options::ENABLE_TASKLISTS);
+/// let summary = task_progress(parser.into_offset_iter());
+///
+/// assert_eq!((summary.total.done, summary.total.total), (2, 4));
+/// assert_eq!(summary.lists[0].own.total, 2);
+/// assert_eq!(summary.lists[1].depth, 1);
+/// ```
+pub fn task_progress<'a, I>(iter: I) -> TaskSummary
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let mut summary = TaskSummary::default();
+    // open lists, with their index in `summary.lists`
+    let mut open: Vec<usize> = Vec::new();
+
+    for (event, span) in iter {
+        match event {
+            Event::Start(Tag::List(..)) => {
+                open.push(summary.lists.len());
+                summary.lists.push(ListTasks {
+                    span,
+                    depth: open.len() - 1,
+                    own: TaskProgress::default(),
+                    nested: TaskProgress::default(),
+                });
+            }
+            Event::End(Tag::List(..)) => {
+                if let Some(ix) = open.pop() {
+                    let nested = summary.lists[ix].nested;
+                    if let Some(&parent) = open.last() {
+                        summary.lists[parent].nested.add(nested);
+                    }
+                }
+            }
+            Event::TaskListMarker(checked) => {
+                let task = TaskProgress {
+                    done: checked as usize,
+                    total: 1,
+                };
+                if let Some(&ix) = open.last() {
+                    summary.lists[ix].own.add(task);
+                    summary.lists[ix].nested.add(task);
+                }
+                summary.total.add(task);
+            }
+            _ => (),
+        }
+    }
+
+    summary.lists.retain(|list| list.nested.total > 0);
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Options, Parser};
+
+    #[test]
+    fn nested_lists() {
+        let input = "* plain\n  1. [x] a\n     * [x] b\n\n# Later\n\n- [ ] c\n- d\n";
+        let parser = Parser::new_ext(input, Options::ENABLE_TASKLISTS);
+        let summary = task_progress(parser.into_offset_iter());
+        let counts: Vec<_> = summary
+            .lists
+            .iter()
+            .map(|list| {
+                (
+                    list.depth,
+                    list.own.done,
+                    list.own.total,
+                    list.nested.done,
+                    list.nested.total,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (0, 0, 0, 2, 2),
+                (1, 1, 1, 2, 2),
+                (2, 1, 1, 1, 1),
+                (0, 0, 1, 0, 1)
+            ],
+            counts
+        );
+        assert_eq!(TaskProgress { done: 2, total: 3 }, summary.total);
+        assert_eq!("- [ ] c\n- d\n", &input[summary.lists[3].span.clone()]);
+    }
+}
@@ -0,0 +1,99 @@
+//! Table of contents extraction.
+
+use crate::utils::push_event_text;
+use crate::utils::slug::Slugger;
+use crate::{Event, HeadingLevel, Tag};
+
+/// A heading collected for the table of contents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TocEntry {
+    /// The heading level.
+    pub level: HeadingLevel,
+    /// The plain text of the heading.
+    pub text: String,
+    /// The slug of the heading, as generated by the slugger.
+    pub slug: String,
+}
+
+/// Collects all headings of the event stream, in document order.
+///
+/// Slugs are generated with the given slugger, so passing a slugger configured
+/// the same way as the one given to
+/// [`HeadingAnchors`](../slug/struct.HeadingAnchors.html) yields matching
+/// anchors. When the anchoring slugger shares its
+/// [`IdAllocator`](../ids/struct.IdAllocator.html) with other adapters, pass a
+/// [`fork`](../slug/struct.Slugger.html#method.fork) of it, so that the entries
+/// do not use up the ids of the anchors.
+pub fn collect_toc<'a, I>(iter: I, slugger: &mut Slugger) -> Vec<TocEntry>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut entries = Vec::new();
+    let mut current: Option<(HeadingLevel, String)> = None;
+    for event in iter {
+        match event {
+            Event::Start(Tag::Heading(level)) => current = Some((level, String::new())),
+            Event::End(Tag::Heading(..)) => {
+                if let Some((level, text)) = current.take() {
+                    let slug = slugger.slug(&text);
+                    entries.push(TocEntry { level, text, slug });
+                }
+            }
+            ref event => {
+                if let Some((_, ref mut text)) = current {
+                    push_event_text(text, event);
+                }
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::html::Render;
+    use crate::utils::slug::{AnchorStyle, HeadingAnchors};
+    use crate::Parser;
+
+    #[test]
+    fn toc_matches_anchors() {
+        let input = "# Title\n\ntext\n\n## Part *one*\n\n## Title\n";
+        let toc = collect_toc(Parser::new(input), &mut Slugger::default());
+        assert_eq!(
+            vec![
+                TocEntry {
+                    level: HeadingLevel::H1,
+                    text: "Title".to_owned(),
+                    slug: "title".to_owned(),
+                },
+                TocEntry {
+                    level: HeadingLevel::H2,
+                    text: "Part one".to_owned(),
+                    slug: "part-one".to_owned(),
+                },
+                TocEntry {
+                    level: HeadingLevel::H2,
+                    text: "Title".to_owned(),
+                    slug: "title-1".to_owned(),
+                },
+            ],
+            toc
+        );
+
+        let anchors = HeadingAnchors::with_slugger(
+            Parser::new(input),
+            Slugger::default(),
+            AnchorStyle::Attribute,
+        );
+        let mut ids = anchors.heading_ids();
+        let ids: Vec<_> = anchors
+            .filter_map(|event| match event {
+                Event::Start(tag @ Tag::Heading(..)) => Some(ids.attributes(&tag).attrs),
+                _ => None,
+            })
+            .collect();
+        let id = |slug: &str| vec![("id".to_owned(), slug.to_owned())];
+        assert_eq!(vec![id("title"), id("part-one"), id("title-1")], ids);
+    }
+}
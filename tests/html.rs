@@ -1,5 +1,7 @@
 // Tests for HTML spec.
 
+use pulldown_cmark::utils::front_matter::FrontMatter;
+use pulldown_cmark::utils::rewrite::BaseUrl;
 use pulldown_cmark::{html, BrokenLink, Options, Parser};
 
 #[test]
@@ -248,3 +250,701 @@ fn html_test_broken_callback() {
 
     assert_eq!(expected, s);
 }
+
+#[test]
+fn html_source_map() {
+    let original = "Some *emphasis*\n\n> quoted\n";
+    let mut s = String::from("<!-- preview -->\n");
+    let source_map =
+        html::push_html_with_source_map(&mut s, Parser::new(original).into_offset_iter());
+
+    let pairs: Vec<_> = source_map
+        .iter()
+        .map(|m| (&s[m.output.clone()], &original[m.input.clone()]))
+        .collect();
+    assert_eq!(
+        vec![
+            ("<p>", "Some *emphasis*\n"),
+            ("Some ", "Some "),
+            ("<em>", "*emphasis*"),
+            ("emphasis", "emphasis"),
+            ("</em>", "*emphasis*"),
+            ("</p>\n", "Some *emphasis*\n"),
+            ("<blockquote>\n", "> quoted\n"),
+            ("<p>", "quoted\n"),
+            ("quoted", "quoted"),
+            ("</p>\n", "quoted\n"),
+            ("</blockquote>\n", "> quoted\n"),
+        ],
+        pairs
+    );
+
+    let mut bytes = Vec::new();
+    let written =
+        html::write_html_with_source_map(&mut bytes, Parser::new(original).into_offset_iter())
+            .unwrap();
+    assert_eq!(0, written[0].output.start);
+    assert_eq!(source_map.len(), written.len());
+}
+
+#[test]
+fn html_custom_renderer() {
+    use pulldown_cmark::escape::StrWrite;
+    use pulldown_cmark::html::{HtmlContext, Render};
+    use pulldown_cmark::LinkType;
+
+    struct Figures;
+
+    impl Render for Figures {
+        fn image<'a, W: StrWrite>(
+            &mut self,
+            ctx: &mut HtmlContext<'a, W>,
+            _link_type: LinkType,
+            dest: &str,
+            _title: &str,
+            alt: &str,
+        ) -> Result<(), W::Error> {
+            ctx.write("<figure><img src=\"")?;
+            ctx.write_escaped_href(dest)?;
+            ctx.write("\" /><figcaption>")?;
+            ctx.write_escaped(alt)?;
+            ctx.write("</figcaption></figure>")
+        }
+    }
+
+    let original = "![A *cat* &\na dog](cat.png)[^1]\n\n[^1]: Note.\n";
+    let expected = "<p><figure><img src=\"cat.png\" /><figcaption>A cat &amp; a dog</figcaption></figure>\
+                    <sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup></p>\n\
+                    <div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+                    <p>Note.</p>\n</div>\n";
+
+    let mut s = String::new();
+    let parser = Parser::new_ext(original, Options::ENABLE_FOOTNOTES);
+    html::push_html_with_renderer(&mut s, parser, &html::Options::default(), Figures);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_heading_ids() {
+    use pulldown_cmark::utils::ids::IdAllocator;
+    use pulldown_cmark::utils::slug::{SlugStyle, Slugger};
+
+    let original = "# Intro\n\n## Intro *again*\n\nText\n";
+    let expected = "<h1 id=\"intro-1\">Intro</h1>\n<h2 id=\"intro-again\">Intro <em>again</em></h2>\n<p>Text</p>\n";
+
+    let ids = IdAllocator::new();
+    ids.reserve("intro");
+    let options = html::Options {
+        heading_ids: Some(Slugger::with_ids(SlugStyle::GitHub, ids.clone())),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+    assert!(ids.contains("intro-again"));
+}
+
+#[test]
+fn html_sourcepos() {
+    use pulldown_cmark::html::SourcePositions;
+
+    let original = "> quote\n> more\n\n```rust\nx\n```\n\n---\n";
+    let expected = "<blockquote data-sourcepos=\"1:1-2:6\">\n<p data-sourcepos=\"1:3-2:6\">quote\nmore</p>\n</blockquote>\n\
+                    <pre data-sourcepos=\"4:1-6:3\"><code class=\"language-rust\">x\n</code></pre>\n\
+                    <hr data-sourcepos=\"8:1-8:3\" />\n";
+
+    let options = html::Options {
+        source_positions: Some(SourcePositions::new(original)),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_with_offsets(&mut s, Parser::new(original).into_offset_iter(), &options);
+    assert_eq!(expected, s);
+
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert!(!s.contains("data-sourcepos"));
+}
+
+#[test]
+fn html_indent() {
+    let original = "> * a\n>\n>   ```\n>   code\n>   ```\n\n|x|\n|-|\n|1|\n";
+    let expected = "<blockquote>
+  <ul>
+    <li>
+      <p>a</p>
+      <pre><code>code
+</code></pre>
+    </li>
+  </ul>
+</blockquote>
+<table>
+  <thead>
+    <tr>
+      <th>x</th>
+    </tr>
+  </thead>
+  <tbody>
+    <tr>
+      <td>1</td>
+    </tr>
+  </tbody>
+</table>
+";
+
+    let options = html::Options {
+        indent: Some(2),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(
+        &mut s,
+        Parser::new_ext(original, Options::ENABLE_TABLES),
+        &options,
+    );
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_gfm_footnotes() {
+    let original = "[^b]: Bee.\n\nText[^a] and[^b], again[^a].\n\n[^a]: Ay[^c].\n\n[^c]: See.\n\n[^unused]: Unused.\n";
+    let expected = "<p>Text<sup class=\"footnote-ref\"><a href=\"#fn-a\" id=\"fnref-a\" data-footnote-ref>1</a></sup> \
+                    and<sup class=\"footnote-ref\"><a href=\"#fn-b\" id=\"fnref-b\" data-footnote-ref>2</a></sup>, \
+                    again<sup class=\"footnote-ref\"><a href=\"#fn-a\" id=\"fnref-a-2\" data-footnote-ref>1</a></sup>.</p>\n\
+                    <section class=\"footnotes\" data-footnotes>\n<ol>\n\
+                    <li id=\"fn-a\">\n\
+                    <p>Ay<sup class=\"footnote-ref\"><a href=\"#fn-c\" id=\"fnref-c\" data-footnote-ref>3</a></sup>. \
+                    <a href=\"#fnref-a\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to reference a\">↩</a> \
+                    <a href=\"#fnref-a-2\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to reference a-2\">↩<sup class=\"footnote-ref\">2</sup></a></p>\n\
+                    </li>\n\
+                    <li id=\"fn-b\">\n<p>Bee. \
+                    <a href=\"#fnref-b\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to reference b\">↩</a></p>\n\
+                    </li>\n\
+                    <li id=\"fn-c\">\n<p>See. \
+                    <a href=\"#fnref-c\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to reference c\">↩</a></p>\n\
+                    </li>\n\
+                    </ol>\n</section>\n";
+
+    let options = html::Options {
+        footnotes: html::FootnoteStyle::Gfm,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(
+        &mut s,
+        Parser::new_ext(original, Options::ENABLE_FOOTNOTES),
+        &options,
+    );
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_footnotes_at_end() {
+    let original = "[^a]: Unused.\n\nText[^b].\n\n[^b]: Bee.\n";
+    let expected = "<p>Text<sup class=\"footnote-reference\"><a href=\"#b\">1</a></sup>.</p>\n\
+                    <section class=\"footnotes\">\n\
+                    <div class=\"footnote-definition\" id=\"a\"><sup class=\"footnote-definition-label\">2</sup>\n\
+                    <p>Unused.</p>\n</div>\n\
+                    <div class=\"footnote-definition\" id=\"b\"><sup class=\"footnote-definition-label\">1</sup>\n\
+                    <p>Bee.</p>\n</div>\n\
+                    </section>\n";
+
+    let options = html::Options {
+        footnotes: html::FootnoteStyle::End,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(
+        &mut s,
+        Parser::new_ext(original, Options::ENABLE_FOOTNOTES),
+        &options,
+    );
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_numbered_footnotes() {
+    let original = "[^first]: Defined first.\n\nText[^b] and[^first].\n\n[^b]: Bee.\n";
+    let expected = "<div class=\"footnote-definition\" id=\"2\"><sup class=\"footnote-definition-label\">2</sup>\n\
+                    <p>Defined first.</p>\n</div>\n\
+                    <p>Text<sup class=\"footnote-reference\"><a href=\"#1\">[1]</a></sup> \
+                    and<sup class=\"footnote-reference\"><a href=\"#2\">[2]</a></sup>.</p>\n\
+                    <div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+                    <p>Bee.</p>\n</div>\n";
+
+    let options = html::Options {
+        numbered_footnotes: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(
+        &mut s,
+        Parser::new_ext(original, Options::ENABLE_FOOTNOTES),
+        &options,
+    );
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_table_alignment_classes() {
+    let original = "| a | b |\n|:--|---|\n| c | d |\n";
+    let expected = "<table><thead><tr><th class=\"text-align-left\">a</th><th>b</th></tr></thead>\
+                    <tbody>\n<tr><td class=\"text-align-left\">c</td><td>d</td></tr>\n</tbody></table>\n";
+
+    let options = html::Options {
+        table_alignment: html::TableAlignment::Class,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(
+        &mut s,
+        Parser::new_ext(original, Options::ENABLE_TABLES),
+        &options,
+    );
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_code_class_prefix() {
+    let original = "```rust\nfn main() {}\n```\n";
+    let expected = "<pre><code class=\"lang-rust\">fn main() {}\n</code></pre>\n";
+
+    let options = html::Options {
+        code_class_prefix: Some("lang-".to_owned()),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_lazy_images() {
+    let original = "![cat](cat.png \"Cat\")\n";
+    let expected =
+        "<p><img src=\"cat.png\" alt=\"cat\" title=\"Cat\" loading=\"lazy\" decoding=\"async\" /></p>\n";
+
+    let options = html::Options {
+        lazy_images: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_external_links() {
+    let original = "[a](/docs) [b](//Example.com/x) [c](http://example.com) \
+                    [d](https://cdn.example.com) <me@example.org>\n";
+    let expected = "<p><a href=\"/docs\">a</a> <a href=\"//Example.com/x\">b</a> \
+                    <a href=\"http://example.com\" target=\"_blank\">c</a> \
+                    <a href=\"https://cdn.example.com\" target=\"_blank\">d</a> \
+                    <a href=\"mailto:me@example.org\">me@example.org</a></p>\n";
+
+    let options = html::Options {
+        external_links: Some(html::ExternalLinks {
+            site: "https://example.com/blog/".to_owned(),
+            rel: None,
+            target_blank: true,
+        }),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_base_url() {
+    let original = "[guide](guide.md#intro) ![logo](../logo.png) [top](#top) <me@example.org>\n";
+    let expected = "<p><a href=\"https://example.com/docs/guide.html#intro\">guide</a> \
+                    <img src=\"https://example.com/logo.png\" alt=\"logo\" /> \
+                    <a href=\"#top\">top</a> \
+                    <a href=\"mailto:me@example.org\">me@example.org</a></p>\n";
+
+    let options = html::Options {
+        base_url: Some(BaseUrl::new("https://example.com/docs/readme.html").md_to_html(true)),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_safe_mode() {
+    let original = "<script>alert(1)</script>\n\n\
+                    [a](JavaScript:alert(1)) [b](https://example.com) \
+                    ![c](data:image/png;base64,AA) [d](data:image/png;base64,AA) \
+                    ![e](data:text/html,x) <i>f</i>\n";
+    let expected = "&lt;script&gt;alert(1)&lt;/script&gt;\n\
+                    <p><a href=\"\">a</a> <a href=\"https://example.com\">b</a> \
+                    <img src=\"data:image/png;base64,AA\" alt=\"c\" /> <a href=\"\">d</a> \
+                    <img src=\"\" alt=\"e\" /> &lt;i&gt;f&lt;/i&gt;</p>\n";
+
+    let options = html::Options {
+        safe: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_element_attributes() {
+    use pulldown_cmark::html::{Attributes, WithAttributes};
+    use pulldown_cmark::Tag;
+
+    let render = WithAttributes(|tag: &Tag| match tag {
+        Tag::TableCell | Tag::FootnoteDefinition(_) => Attributes::new().class("x"),
+        Tag::Link(..) => Attributes::new().attr("data-kind", "\"link\""),
+        _ => Attributes::new(),
+    });
+    let original = "| [a](b) |\n|:-:|\n\n[^1]: Note.\n";
+    let expected = "<table><thead><tr><th class=\"text-align-center x\">\
+                    <a href=\"b\" data-kind=\"&quot;link&quot;\">a</a></th></tr></thead><tbody>\n\
+                    </tbody></table>\n\
+                    <div class=\"footnote-definition x\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+                    <p>Note.</p>\n</div>\n";
+
+    let options = html::Options {
+        table_alignment: html::TableAlignment::Class,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    let parser = Parser::new_ext(original, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES);
+    html::push_html_with_renderer(&mut s, parser, &options, render);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_wikilinks() {
+    let original = "See [[My Page]], `[[code]]` and [[[x]]].\n\n    [[block]]\n\n\
+                    \\[\\[escaped]], [[\\[x]], [[x\\]], [[ Page | *label* ]] [a [[b]]](/c)\n";
+    let expected = "<p>See <a href=\"/wiki/my-page\">My Page</a>, <code>[[code]]</code> and \
+                    [<a href=\"/wiki/x\">x</a>].</p>\n\
+                    <pre><code>[[block]]\n</code></pre>\n\
+                    <p>[[escaped]], [[[x]], [[x]], <a href=\"/wiki/page\">*label*</a> \
+                    [a <a href=\"/wiki/b\">b</a>](/c)</p>\n";
+
+    let options = html::Options {
+        wikilinks: Some(html::WikiLinks::new("/wiki/{}")),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    let parser = Parser::new_ext(original, Options::ENABLE_WIKILINKS);
+    html::push_html_ext(&mut s, parser, &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_heading_offset() {
+    let original = "# One\n\n##### Five\n\n###### Six\n";
+    let expected = "<h3>One</h3>\n<h6>Five</h6>\n<h6>Six</h6>\n";
+
+    let options = html::Options {
+        heading_offset: 2,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_streaming() {
+    use std::io::{self, Write};
+
+    /// Records the size of every write.
+    struct Chunks(Vec<u8>, Vec<usize>);
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.extend_from_slice(buf);
+            self.1.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let original = "# Title\n\n| a | b |\n|---|---|\n| c | d |\n\n> quote *with* emphasis\n";
+    let mut expected = String::new();
+    html::push_html(
+        &mut expected,
+        Parser::new_ext(original, Options::ENABLE_TABLES),
+    );
+
+    let mut chunks = Chunks(Vec::new(), Vec::new());
+    let parser = Parser::new_ext(original, Options::ENABLE_TABLES);
+    html::write_html_streaming(&mut chunks, parser, &html::Options::default(), 16).unwrap();
+    assert_eq!(expected, String::from_utf8(chunks.0).unwrap());
+    assert!(chunks.1.len() > 1);
+    assert!(chunks.1.iter().all(|&len| len <= 16));
+}
+
+#[test]
+fn html_punctuation_entities() {
+    let original = "\"Wait\"... it's -- it's --- a < b\n";
+    let expected =
+        "<p>&ldquo;Wait&rdquo;&hellip; it&rsquo;s &ndash; it&rsquo;s &mdash; a &lt; b</p>\n";
+
+    let options = html::Options {
+        punctuation_entities: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    let parser = Parser::new_ext(original, Options::ENABLE_SMART_PUNCTUATION);
+    html::push_html_ext(&mut s, parser, &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_custom_rule_and_hard_break() {
+    let original = "a  \nb\n\n***\n";
+    let expected = "<p>a<br>\nb</p>\n<hr class=\"fancy\">\n";
+
+    let options = html::Options {
+        rule: Some("<hr class=\"fancy\">".to_owned()),
+        hard_break: Some("<br>".to_owned()),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_accessibility() {
+    let original = "| Name | Age |\n|---|---|\n| Ann | 30 |\n\n- [x] done\n- [ ] todo\n";
+    let expected = "<table><thead><tr><th scope=\"col\">Name</th><th scope=\"col\">Age</th></tr></thead>\
+                    <tbody>\n<tr><th scope=\"row\">Ann</th><td>30</td></tr>\n</tbody></table>\n\
+                    <ul>\n\
+                    <li><input disabled=\"\" type=\"checkbox\" checked=\"\" aria-label=\"Completed task\"/>\ndone</li>\n\
+                    <li><input disabled=\"\" type=\"checkbox\" aria-label=\"Incomplete task\"/>\ntodo</li>\n\
+                    </ul>\n";
+
+    let options = html::Options {
+        accessibility: true,
+        table_row_headers: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    let parser = Parser::new_ext(original, Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS);
+    html::push_html_ext(&mut s, parser, &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_sections() {
+    let original = "Intro\n\n# One\n\n## Two\n\ntext\n\n> # Quoted\n\n# Three\n";
+    let expected = "<p>Intro</p>\n\
+                    <section>\n<h1>One</h1>\n\
+                    <section>\n<h2>Two</h2>\n<p>text</p>\n\
+                    <blockquote>\n<h1>Quoted</h1>\n</blockquote>\n\
+                    </section>\n</section>\n\
+                    <section>\n<h1>Three</h1>\n</section>\n";
+
+    let options = html::Options {
+        sections: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_sections_after_image() {
+    let original = "# One\n\n![alt](a.png)\n\n# Two\n";
+    let expected = "<section>\n<h1>One</h1>\n\
+                    <p><img src=\"a.png\" alt=\"alt\" /></p>\n\
+                    </section>\n\
+                    <section>\n<h1>Two</h1>\n</section>\n";
+
+    let options = html::Options {
+        sections: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_front_matter() {
+    fn title(front_matter: &FrontMatter) -> String {
+        format!(
+            "<h1>{}</h1>",
+            front_matter.raw.trim_start_matches("title = ").trim()
+        )
+    }
+
+    let input = "+++\ntitle = Hi\n+++\ntext\n";
+    let render = |front_matter| {
+        let options = html::Options {
+            front_matter,
+            ..Default::default()
+        };
+        let mut s = String::new();
+        html::push_html_with_front_matter(&mut s, input, Options::empty(), &options);
+        s
+    };
+    assert_eq!("<p>text</p>\n", render(html::FrontMatterHtml::Skip));
+    assert_eq!(
+        "<pre><code class=\"language-toml\">title = Hi\n</code></pre>\n<p>text</p>\n",
+        render(html::FrontMatterHtml::Pre)
+    );
+    assert_eq!(
+        "<h1>Hi</h1>\n<p>text</p>\n",
+        render(html::FrontMatterHtml::Callback(title))
+    );
+}
+
+#[test]
+fn html_minify() {
+    let original = "# Hi\n\n* a\n* b\n\ntext\nmore\n\n```\ncode\n```\n\n<div>\nraw\n</div>\n";
+    let expected = "<h1>Hi</h1><ul><li>a</li><li>b</li></ul><p>text\nmore</p>\
+                    <pre><code>code\n</code></pre><div>\nraw\n</div>\n";
+
+    let options = html::Options {
+        minify: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_code_line_numbers() {
+    let original = "```\na < b\n\nc\n```\n\n    x\n";
+    let expected = "<pre><code><span class=\"line\" data-line=\"1\">a &lt; b</span>\n\
+                    <span class=\"line\" data-line=\"2\"></span>\n\
+                    <span class=\"line\" data-line=\"3\">c</span>\n</code></pre>\n\
+                    <pre><code><span class=\"line\" data-line=\"1\">x</span>\n</code></pre>\n";
+
+    let options = html::Options {
+        code_line_numbers: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}
+
+#[test]
+fn html_attribute_policy() {
+    use pulldown_cmark::utils::slug::Slugger;
+    use pulldown_cmark::Tag;
+
+    let render = |safe| {
+        let attributes = html::WithAttributes(|tag: &Tag| match tag {
+            Tag::Heading(_) => html::Attributes::new()
+                .class("title")
+                .attr("id", "intro")
+                .attr("data-x", "\"quoted\"")
+                .attr("onclick", "evil()")
+                .attr("a b", "c"),
+            Tag::Table(_) => html::Attributes::new().attr("style", "color: red"),
+            _ => html::Attributes::new(),
+        });
+        let options = html::Options {
+            heading_ids: Some(Slugger::default()),
+            safe,
+            ..Default::default()
+        };
+        let mut s = String::new();
+        let parser = Parser::new_ext("# One\n# Two\n\n| a |\n|---|\n", Options::ENABLE_TABLES);
+        html::push_html_with_renderer(&mut s, parser, &options, attributes);
+        s
+    };
+    assert_eq!(
+        "<h1 id=\"intro\" class=\"title\" data-x=\"&quot;quoted&quot;\" onclick=\"evil()\">One</h1>\n\
+         <h1 id=\"intro-1\" class=\"title\" data-x=\"&quot;quoted&quot;\" onclick=\"evil()\">Two</h1>\n\
+         <table style=\"color: red\"><thead><tr><th>a</th></tr></thead><tbody>\n</tbody></table>\n",
+        render(false)
+    );
+    assert_eq!(
+        "<h1 id=\"intro\" class=\"title\" data-x=\"&quot;quoted&quot;\">One</h1>\n\
+         <h1 id=\"intro-1\" class=\"title\" data-x=\"&quot;quoted&quot;\">Two</h1>\n\
+         <table><thead><tr><th>a</th></tr></thead><tbody>\n</tbody></table>\n",
+        render(true)
+    );
+}
+
+#[test]
+fn html_alt_text() {
+    fn first_word(events: &[pulldown_cmark::Event]) -> String {
+        match events.first() {
+            Some(pulldown_cmark::Event::Text(text)) => text.split(' ').next().unwrap().to_owned(),
+            _ => String::new(),
+        }
+    }
+
+    let original = "![Some **bold** ~~old~~ [link](x)](a.png)";
+    let render = |alt_text| {
+        let options = html::Options {
+            alt_text,
+            ..Default::default()
+        };
+        let mut s = String::new();
+        let parser = Parser::new_ext(original, Options::ENABLE_STRIKETHROUGH);
+        html::push_html_ext(&mut s, parser, &options);
+        s
+    };
+    assert_eq!(
+        "<p><img src=\"a.png\" alt=\"Some bold old link\" /></p>\n",
+        render(html::AltText::Plain)
+    );
+    assert_eq!(
+        "<p><img src=\"a.png\" alt=\"Some **bold** ~~old~~ link\" /></p>\n",
+        render(html::AltText::Markers)
+    );
+    assert_eq!(
+        "<p><img src=\"a.png\" alt=\"Some\" /></p>\n",
+        render(html::AltText::Custom(first_word))
+    );
+}
+
+#[test]
+fn html_trailing_newline() {
+    let render = |input, trailing_newline| {
+        let options = html::Options {
+            trailing_newline,
+            ..Default::default()
+        };
+        let mut s = String::new();
+        html::push_html_ext(&mut s, Parser::new(input), &options);
+        s
+    };
+    let input = "a\n\n<div>\n\n\n";
+    assert_eq!(
+        "<p>a</p>\n<div>\n",
+        render(input, html::TrailingNewline::One)
+    );
+    assert_eq!(
+        "<p>a</p>\n<div>",
+        render(input, html::TrailingNewline::Strip)
+    );
+    assert_eq!("", render("", html::TrailingNewline::One));
+}
+
+#[test]
+fn html_minimal_escaping() {
+    let original = "a > \"b\" & <c\n\n[`x > \"y\"`](/u \"t > \\\"q\\\"\")";
+    let expected = "<p>a > \"b\" &amp; &lt;c</p>\n\
+                    <p><a href=\"/u\" title=\"t > &quot;q&quot;\"><code>x > &quot;y&quot;</code></a></p>\n";
+
+    let options = html::Options {
+        minimal_escaping: true,
+        ..Default::default()
+    };
+    let mut s = String::new();
+    html::push_html_ext(&mut s, Parser::new(original), &options);
+    assert_eq!(expected, s);
+}